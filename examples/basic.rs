@@ -8,9 +8,23 @@ use pinocchio::{
 };
 use pinocchio_escrow_lib::{
     instructions::{
-        make::{make, MakeAccounts},
-        take::{take, TakeAccounts},
+        make::{make, make_deposit, make_init, top_up, MakeAccounts, MakeDepositAccounts, TopUpAccounts},
+        take::{take, take_available, TakeAccounts},
         refund::{refund, RefundAccounts},
+        transfer_authority::{transfer_authority, TransferAuthorityAccounts},
+        get_escrow::{get_escrow, GetEscrowAccounts},
+        force_close::{force_close, ForceCloseAccounts},
+        sweep_excess::{sweep_excess, SweepExcessAccounts},
+        basket::{
+            make_basket, refund_basket, take_basket, MakeBasketAccounts, RefundBasketAccounts,
+            TakeBasketAccounts,
+        },
+        migrate_escrow::{migrate_escrow, MigrateEscrowAccounts},
+        vesting::{claim, make_vesting, ClaimAccounts, MakeVestingAccounts},
+        extend_expiry::{extend_expiry, ExtendExpiryAccounts},
+        claim_receive::{claim_receive, ClaimReceiveAccounts},
+        arbiter_resolve::{arbiter_resolve, ArbiterResolveAccounts},
+        config::{init_config, set_paused, InitConfigAccounts, SetPausedAccounts},
     },
     EscrowInstruction,
 };
@@ -29,46 +43,26 @@ pub fn process_instruction(
     
     //process based on instruction type
     match instruction {
-        EscrowInstruction::Make { amount, seed } => {
+        EscrowInstruction::Make { amount, seed, expiry, kind, receive_amount, memo, namespace, price_num, price_den, min_fill, persistent, treasury } => {
             msg!("Creating escrow with amount: {} and seed: {}", amount, seed);
             
             // accounts for make handler
-            let make_accounts = MakeAccounts {
-                maker: &accounts[0],
-                mint_a: &accounts[1],
-                mint_b: &accounts[2],
-                maker_ata_a: &accounts[3],
-                escrow: &accounts[4],
-                vault: &accounts[5],
-                token_program: &accounts[6],
-                system_program: &accounts[7],
-            };
+            let make_accounts = MakeAccounts::from_slice(accounts)?;
             
             // library make handler
-            make(program_id, make_accounts, amount, seed)?;
+            make(program_id, make_accounts, amount, seed, expiry, kind, receive_amount, &memo, namespace, price_num, price_den, min_fill, persistent, treasury)?;
             
             msg!("Escrow created successfully!");
         }
         
-        EscrowInstruction::Take { amount, seed } => {
+        EscrowInstruction::Take { amount, seed, max_in, receive_amount } => {
             msg!("Taking escrow offer with amount: {} and seed: {}", amount, seed);
             
             //accounts for take handler
-            let take_accounts = TakeAccounts {
-                taker: &accounts[0],
-                maker: &accounts[1],
-                escrow: &accounts[2],
-                vault: &accounts[3],
-                mint_a: &accounts[4],
-                mint_b: &accounts[5],
-                taker_ata_a: &accounts[6],
-                taker_ata_b: &accounts[7],
-                maker_ata_b: &accounts[8],
-                token_program: &accounts[9],
-            };
+            let take_accounts = TakeAccounts::from_slice(accounts)?;
             
             // library take handler
-            take(program_id, take_accounts, amount, seed)?;
+            take(program_id, take_accounts, amount, seed, max_in, receive_amount)?;
             
             msg!("Escrow completed successfully!");
         }
@@ -77,19 +71,237 @@ pub fn process_instruction(
             msg!("Refunding escrow with amount: {} and seed: {}", amount, seed);
             
             // accounts for refund handler
-            let refund_accounts = RefundAccounts {
-                maker: &accounts[0],
-                escrow: &accounts[1],
-                vault: &accounts[2],
-                maker_ata_a: &accounts[3],
-                token_program: &accounts[4],
-            };
+            let refund_accounts = RefundAccounts::from_slice(accounts)?;
             
             // library refund handler
             refund(program_id, refund_accounts, amount, seed)?;
             
             msg!("Escrow refunded successfully!");
         }
+        
+        EscrowInstruction::TransferAuthority { seed } => {
+            msg!("Transferring escrow authority for seed: {}", seed);
+            
+            // accounts for transfer authority handler
+            let transfer_accounts = TransferAuthorityAccounts::from_slice(accounts)?;
+            
+            // library transfer authority handler
+            transfer_authority(program_id, transfer_accounts, seed)?;
+            
+            msg!("Escrow authority transferred successfully!");
+        }
+        
+        EscrowInstruction::GetEscrow { seed } => {
+            msg!("Reading escrow for seed: {}", seed);
+            
+            // accounts for get escrow handler
+            let get_accounts = GetEscrowAccounts::from_slice(accounts)?;
+            
+            // library get escrow handler, the snapshot ends up in return data
+            get_escrow(program_id, get_accounts, seed)?;
+        }
+        
+        EscrowInstruction::ForceClose { seed } => {
+            msg!("Force closing escrow for seed: {}", seed);
+            
+            // accounts for force close handler
+            let close_accounts = ForceCloseAccounts::from_slice(accounts)?;
+            
+            // library force close handler
+            force_close(program_id, close_accounts, seed)?;
+            
+            msg!("Escrow force closed!");
+        }
+        
+        EscrowInstruction::SweepExcess { seed } => {
+            msg!("Sweeping excess lamports for seed: {}", seed);
+            
+            // accounts for sweep excess handler
+            let sweep_accounts = SweepExcessAccounts::from_slice(accounts)?;
+            
+            // library sweep excess handler
+            sweep_excess(program_id, sweep_accounts, seed)?;
+        }
+        
+        EscrowInstruction::MakeBasket { seed, receive_amount, expiry, amounts } => {
+            msg!("Making basket with {} legs, seed: {}", amounts.len(), seed);
+            
+            // accounts for make basket handler, the per-leg accounts follow the fixed ones
+            let basket_accounts = MakeBasketAccounts::from_slice(accounts)?;
+            
+            // library make basket handler
+            make_basket(program_id, basket_accounts, seed, receive_amount, expiry, &amounts)?;
+            
+            msg!("Basket created!");
+        }
+        
+        EscrowInstruction::TakeBasket { seed } => {
+            msg!("Taking basket with seed: {}", seed);
+            
+            // accounts for take basket handler
+            let basket_accounts = TakeBasketAccounts::from_slice(accounts)?;
+            
+            // library take basket handler
+            take_basket(program_id, basket_accounts, seed)?;
+            
+            msg!("Basket taken!");
+        }
+        
+        EscrowInstruction::RefundBasket { seed } => {
+            msg!("Refunding basket with seed: {}", seed);
+            
+            // accounts for refund basket handler
+            let basket_accounts = RefundBasketAccounts::from_slice(accounts)?;
+            
+            // library refund basket handler
+            refund_basket(program_id, basket_accounts, seed)?;
+            
+            msg!("Basket refunded!");
+        }
+        
+        EscrowInstruction::MigrateEscrow { seed } => {
+            msg!("Migrating escrow with seed: {}", seed);
+            
+            // accounts for migrate escrow handler
+            let migrate_accounts = MigrateEscrowAccounts::from_slice(accounts)?;
+            
+            // library migrate escrow handler
+            migrate_escrow(program_id, migrate_accounts, seed)?;
+            
+            msg!("Escrow migrated!");
+        }
+        
+        EscrowInstruction::TakeAvailable { desired, seed, max_in } => {
+            msg!("Taking up to {} of escrow with seed: {}, max_in: {}", desired, seed, max_in);
+            
+            // same accounts as a take
+            let take_accounts = TakeAccounts::from_slice(accounts)?;
+            
+            // library take available handler, the filled amount is in the return data
+            take_available(program_id, take_accounts, desired, seed, max_in)?;
+            
+            msg!("Escrow filled!");
+        }
+        
+        EscrowInstruction::MakeInit { amount, seed, expiry, kind, receive_amount, memo, namespace, price_num, price_den, min_fill, persistent, treasury } => {
+            msg!("Initializing escrow with amount: {} and seed: {}", amount, seed);
+            
+            // same accounts as a make
+            let make_accounts = MakeAccounts::from_slice(accounts)?;
+            
+            // library make init handler, no tokens move yet
+            make_init(program_id, make_accounts, amount, seed, expiry, kind, receive_amount, &memo, namespace, price_num, price_den, min_fill, persistent, treasury)?;
+            
+            msg!("Escrow initialized!");
+        }
+        
+        EscrowInstruction::MakeDeposit { seed } => {
+            msg!("Depositing into escrow with seed: {}", seed);
+            
+            // accounts for make deposit handler
+            let deposit_accounts = MakeDepositAccounts::from_slice(accounts)?;
+            
+            // library make deposit handler, safe to retry
+            make_deposit(program_id, deposit_accounts, seed)?;
+            
+            msg!("Escrow funded!");
+        }
+        
+        EscrowInstruction::MakeVesting { seed, amount, start, cliff, duration } => {
+            msg!("Creating vesting schedule with amount: {} and seed: {}", amount, seed);
+            
+            // accounts for make vesting handler
+            let vesting_accounts = MakeVestingAccounts::from_slice(accounts)?;
+            
+            // library make vesting handler
+            make_vesting(program_id, vesting_accounts, seed, amount, start, cliff, duration)?;
+            
+            msg!("Vesting schedule created!");
+        }
+        
+        EscrowInstruction::Claim { seed } => {
+            msg!("Claiming vested tokens with seed: {}", seed);
+            
+            // accounts for claim handler
+            let claim_accounts = ClaimAccounts::from_slice(accounts)?;
+            
+            // library claim handler
+            claim(program_id, claim_accounts, seed)?;
+            
+            msg!("Vested tokens claimed!");
+        }
+        
+        EscrowInstruction::ExtendExpiry { new_expiry, seed } => {
+            msg!("Extending escrow with seed: {} to expiry: {}", seed, new_expiry);
+            
+            // accounts for extend expiry handler
+            let extend_accounts = ExtendExpiryAccounts::from_slice(accounts)?;
+            
+            // library extend expiry handler
+            extend_expiry(program_id, extend_accounts, new_expiry, seed)?;
+            
+            msg!("Escrow expiry extended!");
+        }
+        
+        EscrowInstruction::ClaimReceive { seed } => {
+            msg!("Claiming receive vault with seed: {}", seed);
+            
+            // accounts for claim receive handler
+            let claim_receive_accounts = ClaimReceiveAccounts::from_slice(accounts)?;
+            
+            // library claim receive handler
+            claim_receive(program_id, claim_receive_accounts, seed)?;
+            
+            msg!("Receive vault claimed!");
+        }
+        
+        EscrowInstruction::ArbiterResolve { seed, to_taker } => {
+            msg!("Resolving escrow with seed: {}, to taker: {}", seed, to_taker);
+            
+            // accounts for arbiter resolve handler
+            let arbiter_accounts = ArbiterResolveAccounts::from_slice(accounts)?;
+            
+            // library arbiter resolve handler
+            arbiter_resolve(program_id, arbiter_accounts, seed, to_taker)?;
+            
+            msg!("Escrow resolved!");
+        }
+        
+        EscrowInstruction::InitConfig => {
+            msg!("Initializing config");
+            
+            // accounts for init config handler
+            let init_config_accounts = InitConfigAccounts::from_slice(accounts)?;
+            
+            // library init config handler
+            init_config(program_id, init_config_accounts)?;
+            
+            msg!("Config initialized!");
+        }
+        
+        EscrowInstruction::SetPaused { paused } => {
+            msg!("Setting paused: {}", paused);
+            
+            // accounts for set paused handler
+            let set_paused_accounts = SetPausedAccounts::from_slice(accounts)?;
+            
+            // library set paused handler
+            set_paused(program_id, set_paused_accounts, paused)?;
+            
+            msg!("Pause updated!");
+        }
+        
+        EscrowInstruction::TopUp { amount, seed, receive_amount } => {
+            msg!("Topping up escrow with seed: {}", seed);
+            
+            // accounts for top up handler
+            let top_up_accounts = TopUpAccounts::from_slice(accounts)?;
+            
+            // library top up handler
+            top_up(program_id, top_up_accounts, amount, receive_amount, seed)?;
+            
+            msg!("Escrow topped up!");
+        }
     }
     
     Ok(())
@@ -98,6 +310,7 @@ pub fn process_instruction(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use pinocchio_escrow_lib::EscrowKind;
     
     #[test]
     fn test_instruction_parsing() {
@@ -106,13 +319,29 @@ mod tests {
             let mut data = vec![0u8]; // Make discriminator
             data.extend_from_slice(&100u64.to_le_bytes()); // amount
             data.extend_from_slice(&1u64.to_le_bytes()); // seed
+            data.extend_from_slice(&0i64.to_le_bytes()); // expiry (none)
+            data.push(0); // kind (token)
+            data.extend_from_slice(&150u64.to_le_bytes()); // receive amount
+            data.extend_from_slice(&[0u8; 8]); // namespace (default)
+            data.extend_from_slice(&0u64.to_le_bytes()); // price_num
+            data.extend_from_slice(&0u64.to_le_bytes()); // price_den (fixed price)
+            data.extend_from_slice(&0u64.to_le_bytes()); // min_fill (any fill)
+            data.push(0); // persistent (closed by the last take)
+            data.push(0); // treasury (token B goes to Maker ATA B)
+            data.push(0); // memo length (no memo)
             data
         };
         let instruction = EscrowInstruction::unpack(&make_data).unwrap();
         match instruction {
-            EscrowInstruction::Make { amount, seed } => {
+            EscrowInstruction::Make { amount, seed, expiry, kind, receive_amount, memo, namespace, price_den, .. } => {
                 assert_eq!(amount, 100);
+                assert_eq!(price_den, 0);
+                assert!(memo.is_empty());
+                assert_eq!(namespace, [0u8; 8]);
+                assert_eq!(receive_amount, 150);
                 assert_eq!(seed, 1);
+                assert_eq!(expiry, 0);
+                assert_eq!(kind, EscrowKind::Token);
             }
             _ => panic!("Wrong instruction type"),
         }
@@ -122,12 +351,16 @@ mod tests {
             let mut data = vec![1u8]; // Take discriminator
             data.extend_from_slice(&200u64.to_le_bytes()); // amount
             data.extend_from_slice(&2u64.to_le_bytes()); // seed
+            data.extend_from_slice(&400u64.to_le_bytes()); // max token B in
+            data.extend_from_slice(&400u64.to_le_bytes()); // acknowledged receive amount
             data
         };
         let instruction = EscrowInstruction::unpack(&take_data).unwrap();
         match instruction {
-            EscrowInstruction::Take { amount, seed } => {
+            EscrowInstruction::Take { amount, seed, max_in, receive_amount } => {
                 assert_eq!(amount, 200);
+                assert_eq!(max_in, 400);
+                assert_eq!(receive_amount, 400);
                 assert_eq!(seed, 2);
             }
             _ => panic!("Wrong instruction type"),
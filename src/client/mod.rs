@@ -0,0 +1,647 @@
+// off-chain helpers for building escrow transactions
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    error::EscrowError,
+    math::mul_div_floor,
+    pack_instruction_data,
+    state::{Escrow, EscrowKind, EscrowStatus, MAX_BASKET_LEGS},
+    EscrowInstruction,
+    instructions::config::find_config_address,
+    instructions::make::{
+        find_escrow_address, find_namespaced_escrow_address, find_vault_address,
+        DEFAULT_ESCROW_NAMESPACE, ESCROW_NAMESPACE_LEN,
+    },
+    programs::{ASSOCIATED_TOKEN_PROGRAM_ID, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID},
+};
+
+// escrow PDA and bump for a maker/seed pair, same derivation as make
+pub fn derive_escrow(maker: &Pubkey, seed: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+    find_escrow_address(maker, seed, program_id)
+}
+
+// escrow PDA and bump inside a frontend's namespace
+pub fn derive_namespaced_escrow(
+    maker: &Pubkey,
+    namespace: &[u8; ESCROW_NAMESPACE_LEN],
+    seed: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    find_namespaced_escrow_address(maker, namespace, seed, program_id)
+}
+
+// vault PDA and bump for an escrow, same derivation as make
+pub fn derive_vault(escrow: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    find_vault_address(escrow, program_id)
+}
+
+// whether make can use a seed, given the account fetched at
+// derive_escrow(maker, seed): free when the account doesn't exist (None) or
+// holds no initialized escrow, e.g. one closed by take or refund
+pub fn is_seed_available(rpc_data: Option<&[u8]>) -> bool {
+    match rpc_data {
+        None => true,
+        Some(data) => !Escrow::check_discriminator(data),
+    }
+}
+
+// decode fetched escrow account data, including the stored escrow and vault
+// bumps, so follow-up instructions can re-sign without find_program_address
+pub fn decode_escrow(data: &[u8]) -> Result<Escrow, ProgramError> {
+    if data.len() < Escrow::LEN {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    if !Escrow::check_discriminator(data) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    
+    // the struct has trailing padding past LEN, copy into a full-size buffer first
+    let mut buf = [0u8; core::mem::size_of::<Escrow>()];
+    buf[..Escrow::LEN].copy_from_slice(&data[..Escrow::LEN]);
+    Ok(unsafe { core::ptr::read_unaligned(buf.as_ptr() as *const Escrow) })
+}
+
+// offer-book snapshot, independent of any RPC:
+// count (u32 LE), then per escrow its length (u32 LE) and raw account bytes.
+// Records carry their own length so a later, longer Escrow can still be read
+pub fn serialize_offer_book(escrows: &[Escrow]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + escrows.len() * (4 + Escrow::LEN));
+    out.extend_from_slice(&(escrows.len() as u32).to_le_bytes());
+    for escrow in escrows {
+        let raw = unsafe {
+            core::slice::from_raw_parts(escrow as *const Escrow as *const u8, Escrow::LEN)
+        };
+        out.extend_from_slice(&(Escrow::LEN as u32).to_le_bytes());
+        out.extend_from_slice(raw);
+    }
+    out
+}
+
+// read back a snapshot written by serialize_offer_book
+pub fn deserialize_offer_book(data: &[u8]) -> Result<Vec<Escrow>, ProgramError> {
+    let mut rest = data;
+    let count = take_u32(&mut rest)?;
+    let mut escrows = Vec::with_capacity((count as usize).min(rest.len() / Escrow::LEN));
+    for _ in 0..count {
+        let len = take_u32(&mut rest)? as usize;
+        if rest.len() < len {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (record, tail) = rest.split_at(len);
+        escrows.push(decode_escrow(record)?);
+        rest = tail;
+    }
+    
+    // anything after the last record means the snapshot is corrupt
+    if !rest.is_empty() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(escrows)
+}
+
+fn take_u32(data: &mut &[u8]) -> Result<u32, ProgramError> {
+    let (bytes, rest) = data
+        .split_first_chunk::<4>()
+        .ok_or(ProgramError::InvalidAccountData)?;
+    *data = rest;
+    Ok(u32::from_le_bytes(*bytes))
+}
+
+// escrows that can still be taken: Active and not past their expiry
+pub fn filter_active(escrows: &[Escrow], now: i64) -> Vec<&Escrow> {
+    escrows
+        .iter()
+        .filter(|escrow| escrow.status() == Ok(EscrowStatus::Active) && !escrow.is_expired(now))
+        .collect()
+}
+
+// escrows made at least `min_age` seconds before `now`, e.g. offers older
+// than a week. Escrows with an unknown creation time (migrated from before
+// version 4) are left out
+pub fn filter_older_than(escrows: &[Escrow], now: i64, min_age: i64) -> Vec<&Escrow> {
+    escrows
+        .iter()
+        .filter(|escrow| escrow.age(now).is_some_and(|age| age >= min_age))
+        .collect()
+}
+
+// escrows offering `mint_a` for `mint_b`
+pub fn filter_by_pair<'a>(escrows: &'a [Escrow], mint_a: &Pubkey, mint_b: &Pubkey) -> Vec<&'a Escrow> {
+    escrows
+        .iter()
+        .filter(|escrow| &escrow.mint_a == mint_a && &escrow.mint_b == mint_b)
+        .collect()
+}
+
+// one escrow held by an OfferIndex: its account address and its position
+// in the order the escrows were ingested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscrowRef {
+    pub address: Pubkey,
+    pub index: u32,
+}
+
+// lookups by maker, mint pair and status over a scan of escrow accounts,
+// e.g. a getProgramAccounts result. The fields it looks up by are kept as
+// columns, and each lookup is the refs sorted by its column, so a query is
+// two binary searches over that column and returns a slice, in ingest order
+pub struct OfferIndex {
+    escrows: Vec<Escrow>,
+    makers: Vec<Pubkey>,
+    pairs: Vec<(Pubkey, Pubkey)>,
+    statuses: Vec<u8>,
+    by_maker: Vec<EscrowRef>,
+    by_pair: Vec<EscrowRef>,
+    by_status: Vec<EscrowRef>,
+}
+
+impl OfferIndex {
+    // index decoded escrows keyed by their account address
+    pub fn new(accounts: impl IntoIterator<Item = (Pubkey, Escrow)>) -> Self {
+        let (refs, escrows): (Vec<EscrowRef>, Vec<Escrow>) = accounts
+            .into_iter()
+            .enumerate()
+            .map(|(index, (address, escrow))| (EscrowRef { address, index: index as u32 }, escrow))
+            .unzip();
+        let makers: Vec<Pubkey> = escrows.iter().map(|escrow| escrow.maker).collect();
+        let pairs: Vec<(Pubkey, Pubkey)> = escrows.iter().map(|escrow| (escrow.mint_a, escrow.mint_b)).collect();
+        let statuses: Vec<u8> = escrows.iter().map(|escrow| escrow.status).collect();
+        
+        let by_maker = sorted_refs(&refs, |i| makers[i]);
+        let by_pair = sorted_refs(&refs, |i| pairs[i]);
+        let by_status = sorted_refs(&refs, |i| statuses[i]);
+        
+        Self { escrows, makers, pairs, statuses, by_maker, by_pair, by_status }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.escrows.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.escrows.is_empty()
+    }
+    
+    // the escrow behind a ref from this index
+    pub fn get(&self, entry: &EscrowRef) -> &Escrow {
+        &self.escrows[entry.index as usize]
+    }
+    
+    // escrows currently owned by `maker`
+    pub fn by_maker(&self, maker: &Pubkey) -> &[EscrowRef] {
+        equal_range(&self.by_maker, |i| self.makers[i], *maker)
+    }
+    
+    // escrows offering `mint_a` for `mint_b`
+    pub fn by_pair(&self, mint_a: &Pubkey, mint_b: &Pubkey) -> &[EscrowRef] {
+        equal_range(&self.by_pair, |i| self.pairs[i], (*mint_a, *mint_b))
+    }
+    
+    // escrows in `status`, whether or not they have expired
+    pub fn by_status(&self, status: EscrowStatus) -> &[EscrowRef] {
+        equal_range(&self.by_status, |i| self.statuses[i], status as u8)
+    }
+}
+
+// `refs` ordered by the column `key` reads, the stable sort keeps equal keys
+// in ingest order
+fn sorted_refs<K: Ord>(refs: &[EscrowRef], key: impl Fn(usize) -> K) -> Vec<EscrowRef> {
+    let mut sorted = refs.to_vec();
+    sorted.sort_by_key(|entry| key(entry.index as usize));
+    sorted
+}
+
+// the run of `sorted` whose key equals `wanted`
+fn equal_range<K: Ord>(sorted: &[EscrowRef], key: impl Fn(usize) -> K, wanted: K) -> &[EscrowRef] {
+    let start = sorted.partition_point(|entry| key(entry.index as usize) < wanted);
+    let len = sorted[start..].partition_point(|entry| key(entry.index as usize) == wanted);
+    &sorted[start..start + len]
+}
+
+// associated token account of `wallet` for `mint` under the legacy token program
+pub fn find_associated_token_address(wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            wallet.as_ref(),
+            TOKEN_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+        ],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+// every address involved in an escrow, derived in one place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscrowKeys {
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub maker_ata_a: Pubkey,
+    pub maker_ata_b: Pubkey,
+    pub taker_ata_a: Pubkey,
+    pub taker_ata_b: Pubkey,
+    pub escrow: Pubkey,
+    pub escrow_bump: u8,
+    pub vault: Pubkey,
+    pub vault_bump: u8,
+    pub seed: u64,
+    pub namespace: [u8; ESCROW_NAMESPACE_LEN],
+    pub program_id: Pubkey,
+    pub token_program: Pubkey,
+    pub system_program: Pubkey,
+    // the program's config PDA, passed to every make and take
+    pub config: Pubkey,
+}
+
+impl EscrowKeys {
+    // derive the escrow/vault PDAs and all four ATAs for a maker/taker pair
+    pub fn derive(
+        maker: &Pubkey,
+        taker: &Pubkey,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        seed: u64,
+        program_id: &Pubkey,
+    ) -> Self {
+        Self::derive_namespaced(maker, taker, mint_a, mint_b, &DEFAULT_ESCROW_NAMESPACE, seed, program_id)
+    }
+    
+    // same as derive, with the escrow PDA inside `namespace`
+    pub fn derive_namespaced(
+        maker: &Pubkey,
+        taker: &Pubkey,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        namespace: &[u8; ESCROW_NAMESPACE_LEN],
+        seed: u64,
+        program_id: &Pubkey,
+    ) -> Self {
+        let (escrow, escrow_bump) = derive_namespaced_escrow(maker, namespace, seed, program_id);
+        let (vault, vault_bump) = derive_vault(&escrow, program_id);
+        
+        Self {
+            maker: *maker,
+            taker: *taker,
+            mint_a: *mint_a,
+            mint_b: *mint_b,
+            maker_ata_a: find_associated_token_address(maker, mint_a),
+            maker_ata_b: find_associated_token_address(maker, mint_b),
+            taker_ata_a: find_associated_token_address(taker, mint_a),
+            taker_ata_b: find_associated_token_address(taker, mint_b),
+            escrow,
+            escrow_bump,
+            vault,
+            vault_bump,
+            seed,
+            namespace: *namespace,
+            program_id: *program_id,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: SYSTEM_PROGRAM_ID,
+            config: find_config_address(program_id).0,
+        }
+    }
+}
+
+// the addresses a Take needs for a maker/taker swap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapAccounts {
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub escrow: Pubkey,
+    pub vault: Pubkey,
+    pub taker_ata_a: Pubkey,
+    pub taker_ata_b: Pubkey,
+    pub maker_ata_b: Pubkey,
+    pub token_program: Pubkey,
+    pub config: Pubkey,
+    pub seed: u64,
+    pub program_id: Pubkey,
+}
+
+// derive the escrow/vault PDAs and the three ATAs a Take touches, for an
+// escrow made in the default namespace with the maker's ATA B as receive account
+pub fn swap_accounts(
+    maker: &Pubkey,
+    taker: &Pubkey,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+    seed: u64,
+    program_id: &Pubkey,
+) -> SwapAccounts {
+    let keys = EscrowKeys::derive(maker, taker, mint_a, mint_b, seed, program_id);
+    SwapAccounts {
+        maker: keys.maker,
+        taker: keys.taker,
+        mint_a: keys.mint_a,
+        mint_b: keys.mint_b,
+        escrow: keys.escrow,
+        vault: keys.vault,
+        taker_ata_a: keys.taker_ata_a,
+        taker_ata_b: keys.taker_ata_b,
+        maker_ata_b: keys.maker_ata_b,
+        token_program: keys.token_program,
+        config: keys.config,
+        seed,
+        program_id: *program_id,
+    }
+}
+
+// owned account meta for instructions built off-chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl AccountMeta {
+    pub fn new(pubkey: Pubkey, is_signer: bool, is_writable: bool) -> Self {
+        Self { pubkey, is_signer, is_writable }
+    }
+}
+
+// owned instruction, ready to be converted into the client SDK's type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<AccountMeta>,
+    pub data: Vec<u8>,
+}
+
+// Make instruction for the escrow described by `keys`, accounts in the
+// order MakeAccounts::from_slice expects
+pub fn make_instruction(
+    keys: &EscrowKeys,
+    amount: u64,
+    expiry: i64,
+    kind: EscrowKind,
+    receive_amount: u64,
+    memo: &[u8],
+) -> Instruction {
+    build_make(keys, amount, expiry, kind, receive_amount, memo, 0, 0)
+}
+
+// Make instruction for a PriceMode::Ratio escrow, the program derives the
+// total token B from the price
+pub fn make_priced_instruction(
+    keys: &EscrowKeys,
+    amount: u64,
+    expiry: i64,
+    kind: EscrowKind,
+    price_num: u64,
+    price_den: u64,
+    memo: &[u8],
+) -> Instruction {
+    build_make(keys, amount, expiry, kind, 0, memo, price_num, price_den)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_make(
+    keys: &EscrowKeys,
+    amount: u64,
+    expiry: i64,
+    kind: EscrowKind,
+    receive_amount: u64,
+    memo: &[u8],
+    price_num: u64,
+    price_den: u64,
+) -> Instruction {
+    let data = pack_instruction_data(&EscrowInstruction::Make {
+        amount,
+        seed: keys.seed,
+        expiry,
+        kind,
+        receive_amount,
+        memo: memo.to_vec(),
+        namespace: keys.namespace,
+        price_num,
+        price_den,
+        min_fill: 0,
+        persistent: false,
+        treasury: false,
+    });
+    
+    Instruction {
+        program_id: keys.program_id,
+        accounts: vec![
+            AccountMeta::new(keys.maker, true, true),
+            AccountMeta::new(keys.mint_a, false, false),
+            AccountMeta::new(keys.mint_b, false, false),
+            AccountMeta::new(keys.maker_ata_a, false, true),
+            AccountMeta::new(keys.escrow, false, true),
+            AccountMeta::new(keys.vault, false, true),
+            AccountMeta::new(keys.token_program, false, false),
+            AccountMeta::new(keys.system_program, false, false),
+            AccountMeta::new(keys.maker_ata_b, false, false),
+            AccountMeta::new(keys.config, false, false),
+        ],
+        data,
+    }
+}
+
+// Take instruction for the swap described by `accounts`, accounts in the
+// order TakeAccounts::from_slice expects
+pub fn take_instruction(
+    accounts: &SwapAccounts,
+    amount: u64,
+    max_in: u64,
+    receive_amount: u64,
+) -> Instruction {
+    let data = pack_instruction_data(&EscrowInstruction::Take {
+        amount,
+        seed: accounts.seed,
+        max_in,
+        receive_amount,
+    });
+    
+    Instruction {
+        program_id: accounts.program_id,
+        accounts: vec![
+            AccountMeta::new(accounts.taker, true, true),
+            AccountMeta::new(accounts.maker, false, false),
+            AccountMeta::new(accounts.escrow, false, true),
+            AccountMeta::new(accounts.vault, false, true),
+            AccountMeta::new(accounts.mint_a, false, false),
+            AccountMeta::new(accounts.mint_b, false, false),
+            AccountMeta::new(accounts.taker_ata_a, false, true),
+            AccountMeta::new(accounts.taker_ata_b, false, true),
+            AccountMeta::new(accounts.maker_ata_b, false, true),
+            AccountMeta::new(accounts.token_program, false, false),
+            AccountMeta::new(accounts.config, false, false),
+        ],
+        data,
+    }
+}
+
+// one large offer split across several escrows of at most `chunk_size` each
+#[derive(Debug, Clone)]
+pub struct ChunkedMake {
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub total_amount: u64,
+    pub chunk_size: u64,
+    // token B asked for the whole offer, split pro rata over the chunks
+    pub total_receive_amount: u64,
+    // chunk i uses seed `first_seed + i`, so pick a range the maker has not used
+    pub first_seed: u64,
+    pub expiry: i64,
+    // same memo on every chunk, at most MAX_MEMO_LEN bytes
+    pub memo: Vec<u8>,
+}
+
+impl ChunkedMake {
+    // build one Make per chunk, returned with its escrow address and seed
+    // the last chunk takes whatever is left of both amounts, so the chunks
+    // always add up to the totals exactly
+    pub fn instructions(&self, program_id: &Pubkey) -> Result<Vec<(Instruction, Pubkey, u64)>, ProgramError> {
+        if self.chunk_size == 0 || self.total_amount == 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        
+        let chunks = self.total_amount.div_ceil(self.chunk_size);
+        // seeds are sequential, so they are unique as long as none wraps
+        self.first_seed
+            .checked_add(chunks - 1)
+            .ok_or(EscrowError::AmountOverflow)?;
+        
+        let mut out = Vec::with_capacity(chunks as usize);
+        let mut remaining_amount = self.total_amount;
+        let mut remaining_receive = self.total_receive_amount;
+        
+        for i in 0..chunks {
+            let seed = self.first_seed + i;
+            let amount = remaining_amount.min(self.chunk_size);
+            let receive_amount = if i + 1 == chunks {
+                remaining_receive
+            } else {
+                mul_div_floor(self.total_receive_amount, amount, self.total_amount)?
+            };
+            remaining_amount -= amount;
+            remaining_receive -= receive_amount;
+            
+            // no taker yet, the maker stands in for the taker side of the keys
+            let keys = EscrowKeys::derive(
+                &self.maker,
+                &self.maker,
+                &self.mint_a,
+                &self.mint_b,
+                seed,
+                program_id,
+            );
+            let ix = make_instruction(
+                &keys,
+                amount,
+                self.expiry,
+                EscrowKind::Token,
+                receive_amount,
+                &self.memo,
+            );
+            out.push((ix, keys.escrow, seed));
+        }
+        
+        Ok(out)
+    }
+}
+
+// rough compute unit costs for the default build, from profiling the CPIs and
+// PDA derivations each handler makes. `verbose-logs` and `metrics` add a few
+// thousand per instruction on top. Budget with some margin
+pub const MAKE_COMPUTE_UNITS: u32 = 45_000;
+pub const TAKE_COMPUTE_UNITS: u32 = 38_000;
+pub const REFUND_COMPUTE_UNITS: u32 = 28_000;
+pub const FORCE_CLOSE_COMPUTE_UNITS: u32 = 28_000;
+pub const TRANSFER_AUTHORITY_COMPUTE_UNITS: u32 = 4_000;
+pub const GET_ESCROW_COMPUTE_UNITS: u32 = 4_000;
+pub const SWEEP_EXCESS_COMPUTE_UNITS: u32 = 5_000;
+pub const MAKE_BASKET_BASE_COMPUTE_UNITS: u32 = 15_000;
+pub const MAKE_BASKET_LEG_COMPUTE_UNITS: u32 = 28_000;
+pub const TAKE_BASKET_BASE_COMPUTE_UNITS: u32 = 15_000;
+pub const RELEASE_BASKET_LEG_COMPUTE_UNITS: u32 = 18_000;
+pub const REFUND_BASKET_BASE_COMPUTE_UNITS: u32 = 8_000;
+pub const MIGRATE_ESCROW_COMPUTE_UNITS: u32 = 8_000;
+pub const MAKE_INIT_COMPUTE_UNITS: u32 = 38_000;
+pub const MAKE_DEPOSIT_COMPUTE_UNITS: u32 = 12_000;
+pub const MAKE_VESTING_COMPUTE_UNITS: u32 = 45_000;
+pub const CLAIM_COMPUTE_UNITS: u32 = 30_000;
+pub const EXTEND_EXPIRY_COMPUTE_UNITS: u32 = 4_000;
+pub const CLAIM_RECEIVE_COMPUTE_UNITS: u32 = 20_000;
+pub const ARBITER_RESOLVE_COMPUTE_UNITS: u32 = 28_000;
+pub const INIT_CONFIG_COMPUTE_UNITS: u32 = 12_000;
+pub const SET_PAUSED_COMPUTE_UNITS: u32 = 3_000;
+pub const TOP_UP_COMPUTE_UNITS: u32 = 12_000;
+
+// estimated compute units for `instruction`, e.g. to size a ComputeBudget
+// request when batching several escrow instructions in one transaction.
+// This is an estimate, not a bound. TakeBasket and RefundBasket don't carry
+// their leg count, so they are priced at MAX_BASKET_LEGS legs
+pub fn estimated_compute_units(instruction: &EscrowInstruction) -> u32 {
+    let max_legs = MAX_BASKET_LEGS as u32;
+    match instruction {
+        EscrowInstruction::Make { .. } => MAKE_COMPUTE_UNITS,
+        EscrowInstruction::Take { .. } => TAKE_COMPUTE_UNITS,
+        EscrowInstruction::Refund { .. } => REFUND_COMPUTE_UNITS,
+        EscrowInstruction::TransferAuthority { .. } => TRANSFER_AUTHORITY_COMPUTE_UNITS,
+        EscrowInstruction::GetEscrow { .. } => GET_ESCROW_COMPUTE_UNITS,
+        EscrowInstruction::ForceClose { .. } => FORCE_CLOSE_COMPUTE_UNITS,
+        EscrowInstruction::SweepExcess { .. } => SWEEP_EXCESS_COMPUTE_UNITS,
+        EscrowInstruction::MakeBasket { amounts, .. } => {
+            // amounts beyond MAX_BASKET_LEGS can't unpack, cap them anyway
+            let legs = (amounts.len() as u32).min(max_legs);
+            MAKE_BASKET_BASE_COMPUTE_UNITS + legs * MAKE_BASKET_LEG_COMPUTE_UNITS
+        }
+        EscrowInstruction::TakeBasket { .. } => {
+            TAKE_BASKET_BASE_COMPUTE_UNITS + max_legs * RELEASE_BASKET_LEG_COMPUTE_UNITS
+        }
+        EscrowInstruction::RefundBasket { .. } => {
+            REFUND_BASKET_BASE_COMPUTE_UNITS + max_legs * RELEASE_BASKET_LEG_COMPUTE_UNITS
+        }
+        EscrowInstruction::MigrateEscrow { .. } => MIGRATE_ESCROW_COMPUTE_UNITS,
+        // a partial fill skips the close, so this errs on the high side
+        EscrowInstruction::TakeAvailable { .. } => TAKE_COMPUTE_UNITS,
+        EscrowInstruction::MakeInit { .. } => MAKE_INIT_COMPUTE_UNITS,
+        EscrowInstruction::MakeDeposit { .. } => MAKE_DEPOSIT_COMPUTE_UNITS,
+        EscrowInstruction::MakeVesting { .. } => MAKE_VESTING_COMPUTE_UNITS,
+        // the last claim also closes the vault and the vesting account
+        EscrowInstruction::Claim { .. } => CLAIM_COMPUTE_UNITS,
+        EscrowInstruction::ExtendExpiry { .. } => EXTEND_EXPIRY_COMPUTE_UNITS,
+        EscrowInstruction::ClaimReceive { .. } => CLAIM_RECEIVE_COMPUTE_UNITS,
+        EscrowInstruction::ArbiterResolve { .. } => ARBITER_RESOLVE_COMPUTE_UNITS,
+        EscrowInstruction::InitConfig => INIT_CONFIG_COMPUTE_UNITS,
+        EscrowInstruction::SetPaused { .. } => SET_PAUSED_COMPUTE_UNITS,
+        EscrowInstruction::TopUp { .. } => TOP_UP_COMPUTE_UNITS,
+    }
+}
+
+// packed instruction bytes as lowercase hex, e.g. to paste into a bug report
+pub fn to_hex(instruction: &EscrowInstruction) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    pack_instruction_data(instruction)
+        .iter()
+        .flat_map(|byte| [DIGITS[(byte >> 4) as usize] as char, DIGITS[(byte & 0x0f) as usize] as char])
+        .collect()
+}
+
+// read back an instruction written by to_hex, either case is accepted. An odd
+// length or a non-hex digit is an InvalidInstruction like any malformed data
+pub fn from_hex(hex: &str) -> Result<EscrowInstruction, ProgramError> {
+    let hex = hex.as_bytes();
+    if hex.len() % 2 != 0 {
+        return Err(EscrowError::InvalidInstruction.into());
+    }
+    let digit = |c: u8| {
+        (c as char)
+            .to_digit(16)
+            .map(|value| value as u8)
+            .ok_or(EscrowError::InvalidInstruction)
+    };
+    let data = hex
+        .chunks(2)
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect::<Result<Vec<u8>, EscrowError>>()?;
+    EscrowInstruction::unpack(&data)
+}
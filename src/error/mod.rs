@@ -1,39 +1,88 @@
-use pinocchio::{msg, program_error::ProgramError};
+use pinocchio::program_error::ProgramError;
 use thiserror::Error;
 
+// error codes are fixed so clients can match on them across releases.
+// They start at 6000 (the Anchor custom error range); new variants take the
+// next free number and existing numbers are never changed or reused.
 #[derive(Debug, Error)]
+#[repr(u32)]
 pub enum EscrowError {
     #[error("Invalid Instruction")]
-    InvalidInstruction,
+    InvalidInstruction = 6000,
     
     #[error("Not Rent Exempt")]
-    NotRentExempt,
+    NotRentExempt = 6001,
     
     #[error("Expected Amount Mismatch")]
-    ExpectedAmountMismatch,
+    ExpectedAmountMismatch = 6002,
     
     #[error("Amount Overflow")]
-    AmountOverflow,
+    AmountOverflow = 6003,
     
     #[error("Invalid State")]
-    InvalidState,
+    InvalidState = 6004,
     
     #[error("Invalid Authority")]
-    InvalidAuthority,
+    InvalidAuthority = 6005,
     
     #[error("Invalid Token Program")]
-    InvalidTokenProgram,
+    InvalidTokenProgram = 6006,
     
     #[error("Invalid Token Mint")]
-    InvalidTokenMint,
+    InvalidTokenMint = 6007,
     
     #[error("Invalid Escrow Account")]
-    InvalidEscrowAccount,
+    InvalidEscrowAccount = 6008,
+    
+    #[error("Escrow Expired")]
+    EscrowExpired = 6009,
+    
+    #[error("Mint Has Freeze Authority")]
+    MintHasFreezeAuthority = 6010,
+    
+    #[error("Invalid Expiry")]
+    InvalidExpiry = 6011,
+    
+    #[error("Slippage Exceeded")]
+    SlippageExceeded = 6012,
+    
+    #[error("Insufficient Vault Balance")]
+    InsufficientVaultBalance = 6013,
+    
+    #[error("Insufficient Taker Balance")]
+    InsufficientTakerBalance = 6014,
+    
+    #[error("Amount Exceeds Cap")]
+    AmountExceedsCap = 6015,
+    
+    #[error("Mint Not Allowed")]
+    MintNotAllowed = 6016,
+    
+    #[error("Account Frozen")]
+    AccountFrozen = 6017,
+    
+    #[error("Nothing To Claim")]
+    NothingToClaim = 6018,
+    
+    #[error("Insufficient Maker Balance")]
+    InsufficientMakerBalance = 6019,
+    
+    #[error("Amount Below Precision")]
+    AmountBelowPrecision = 6020,
+    
+    #[error("Cpi Not Allowed")]
+    CpiNotAllowed = 6021,
+    
+    #[error("Fill Below Minimum")]
+    FillBelowMinimum = 6022,
+    
+    #[error("Program Paused")]
+    ProgramPaused = 6023,
 }
 
 impl From<EscrowError> for ProgramError {
     fn from(e: EscrowError) -> Self {
-        msg!(&format!("Escrow error: {}", e));
+        verbose_msg!("Escrow error: {}", e);
         ProgramError::Custom(e as u32)
     }
 } 
\ No newline at end of file
@@ -0,0 +1,203 @@
+use crate::{
+    error::EscrowError,
+    programs::verify_token_program,
+    state::{close_program_account, Escrow, EscrowKind, EscrowStatus},
+    sysvar::current_unix_timestamp,
+    token::{
+        check_accounts_not_frozen, check_token_account_mint, token_account_amount, verify_token_account,
+        verify_vault_authority,
+    },
+};
+use super::{AccountRole, require_signer, check_distinct_accounts, token_ops::{self, harvest_withheld_fees, transfer_tokens}};
+use crate::{pda::verify_vault_pda, sysvar::check_not_cpi};
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+// Accounts for the ArbiterResolve instruction
+pub struct ArbiterResolveAccounts<'a> {
+    pub arbiter: &'a AccountInfo,
+    // gets the rent of the escrow and the vault either way
+    pub maker: &'a AccountInfo,
+    pub escrow: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    // receives the vault: a token A account of the taker, or of the maker on
+    // a refund. For native escrows the taker's wallet, or the maker
+    pub destination: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    // a call through another program's CPI is rejected
+    pub instructions_sysvar: &'a AccountInfo,
+    // Token-2022 transfers need the mint, legacy escrows may leave it out
+    pub mint_a: Option<&'a AccountInfo>,
+}
+
+impl<'a> ArbiterResolveAccounts<'a> {
+    pub const ROLES: [AccountRole; 8] = [
+        AccountRole::new("arbiter", true, false),
+        AccountRole::new("maker", false, true),
+        AccountRole::new("escrow", false, true),
+        AccountRole::new("vault", false, true),
+        AccountRole::new("destination", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("instructions_sysvar", false, false),
+        AccountRole::new("mint_a", false, false).optional(),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [arbiter, maker, escrow, vault, destination, token_program, instructions_sysvar, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            arbiter,
+            maker,
+            escrow,
+            vault,
+            destination,
+            token_program,
+            instructions_sysvar,
+            mint_a: rest.first(),
+        })
+    }
+}
+
+// settle a disputed escrow: the arbiter sends the whole vault to the taker
+// (`to_taker`) or back to the maker and closes the escrow. No token B moves,
+// the arbiter decides on what happened off-chain
+pub fn arbiter_resolve(
+    program_id: &Pubkey,
+    accounts: ArbiterResolveAccounts,
+    seed: u64,
+    to_taker: bool,
+) -> ProgramResult {
+    arbiter_resolve_at(program_id, accounts, seed, to_taker, current_unix_timestamp()?)
+}
+
+// resolve as of `now`, the clock read is kept in `arbiter_resolve` so the
+// dispute window can be exercised at any time
+pub fn arbiter_resolve_at(
+    program_id: &Pubkey,
+    accounts: ArbiterResolveAccounts,
+    seed: u64,
+    to_taker: bool,
+    now: i64,
+) -> ProgramResult {
+    verbose_msg!("ArbiterResolve instruction: seed={}, to_taker={}", seed, to_taker);
+
+    // verify the arbiter is a signer
+    require_signer(accounts.arbiter, "arbiter")?;
+    check_not_cpi(accounts.instructions_sysvar, program_id)?;
+
+    verify_token_program(accounts.token_program.key())?;
+
+    Escrow::check_live(accounts.escrow)?;
+
+    // verify the escrow account (and load it)
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    if escrow.status()? != EscrowStatus::Active {
+        return Err(EscrowError::InvalidState.into());
+    }
+    if !escrow.has_arbiter() || escrow.arbiter != *accounts.arbiter.key() {
+        return Err(EscrowError::InvalidAuthority.into());
+    }
+    escrow.verify_maker(accounts.maker.key())?;
+
+    // the dispute window closes when the escrow expires, after that the
+    // maker refunds as usual
+    if escrow.is_expired(now) {
+        return Err(EscrowError::EscrowExpired.into());
+    }
+
+    check_distinct_accounts(&[accounts.escrow, accounts.vault, accounts.destination])?;
+
+    let kind = escrow.kind()?;
+    match kind {
+        // the taker is whoever the arbiter names, except the arbiter itself.
+        // A refund goes to a mint A account of the maker
+        EscrowKind::Token => {
+            if to_taker {
+                if !accounts.destination.is_owned_by(accounts.token_program.key()) {
+                    return Err(EscrowError::InvalidTokenProgram.into());
+                }
+                let destination = check_token_account_mint(&accounts.destination.try_borrow_data()?, &escrow.mint_a)?;
+                if destination.owner() == accounts.arbiter.key() {
+                    msg!("the arbiter can't resolve to itself");
+                    return Err(EscrowError::InvalidAuthority.into());
+                }
+            } else {
+                verify_token_account(accounts.destination, &escrow.mint_a, &escrow.maker)?;
+            }
+            if accounts.mint_a.is_some_and(|mint| mint.key() != &escrow.mint_a) {
+                return Err(EscrowError::InvalidTokenMint.into());
+            }
+            verify_vault_authority(accounts.vault, accounts.escrow.key())?;
+        }
+        EscrowKind::Native => {
+            if !to_taker && accounts.destination.key() != &escrow.maker {
+                return Err(EscrowError::InvalidAuthority.into());
+            }
+            if to_taker && accounts.destination.key() == accounts.arbiter.key() {
+                msg!("the arbiter can't resolve to itself");
+                return Err(EscrowError::InvalidAuthority.into());
+            }
+            if !accounts.vault.is_owned_by(program_id) {
+                return Err(EscrowError::InvalidEscrowAccount.into());
+            }
+        }
+    }
+
+    // no re-entry while the transfers below are in flight
+    escrow.begin_processing()?;
+
+    // derive and verify vault address
+    let vault_bump = verify_vault_pda(accounts.vault, accounts.escrow.key(), program_id)?;
+
+    match kind {
+        EscrowKind::Token => {
+            // everything the vault holds goes, so it can be closed
+            let vault_balance = token_account_amount(accounts.vault)?;
+            check_accounts_not_frozen(&[accounts.vault, accounts.destination])?;
+
+            let vault_signer_seeds = &[
+                b"vault" as &[u8],
+                accounts.escrow.key().as_ref(),
+                &[vault_bump],
+            ];
+
+            if vault_balance > 0 {
+                transfer_tokens(
+                    accounts.token_program,
+                    accounts.vault,
+                    accounts.destination,
+                    accounts.escrow,
+                    accounts.mint_a,
+                    vault_balance,
+                    Some(vault_signer_seeds),
+                )?;
+            }
+            harvest_withheld_fees(accounts.token_program, accounts.mint_a, accounts.vault)?;
+
+            token_ops::close(
+                accounts.token_program,
+                accounts.vault,
+                accounts.maker,
+                accounts.escrow,
+                Some(vault_signer_seeds),
+            )?;
+        }
+        // the whole native vault goes, as in take and refund
+        EscrowKind::Native => close_program_account(accounts.vault, accounts.destination)?,
+    }
+
+    escrow.finish(if to_taker { EscrowStatus::Completed } else { EscrowStatus::Cancelled });
+
+    // close the escrow account and return lamports to maker
+    close_program_account(accounts.escrow, accounts.maker)?;
+
+    msg!("Escrow resolved by arbiter");
+    Ok(())
+}
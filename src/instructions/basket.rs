@@ -0,0 +1,570 @@
+use crate::{
+    error::EscrowError,
+    programs::{verify_system_program, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID},
+    state::{close_program_account, BasketEscrow, BasketLeg, EscrowStatus, MAX_BASKET_LEGS},
+    sysvar::{current_rent, current_unix_timestamp},
+    token::{
+        check_token_account_data, check_token_account_mint, sweep_amount, token_account_amount, vault_account_size,
+        vault_rent_exempt_lamports, verify_mint, verify_token_account,
+    },
+};
+use super::{
+    AccountRole,
+    check_distinct_accounts,
+    require_signer,
+    config::check_not_paused,
+    make::{ALLOWED_MINT_B, check_mint_b_allowed, validate_expiry},
+    take::{check_receive_account, verify_token_program_owner},
+    token_ops::{self, transfer_tokens},
+};
+use pinocchio::{
+    account_info::AccountInfo,
+    program::invoke_signed,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+    system_program,
+};
+
+// find the basket account PDA
+pub fn find_basket_address(
+    maker: &Pubkey,
+    seed: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"basket",
+            maker.as_ref(),
+            &seed.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+// find the vault PDA of one basket leg
+pub fn find_basket_vault_address(
+    basket: &Pubkey,
+    index: u8,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"vault",
+            basket.as_ref(),
+            &[index],
+        ],
+        program_id,
+    )
+}
+
+// the `N` accounts of leg `index` in a per-leg account list
+fn leg_accounts<'a, const N: usize>(
+    legs: &'a [AccountInfo],
+    index: usize,
+) -> Result<&'a [AccountInfo; N], ProgramError> {
+    legs.get(index * N..(index + 1) * N)
+        .and_then(|leg| leg.try_into().ok())
+        .ok_or(ProgramError::NotEnoughAccountKeys)
+}
+
+// take and refund legs carry no mint account, which Token-2022 transfers
+// need, so baskets stay on the legacy token program
+fn verify_basket_token_program(token_program: &Pubkey) -> ProgramResult {
+    if token_program != &TOKEN_PROGRAM_ID {
+        return Err(EscrowError::InvalidTokenProgram.into());
+    }
+    Ok(())
+}
+
+// Accounts for the MakeBasket instruction, followed by LEG_ROLES once per leg
+pub struct MakeBasketAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub basket: &'a AccountInfo,
+    pub mint_b: &'a AccountInfo,
+    pub maker_ata_b: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+    // the program's config PDA, checked for a pause
+    pub config: &'a AccountInfo,
+    pub legs: &'a [AccountInfo],
+}
+
+impl<'a> MakeBasketAccounts<'a> {
+    pub const ROLES: [AccountRole; 7] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("basket", false, true),
+        AccountRole::new("mint_b", false, false),
+        AccountRole::new("maker_ata_b", false, false),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("system_program", false, false),
+        AccountRole::new("config", false, false),
+    ];
+
+    pub const LEG_ROLES: [AccountRole; 3] = [
+        AccountRole::new("mint_a", false, false),
+        AccountRole::new("maker_ata_a", false, true),
+        AccountRole::new("vault", false, true),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, basket, mint_b, maker_ata_b, token_program, system_program, config, legs @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            maker,
+            basket,
+            mint_b,
+            maker_ata_b,
+            token_program,
+            system_program,
+            config,
+            legs,
+        })
+    }
+
+    // mint_a, maker_ata_a and vault of leg `index`
+    pub fn leg(&self, index: usize) -> Result<&'a [AccountInfo; 3], ProgramError> {
+        leg_accounts(self.legs, index)
+    }
+}
+
+// Accounts for the TakeBasket instruction, followed by LEG_ROLES once per leg
+pub struct TakeBasketAccounts<'a> {
+    pub taker: &'a AccountInfo,
+    pub maker: &'a AccountInfo,
+    pub basket: &'a AccountInfo,
+    pub taker_ata_b: &'a AccountInfo,
+    pub maker_ata_b: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    // the program's config PDA, checked for a pause
+    pub config: &'a AccountInfo,
+    pub legs: &'a [AccountInfo],
+}
+
+impl<'a> TakeBasketAccounts<'a> {
+    pub const ROLES: [AccountRole; 7] = [
+        AccountRole::new("taker", true, true),
+        AccountRole::new("maker", false, false),
+        AccountRole::new("basket", false, true),
+        AccountRole::new("taker_ata_b", false, true),
+        AccountRole::new("maker_ata_b", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("config", false, false),
+    ];
+
+    pub const LEG_ROLES: [AccountRole; 2] = [
+        AccountRole::new("vault", false, true),
+        AccountRole::new("taker_ata_a", false, true),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [taker, maker, basket, taker_ata_b, maker_ata_b, token_program, config, legs @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            taker,
+            maker,
+            basket,
+            taker_ata_b,
+            maker_ata_b,
+            token_program,
+            config,
+            legs,
+        })
+    }
+
+    // vault and taker_ata_a of leg `index`
+    pub fn leg(&self, index: usize) -> Result<&'a [AccountInfo; 2], ProgramError> {
+        leg_accounts(self.legs, index)
+    }
+}
+
+// Accounts for the RefundBasket instruction, followed by LEG_ROLES once per leg
+pub struct RefundBasketAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub basket: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    pub legs: &'a [AccountInfo],
+}
+
+impl<'a> RefundBasketAccounts<'a> {
+    pub const ROLES: [AccountRole; 3] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("basket", false, true),
+        AccountRole::new("token_program", false, false),
+    ];
+
+    pub const LEG_ROLES: [AccountRole; 2] = [
+        AccountRole::new("vault", false, true),
+        AccountRole::new("maker_ata_a", false, true),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, basket, token_program, legs @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            maker,
+            basket,
+            token_program,
+            legs,
+        })
+    }
+
+    // vault and maker_ata_a of leg `index`
+    pub fn leg(&self, index: usize) -> Result<&'a [AccountInfo; 2], ProgramError> {
+        leg_accounts(self.legs, index)
+    }
+}
+
+// a leg's mint may appear only once, a repeated mint is just a bigger leg
+pub fn check_distinct_mints(legs: &[BasketLeg]) -> ProgramResult {
+    for (i, leg) in legs.iter().enumerate() {
+        if legs[..i].iter().any(|other| other.mint == leg.mint) {
+            return Err(EscrowError::InvalidTokenMint.into());
+        }
+    }
+    Ok(())
+}
+
+// create a basket offering `amounts[i]` of each leg's mint A for
+// `receive_amount` of token B
+pub fn make_basket(
+    program_id: &Pubkey,
+    accounts: MakeBasketAccounts,
+    seed: u64,
+    receive_amount: u64,
+    expiry: i64,
+    amounts: &[u64],
+) -> ProgramResult {
+    verbose_msg!(
+        "MakeBasket instruction: seed={}, receive_amount={}, expiry={}, amounts={:?}",
+        seed,
+        receive_amount,
+        expiry,
+        amounts
+    );
+
+    // Verify the maker is a signer
+    require_signer(accounts.maker, "maker")?;
+
+    // verify programs
+    verify_system_program(accounts.system_program.key())?;
+
+    verify_basket_token_program(accounts.token_program.key())?;
+
+    BasketEscrow::check_leg_count(amounts.len())?;
+
+    // reject baskets that are born expired or never realistically expire
+    if expiry != 0 {
+        validate_expiry(expiry, current_unix_timestamp()?)?;
+    }
+
+    // token B has to be paid into a mint B account of the maker
+    verify_mint(accounts.mint_b, accounts.token_program.key())?;
+    check_mint_b_allowed(accounts.mint_b.key(), ALLOWED_MINT_B)?;
+    check_token_account_data(
+        &accounts.maker_ata_b.try_borrow_data()?,
+        accounts.mint_b.key(),
+        accounts.maker.key(),
+    )?;
+
+    // a paused program creates nothing
+    check_not_paused(accounts.config, program_id)?;
+
+    // derive and verify basket address
+    let (basket_key, basket_bump) = find_basket_address(
+        accounts.maker.key(),
+        seed,
+        program_id,
+    );
+    if basket_key != *accounts.basket.key() {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+
+    // check every leg before anything is created
+    let mut legs = [BasketLeg { mint: [0u8; 32], amount: 0, vault_bump: 0 }; MAX_BASKET_LEGS];
+    for (i, amount) in amounts.iter().enumerate() {
+        let [mint_a, _, vault] = accounts.leg(i)?;
+        verify_mint(mint_a, accounts.token_program.key())?;
+
+        let (vault_key, vault_bump) = find_basket_vault_address(&basket_key, i as u8, program_id);
+        if vault_key != *vault.key() {
+            return Err(EscrowError::InvalidEscrowAccount.into());
+        }
+        legs[i] = BasketLeg { mint: *mint_a.key(), amount: *amount, vault_bump };
+    }
+    let legs = &legs[..amounts.len()];
+    check_distinct_mints(legs)?;
+
+    // create the basket account
+    let rent = current_rent()?;
+    let create_account_ix = system_program::create_account(
+        &SYSTEM_PROGRAM_ID,
+        &[
+            system_program::CreateAccountParams {
+                from: accounts.maker.key(),
+                new_account: accounts.basket.key(),
+                lamports: rent.minimum_balance(BasketEscrow::LEN),
+                space: BasketEscrow::LEN,
+                owner: program_id,
+            },
+        ],
+    )?;
+
+    let seed_bytes = seed.to_le_bytes();
+    let basket_signer_seeds: &[&[u8]] = &[
+        b"basket",
+        accounts.maker.key().as_ref(),
+        &seed_bytes,
+        &[basket_bump],
+    ];
+
+    invoke_signed(
+        &create_account_ix,
+        &[
+            accounts.maker,
+            accounts.basket,
+            accounts.system_program,
+        ],
+        &[basket_signer_seeds],
+    )?;
+
+    BasketEscrow::init(
+        accounts.basket,
+        *accounts.maker.key(),
+        *accounts.mint_b.key(),
+        *accounts.maker_ata_b.key(),
+        legs,
+        receive_amount,
+        expiry,
+        basket_bump,
+    )?;
+
+    // one vault per leg, filled from the maker's mint A account
+    for (i, leg) in legs.iter().enumerate() {
+        let [mint_a, maker_ata_a, vault] = accounts.leg(i)?;
+
+        let vault_size = vault_account_size(
+            accounts.token_program.key(),
+            &mint_a.try_borrow_data()?,
+        )?;
+        let create_vault_ix = system_program::create_account(
+            &SYSTEM_PROGRAM_ID,
+            &[
+                system_program::CreateAccountParams {
+                    from: accounts.maker.key(),
+                    new_account: vault.key(),
+                    lamports: vault_rent_exempt_lamports(accounts.token_program.key(), mint_a, &rent)?,
+                    space: vault_size,
+                    owner: accounts.token_program.key(),
+                },
+            ],
+        )?;
+
+        let index = [i as u8];
+        let vault_signer_seeds: &[&[u8]] = &[
+            b"vault",
+            accounts.basket.key().as_ref(),
+            &index,
+            &[leg.vault_bump],
+        ];
+
+        invoke_signed(
+            &create_vault_ix,
+            &[
+                accounts.maker,
+                vault,
+                accounts.system_program,
+            ],
+            &[vault_signer_seeds],
+        )?;
+
+        // the basket PDA owns every vault
+        token_ops::initialize_account(accounts.token_program, vault, mint_a, accounts.basket.key(), None)?;
+        transfer_tokens(accounts.token_program, maker_ata_a, vault, accounts.maker, Some(mint_a), leg.amount, None)?;
+    }
+
+    msg!("Basket created successfully");
+    Ok(())
+}
+
+// check every leg's vault and the account `destination` picks for it before
+// any tokens move, so a bad leg fails the instruction up front
+fn verify_legs<'a>(
+    program_id: &Pubkey,
+    basket_account: &AccountInfo,
+    basket: &BasketEscrow,
+    token_program: &Pubkey,
+    destination: &impl Fn(usize, &BasketLeg) -> Result<(&'a AccountInfo, &'a AccountInfo), ProgramError>,
+) -> ProgramResult {
+    for (i, leg) in basket.legs().enumerate() {
+        let (vault, to) = destination(i, &leg)?;
+
+        let (vault_key, _) = find_basket_vault_address(basket_account.key(), i as u8, program_id);
+        if vault_key != *vault.key() {
+            return Err(EscrowError::InvalidEscrowAccount.into());
+        }
+        verify_token_program_owner(token_program, &[vault, to])?;
+    }
+    Ok(())
+}
+
+// empty every leg's vault into its destination and close it, signed by the
+// basket PDA. Vault rent goes to `rent_destination`
+fn release_legs<'a>(
+    token_program: &AccountInfo,
+    basket_account: &AccountInfo,
+    basket: &BasketEscrow,
+    seed: u64,
+    rent_destination: &AccountInfo,
+    destination: &impl Fn(usize, &BasketLeg) -> Result<(&'a AccountInfo, &'a AccountInfo), ProgramError>,
+) -> ProgramResult {
+    let seed_bytes = seed.to_le_bytes();
+    let bump = [basket.bump];
+    let basket_signer_seeds: &[&[u8]] = &[
+        b"basket",
+        basket.maker.as_ref(),
+        &seed_bytes,
+        &bump,
+    ];
+
+    for (i, leg) in basket.legs().enumerate() {
+        let (vault, to) = destination(i, &leg)?;
+
+        // everything goes, including dust, so the close below finds it empty
+        let to_send = sweep_amount(token_account_amount(vault)?, leg.amount)?;
+
+        token_ops::transfer(token_program, vault, to, basket_account, to_send, Some(basket_signer_seeds))?;
+        token_ops::close(token_program, vault, rent_destination, basket_account, Some(basket_signer_seeds))?;
+    }
+
+    Ok(())
+}
+
+// load a basket and check it sits at the address derived from its maker and `seed`
+fn load_basket<'a>(
+    account: &'a AccountInfo,
+    seed: u64,
+    program_id: &Pubkey,
+) -> Result<&'a mut BasketEscrow, ProgramError> {
+    let basket = BasketEscrow::from_account(account, program_id)?;
+    let (basket_key, _) = find_basket_address(&basket.maker, seed, program_id);
+    if basket_key != *account.key() {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    Ok(basket)
+}
+
+// take a whole basket: the taker pays receive_amount of token B once and
+// receives every leg
+pub fn take_basket(
+    program_id: &Pubkey,
+    accounts: TakeBasketAccounts,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("TakeBasket instruction: seed={}", seed);
+
+    // verify the taker is a signer
+    require_signer(accounts.taker, "taker")?;
+
+    // verify token program
+    verify_basket_token_program(accounts.token_program.key())?;
+
+    let basket = load_basket(accounts.basket, seed, program_id)?;
+    basket.verify_maker(accounts.maker.key())?;
+
+    // token B can't be paid from the account it is paid into. Leg vaults are
+    // distinct PDAs and leg mints are distinct, so the legs can't collide
+    check_distinct_accounts(&[accounts.basket, accounts.taker_ata_b, accounts.maker_ata_b])?;
+
+    // verify the maker's receive account, and that it still exists
+    if basket.receive_account != *accounts.maker_ata_b.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    check_receive_account(accounts.maker_ata_b, accounts.token_program.key(), &basket.mint_b, &basket.maker)?;
+    verify_token_program_owner(accounts.token_program.key(), &[accounts.taker_ata_b])?;
+
+    // expired baskets can only be refunded
+    if basket.expiry != 0 && basket.is_expired(current_unix_timestamp()?) {
+        return Err(EscrowError::EscrowExpired.into());
+    }
+    check_not_paused(accounts.config, program_id)?;
+
+    // each leg's token A goes to a Taker account of that leg's mint
+    let leg_accounts = |i: usize, leg: &BasketLeg| {
+        let [vault, taker_ata_a] = accounts.leg(i)?;
+        check_token_account_mint(&taker_ata_a.try_borrow_data()?, &leg.mint)?;
+        Ok::<_, ProgramError>((vault, taker_ata_a))
+    };
+    verify_legs(program_id, accounts.basket, basket, accounts.token_program.key(), &leg_accounts)?;
+
+    // no re-entry while the transfers below are in flight
+    basket.begin_processing()?;
+
+    // transfer token B from Taker to Maker, once for the whole basket
+    token_ops::transfer(
+        accounts.token_program,
+        accounts.taker_ata_b,
+        accounts.maker_ata_b,
+        accounts.taker,
+        basket.receive_amount,
+        None,
+    )?;
+
+    // a CPI failing on any leg reverts the whole take
+    release_legs(accounts.token_program, accounts.basket, basket, seed, accounts.taker, &leg_accounts)?;
+
+    basket.finish(EscrowStatus::Completed);
+
+    // close the basket account and return lamports to Taker
+    close_program_account(accounts.basket, accounts.taker)?;
+
+    msg!("Basket completed successfully");
+    Ok(())
+}
+
+// cancel a basket and return every leg to the maker
+pub fn refund_basket(
+    program_id: &Pubkey,
+    accounts: RefundBasketAccounts,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("RefundBasket instruction: seed={}", seed);
+
+    // Verify the maker is a signer
+    require_signer(accounts.maker, "maker")?;
+
+    // Verify token program
+    verify_basket_token_program(accounts.token_program.key())?;
+
+    let basket = load_basket(accounts.basket, seed, program_id)?;
+    basket.verify_maker(accounts.maker.key())?;
+
+    // each leg goes back to a mint A account of the maker
+    let maker = *accounts.maker.key();
+    let leg_accounts = |i: usize, leg: &BasketLeg| {
+        let [vault, maker_ata_a] = accounts.leg(i)?;
+        verify_token_account(maker_ata_a, &leg.mint, &maker)?;
+        Ok::<_, ProgramError>((vault, maker_ata_a))
+    };
+    verify_legs(program_id, accounts.basket, basket, accounts.token_program.key(), &leg_accounts)?;
+
+    // no re-entry while the transfers below are in flight
+    basket.begin_processing()?;
+
+    release_legs(accounts.token_program, accounts.basket, basket, seed, accounts.maker, &leg_accounts)?;
+
+    basket.finish(EscrowStatus::Cancelled);
+
+    // close the basket account and return lamports to maker
+    close_program_account(accounts.basket, accounts.maker)?;
+
+    msg!("Basket refunded successfully");
+    Ok(())
+}
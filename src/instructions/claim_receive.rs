@@ -0,0 +1,125 @@
+use crate::{
+    error::EscrowError,
+    pda::verify_receive_vault_pda,
+    programs::verify_token_program,
+    token::{check_accounts_not_frozen, verify_token_account, TokenAccount},
+};
+use super::{AccountRole, require_signer, check_distinct_accounts, token_ops::{self, harvest_withheld_fees, transfer_tokens}};
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+// Accounts for the ClaimReceive instruction
+pub struct ClaimReceiveAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub receive_vault: &'a AccountInfo,
+    pub maker_ata_b: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    // Token-2022 transfers need the mint, legacy mints may leave it out
+    pub mint_b: Option<&'a AccountInfo>,
+}
+
+impl<'a> ClaimReceiveAccounts<'a> {
+    pub const ROLES: [AccountRole; 5] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("receive_vault", false, true),
+        AccountRole::new("maker_ata_b", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("mint_b", false, false).optional(),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, receive_vault, maker_ata_b, token_program, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            maker,
+            receive_vault,
+            maker_ata_b,
+            token_program,
+            mint_b: rest.first(),
+        })
+    }
+}
+
+// move everything takers paid into the maker's receive vault to one of the
+// maker's mint B accounts and close the vault. The PDA is derived from the
+// signer, so after TransferAuthority the original maker still claims
+pub fn claim_receive(
+    program_id: &Pubkey,
+    accounts: ClaimReceiveAccounts,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("ClaimReceive instruction: seed={}", seed);
+
+    // verify the maker is a signer
+    require_signer(accounts.maker, "maker")?;
+
+    verify_token_program(accounts.token_program.key())?;
+
+    check_distinct_accounts(&[accounts.maker, accounts.receive_vault, accounts.maker_ata_b])?;
+
+    // the receive vault is its own authority, its mint decides the destination
+    if accounts.receive_vault.data_len() == 0 || !accounts.receive_vault.is_owned_by(accounts.token_program.key()) {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    let receive_vault = TokenAccount::from_account_data(&accounts.receive_vault.try_borrow_data()?)?;
+    if receive_vault.owner() != accounts.receive_vault.key() {
+        return Err(EscrowError::InvalidAuthority.into());
+    }
+    let mint_b = *receive_vault.mint();
+    let amount = receive_vault.amount();
+    if accounts.mint_b.is_some_and(|mint| mint.key() != &mint_b) {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    verify_token_account(accounts.maker_ata_b, &mint_b, accounts.maker.key())?;
+
+    if amount != 0 {
+        check_accounts_not_frozen(&[accounts.receive_vault, accounts.maker_ata_b])?;
+    }
+
+    // only the maker the vault was derived for can sign for it
+    let receive_vault_bump = verify_receive_vault_pda(
+        accounts.receive_vault,
+        accounts.maker.key(),
+        seed,
+        program_id,
+    )?;
+    let seed_bytes = seed.to_le_bytes();
+    let receive_vault_signer_seeds: &[&[u8]] = &[
+        b"receive_vault",
+        accounts.maker.key().as_ref(),
+        &seed_bytes,
+        &[receive_vault_bump],
+    ];
+
+    // an unpaid vault is still closed, the maker gets its rent back
+    if amount != 0 {
+        transfer_tokens(
+            accounts.token_program,
+            accounts.receive_vault,
+            accounts.maker_ata_b,
+            accounts.receive_vault,
+            accounts.mint_b,
+            amount,
+            Some(receive_vault_signer_seeds),
+        )?;
+    }
+    harvest_withheld_fees(accounts.token_program, accounts.mint_b, accounts.receive_vault)?;
+
+    token_ops::close(
+        accounts.token_program,
+        accounts.receive_vault,
+        accounts.maker,
+        accounts.receive_vault,
+        Some(receive_vault_signer_seeds),
+    )?;
+
+    msg!("Receive vault claimed successfully");
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use crate::{
+    error::EscrowError,
+    pda::{verify_config_bump, verify_config_pda},
+    programs::{verify_system_program, BPF_LOADER_UPGRADEABLE_ID, SYSTEM_PROGRAM_ID},
+    state::Config,
+    sysvar::current_rent,
+};
+use super::{AccountRole, require_signer};
+use pinocchio::{
+    account_info::AccountInfo,
+    program::invoke_signed,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+    system_program,
+};
+
+// find the program's config PDA
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], program_id)
+}
+
+// find the ProgramData account the upgradeable loader keeps for the program
+pub fn find_program_data_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[program_id.as_ref()], &BPF_LOADER_UPGRADEABLE_ID)
+}
+
+// fail with ProgramPaused while the admin has paused the program. The config
+// has to be the program's config PDA, any other account is refused. Until
+// InitConfig runs, which an immutable deployment never can, the PDA is empty
+// and nothing is paused
+pub fn check_not_paused(config: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+    if config.data_len() == 0 {
+        verify_config_pda(config, program_id)?;
+        return Ok(());
+    }
+    let config_state = Config::from_account(config, program_id)?;
+    verify_config_bump(config, config_state.bump, program_id)?;
+    if config_state.is_paused() {
+        msg!("program is paused");
+        return Err(EscrowError::ProgramPaused.into());
+    }
+    Ok(())
+}
+
+// the upgrade authority in ProgramData account data: a u32 state tag (3 for
+// ProgramData), the deploy slot, then an optional authority, gone once the
+// program was made immutable
+pub fn upgrade_authority(data: &[u8]) -> Option<Pubkey> {
+    if data.len() < 45 || data[0..4] != 3u32.to_le_bytes() || data[12] != 1 {
+        return None;
+    }
+    data[13..45].try_into().ok()
+}
+
+// only the program's upgrade authority can create the config
+fn check_upgrade_authority(program_data: &AccountInfo, admin: &Pubkey, program_id: &Pubkey) -> ProgramResult {
+    if !program_data.is_owned_by(&BPF_LOADER_UPGRADEABLE_ID) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if upgrade_authority(&program_data.try_borrow_data()?).as_ref() != Some(admin) {
+        msg!("only the upgrade authority can initialize the config");
+        return Err(EscrowError::InvalidAuthority.into());
+    }
+    // another program's ProgramData would vouch for its own authority
+    if program_data.key() != &find_program_data_address(program_id).0 {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    Ok(())
+}
+
+// Accounts for the InitConfig instruction
+pub struct InitConfigAccounts<'a> {
+    pub admin: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+    // the program's ProgramData account, naming its upgrade authority
+    pub program_data: &'a AccountInfo,
+}
+
+impl<'a> InitConfigAccounts<'a> {
+    pub const ROLES: [AccountRole; 4] = [
+        AccountRole::new("admin", true, true),
+        AccountRole::new("config", false, true),
+        AccountRole::new("system_program", false, false),
+        AccountRole::new("program_data", false, false),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [admin, config, system_program, program_data, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { admin, config, system_program, program_data })
+    }
+}
+
+// create the config PDA with the signer as admin, unpaused. There is a single
+// config per deployment and only the program's upgrade authority can create
+// it, so nobody can claim it ahead of the deployer
+pub fn init_config(program_id: &Pubkey, accounts: InitConfigAccounts) -> ProgramResult {
+    require_signer(accounts.admin, "admin")?;
+    verify_system_program(accounts.system_program.key())?;
+
+    if Config::check_discriminator(&accounts.config.try_borrow_data()?) {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    check_upgrade_authority(accounts.program_data, accounts.admin.key(), program_id)?;
+    let bump = verify_config_pda(accounts.config, program_id)?;
+
+    let create_config_ix = system_program::create_account(
+        &SYSTEM_PROGRAM_ID,
+        &[
+            system_program::CreateAccountParams {
+                from: accounts.admin.key(),
+                new_account: accounts.config.key(),
+                lamports: current_rent()?.minimum_balance(Config::LEN),
+                space: Config::LEN,
+                owner: program_id,
+            },
+        ],
+    )?;
+
+    let config_signer_seeds: &[&[u8]] = &[b"config", &[bump]];
+
+    invoke_signed(
+        &create_config_ix,
+        &[
+            accounts.admin,
+            accounts.config,
+            accounts.system_program,
+        ],
+        &[config_signer_seeds],
+    )?;
+
+    Config::init(accounts.config, *accounts.admin.key(), bump)?;
+
+    msg!("Config initialized successfully");
+    Ok(())
+}
+
+// Accounts for the SetPaused instruction
+pub struct SetPausedAccounts<'a> {
+    pub admin: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> SetPausedAccounts<'a> {
+    pub const ROLES: [AccountRole; 2] = [
+        AccountRole::new("admin", true, false),
+        AccountRole::new("config", false, true),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [admin, config, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { admin, config })
+    }
+}
+
+// pause or resume new makes and takes, only the config's admin can
+pub fn set_paused(program_id: &Pubkey, accounts: SetPausedAccounts, paused: bool) -> ProgramResult {
+    verbose_msg!("SetPaused instruction: paused={}", paused);
+
+    require_signer(accounts.admin, "admin")?;
+
+    let config = Config::from_account(accounts.config, program_id)?;
+    if &config.admin != accounts.admin.key() {
+        return Err(EscrowError::InvalidAuthority.into());
+    }
+
+    config.paused = paused as u8;
+
+    msg!(if paused { "Program paused" } else { "Program resumed" });
+    Ok(())
+}
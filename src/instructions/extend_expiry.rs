@@ -0,0 +1,80 @@
+use crate::{error::EscrowError, state::{Escrow, EscrowStatus}, sysvar::current_unix_timestamp};
+use super::{AccountRole, require_signer, make::validate_expiry};
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+// Accounts for the ExtendExpiry instruction
+pub struct ExtendExpiryAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub escrow: &'a AccountInfo,
+}
+
+impl<'a> ExtendExpiryAccounts<'a> {
+    pub const ROLES: [AccountRole; 2] = [
+        AccountRole::new("maker", true, false),
+        AccountRole::new("escrow", false, true),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, escrow, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { maker, escrow })
+    }
+}
+
+// push back the expiry of an active offer, no tokens move
+pub fn extend_expiry(
+    program_id: &Pubkey,
+    accounts: ExtendExpiryAccounts,
+    new_expiry: i64,
+    seed: u64,
+) -> ProgramResult {
+    extend_expiry_at(program_id, accounts, new_expiry, seed, current_unix_timestamp()?)
+}
+
+// extend as of `now`, the Clock read is kept in `extend_expiry` so the
+// checks can be exercised at any time
+pub fn extend_expiry_at(
+    program_id: &Pubkey,
+    accounts: ExtendExpiryAccounts,
+    new_expiry: i64,
+    seed: u64,
+    now: i64,
+) -> ProgramResult {
+    verbose_msg!("ExtendExpiry instruction: new_expiry={}, seed={}", new_expiry, seed);
+
+    // verify the maker is a signer
+    require_signer(accounts.maker, "maker")?;
+
+    // verify the escrow account (and load it)
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    escrow.verify_maker(accounts.maker.key())?;
+    if escrow.status()? != EscrowStatus::Active {
+        return Err(EscrowError::InvalidState.into());
+    }
+
+    // expired offers are not revived: takers may already treat them as dead
+    // and the maker can refund and make a new one
+    if escrow.is_expired(now) {
+        return Err(EscrowError::EscrowExpired.into());
+    }
+
+    // only ever later: an offer without an expiry has nothing to extend, and
+    // the new expiry obeys the same horizon as make
+    if new_expiry == 0 || escrow.expiry == 0 || new_expiry <= escrow.expiry {
+        return Err(EscrowError::InvalidExpiry.into());
+    }
+    validate_expiry(new_expiry, now)?;
+
+    escrow.expiry = new_expiry;
+
+    msg!("Escrow expiry extended successfully");
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use crate::{
+    error::EscrowError,
+    programs::verify_token_program,
+    state::{close_program_account, Escrow, EscrowStatus},
+    sysvar::{check_not_cpi, current_unix_timestamp},
+    token::{verify_token_account, TokenAccount},
+};
+use super::{AccountRole, require_signer, check_distinct_accounts, token_ops::{self, harvest_withheld_fees, transfer_tokens}};
+use crate::pda::verify_vault_pda;
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+// Accounts for the ForceClose instruction
+pub struct ForceCloseAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub escrow: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    pub maker_ata_a: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    // a call through another program's CPI is rejected
+    pub instructions_sysvar: &'a AccountInfo,
+    // Token-2022 transfers need the mint, legacy escrows may leave it out
+    pub mint_a: Option<&'a AccountInfo>,
+}
+
+impl<'a> ForceCloseAccounts<'a> {
+    pub const ROLES: [AccountRole; 7] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("escrow", false, true),
+        AccountRole::new("vault", false, true),
+        AccountRole::new("maker_ata_a", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("instructions_sysvar", false, false),
+        AccountRole::new("mint_a", false, false).optional(),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, escrow, vault, maker_ata_a, token_program, instructions_sysvar, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            maker,
+            escrow,
+            vault,
+            maker_ata_a,
+            token_program,
+            instructions_sysvar,
+            mint_a: rest.first(),
+        })
+    }
+}
+
+// what force_close finds at the vault address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbandonedVault {
+    // never created, or already closed: nothing to do
+    Missing,
+    // created but never initialized as a token account, or a native vault
+    ProgramOwned,
+    // an initialized token account holding `amount` of token A
+    Token { amount: u64 },
+    // owned by the token program but not initialized, its rent can't be reclaimed
+    UninitializedToken,
+}
+
+impl AbandonedVault {
+    // classify the vault from its owner and data
+    pub fn classify(
+        owned_by_program: bool,
+        owned_by_token_program: bool,
+        data: &[u8],
+    ) -> Result<Self, ProgramError> {
+        if owned_by_program {
+            return Ok(AbandonedVault::ProgramOwned);
+        }
+        if !owned_by_token_program {
+            return Ok(AbandonedVault::Missing);
+        }
+        match TokenAccount::from_account_data(data) {
+            Ok(account) => Ok(AbandonedVault::Token { amount: account.amount() }),
+            Err(ProgramError::UninitializedAccount) => Ok(AbandonedVault::UninitializedToken),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// only an escrow that is still open can be force closed. A filled escrow
+// (or one mid-take) is never Active, so this can't pull tokens out from
+// under a taker
+pub fn check_force_closable(escrow: &Escrow) -> ProgramResult {
+    if escrow.status()? != EscrowStatus::Active {
+        return Err(EscrowError::InvalidState.into());
+    }
+    Ok(())
+}
+
+// close an escrow and its vault whatever the vault holds, for escrows left
+// behind by a make that didn't complete. Any token A goes to the maker and
+// all rent is reclaimed
+pub fn force_close(
+    program_id: &Pubkey,
+    accounts: ForceCloseAccounts,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("ForceClose instruction: seed={}", seed);
+
+    // verify the maker is a signer
+    require_signer(accounts.maker, "maker")?;
+    check_not_cpi(accounts.instructions_sysvar, program_id)?;
+
+    // verify token program
+    verify_token_program(accounts.token_program.key())?;
+
+    // verify the escrow account (and load it)
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    escrow.verify_maker(accounts.maker.key())?;
+    check_force_closable(escrow)?;
+    escrow.check_not_held(current_unix_timestamp()?)?;
+    if accounts.mint_a.is_some_and(|mint| mint.key() != &escrow.mint_a) {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    
+    // escrow, vault and the maker's token A account must all differ
+    check_distinct_accounts(&[accounts.escrow, accounts.vault, accounts.maker_ata_a])?;
+
+    // no re-entry while the transfers below are in flight
+    escrow.begin_processing()?;
+
+    // derive and verify vault address
+    let vault_bump = verify_vault_pda(accounts.vault, accounts.escrow.key(), program_id)?;
+
+    let vault = AbandonedVault::classify(
+        accounts.vault.is_owned_by(program_id),
+        accounts.vault.is_owned_by(accounts.token_program.key()),
+        &accounts.vault.try_borrow_data()?,
+    )?;
+
+    match vault {
+        AbandonedVault::Missing | AbandonedVault::UninitializedToken => {}
+        AbandonedVault::ProgramOwned => {
+            close_program_account(accounts.vault, accounts.maker)?;
+        }
+        AbandonedVault::Token { amount } => {
+            let vault_signer_seeds = &[
+                b"vault" as &[u8],
+                accounts.escrow.key().as_ref(),
+                &[vault_bump],
+            ];
+
+            // sweep whatever made it into the vault back to the maker, a
+            // Token-2022 transfer fee comes out of what the maker receives
+            if amount > 0 {
+                verify_token_account(accounts.maker_ata_a, &escrow.mint_a, &escrow.maker)?;
+                transfer_tokens(
+                    accounts.token_program,
+                    accounts.vault,
+                    accounts.maker_ata_a,
+                    accounts.escrow,
+                    accounts.mint_a,
+                    amount,
+                    Some(vault_signer_seeds),
+                )?;
+            }
+            harvest_withheld_fees(accounts.token_program, accounts.mint_a, accounts.vault)?;
+
+            token_ops::close(
+                accounts.token_program,
+                accounts.vault,
+                accounts.maker,
+                accounts.escrow,
+                Some(vault_signer_seeds),
+            )?;
+        }
+    }
+
+    escrow.finish(EscrowStatus::Cancelled);
+
+    // close the escrow account and return lamports to maker
+    close_program_account(accounts.escrow, accounts.maker)?;
+
+    msg!("Escrow force closed");
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use crate::state::{Escrow, EscrowStatus};
+use super::AccountRole;
+use pinocchio::{
+    account_info::AccountInfo,
+    program::set_return_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+// Accounts for the GetEscrow instruction
+pub struct GetEscrowAccounts<'a> {
+    pub escrow: &'a AccountInfo,
+}
+
+impl<'a> GetEscrowAccounts<'a> {
+    pub const ROLES: [AccountRole; 1] = [
+        AccountRole::new("escrow", false, false),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [escrow, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { escrow })
+    }
+}
+
+// what GetEscrow writes to return data:
+// maker (0..32), mint_a (32..64), mint_b (64..96), amount (96..104),
+// receive_amount (104..112), status (112)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscrowSnapshot {
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub amount: u64,
+    pub receive_amount: u64,
+    pub status: EscrowStatus,
+}
+
+impl EscrowSnapshot {
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 8 + 1;
+
+    pub fn from_escrow(escrow: &Escrow) -> Result<Self, ProgramError> {
+        Ok(Self {
+            maker: escrow.maker,
+            mint_a: escrow.mint_a,
+            mint_b: escrow.mint_b,
+            amount: escrow.amount,
+            receive_amount: escrow.receive_amount,
+            status: escrow.status()?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut data = [0u8; Self::LEN];
+        data[0..32].copy_from_slice(&self.maker);
+        data[32..64].copy_from_slice(&self.mint_a);
+        data[64..96].copy_from_slice(&self.mint_b);
+        data[96..104].copy_from_slice(&self.amount.to_le_bytes());
+        data[104..112].copy_from_slice(&self.receive_amount.to_le_bytes());
+        data[112] = self.status as u8;
+        data
+    }
+
+    // decode what a caller reads back with get_return_data
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let pubkey = |offset: usize| -> Result<Pubkey, ProgramError> {
+            data[offset..offset + 32]
+                .try_into()
+                .map_err(|_| ProgramError::InvalidAccountData)
+        };
+        let u64_at = |offset: usize| -> Result<u64, ProgramError> {
+            data[offset..offset + 8]
+                .try_into()
+                .map(u64::from_le_bytes)
+                .map_err(|_| ProgramError::InvalidAccountData)
+        };
+        Ok(Self {
+            maker: pubkey(0)?,
+            mint_a: pubkey(32)?,
+            mint_b: pubkey(64)?,
+            amount: u64_at(96)?,
+            receive_amount: u64_at(104)?,
+            status: EscrowStatus::try_from(data[112])?,
+        })
+    }
+}
+
+// write a snapshot of the escrow into return data for CPI callers, read only
+pub fn get_escrow(
+    program_id: &Pubkey,
+    accounts: GetEscrowAccounts,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("GetEscrow instruction: seed={}", seed);
+
+    // only report on escrows this program actually owns
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    let snapshot = EscrowSnapshot::from_escrow(escrow)?;
+
+    set_return_data(&snapshot.to_bytes());
+    Ok(())
+}
@@ -1,29 +1,67 @@
-use crate::{error::EscrowError, state::Escrow};
+use crate::{
+    error::EscrowError,
+    programs::{verify_system_program, verify_token_program, SYSTEM_PROGRAM_ID},
+    state::{price_amount, Escrow, EscrowKind, EscrowStatus},
+    sysvar::{current_rent, current_unix_timestamp},
+    token::{
+        check_accounts_not_frozen, check_token_account_data, check_token_account_mint, current_transfer_fee,
+        mint::MINT_DECIMALS_OFFSET, token_account_amount, vault_account_size,
+        vault_rent_exempt_lamports, verify_mint, NATIVE_MINT,
+    },
+};
+use super::{AccountRole, require_signer, check_distinct_accounts, check_escrow_not_vault, token_ops::{self, sync_native, transfer_tokens}, config::check_not_paused};
+use crate::pda::{verify_namespaced_escrow_pda, verify_receive_vault_pda, verify_vault_pda};
 use pinocchio::{
     account_info::AccountInfo,
-    program::{invoke, invoke_signed},
+    program::{invoke, invoke_signed, set_return_data},
     msg,
     program_error::ProgramError,
     pubkey::Pubkey,
     ProgramResult,
     system_program,
-    spl_token,
+    sysvars::rent::Rent,
 };
 
-// Pinocchio constants
-pub use spl_token::ID as TOKEN_PROGRAM_ID;
-pub use system_program::ID as SYSTEM_PROGRAM_ID;
+// escrow PDA seeds:
+//   default namespace: ["escrow", maker, seed (u64 LE)]
+//   any other namespace: ["escrow", namespace, maker, seed (u64 LE)]
+// the all-zero namespace keeps the original derivation so existing escrows
+// keep their addresses, a frontend picks its own namespace to keep its seeds
+// from colliding with other frontends serving the same maker
+pub const ESCROW_NAMESPACE_LEN: usize = 8;
+pub const DEFAULT_ESCROW_NAMESPACE: [u8; ESCROW_NAMESPACE_LEN] = [0; ESCROW_NAMESPACE_LEN];
 
 // find the escrow account PDA
 pub fn find_escrow_address(
     maker: &Pubkey,
     seed: u64,
     program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    find_namespaced_escrow_address(maker, &DEFAULT_ESCROW_NAMESPACE, seed, program_id)
+}
+
+// find the escrow account PDA within a namespace
+pub fn find_namespaced_escrow_address(
+    maker: &Pubkey,
+    namespace: &[u8; ESCROW_NAMESPACE_LEN],
+    seed: u64,
+    program_id: &Pubkey,
 ) -> (Pubkey, u8) {
     let seed_bytes = seed.to_le_bytes();
+    if namespace == &DEFAULT_ESCROW_NAMESPACE {
+        return Pubkey::find_program_address(
+            &[
+                b"escrow",
+                maker.as_ref(),
+                &seed_bytes,
+            ],
+            program_id,
+        );
+    }
     Pubkey::find_program_address(
         &[
             b"escrow",
+            namespace,
             maker.as_ref(),
             &seed_bytes,
         ],
@@ -45,6 +83,173 @@ pub fn find_vault_address(
     )
 }
 
+// find the maker's receive vault PDA: a mint B token account that owns
+// itself, which take pays into in place of Maker ATA B and ClaimReceive
+// empties. Keyed like the escrow so one seed names both
+pub fn find_receive_vault_address(
+    maker: &Pubkey,
+    seed: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"receive_vault",
+            maker.as_ref(),
+            &seed.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+// furthest an expiry may be set in the future (one year)
+pub const MAX_EXPIRY_HORIZON: i64 = 365 * 24 * 60 * 60;
+
+// an expiry must be 0 (never expires) or lie in (now, now + MAX_EXPIRY_HORIZON]
+pub fn validate_expiry(expiry: i64, now: i64) -> Result<(), ProgramError> {
+    if expiry == 0 {
+        return Ok(());
+    }
+    if expiry <= now || expiry > now.saturating_add(MAX_EXPIRY_HORIZON) {
+        return Err(EscrowError::InvalidExpiry.into());
+    }
+    Ok(())
+}
+
+// most token A (or lamports) a single escrow may hold. Uncapped by default,
+// the `amount-cap` feature takes the cap from the ESCROW_MAX_AMOUNT
+// environment variable at build time
+#[cfg(not(feature = "amount-cap"))]
+pub const MAX_ESCROW_AMOUNT: u64 = u64::MAX;
+#[cfg(feature = "amount-cap")]
+pub const MAX_ESCROW_AMOUNT: u64 = parse_amount_cap(env!("ESCROW_MAX_AMOUNT"));
+
+// decimal u64 parsed at compile time, a malformed cap fails the build
+pub const fn parse_amount_cap(digits: &str) -> u64 {
+    let bytes = digits.as_bytes();
+    assert!(!bytes.is_empty(), "ESCROW_MAX_AMOUNT is empty");
+    let mut value: u64 = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let digit = bytes[i];
+        assert!(digit.is_ascii_digit(), "ESCROW_MAX_AMOUNT is not a decimal number");
+        value = match value.checked_mul(10) {
+            Some(value) => match value.checked_add((digit - b'0') as u64) {
+                Some(value) => value,
+                None => panic!("ESCROW_MAX_AMOUNT does not fit in a u64"),
+            },
+            None => panic!("ESCROW_MAX_AMOUNT does not fit in a u64"),
+        };
+        i += 1;
+    }
+    value
+}
+
+// reject an escrow holding more than `cap`
+pub fn check_amount_cap(amount: u64, cap: u64) -> Result<(), ProgramError> {
+    if amount > cap {
+        return Err(EscrowError::AmountExceedsCap.into());
+    }
+    Ok(())
+}
+
+// token A deposits below 10^(decimals - MIN_AMOUNT_PRECISION) base units,
+// a thousandth of a token by default, are most likely an amount given in
+// whole tokens (1 instead of 1_000_000 for 6 decimals). Make logs a warning,
+// or rejects them with the `strict-amount-precision` feature
+pub const MIN_AMOUNT_PRECISION: u8 = 3;
+
+// smallest amount of a mint with `decimals` that passes the precision check
+pub fn min_precise_amount(decimals: u8, precision: u8) -> u64 {
+    10u64.checked_pow(decimals.saturating_sub(precision) as u32).unwrap_or(u64::MAX)
+}
+
+// warn about (or with `strict` reject) an amount below min_precise_amount
+pub fn check_amount_precision(amount: u64, decimals: u8, precision: u8, strict: bool) -> Result<(), ProgramError> {
+    if amount >= min_precise_amount(decimals, precision) {
+        return Ok(());
+    }
+    if strict {
+        return Err(EscrowError::AmountBelowPrecision.into());
+    }
+    msg!("warning: amount is a tiny fraction of one token, check the mint's decimals");
+    Ok(())
+}
+
+// mint B pubkeys a curated deployment accepts as payment, for example a
+// single stablecoin. Empty accepts any mint B
+pub const ALLOWED_MINT_B: &[Pubkey] = &[];
+
+// reject a mint B outside a non-empty `allowlist`
+pub fn check_mint_b_allowed(mint_b: &Pubkey, allowlist: &[Pubkey]) -> Result<(), ProgramError> {
+    if !allowlist.is_empty() && !allowlist.contains(mint_b) {
+        return Err(EscrowError::MintNotAllowed.into());
+    }
+    Ok(())
+}
+
+// check an account holds at least the rent-exempt minimum for `size` bytes
+pub fn check_rent_exempt(account: &AccountInfo, rent: &Rent, size: usize) -> ProgramResult {
+    if account.lamports() < rent.minimum_balance(size) {
+        msg!("escrow account is not rent exempt");
+        return Err(EscrowError::NotRentExempt.into());
+    }
+    Ok(())
+}
+
+// check the vault make just initialized holds the escrow's recorded mint_a.
+// Both come from accounts.mint_a today; this keeps a refactor from letting
+// them drift apart
+fn verify_created_vault_mint(vault: &AccountInfo, escrow: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+    let mint_a = Escrow::from_account(escrow, program_id)?.mint_a;
+    check_token_account_mint(&vault.try_borrow_data()?, &mint_a).map(|_| ())
+}
+
+// what Make and MakeInit write to return data once the escrow exists:
+// escrow (0..32), escrow bump (32), vault (33..65), vault bump (65)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MakeReturnData {
+    pub escrow: Pubkey,
+    pub escrow_bump: u8,
+    pub vault: Pubkey,
+    pub vault_bump: u8,
+}
+
+impl MakeReturnData {
+    pub const LEN: usize = 32 + 1 + 32 + 1;
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut data = [0u8; Self::LEN];
+        data[0..32].copy_from_slice(&self.escrow);
+        data[32] = self.escrow_bump;
+        data[33..65].copy_from_slice(&self.vault);
+        data[65] = self.vault_bump;
+        data
+    }
+
+    // decode what a caller reads back with get_return_data
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let pubkey = |offset: usize| -> Result<Pubkey, ProgramError> {
+            data[offset..offset + 32]
+                .try_into()
+                .map_err(|_| ProgramError::InvalidAccountData)
+        };
+        Ok(Self {
+            escrow: pubkey(0)?,
+            escrow_bump: data[32],
+            vault: pubkey(33)?,
+            vault_bump: data[65],
+        })
+    }
+
+    // hand the addresses back to the caller
+    fn set(&self) {
+        set_return_data(&self.to_bytes());
+    }
+}
+
 // accounts for Make instruction
 pub struct MakeAccounts<'a> {
     pub maker: &'a AccountInfo,
@@ -55,57 +260,482 @@ pub struct MakeAccounts<'a> {
     pub vault: &'a AccountInfo,
     pub token_program: &'a AccountInfo,
     pub system_program: &'a AccountInfo,
+    // the maker's mint B account, stored as the escrow's receive account. With
+    // the treasury flag, the maker's receive vault PDA for `seed`, created
+    // here when empty
+    pub maker_ata_b: &'a AccountInfo,
+    // the program's config PDA, checked for a pause
+    pub config: &'a AccountInfo,
+    // funds the rent of the escrow and vault, the maker unless another signer is passed
+    pub payer: &'a AccountInfo,
+    // may settle the escrow to either side with ArbiterResolve until it expires
+    pub arbiter: Option<&'a AccountInfo>,
+}
+
+impl<'a> MakeAccounts<'a> {
+    pub const ROLES: [AccountRole; 12] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("mint_a", false, false),
+        AccountRole::new("mint_b", false, false),
+        AccountRole::new("maker_ata_a", false, true),
+        AccountRole::new("escrow", false, true),
+        AccountRole::new("vault", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("system_program", false, false),
+        AccountRole::new("maker_ata_b", false, true),
+        AccountRole::new("config", false, false),
+        AccountRole::new("payer", true, true).optional(),
+        AccountRole::new("arbiter", false, false).optional(),
+    ];
+    
+    // build from the instruction's account list in the documented order,
+    // an eleventh account is taken as the rent payer and a twelfth as the
+    // arbiter
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, mint_a, mint_b, maker_ata_a, escrow, vault, token_program, system_program, maker_ata_b, config, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            maker,
+            mint_a,
+            mint_b,
+            maker_ata_a,
+            escrow,
+            vault,
+            token_program,
+            system_program,
+            maker_ata_b,
+            config,
+            payer: rest.first().unwrap_or(maker),
+            arbiter: rest.get(1),
+        })
+    }
+    
+    // every account in the documented order (ROLES), for bulk checks. The
+    // maker stands in for a payer or arbiter that wasn't passed, it can't
+    // arbitrate its own offer so that slot stays unambiguous
+    pub fn as_account_slice(&self) -> [&'a AccountInfo; 12] {
+        [
+            self.maker,
+            self.mint_a,
+            self.mint_b,
+            self.maker_ata_a,
+            self.escrow,
+            self.vault,
+            self.token_program,
+            self.system_program,
+            self.maker_ata_b,
+            self.config,
+            self.payer,
+            self.arbiter.unwrap_or(self.maker),
+        ]
+    }
+    
+    // the accounts a single-phase make deposits with
+    fn deposit_accounts(&self) -> MakeDepositAccounts<'a> {
+        MakeDepositAccounts {
+            maker: self.maker,
+            escrow: self.escrow,
+            vault: self.vault,
+            maker_ata_a: self.maker_ata_a,
+            token_program: self.token_program,
+            system_program: self.system_program,
+            mint_a: Some(self.mint_a),
+        }
+    }
+}
+
+// accounts for the MakeDeposit instruction
+pub struct MakeDepositAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub escrow: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    pub maker_ata_a: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+    // Token-2022 transfers need the mint, legacy escrows may leave it out
+    pub mint_a: Option<&'a AccountInfo>,
+}
+
+impl<'a> MakeDepositAccounts<'a> {
+    pub const ROLES: [AccountRole; 7] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("escrow", false, false),
+        AccountRole::new("vault", false, true),
+        AccountRole::new("maker_ata_a", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("system_program", false, false),
+        AccountRole::new("mint_a", false, false).optional(),
+    ];
+    
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, escrow, vault, maker_ata_a, token_program, system_program, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { maker, escrow, vault, maker_ata_a, token_program, system_program, mint_a: rest.first() })
+    }
+}
+
+// accounts for the TopUp instruction, MakeDeposit's with the escrow writable
+pub struct TopUpAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub escrow: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    pub maker_ata_a: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+    // Token-2022 transfers need the mint, legacy escrows may leave it out
+    pub mint_a: Option<&'a AccountInfo>,
+}
+
+impl<'a> TopUpAccounts<'a> {
+    pub const ROLES: [AccountRole; 7] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("escrow", false, true),
+        AccountRole::new("vault", false, true),
+        AccountRole::new("maker_ata_a", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("system_program", false, false),
+        AccountRole::new("mint_a", false, false).optional(),
+    ];
+    
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, escrow, vault, maker_ata_a, token_program, system_program, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { maker, escrow, vault, maker_ata_a, token_program, system_program, mint_a: rest.first() })
+    }
+    
+    // the accounts the refill deposits with
+    fn deposit_accounts(&self) -> MakeDepositAccounts<'a> {
+        MakeDepositAccounts {
+            maker: self.maker,
+            escrow: self.escrow,
+            vault: self.vault,
+            maker_ata_a: self.maker_ata_a,
+            token_program: self.token_program,
+            system_program: self.system_program,
+            mint_a: self.mint_a,
+        }
+    }
 }
 
 //create an escrow
+#[allow(clippy::too_many_arguments)]
 pub fn make(
     program_id: &Pubkey,
     accounts: MakeAccounts,
     amount: u64,
     seed: u64,
+    expiry: i64,
+    kind: EscrowKind,
+    receive_amount: u64,
+    memo: &[u8],
+    namespace: [u8; ESCROW_NAMESPACE_LEN],
+    price_num: u64,
+    price_den: u64,
+    min_fill: u64,
+    persistent: bool,
+    treasury: bool,
+) -> ProgramResult {
+    make_at(
+        program_id,
+        accounts,
+        amount,
+        seed,
+        expiry,
+        kind,
+        receive_amount,
+        memo,
+        namespace,
+        price_num,
+        price_den,
+        min_fill,
+        persistent,
+        treasury,
+        current_unix_timestamp()?,
+    )
+}
+
+// make as of `now`, the clock read is kept in `make` so tests can pick the
+// escrow's creation time
+#[allow(clippy::too_many_arguments)]
+pub fn make_at(
+    program_id: &Pubkey,
+    accounts: MakeAccounts,
+    amount: u64,
+    seed: u64,
+    expiry: i64,
+    kind: EscrowKind,
+    receive_amount: u64,
+    memo: &[u8],
+    namespace: [u8; ESCROW_NAMESPACE_LEN],
+    price_num: u64,
+    price_den: u64,
+    min_fill: u64,
+    persistent: bool,
+    treasury: bool,
+    now: i64,
 ) -> ProgramResult {
-    msg!(&format!("Make instruction: amount={}, seed={}", amount, seed));
+    verbose_msg!(
+        "Make instruction: amount={}, seed={}, expiry={}, kind={:?}, receive_amount={}, memo={:?}, namespace={:?}, price={}/{}, min_fill={}, persistent={}, treasury={}",
+        amount,
+        seed,
+        expiry,
+        kind,
+        receive_amount,
+        memo,
+        namespace,
+        price_num,
+        price_den,
+        min_fill,
+        persistent,
+        treasury
+    );
     
-    // Verify the maker is a signer
-    if !accounts.maker.is_signer() {
-        return Err(ProgramError::MissingRequiredSignature);
+    // an overdraw would otherwise only fail inside the deposit CPI, after
+    // the escrow and vault were created. An unreadable balance is left to
+    // the other checks
+    if kind == EscrowKind::Token {
+        check_maker_balance(token_account_amount(accounts.maker_ata_a).ok(), amount)?;
     }
     
+    let created = create_escrow(
+        program_id,
+        &accounts,
+        amount,
+        seed,
+        expiry,
+        kind,
+        receive_amount,
+        memo,
+        namespace,
+        price_num,
+        price_den,
+        min_fill,
+        persistent,
+        treasury,
+        now,
+    )?;
+    deposit(&accounts.deposit_accounts(), accounts.mint_a.key(), kind, amount)?;
+    created.set();
+    
+    msg!("Escrow created successfully");
+    Ok(())
+}
+
+// first phase of a two-phase make: create the escrow and an empty vault
+// without moving any tokens. MakeDeposit funds the vault afterwards and can
+// be retried on its own; until then the escrow can't be taken
+#[allow(clippy::too_many_arguments)]
+pub fn make_init(
+    program_id: &Pubkey,
+    accounts: MakeAccounts,
+    amount: u64,
+    seed: u64,
+    expiry: i64,
+    kind: EscrowKind,
+    receive_amount: u64,
+    memo: &[u8],
+    namespace: [u8; ESCROW_NAMESPACE_LEN],
+    price_num: u64,
+    price_den: u64,
+    min_fill: u64,
+    persistent: bool,
+    treasury: bool,
+) -> ProgramResult {
+    verbose_msg!("MakeInit instruction: amount={}, seed={}, kind={:?}", amount, seed, kind);
+    
+    let created = create_escrow(
+        program_id,
+        &accounts,
+        amount,
+        seed,
+        expiry,
+        kind,
+        receive_amount,
+        memo,
+        namespace,
+        price_num,
+        price_den,
+        min_fill,
+        persistent,
+        treasury,
+        current_unix_timestamp()?,
+    )?;
+    created.set();
+    
+    msg!("Escrow initialized, awaiting deposit");
+    Ok(())
+}
+
+// validate a make and create the escrow and its (empty) vault.
+// Checks run cheapest first so an invalid make fails for as few compute
+// units as possible: signatures and program keys, then the instruction
+// arguments and key comparisons, then reads of account data, and the PDA
+// derivations (find_program_address loops over bumps and costs the most)
+// last, right before the first CPI
+#[allow(clippy::too_many_arguments)]
+fn create_escrow(
+    program_id: &Pubkey,
+    accounts: &MakeAccounts,
+    amount: u64,
+    seed: u64,
+    expiry: i64,
+    kind: EscrowKind,
+    receive_amount: u64,
+    memo: &[u8],
+    namespace: [u8; ESCROW_NAMESPACE_LEN],
+    price_num: u64,
+    price_den: u64,
+    min_fill: u64,
+    persistent: bool,
+    treasury: bool,
+    now: i64,
+) -> Result<MakeReturnData, ProgramError> {
+    // Verify the maker is a signer
+    require_signer(accounts.maker, "maker")?;
+    
+    // the payer's lamports fund the new accounts, so it has to sign too
+    require_signer(accounts.payer, "payer")?;
+    
     // verify programs
-    if accounts.system_program.key().as_ref() != &SYSTEM_PROGRAM_ID {
-        return Err(ProgramError::IncorrectProgramId);
+    verify_system_program(accounts.system_program.key())?;
+    
+    verify_token_program(accounts.token_program.key())?;
+    
+    // a no-op unless the deployment was built with a cap
+    check_amount_cap(amount, MAX_ESCROW_AMOUNT)?;
+    
+    check_escrow_not_vault(accounts.escrow, accounts.vault)?;
+    
+    // the new escrow and vault can't be each other or one of the maker's
+    // accounts. Native escrows don't use Maker ATA A, so it may repeat
+    match kind {
+        EscrowKind::Token => check_distinct_accounts(&[
+            accounts.maker,
+            accounts.maker_ata_a,
+            accounts.maker_ata_b,
+            accounts.escrow,
+            accounts.vault,
+        ])?,
+        EscrowKind::Native => check_distinct_accounts(&[
+            accounts.maker,
+            accounts.maker_ata_b,
+            accounts.escrow,
+            accounts.vault,
+        ])?,
     }
     
-    if accounts.token_program.key().as_ref() != &TOKEN_PROGRAM_ID {
-        return Err(EscrowError::InvalidTokenProgram.into());
+    // native escrows offer SOL, the system program stands in for mint A
+    if kind == EscrowKind::Native && accounts.mint_a.key() != &SYSTEM_PROGRAM_ID {
+        return Err(EscrowError::InvalidTokenMint.into());
     }
+    check_mint_b_allowed(accounts.mint_b.key(), ALLOWED_MINT_B)?;
     
-    // derive and verify escrow address
-    let (escrow_key, escrow_bump) = find_escrow_address(
+    // both sides must be real mints, otherwise the escrow could never be filled
+    if kind == EscrowKind::Token {
+        verify_mint(accounts.mint_a, accounts.token_program.key())?;
+    }
+    verify_mint(accounts.mint_b, accounts.token_program.key())?;
+    
+    // token B is paid into Maker ATA B, an account of another mint or of
+    // someone else would leave an offer the maker can never be paid for.
+    // With `treasury` it is the maker's receive vault instead: an account
+    // that doesn't exist yet is created below, an existing one must be a
+    // mint B account that owns itself. Its address is checked with the other PDAs
+    let create_receive_vault = treasury
+        && accounts.maker_ata_b.data_len() == 0
+        && accounts.maker_ata_b.is_owned_by(&SYSTEM_PROGRAM_ID);
+    let receive_authority = if treasury { accounts.maker_ata_b.key() } else { accounts.maker.key() };
+    if !create_receive_vault && check_token_account_data(
+        &accounts.maker_ata_b.try_borrow_data()?,
+        accounts.mint_b.key(),
+        receive_authority,
+    ).is_err() {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    
+    // optionally refuse mints whose freeze authority could lock the vault
+    #[cfg(feature = "reject-freeze-authority")]
+    if kind == EscrowKind::Token && crate::token::mint_has_freeze_authority(&accounts.mint_a.try_borrow_data()?)? {
+        return Err(EscrowError::MintHasFreezeAuthority.into());
+    }
+    
+    // catch amounts given in whole tokens instead of base units
+    if kind == EscrowKind::Token {
+        let decimals = accounts.mint_a.try_borrow_data()?[MINT_DECIMALS_OFFSET];
+        check_amount_precision(amount, decimals, MIN_AMOUNT_PRECISION, cfg!(feature = "strict-amount-precision"))?;
+    }
+    
+    // the arbiter can act until the escrow expires, so an arbitrated offer
+    // needs an expiry. Makers can't arbitrate their own offers
+    let arbiter = match accounts.arbiter {
+        Some(_) if expiry == 0 => {
+            msg!("an escrow with an arbiter needs an expiry");
+            return Err(EscrowError::InvalidExpiry.into());
+        }
+        Some(arbiter) if arbiter.key() == accounts.maker.key() => {
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+        Some(arbiter) => *arbiter.key(),
+        None => [0u8; 32],
+    };
+    
+    // reject offers that are born expired or never realistically expire
+    validate_expiry(expiry, now)?;
+    
+    // a Token-2022 transfer fee is withheld from the deposit, the escrow
+    // offers what actually reaches the vault
+    let escrowed = match kind {
+        EscrowKind::Token => match current_transfer_fee(accounts.token_program.key(), accounts.mint_a)? {
+            Some(fee) => fee.net_amount(amount)?,
+            None => amount,
+        },
+        EscrowKind::Native => amount,
+    };
+    if escrowed == 0 {
+        return Err(EscrowError::InsufficientVaultBalance.into());
+    }
+    
+    // a priced offer asks for whatever the whole deposit costs at that price,
+    // so snapshots and slippage checks see the same total as a fixed offer
+    let receive_amount = if price_den == 0 {
+        receive_amount
+    } else {
+        price_amount(escrowed, price_num, price_den)?
+    };
+    
+    // a paused program creates nothing
+    check_not_paused(accounts.config, program_id)?;
+    
+    // derive and verify the escrow and vault addresses
+    let escrow_bump = verify_namespaced_escrow_pda(
+        accounts.escrow,
         accounts.maker.key(),
+        &namespace,
         seed,
         program_id,
-    );
-    if escrow_key != *accounts.escrow.key() {
-        return Err(EscrowError::InvalidEscrowAccount.into());
-    }
+    )?;
+    let vault_bump = verify_vault_pda(accounts.vault, accounts.escrow.key(), program_id)?;
+    let receive_vault_bump = if treasury {
+        Some(verify_receive_vault_pda(accounts.maker_ata_b, accounts.maker.key(), seed, program_id)?)
+    } else {
+        None
+    };
     
-    // create the escrow account
+    // create the escrow account, funded with the rent-exempt minimum
     let escrow_size = Escrow::LEN;
-    // Calculate minimum balance for rent exemption (1.5x the size in lamports as approximation)
-    let lamports = ((escrow_size as u64) * 3564480) / 165;
-    
-    // create account instruction data
-    let mut create_account_data = vec![0u8]; // CreateAccount discriminator
-    create_account_data.extend_from_slice(&lamports.to_le_bytes());
-    create_account_data.extend_from_slice(&(escrow_size as u64).to_le_bytes());
-    create_account_data.extend_from_slice(program_id.as_ref());
+    let rent = current_rent()?;
+    let lamports = rent.minimum_balance(escrow_size);
     
     let create_account_ix = system_program::create_account(
         &SYSTEM_PROGRAM_ID,
         &[
             system_program::CreateAccountParams {
-                from: accounts.maker.key(),
+                from: accounts.payer.key(),
                 new_account: accounts.escrow.key(),
                 lamports,
                 space: escrow_size,
@@ -114,23 +744,39 @@ pub fn make(
         ],
     )?;
     
+    // same seeds as find_namespaced_escrow_address
     let seed_bytes = seed.to_le_bytes();
-    let escrow_signer_seeds = &[
-        b"escrow" as &[u8],
+    let escrow_bump_bytes = [escrow_bump];
+    let default_seeds: [&[u8]; 4] = [
+        b"escrow",
         accounts.maker.key().as_ref(),
         &seed_bytes,
-        &[escrow_bump],
+        &escrow_bump_bytes,
     ];
+    let namespaced_seeds: [&[u8]; 5] = [
+        b"escrow",
+        &namespace,
+        accounts.maker.key().as_ref(),
+        &seed_bytes,
+        &escrow_bump_bytes,
+    ];
+    let escrow_signer_seeds: &[&[u8]] = if namespace == DEFAULT_ESCROW_NAMESPACE {
+        &default_seeds
+    } else {
+        &namespaced_seeds
+    };
     
     invoke_signed(
         &create_account_ix,
         &[
-            accounts.maker,
+            accounts.payer,
             accounts.escrow,
             accounts.system_program,
         ],
         &[escrow_signer_seeds],
     )?;
+    // a short create leaves an escrow the runtime can purge, fail here instead
+    check_rent_exempt(accounts.escrow, &rent, escrow_size)?;
     
     // Initialize the escrow state
     Escrow::init(
@@ -138,41 +784,20 @@ pub fn make(
         *accounts.maker.key(),
         *accounts.mint_a.key(),
         *accounts.mint_b.key(),
-        *accounts.maker_ata_a.key(), // This will be the receive account for token B
-        amount,
+        *accounts.maker_ata_b.key(),
+        escrowed,
+        receive_amount,
+        expiry,
         escrow_bump,
-    )?;
-    
-    // derive and verify vault address
-    let (vault_key, vault_bump) = find_vault_address(
-        accounts.escrow.key(),
-        program_id,
-    );
-    if vault_key != *accounts.vault.key() {
-        return Err(EscrowError::InvalidEscrowAccount.into());
-    }
-    
-    // Create vault token account
-    let vault_size = 165; // SPL Token account size
-    let vault_lamports = ((vault_size as u64) * 3564480) / 165;
-    
-    // create vault account instruction data
-    let mut create_vault_data = vec![0u8]; // CreateAccount discriminator
-    create_vault_data.extend_from_slice(&vault_lamports.to_le_bytes());
-    create_vault_data.extend_from_slice(&(vault_size as u64).to_le_bytes());
-    create_vault_data.extend_from_slice(&TOKEN_PROGRAM_ID);
-    
-    let create_vault_ix = system_program::create_account(
-        &SYSTEM_PROGRAM_ID,
-        &[
-            system_program::CreateAccountParams {
-                from: accounts.maker.key(),
-                new_account: accounts.vault.key(),
-                lamports: vault_lamports,
-                space: vault_size,
-                owner: program_id,
-            },
-        ],
+        vault_bump,
+        kind,
+        memo,
+        price_num,
+        price_den,
+        arbiter,
+        now,
+        min_fill,
+        persistent,
     )?;
     
     let vault_signer_seeds = &[
@@ -181,66 +806,352 @@ pub fn make(
         &[vault_bump],
     ];
     
-    invoke_signed(
-        &create_vault_ix,
-        &[
+    match kind {
+        EscrowKind::Token => {
+            // Create vault token account, sized for the mint's token program
+            let vault_size = vault_account_size(
+                accounts.token_program.key(),
+                &accounts.mint_a.try_borrow_data()?,
+            )?;
+            let vault_lamports = vault_rent_exempt_lamports(
+                accounts.token_program.key(),
+                accounts.mint_a,
+                &current_rent()?,
+            )?;
+            
+            let create_vault_ix = system_program::create_account(
+                &SYSTEM_PROGRAM_ID,
+                &[
+                    system_program::CreateAccountParams {
+                        from: accounts.payer.key(),
+                        new_account: accounts.vault.key(),
+                        lamports: vault_lamports,
+                        space: vault_size,
+                        // the token program has to own the account to initialize it
+                        owner: accounts.token_program.key(),
+                    },
+                ],
+            )?;
+            
+            invoke_signed(
+                &create_vault_ix,
+                &[
+                    accounts.payer,
+                    accounts.vault,
+                    accounts.system_program,
+                ],
+                &[vault_signer_seeds],
+            )?;
+            
+            // Initialize vault token account
+            token_ops::initialize_account(
+                accounts.token_program,
+                accounts.vault,
+                accounts.mint_a,
+                accounts.escrow.key(),
+                Some(vault_signer_seeds),
+            )?;
+            verify_created_vault_mint(accounts.vault, accounts.escrow, program_id)?;
+        }
+        EscrowKind::Native => {
+            // the native vault is a data-less account owned by this program.
+            // The payer covers its rent and the maker deposits the offered
+            // lamports on top, so take/refund can move them out directly
+            let vault_rent = current_rent()?.minimum_balance(0);
+            
+            let create_vault_ix = system_program::create_account(
+                &SYSTEM_PROGRAM_ID,
+                &[
+                    system_program::CreateAccountParams {
+                        from: accounts.payer.key(),
+                        new_account: accounts.vault.key(),
+                        lamports: vault_rent,
+                        space: 0,
+                        owner: program_id,
+                    },
+                ],
+            )?;
+            
+            invoke_signed(
+                &create_vault_ix,
+                &[
+                    accounts.payer,
+                    accounts.vault,
+                    accounts.system_program,
+                ],
+                &[vault_signer_seeds],
+            )?;
+        }
+    }
+    
+    // the receive vault is a mint B token account that is its own
+    // authority, so only this program (signing with its seeds) can move
+    // what takers pay into it
+    if let Some(receive_vault_bump) = receive_vault_bump.filter(|_| create_receive_vault) {
+        let receive_vault_signer_seeds: &[&[u8]] = &[
+            b"receive_vault",
+            accounts.maker.key().as_ref(),
+            &seed_bytes,
+            &[receive_vault_bump],
+        ];
+        let receive_vault_size = vault_account_size(
+            accounts.token_program.key(),
+            &accounts.mint_b.try_borrow_data()?,
+        )?;
+        let receive_vault_lamports = vault_rent_exempt_lamports(
+            accounts.token_program.key(),
+            accounts.mint_b,
+            &current_rent()?,
+        )?;
+        
+        let create_receive_vault_ix = system_program::create_account(
+            &SYSTEM_PROGRAM_ID,
+            &[
+                system_program::CreateAccountParams {
+                    from: accounts.payer.key(),
+                    new_account: accounts.maker_ata_b.key(),
+                    lamports: receive_vault_lamports,
+                    space: receive_vault_size,
+                    owner: accounts.token_program.key(),
+                },
+            ],
+        )?;
+        
+        invoke_signed(
+            &create_receive_vault_ix,
+            &[
+                accounts.payer,
+                accounts.maker_ata_b,
+                accounts.system_program,
+            ],
+            &[receive_vault_signer_seeds],
+        )?;
+        
+        token_ops::initialize_account(
+            accounts.token_program,
+            accounts.maker_ata_b,
+            accounts.mint_b,
+            accounts.maker_ata_b.key(),
+            Some(receive_vault_signer_seeds),
+        )?;
+    }
+    
+    Ok(MakeReturnData {
+        escrow: *accounts.escrow.key(),
+        escrow_bump,
+        vault: *accounts.vault.key(),
+        vault_bump,
+    })
+}
+
+// second phase of a two-phase make: move the escrowed amount into the vault.
+// Only the shortfall is deposited, so a retry after a failed (or partial)
+// deposit never overfunds the vault, and a funded vault is left alone
+pub fn make_deposit(
+    program_id: &Pubkey,
+    accounts: MakeDepositAccounts,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("MakeDeposit instruction: seed={}", seed);
+    
+    require_signer(accounts.maker, "maker")?;
+    
+    verify_system_program(accounts.system_program.key())?;
+    verify_token_program(accounts.token_program.key())?;
+    
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    escrow.verify_maker(accounts.maker.key())?;
+    if escrow.status()? != EscrowStatus::Active {
+        return Err(EscrowError::InvalidState.into());
+    }
+    let kind = escrow.kind()?;
+    let mint_a = escrow.mint_a;
+    let amount = escrow.amount;
+    
+    // Maker ATA A is only used by token escrows
+    match kind {
+        EscrowKind::Token => check_distinct_accounts(&[
             accounts.maker,
+            accounts.maker_ata_a,
+            accounts.escrow,
             accounts.vault,
-            accounts.system_program,
-        ],
-        &[vault_signer_seeds],
-    )?;
+        ])?,
+        EscrowKind::Native => check_distinct_accounts(&[accounts.maker, accounts.escrow, accounts.vault])?,
+    }
     
-    // Initialize vault token account
-    // and InitializeAccount3 instruction discriminator
-    let init_data = vec![18u8]; // InitializeAccount3 discriminator
+    verify_vault_pda(accounts.vault, accounts.escrow.key(), program_id)?;
     
-    let init_vault_ix = spl_token::initialize_account(
-        &TOKEN_PROGRAM_ID,
-        &[
-            spl_token::InitializeAccountParams {
-                account: accounts.vault.key(),
-                mint: accounts.mint_a.key(),
-                owner: program_id,
-            },
-        ],
-    )?;
+    // what the vault already holds, a native vault's rent doesn't count
+    let vault_balance = match kind {
+        EscrowKind::Token => check_token_account_mint(&accounts.vault.try_borrow_data()?, &mint_a)?.amount(),
+        EscrowKind::Native => accounts
+            .vault
+            .lamports()
+            .saturating_sub(current_rent()?.minimum_balance(0)),
+    };
     
-    invoke_signed(
-        &init_vault_ix,
-        &[
-            accounts.vault,
-            accounts.mint_a,
-        ],
-        &[vault_signer_seeds],
-    )?;
+    let shortfall = deposit_shortfall(amount, vault_balance);
+    if shortfall == 0 {
+        msg!("Vault already funded");
+        return Ok(());
+    }
     
-    // transfer tokens from maker to vault
-    // transfer instruction: discriminator (1) + amount (8)
-    let mut transfer_data = vec![3u8]; // Transfer discriminator
-    transfer_data.extend_from_slice(&amount.to_le_bytes());
+    // send enough that the shortfall still arrives after a transfer fee
+    let to_deposit = match (kind, accounts.mint_a) {
+        (EscrowKind::Token, Some(mint)) => {
+            if mint.key() != &mint_a {
+                return Err(EscrowError::InvalidTokenMint.into());
+            }
+            match current_transfer_fee(accounts.token_program.key(), mint)? {
+                Some(fee) => fee.gross_amount(shortfall)?,
+                None => shortfall,
+            }
+        }
+        _ => shortfall,
+    };
     
-    let transfer_ix = spl_token::transfer(
-        &TOKEN_PROGRAM_ID,
-        &[
-            spl_token::TransferParams {
-                from: accounts.maker_ata_a.key(),
-                to: accounts.vault.key(),
-                authority: accounts.maker.key(),
-                amount: amount,
-            },
-        ],
-    )?;
+    deposit(&accounts, &mint_a, kind, to_deposit)?;
     
-    invoke(
-        &transfer_ix,
-        &[
+    msg!("Escrow deposit completed");
+    Ok(())
+}
+
+// refill a persistent escrow: the maker sends `amount` more token A (or
+// lamports) into the vault and asks `receive_amount` more token B for it,
+// ignored for a priced offer. Works on a drained escrow as well as on one
+// that still holds part of its offer
+pub fn top_up(
+    program_id: &Pubkey,
+    accounts: TopUpAccounts,
+    amount: u64,
+    receive_amount: u64,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("TopUp instruction: amount={}, receive_amount={}, seed={}", amount, receive_amount, seed);
+    
+    require_signer(accounts.maker, "maker")?;
+    
+    verify_system_program(accounts.system_program.key())?;
+    verify_token_program(accounts.token_program.key())?;
+    
+    if amount == 0 {
+        return Err(EscrowError::ExpectedAmountMismatch.into());
+    }
+    
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    escrow.verify_maker(accounts.maker.key())?;
+    if escrow.status()? != EscrowStatus::Active {
+        return Err(EscrowError::InvalidState.into());
+    }
+    // any other escrow is closed by its last take, there's nothing to refill
+    if !escrow.is_persistent() {
+        msg!("only a persistent escrow can be topped up");
+        return Err(EscrowError::InvalidState.into());
+    }
+    let kind = escrow.kind()?;
+    let mint_a = escrow.mint_a;
+    
+    // Maker ATA A is only used by token escrows
+    match kind {
+        EscrowKind::Token => check_distinct_accounts(&[
+            accounts.maker,
             accounts.maker_ata_a,
+            accounts.escrow,
             accounts.vault,
-            accounts.maker,
-        ],
-    )?;
+        ])?,
+        EscrowKind::Native => check_distinct_accounts(&[accounts.maker, accounts.escrow, accounts.vault])?,
+    }
     
-    msg!("Escrow created successfully");
+    // a Token-2022 transfer fee is withheld from the deposit, the escrow
+    // offers what actually reaches the vault
+    let added = match (kind, accounts.mint_a) {
+        (EscrowKind::Token, Some(mint)) => {
+            if mint.key() != &mint_a {
+                return Err(EscrowError::InvalidTokenMint.into());
+            }
+            match current_transfer_fee(accounts.token_program.key(), mint)? {
+                Some(fee) => fee.net_amount(amount)?,
+                None => amount,
+            }
+        }
+        _ => amount,
+    };
+    if added == 0 {
+        return Err(EscrowError::InsufficientVaultBalance.into());
+    }
+    check_amount_cap(escrow.amount.saturating_add(added), MAX_ESCROW_AMOUNT)?;
+    
+    verify_vault_pda(accounts.vault, accounts.escrow.key(), program_id)?;
+    
+    escrow.top_up(added, receive_amount)?;
+    deposit(&accounts.deposit_accounts(), &mint_a, kind, amount)?;
+    
+    msg!("Escrow topped up");
     Ok(())
-} 
\ No newline at end of file
+}
+
+// reject the make up front when Maker ATA A is known to hold less than the deposit
+pub fn check_maker_balance(balance: Option<u64>, required: u64) -> ProgramResult {
+    match balance {
+        Some(balance) if balance < required => Err(EscrowError::InsufficientMakerBalance.into()),
+        _ => Ok(()),
+    }
+}
+
+// how much a vault holding `vault_balance` still needs to back `amount`
+pub fn deposit_shortfall(amount: u64, vault_balance: u64) -> u64 {
+    amount.saturating_sub(vault_balance)
+}
+
+// move `amount` of token A (or lamports) from the maker into the vault
+fn deposit(
+    accounts: &MakeDepositAccounts,
+    mint_a: &Pubkey,
+    kind: EscrowKind,
+    amount: u64,
+) -> ProgramResult {
+    match kind {
+        EscrowKind::Token => {
+            check_accounts_not_frozen(&[accounts.maker_ata_a, accounts.vault])?;
+            
+            // transfer tokens from maker to vault
+            transfer_tokens(
+                accounts.token_program,
+                accounts.maker_ata_a,
+                accounts.vault,
+                accounts.maker,
+                accounts.mint_a,
+                amount,
+                None,
+            )?;
+            
+            // a wSOL vault reports the deposit only after a sync
+            if mint_a == &NATIVE_MINT {
+                sync_native(accounts.vault)?;
+            }
+        }
+        EscrowKind::Native => {
+            let deposit_ix = system_program::transfer(
+                &SYSTEM_PROGRAM_ID,
+                &[
+                    system_program::TransferParams {
+                        from: accounts.maker.key(),
+                        to: accounts.vault.key(),
+                        lamports: amount,
+                    },
+                ],
+            )?;
+            
+            invoke(
+                &deposit_ix,
+                &[
+                    accounts.maker,
+                    accounts.vault,
+                    accounts.system_program,
+                ],
+            )?;
+        }
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use crate::{
+    error::EscrowError,
+    programs::{verify_system_program, SYSTEM_PROGRAM_ID},
+    state::{Escrow, ESCROW_VERSION},
+    sysvar::current_rent,
+};
+use super::{AccountRole, require_signer, make::{check_rent_exempt, find_vault_address}};
+use pinocchio::{
+    account_info::AccountInfo,
+    program::invoke,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+    system_program,
+};
+
+// Accounts for the MigrateEscrow instruction
+pub struct MigrateEscrowAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub escrow: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+}
+
+impl<'a> MigrateEscrowAccounts<'a> {
+    pub const ROLES: [AccountRole; 3] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("escrow", false, true),
+        AccountRole::new("system_program", false, false),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, escrow, system_program, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { maker, escrow, system_program })
+    }
+}
+
+// bring an escrow written by an older version up to the current layout:
+// grow it to Escrow::LEN, fill the new fields and bump its version.
+// Escrows already at ESCROW_VERSION are left alone
+pub fn migrate_escrow(
+    program_id: &Pubkey,
+    accounts: MigrateEscrowAccounts,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("MigrateEscrow instruction: seed={}", seed);
+
+    // verify the maker is a signer
+    require_signer(accounts.maker, "maker")?;
+
+    // the maker pays for the larger account
+    verify_system_program(accounts.system_program.key())?;
+
+    if !accounts.escrow.is_owned_by(program_id) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // read what the old layout holds before the account is resized
+    let (version, upgraded) = {
+        let data = accounts.escrow.try_borrow_data()?;
+        let version = Escrow::layout_version(&data)?;
+
+        // only the maker can migrate
+        if Escrow::maker_of(&data)? != *accounts.maker.key() {
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+
+        // v0 didn't store the vault bump
+        let upgraded = if version == 0 {
+            let (_, vault_bump) = find_vault_address(accounts.escrow.key(), program_id);
+            Some(Escrow::from_v0(&data, vault_bump)?)
+        } else {
+            None
+        };
+        (version, upgraded)
+    };
+
+    if version == ESCROW_VERSION {
+        msg!("Escrow already at the current version");
+        return Ok(());
+    }
+    if version > ESCROW_VERSION {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // top the escrow up to rent exemption at its new size
+    let rent = current_rent()?;
+    let required = rent.minimum_balance(Escrow::LEN);
+    let top_up = required.saturating_sub(accounts.escrow.lamports());
+    if top_up > 0 {
+        let transfer_ix = system_program::transfer(
+            &SYSTEM_PROGRAM_ID,
+            &[
+                system_program::TransferParams {
+                    from: accounts.maker.key(),
+                    to: accounts.escrow.key(),
+                    lamports: top_up,
+                },
+            ],
+        )?;
+
+        invoke(
+            &transfer_ix,
+            &[
+                accounts.maker,
+                accounts.escrow,
+                accounts.system_program,
+            ],
+        )?;
+    }
+
+    // new bytes start zeroed
+    accounts.escrow.realloc(Escrow::LEN, true)?;
+    check_rent_exempt(accounts.escrow, &rent, Escrow::LEN)?;
+
+    match upgraded {
+        // v0 fields moved, rewrite the whole struct
+        Some(escrow) => escrow.store(accounts.escrow)?,
+        // v1 to v5 only lack trailing fields: the version byte, the arbiter,
+        // which stays zeroed (no arbiter), the creation time, which stays
+        // zeroed (unknown), the minimum fill, which stays zeroed (any), and
+        // the persistent flag, which stays zeroed (closed by the last take)
+        None => accounts.escrow.try_borrow_mut_data()?[Escrow::VERSION_OFFSET] = ESCROW_VERSION,
+    }
+
+    msg!("Escrow migrated successfully");
+    Ok(())
+}
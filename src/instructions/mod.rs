@@ -1,7 +1,89 @@
 pub mod make;
 pub mod take;
 pub mod refund;
+pub mod transfer_authority;
+pub mod get_escrow;
+pub mod force_close;
+pub mod sweep_excess;
+pub mod basket;
+pub mod migrate_escrow;
+pub mod vesting;
+pub mod extend_expiry;
+pub mod claim_receive;
+pub mod arbiter_resolve;
+pub mod token_ops;
+pub mod config;
+
+use pinocchio::{account_info::AccountInfo, msg, program_error::ProgramError, ProgramResult};
+
+// one entry of an instruction's documented account list, kept next to each
+// *Accounts struct so the order, the docs and from_slice stay in sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountRole {
+    pub name: &'static str,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub is_optional: bool,
+}
+
+impl AccountRole {
+    pub const fn new(name: &'static str, is_signer: bool, is_writable: bool) -> Self {
+        Self { name, is_signer, is_writable, is_optional: false }
+    }
+    
+    // trailing account the caller may leave out
+    pub const fn optional(self) -> Self {
+        Self { is_optional: true, ..self }
+    }
+}
+
+// reject the instruction when two accounts that must differ share a key, e.g.
+// a vault passed again as the escrow or as the destination of its own tokens.
+// Accounts that may repeat (a maker paying their own rent) are simply left
+// out of the list
+pub fn check_distinct_accounts(accounts: &[&AccountInfo]) -> ProgramResult {
+    for (i, account) in accounts.iter().enumerate() {
+        if accounts[..i].iter().any(|other| other.key() == account.key()) {
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+    Ok(())
+}
+
+// the escrow and its vault are different PDAs. Checked before either
+// account's data is read, so one account passed as both can't be loaded as
+// an escrow and then debited as its own vault
+pub fn check_escrow_not_vault(escrow: &AccountInfo, vault: &AccountInfo) -> ProgramResult {
+    if escrow.key() == vault.key() {
+        msg!("escrow and vault must be different accounts");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+// fail with MissingRequiredSignature unless `account` signed. The log names
+// the role (as in the *Accounts ROLES), so a failed transaction shows which
+// of several signers was missing
+pub fn require_signer(account: &AccountInfo, role: &str) -> Result<(), ProgramError> {
+    if account.is_signer() {
+        return Ok(());
+    }
+    // two static pieces, no formatting on the failure path
+    msg!("missing required signature:");
+    msg!(role);
+    Err(ProgramError::MissingRequiredSignature)
+}
  
 pub use make::*;
 pub use take::*;
-pub use refund::*; 
\ No newline at end of file
+pub use refund::*;
+pub use transfer_authority::*;
+pub use get_escrow::*;
+pub use force_close::*;
+pub use sweep_excess::*;
+pub use basket::*;
+pub use migrate_escrow::*;
+pub use extend_expiry::*;
+pub use claim_receive::*;
+pub use arbiter_resolve::*;
+pub use config::*;
@@ -1,15 +1,22 @@
-use crate::{error::EscrowError, state::Escrow};
+use crate::{
+    error::EscrowError,
+    programs::{verify_system_program, verify_token_program},
+    state::{close_program_account, Escrow, EscrowKind, EscrowStatus},
+    sysvar::current_unix_timestamp,
+    token::{check_accounts_not_frozen, sweep_amount, token_account_amount, verify_token_account, verify_vault_authority},
+};
 use pinocchio::{
     account_info::AccountInfo,
-    program::{invoke, invoke_signed},
     msg,
     program_error::ProgramError,
     pubkey::Pubkey,
     ProgramResult,
-    spl_token,
 };
 
-use super::make::{TOKEN_PROGRAM_ID, find_vault_address};
+use super::{AccountRole, require_signer, check_distinct_accounts, check_escrow_not_vault, token_ops::{
+    self, harvest_withheld_fees, sync_native, transfer_tokens,
+}};
+use crate::pda::verify_vault_pda;
 
 // Accounts for the fefund instruction
 pub struct RefundAccounts<'a> {
@@ -18,109 +25,215 @@ pub struct RefundAccounts<'a> {
     pub vault: &'a AccountInfo,
     pub maker_ata_a: &'a AccountInfo,
     pub token_program: &'a AccountInfo,
+    // only needed by paths that invoke the system program, checked whenever passed
+    pub system_program: Option<&'a AccountInfo>,
+    // Token-2022 transfers need the mint, legacy escrows may leave it out
+    pub mint_a: Option<&'a AccountInfo>,
 }
 
-// Refund escrow, cancel and return tokens to maker
+impl<'a> RefundAccounts<'a> {
+    pub const ROLES: [AccountRole; 7] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("escrow", false, true),
+        AccountRole::new("vault", false, true),
+        AccountRole::new("maker_ata_a", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("system_program", false, false).optional(),
+        AccountRole::new("mint_a", false, false).optional(),
+    ];
+    
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, escrow, vault, maker_ata_a, token_program, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            maker,
+            escrow,
+            vault,
+            maker_ata_a,
+            token_program,
+            system_program: rest.first(),
+            mint_a: rest.get(1),
+        })
+    }
+    
+    // the required accounts in the documented order (ROLES), for bulk checks
+    pub fn as_account_slice(&self) -> [&'a AccountInfo; 5] {
+        [
+            self.maker,
+            self.escrow,
+            self.vault,
+            self.maker_ata_a,
+            self.token_program,
+        ]
+    }
+}
+
+// Refund escrow, cancel and return tokens to maker. As in make, checks run
+// cheapest first and the vault PDA is derived last, before the first CPI
 pub fn refund(
     program_id: &Pubkey,
     accounts: RefundAccounts,
     amount: u64,
     seed: u64,
 ) -> ProgramResult {
-    msg!(&format!("Refund instruction: amount={}, seed={}", amount, seed));
+    verbose_msg!("Refund instruction: amount={}, seed={}", amount, seed);
     
     // Verify the maker is a signer
-    if !accounts.maker.is_signer() {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
+    require_signer(accounts.maker, "maker")?;
     
     // Verify token program
-    if accounts.token_program.key() != &TOKEN_PROGRAM_ID {
-        return Err(EscrowError::InvalidTokenProgram.into());
+    verify_token_program(accounts.token_program.key())?;
+    
+    if let Some(system_program) = accounts.system_program {
+        verify_system_program(system_program.key())?;
     }
+    
+    check_escrow_not_vault(accounts.escrow, accounts.vault)?;
 
+    // a refund that lost the race against another refund/take sees a drained
+    // or zeroed account
+    Escrow::check_live(accounts.escrow)?;
+    
     // verify the escrow account (and load it)
-    let escrow = Escrow::from_account(accounts.escrow)?;
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    
+    // authorization keys off the current `escrow.maker`, never the maker the
+    // PDA was derived from: after TransferAuthority only the new maker may
+    // refund. For the same reason the escrow address is not re-derived from
+    // the signer here
+    escrow.verify_maker(accounts.maker.key())?;
+    
+    // escrow, vault and the refund account must all differ
+    check_distinct_accounts(&[accounts.escrow, accounts.vault, accounts.maker_ata_a])?;
     
-    // verify if the maker matches
-    if escrow.maker != *accounts.maker.key() {
-        return Err(EscrowError::InvalidAuthority.into());
+    // a make that never got to create the vault, or a vault that is already
+    // closed, leaves nothing to move and only the escrow is closed. The vault
+    // PDA is still verified below, so an empty stand-in can't skip a funded vault
+    let vault_missing = vault_is_missing(accounts.vault);
+    
+    let kind = escrow.kind()?;
+    match kind {
+        // the refund can go to any mint A account of the maker, not only
+        // the one the deposit came from
+        EscrowKind::Token => {
+            verify_token_account(accounts.maker_ata_a, &escrow.mint_a, &escrow.maker)?;
+            if accounts.mint_a.is_some_and(|mint| mint.key() != &escrow.mint_a) {
+                return Err(EscrowError::InvalidTokenMint.into());
+            }
+            // the vault transfer below is signed by the escrow
+            if !vault_missing {
+                verify_vault_authority(accounts.vault, accounts.escrow.key())?;
+            }
+        }
+        EscrowKind::Native => {
+            if !vault_missing && !accounts.vault.is_owned_by(program_id) {
+                return Err(EscrowError::InvalidEscrowAccount.into());
+            }
+        }
     }
+    
+    // work out how much goes back to the maker
+    let now = current_unix_timestamp()?;
+    
+    escrow.check_not_held(now)?;
 
-    // verify if the amount matches
-    if escrow.amount != amount {
-        return Err(EscrowError::ExpectedAmountMismatch.into());
-    }
+    // no re-entry while the transfers below are in flight
+    escrow.begin_processing()?;
     
     // derive and verify vault address
-    let (vault_key, vault_bump) = find_vault_address(
-        accounts.escrow.key(),
-        program_id,
-    );
-    if vault_key != *accounts.vault.key() {
-        return Err(EscrowError::InvalidEscrowAccount.into());
-    }
+    let vault_bump = verify_vault_pda(accounts.vault, accounts.escrow.key(), program_id)?;
     
-    // transfer tokens from vault back to maker
-    let transfer_ix = spl_token::transfer(
-        &TOKEN_PROGRAM_ID,
-        &[
-            spl_token::TransferParams {
-                from: accounts.vault.key(),
-                to: accounts.maker_ata_a.key(),
-                authority: accounts.escrow.key(),
-                amount: escrow.amount,
-            },
-        ],
-    )?;
-    
-    let vault_signer_seeds = &[
-        b"vault" as &[u8],
-        accounts.escrow.key().as_ref(),
-        &[vault_bump],
-    ];
+    match (kind, vault_missing) {
+        (_, true) => {
+            // an active escrow still has to name its amount, the vault just
+            // has nothing to give back
+            refund_amount(escrow, amount, 0, now)?;
+            msg!("vault does not exist, closing the escrow only");
+        }
+        (EscrowKind::Token, false) => {
+            // lamports sent straight to a wSOL vault only count once synced
+            if escrow.is_wrapped_sol() {
+                sync_native(accounts.vault)?;
+            }
+            
+            let vault_balance = token_account_amount(accounts.vault)?;
+            let to_refund = refund_amount(escrow, amount, vault_balance, now)?;
+            
+            // active refunds need the full amount in the vault, expired ones
+            // take whatever is left. Any dust on top goes back too so the
+            // vault can be closed
+            let to_send = sweep_amount(vault_balance, to_refund)?;
+            check_accounts_not_frozen(&[accounts.vault, accounts.maker_ata_a])?;
+        
+            let vault_signer_seeds = &[
+                b"vault" as &[u8],
+                accounts.escrow.key().as_ref(),
+                &[vault_bump],
+            ];
+        
+            // transfer tokens from vault back to maker, a Token-2022 transfer
+            // fee comes out of what the maker receives
+            transfer_tokens(
+                accounts.token_program,
+                accounts.vault,
+                accounts.maker_ata_a,
+                accounts.escrow,
+                accounts.mint_a,
+                to_send,
+                Some(vault_signer_seeds),
+            )?;
+            harvest_withheld_fees(accounts.token_program, accounts.mint_a, accounts.vault)?;
+        
+            //close the vault account
+            token_ops::close(
+                accounts.token_program,
+                accounts.vault,
+                accounts.maker,
+                accounts.escrow,
+                Some(vault_signer_seeds),
+            )?;
+        }
+        (EscrowKind::Native, false) => {
+            // still enforces the amount check on active escrows, the whole
+            // vault (offer plus rent) goes back to the maker either way
+            refund_amount(escrow, amount, escrow.amount, now)?;
+            
+            close_program_account(accounts.vault, accounts.maker)?;
+        }
+    }
     
-    invoke_signed(
-        &transfer_ix,
-        &[
-            accounts.vault,
-            accounts.maker_ata_a,
-            accounts.escrow,
-        ],
-        &[vault_signer_seeds],
-    )?;
-    
-    //close the vault account
-    let close_vault_ix = spl_token::close_account(
-        &TOKEN_PROGRAM_ID,
-        &[
-            spl_token::CloseAccountParams {
-                account: accounts.vault.key(),
-                destination: accounts.maker.key(),
-                authority: accounts.escrow.key(),
-            },
-        ],
-    )?;
-    
-    invoke_signed(
-        &close_vault_ix,
-        &[
-            accounts.vault,
-            accounts.maker,
-            accounts.escrow,
-        ],
-        &[vault_signer_seeds],
-    )?;
+    escrow.finish(EscrowStatus::Cancelled);
     
     // close the escrow account and return lamports to maker
-    let escrow_lamports = accounts.escrow.lamports();
-    *accounts.escrow.try_borrow_mut_lamports()? = 0;
-    *accounts.maker.try_borrow_mut_lamports()? += escrow_lamports;
-    
-    // clear escrow data
-    let mut escrow_data = accounts.escrow.try_borrow_mut_data()?;
-    escrow_data.fill(0);
+    close_program_account(accounts.escrow, accounts.maker)?;
     
     msg!("Escrow refunded successfully");
     Ok(())
+}
+
+// an account that was never created or has been closed: no lamports, no data
+pub fn vault_is_missing(vault: &AccountInfo) -> bool {
+    vault.lamports() == 0 && vault.data_len() == 0
+}
+
+// amount to send back to the maker
+// active escrows must match the expected amount exactly to catch client bugs,
+// expired ones refund whatever the vault holds without re-checking the amount
+pub fn refund_amount(
+    escrow: &Escrow,
+    amount: u64,
+    vault_balance: u64,
+    now: i64,
+) -> Result<u64, ProgramError> {
+    if escrow.is_expired(now) {
+        return Ok(vault_balance);
+    }
+    
+    if escrow.amount != amount {
+        return Err(EscrowError::ExpectedAmountMismatch.into());
+    }
+    
+    Ok(escrow.amount)
 } 
\ No newline at end of file
@@ -0,0 +1,66 @@
+use crate::{state::Escrow, sysvar::current_rent};
+use super::{AccountRole, require_signer};
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+// Accounts for the SweepExcess instruction
+pub struct SweepExcessAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub escrow: &'a AccountInfo,
+}
+
+impl<'a> SweepExcessAccounts<'a> {
+    pub const ROLES: [AccountRole; 2] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("escrow", false, true),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, escrow, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { maker, escrow })
+    }
+}
+
+// lamports an account holds above its rent-exempt minimum
+pub fn excess_lamports(balance: u64, rent_exempt_minimum: u64) -> u64 {
+    balance.saturating_sub(rent_exempt_minimum)
+}
+
+// move lamports the escrow PDA holds above its rent-exempt minimum to the
+// maker, the escrow stays open
+pub fn sweep_excess(
+    program_id: &Pubkey,
+    accounts: SweepExcessAccounts,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("SweepExcess instruction: seed={}", seed);
+
+    // verify the maker is a signer
+    require_signer(accounts.maker, "maker")?;
+
+    // verify the escrow account (and load it)
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    escrow.verify_maker(accounts.maker.key())?;
+
+    let minimum = current_rent()?.minimum_balance(Escrow::LEN);
+    let excess = excess_lamports(accounts.escrow.lamports(), minimum);
+    if excess == 0 {
+        msg!("No excess lamports to sweep");
+        return Ok(());
+    }
+
+    // the escrow is owned by this program, so its lamports move directly
+    *accounts.escrow.try_borrow_mut_lamports()? -= excess;
+    *accounts.maker.try_borrow_mut_lamports()? += excess;
+
+    msg!("Excess lamports swept to maker");
+    Ok(())
+}
@@ -1,15 +1,54 @@
-use crate::{error::EscrowError, state::Escrow};
+use crate::{
+    error::EscrowError,
+    programs::{
+        verify_associated_token_program, verify_system_program, verify_token_program, ASSOCIATED_TOKEN_PROGRAM_ID,
+        SYSTEM_PROGRAM_ID,
+    },
+    state::{close_program_account, Escrow, EscrowKind, EscrowStatus, TakeReceipt},
+    sysvar::{current_rent, current_unix_timestamp},
+    token::{
+        check_accounts_not_frozen, check_token_account_data, check_token_account_mint, sweep_amount,
+        token_account_amount, verify_vault_authority,
+    },
+};
 use pinocchio::{
     account_info::AccountInfo,
-    program::{invoke, invoke_signed},
+    instruction::{AccountMeta, Instruction},
+    program::{invoke, invoke_signed, set_return_data},
     msg,
     program_error::ProgramError,
     pubkey::Pubkey,
     ProgramResult,
-    spl_token,
+    system_program,
 };
 
-use super::make::{TOKEN_PROGRAM_ID, find_vault_address};
+use super::{AccountRole, require_signer, check_distinct_accounts, check_escrow_not_vault, config::check_not_paused, token_ops::{
+    self, harvest_withheld_fees, sync_native, transfer_tokens,
+}};
+use crate::pda::{verify_receipt_pda, verify_vault_pda};
+
+// find the receipt PDA of a take. `created_at` and `filled` are the
+// escrow's creation time and filled amount before the take, so a taker gets a
+// new receipt for every take of a persistent escrow and of one re-made at the
+// same address
+pub fn find_receipt_address(
+    escrow: &Pubkey,
+    taker: &Pubkey,
+    created_at: i64,
+    filled: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"receipt",
+            escrow.as_ref(),
+            taker.as_ref(),
+            &created_at.to_le_bytes(),
+            &filled.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
 
 // Accounts needed for the Take instruction
 pub struct TakeAccounts<'a> {
@@ -23,29 +62,348 @@ pub struct TakeAccounts<'a> {
     pub taker_ata_b: &'a AccountInfo,
     pub maker_ata_b: &'a AccountInfo,
     pub token_program: &'a AccountInfo,
+    // the program's config PDA, checked for a pause
+    pub config: &'a AccountInfo,
+    // receives the vault's token A, the taker's ATA A unless another account is passed
+    pub destination_a: &'a AccountInfo,
+    // only needed by paths that invoke the system program, checked whenever passed
+    pub system_program: Option<&'a AccountInfo>,
+    // lets the taker recreate a closed Maker ATA B (needs the system program)
+    pub associated_token_program: Option<&'a AccountInfo>,
+    // opt-in TakeReceipt PDA, created by take (needs the system program)
+    pub receipt: Option<&'a AccountInfo>,
+}
+
+impl<'a> TakeAccounts<'a> {
+    pub const ROLES: [AccountRole; 13] = [
+        AccountRole::new("taker", true, true),
+        AccountRole::new("maker", false, false),
+        AccountRole::new("escrow", false, true),
+        AccountRole::new("vault", false, true),
+        AccountRole::new("mint_a", false, false),
+        AccountRole::new("mint_b", false, false),
+        AccountRole::new("taker_ata_a", false, true),
+        AccountRole::new("taker_ata_b", false, true),
+        AccountRole::new("maker_ata_b", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("config", false, false),
+        AccountRole::new("destination_a", false, true).optional(),
+        AccountRole::new("system_program", false, false).optional(),
+        AccountRole::new("associated_token_program", false, false).optional(),
+        AccountRole::new("receipt", false, true).optional(),
+    ];
+    
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [taker, maker, escrow, vault, mint_a, mint_b, taker_ata_a, taker_ata_b, maker_ata_b, token_program, config, rest @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            taker,
+            maker,
+            escrow,
+            vault,
+            mint_a,
+            mint_b,
+            taker_ata_a,
+            taker_ata_b,
+            maker_ata_b,
+            token_program,
+            config,
+            destination_a: rest.first().unwrap_or(taker_ata_a),
+            system_program: rest.get(1),
+            associated_token_program: rest.get(2),
+            receipt: rest.get(3),
+        })
+    }
+    
+    // the accounts up to destination_a in the documented order (ROLES), for
+    // bulk checks. The destination is Taker ATA A when none was passed, the
+    // optional accounts after it are left out
+    pub fn as_account_slice(&self) -> [&'a AccountInfo; 12] {
+        [
+            self.taker,
+            self.maker,
+            self.escrow,
+            self.vault,
+            self.mint_a,
+            self.mint_b,
+            self.taker_ata_a,
+            self.taker_ata_b,
+            self.maker_ata_b,
+            self.token_program,
+            self.config,
+            self.destination_a,
+        ]
+    }
 }
 
-// complete an escrow by taking the offer
+// complete an escrow by taking the offer. The taker names the exact amount
+// the escrow holds and fills all of it, see take_available for partial fills.
+// A persistent escrow and its vault stay open, drained, for the maker to refill
 pub fn take(
     program_id: &Pubkey,
     accounts: TakeAccounts,
     amount: u64,
     seed: u64,
+    max_in: u64,
+    expected_receive_amount: u64,
 ) -> ProgramResult {
-    msg!(&format!("Take instruction: amount={}, seed={}", amount, seed));
+    verbose_msg!("Take instruction: amount={}, seed={}, max_in={}", amount, seed, max_in);
     
-    // verify the taker is a signer
-    if !accounts.taker.is_signer() {
-        return Err(ProgramError::MissingRequiredSignature);
+    let (escrow, kind) = validate_take(program_id, &accounts)?;
+    
+    // verify the amount matches. A drained persistent escrow has nothing to
+    // take until the maker refills it
+    if escrow.amount != amount || amount == 0 {
+        return Err(EscrowError::ExpectedAmountMismatch.into());
+    }
+    
+    // don't charge the taker more token B than they signed up for
+    check_slippage(escrow, max_in)?;
+    let receive_amount = escrow.counter_amount(amount)?;
+    
+    // the taker acknowledges the exact price, not just an upper bound
+    if receive_amount != expected_receive_amount {
+        return Err(EscrowError::ExpectedAmountMismatch.into());
     }
     
+    // creating a receipt needs the system program
+    if accounts.receipt.is_some() && accounts.system_program.is_none() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    
+    // expired offers can only be refunded
+    check_not_expired(escrow)?;
+    
+    // a paused program leaves the escrow as is
+    check_not_paused(accounts.config, program_id)?;
+    
+    // no re-entry while the transfers below are in flight
+    escrow.begin_processing()?;
+    
+    // the PDA derivations cost the most, so they run after every cheaper
+    // check and right before the first CPI
+    let vault_bump = verify_vault_pda(accounts.vault, accounts.escrow.key(), program_id)?;
+    let receipt_bump = accounts
+        .receipt
+        .map(|receipt| {
+            verify_receipt_pda(
+                receipt,
+                accounts.escrow.key(),
+                accounts.taker.key(),
+                escrow.created_at(),
+                escrow.filled_amount(),
+                program_id,
+            )
+        })
+        .transpose()?;
+    
+    recreate_maker_receive_ata(&accounts, escrow)?;
+    
+    // lamports sent straight to a wSOL vault only count once synced
+    if escrow.is_wrapped_sol() {
+        sync_native(accounts.vault)?;
+    }
+    
+    // the vault has to hold the full offer before the taker pays
+    escrow.verify_vault_funded(accounts.vault)?;
+    
+    check_take_not_frozen(&accounts, kind)?;
+    pay_maker(&accounts, receive_amount)?;
+    if escrow.is_persistent() {
+        // only the offer leaves the vault, which stays open with the escrow
+        // for the maker to refill
+        release_partial(&accounts, kind, vault_bump, escrow.amount)?;
+    } else {
+        release_vault(&accounts, kind, vault_bump, escrow.amount)?;
+    }
+    
+    // the receipt outlives the escrow closed below, and records the offer
+    // before a persistent escrow is drained
+    if let (Some(receipt), Some(bump)) = (accounts.receipt, receipt_bump) {
+        write_receipt(program_id, &accounts, receipt, bump, escrow, receive_amount)?;
+    }
+    
+    if escrow.is_persistent() {
+        // stays Active with nothing left, refund or force close closes it
+        escrow.record_fill(amount, receive_amount);
+        escrow.finish(EscrowStatus::Active);
+    } else {
+        escrow.finish(EscrowStatus::Completed);
+        
+        // close the escrow account and return lamports to Taker
+        close_program_account(accounts.escrow, accounts.taker)?;
+    }
+    
+    msg!("Escrow completed successfully");
+    Ok(())
+}
+
+// fill up to `desired` of the offer, as much as the escrow and its vault
+// still hold, e.g. after earlier partial takes. The vault still has to cover
+// what the escrow has left, as in take. Token B is charged in proportion to
+// the fill and capped at `max_in`. The filled amount is returned as return
+// data (u64 LE). The escrow stays open until its last token A is taken
+pub fn take_available(
+    program_id: &Pubkey,
+    accounts: TakeAccounts,
+    desired: u64,
+    seed: u64,
+    max_in: u64,
+) -> ProgramResult {
+    verbose_msg!("TakeAvailable instruction: desired={}, seed={}, max_in={}", desired, seed, max_in);
+    
+    let (escrow, kind) = validate_take(program_id, &accounts)?;
+    
+    if desired == 0 {
+        return Err(EscrowError::ExpectedAmountMismatch.into());
+    }
+    
+    // expired offers can only be refunded
+    check_not_expired(escrow)?;
+    
+    // a paused program leaves the escrow as is
+    check_not_paused(accounts.config, program_id)?;
+    
+    // no re-entry while the transfers below are in flight
+    escrow.begin_processing()?;
+    
+    let vault_bump = verify_vault_pda(accounts.vault, accounts.escrow.key(), program_id)?;
+    
+    recreate_maker_receive_ata(&accounts, escrow)?;
+    
+    // lamports sent straight to a wSOL vault only count once synced
+    if escrow.is_wrapped_sol() {
+        sync_native(accounts.vault)?;
+    }
+    
+    // the vault has to back the rest of the offer before it is priced, a
+    // shortfall the escrow never booked isn't the taker's to absorb
+    escrow.verify_vault_funded(accounts.vault)?;
+    
+    // a native vault's rent stays behind with the vault
+    let held = escrow.vault_balance(accounts.vault)?;
+    let filled = available_fill(desired, escrow.amount, held);
+    if filled == 0 {
+        return Err(EscrowError::InsufficientVaultBalance.into());
+    }
+    escrow.check_fill(filled)?;
+    
+    // don't charge the taker more token B than they signed up for
+    let receive_amount = escrow.counter_amount(filled)?;
+    if receive_amount > max_in {
+        return Err(EscrowError::SlippageExceeded.into());
+    }
+    
+    check_take_not_frozen(&accounts, kind)?;
+    pay_maker(&accounts, receive_amount)?;
+    
+    if filled == escrow.amount && !escrow.is_persistent() {
+        // the last of the offer, settle like a full take
+        release_vault(&accounts, kind, vault_bump, filled)?;
+        escrow.record_fill(filled, receive_amount);
+        escrow.finish(EscrowStatus::Completed);
+        close_program_account(accounts.escrow, accounts.taker)?;
+    } else {
+        release_partial(&accounts, kind, vault_bump, filled)?;
+        escrow.record_fill(filled, receive_amount);
+        escrow.finish(EscrowStatus::Active);
+    }
+    
+    set_return_data(&filled.to_le_bytes());
+    
+    msg!("Escrow filled successfully");
+    Ok(())
+}
+
+// token A a take_available fills: what the taker asks for, capped by what
+// the escrow still offers and what its vault actually holds
+pub fn available_fill(desired: u64, remaining: u64, vault_balance: u64) -> u64 {
+    desired.min(remaining).min(vault_balance)
+}
+
+// checks shared by take and take_available, everything but the amount: the
+// programs, the escrow and its receive account, token account owners and
+// mints, and the maker
+fn validate_take<'a>(
+    program_id: &Pubkey,
+    accounts: &TakeAccounts<'a>,
+) -> Result<(&'a mut Escrow, EscrowKind), ProgramError> {
+    // verify the taker is a signer
+    require_signer(accounts.taker, "taker")?;
+    
     // verify token program
-    if accounts.token_program.key() != &TOKEN_PROGRAM_ID {
-        return Err(EscrowError::InvalidTokenProgram.into());
+    verify_token_program(accounts.token_program.key())?;
+    
+    if let Some(system_program) = accounts.system_program {
+        verify_system_program(system_program.key())?;
     }
     
+    check_escrow_not_vault(accounts.escrow, accounts.vault)?;
+    
+    // a second take (or a take after refund) of the same escrow sees a
+    // drained or zeroed account
+    Escrow::check_live(accounts.escrow)?;
+    
     // verify the escrow account (and load it)
-    let escrow = Escrow::from_account(accounts.escrow)?;
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    let kind = escrow.kind()?;
+    
+    // the escrow, the vault and every token account that is debited or
+    // credited must be different accounts. A native escrow has no token A
+    // destination, so that one may repeat another account
+    match kind {
+        EscrowKind::Token => check_distinct_accounts(&[
+            accounts.escrow,
+            accounts.vault,
+            accounts.destination_a,
+            accounts.taker_ata_b,
+            accounts.maker_ata_b,
+        ])?,
+        EscrowKind::Native => check_distinct_accounts(&[
+            accounts.escrow,
+            accounts.vault,
+            accounts.taker_ata_b,
+            accounts.maker_ata_b,
+        ])?,
+    }
+    
+    // verify the maker's receive account, and that the maker hasn't closed it
+    // since making the offer. One the taker recreates is checked once it exists
+    if escrow.receive_account != *accounts.maker_ata_b.key() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if CreateReceiveAta::for_take(accounts)?.is_none() {
+        check_receive_account(accounts.maker_ata_b, accounts.token_program.key(), &escrow.mint_b, &escrow.maker)?;
+    }
+    
+    // every token account must belong to that same token program, mixing
+    // legacy and Token-2022 accounts would only fail later inside a CPI.
+    // A native vault holds lamports and is owned by this program instead.
+    // check_receive_account covers Maker ATA B's owner
+    match kind {
+        EscrowKind::Token => {
+            verify_token_program_owner(
+                accounts.token_program.key(),
+                &[
+                    accounts.vault,
+                    accounts.destination_a,
+                    accounts.taker_ata_b,
+                ],
+            )?;
+            // token A may be routed to any mint A account, e.g. a settlement account
+            check_token_account_mint(&accounts.destination_a.try_borrow_data()?, &escrow.mint_a)?;
+            // the vault transfers below are signed by the escrow
+            verify_vault_authority(accounts.vault, accounts.escrow.key())?;
+        }
+        EscrowKind::Native => {
+            verify_token_program_owner(accounts.token_program.key(), &[accounts.taker_ata_b])?;
+            if !accounts.vault.is_owned_by(program_id) {
+                return Err(EscrowError::InvalidEscrowAccount.into());
+            }
+        }
+    }
     
     // verify the maker matches
     if escrow.maker != *accounts.maker.key() {
@@ -57,107 +415,330 @@ pub fn take(
         return Err(EscrowError::InvalidTokenMint.into());
     }
     
-    // verify the maker's receive account
-    if escrow.receive_account != *accounts.maker_ata_b.key() {
-        return Err(ProgramError::InvalidAccountData);
-    }
+    Ok((escrow, kind))
+}
 
-    // verify the amount matches
-    if escrow.amount != amount {
-        return Err(EscrowError::ExpectedAmountMismatch.into());
+// the clock is only read for escrows that can expire
+fn check_not_expired(escrow: &Escrow) -> ProgramResult {
+    if escrow.expiry != 0 && escrow.is_expired(current_unix_timestamp()?) {
+        return Err(EscrowError::EscrowExpired.into());
     }
-    
-    // derive and verify vault address
-    let (vault_key, vault_bump) = find_vault_address(
-        accounts.escrow.key(),
-        program_id,
-    );
-    if vault_key != *accounts.vault.key() {
-        return Err(EscrowError::InvalidEscrowAccount.into());
+    Ok(())
+}
+
+// every token account a take moves tokens through, checked before the first CPI
+fn check_take_not_frozen(accounts: &TakeAccounts, kind: EscrowKind) -> ProgramResult {
+    match kind {
+        EscrowKind::Token => check_accounts_not_frozen(&[
+            accounts.taker_ata_b,
+            accounts.maker_ata_b,
+            accounts.vault,
+            accounts.destination_a,
+        ]),
+        EscrowKind::Native => check_accounts_not_frozen(&[accounts.taker_ata_b, accounts.maker_ata_b]),
     }
-    
-    // transfer token B from Taker to Maker
-    let transfer_b_ix = spl_token::transfer(
-        &TOKEN_PROGRAM_ID,
-        &[
-            spl_token::TransferParams {
-                from: accounts.taker_ata_b.key(),
-                to: accounts.maker_ata_b.key(),
-                authority: accounts.taker.key(),
-                amount: escrow.amount,
-            },
-        ],
+}
+
+// transfer token B from Taker to Maker. A Token-2022 transfer fee on mint B
+// is withheld from what the maker receives, the taker sends receive_amount
+fn pay_maker(accounts: &TakeAccounts, receive_amount: u64) -> ProgramResult {
+    // fail with a clear error if the taker can't pay, rather than mid-CPI.
+    // An unreadable balance skips the check and leaves it to the token program
+    check_taker_balance(
+        token_account_amount(accounts.taker_ata_b).ok(),
+        receive_amount,
     )?;
     
-    invoke(
-        &transfer_b_ix,
-        &[
-            accounts.taker_ata_b,
-            accounts.maker_ata_b,
-            accounts.taker,
-        ],
+    transfer_tokens(
+        accounts.token_program,
+        accounts.taker_ata_b,
+        accounts.maker_ata_b,
+        accounts.taker,
+        Some(accounts.mint_b),
+        receive_amount,
+        None,
     )?;
+    Ok(())
+}
+
+// hand the whole vault to the taker and close it, `owed` is the token A
+// the vault has to cover
+fn release_vault(
+    accounts: &TakeAccounts,
+    kind: EscrowKind,
+    vault_bump: u8,
+    owed: u64,
+) -> ProgramResult {
+    match kind {
+        EscrowKind::Token => {
+            // the taker gets the whole vault, including any dust above the
+            // offer, so the close below always finds it empty
+            let to_send = sweep_amount(token_account_amount(accounts.vault)?, owed)?;
+            transfer_from_vault(accounts, vault_bump, to_send)?;
+            
+            // fees withheld on the deposit would block the close
+            harvest_withheld_fees(accounts.token_program, Some(accounts.mint_a), accounts.vault)?;
+            
+            // close the vault account
+            token_ops::close(
+                accounts.token_program,
+                accounts.vault,
+                accounts.taker,
+                accounts.escrow,
+                Some(&vault_signer_seeds(accounts, &[vault_bump])),
+            )?;
+        }
+        EscrowKind::Native => {
+            // the vault is owned by this program, hand its lamports (offer plus
+            // rent) straight to the Taker
+            close_program_account(accounts.vault, accounts.taker)?;
+        }
+    }
+    Ok(())
+}
+
+// move `filled` token A (or lamports) from the vault to the taker, the vault
+// stays open for the rest of the offer
+fn release_partial(
+    accounts: &TakeAccounts,
+    kind: EscrowKind,
+    vault_bump: u8,
+    filled: u64,
+) -> ProgramResult {
+    match kind {
+        EscrowKind::Token => transfer_from_vault(accounts, vault_bump, filled),
+        EscrowKind::Native => {
+            *accounts.vault.try_borrow_mut_lamports()? -= filled;
+            *accounts.taker.try_borrow_mut_lamports()? += filled;
+            Ok(())
+        }
+    }
+}
+
+// transfer token A from the vault to the Taker's chosen destination. A
+// Token-2022 transfer fee on mint A is withheld from what the taker receives
+fn transfer_from_vault(accounts: &TakeAccounts, vault_bump: u8, amount: u64) -> ProgramResult {
+    transfer_tokens(
+        accounts.token_program,
+        accounts.vault,
+        accounts.destination_a,
+        accounts.escrow,
+        Some(accounts.mint_a),
+        amount,
+        Some(&vault_signer_seeds(accounts, &[vault_bump])),
+    )?;
+    Ok(())
+}
+
+// create the taker's receipt PDA, already verified to have `bump`, and
+// record the settled take in it. The taker pays its rent. Runs before the
+// fill is booked, so the seeds match the ones verified
+fn write_receipt(
+    program_id: &Pubkey,
+    accounts: &TakeAccounts,
+    receipt: &AccountInfo,
+    bump: u8,
+    escrow: &Escrow,
+    receive_amount: u64,
+) -> ProgramResult {
+    let system_program = accounts.system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
     
-    // transfer token A from vault to Taker
-    let transfer_a_ix = spl_token::transfer(
-        &TOKEN_PROGRAM_ID,
+    let create_receipt_ix = system_program::create_account(
+        &SYSTEM_PROGRAM_ID,
         &[
-            spl_token::TransferParams {
-                from: accounts.vault.key(),
-                to: accounts.taker_ata_a.key(),
-                authority: accounts.escrow.key(),
-                amount: escrow.amount,
+            system_program::CreateAccountParams {
+                from: accounts.taker.key(),
+                new_account: receipt.key(),
+                lamports: current_rent()?.minimum_balance(TakeReceipt::LEN),
+                space: TakeReceipt::LEN,
+                owner: program_id,
             },
         ],
     )?;
     
-    let vault_signer_seeds = &[
-        b"vault" as &[u8],
+    let created_at = escrow.created_at().to_le_bytes();
+    let filled = escrow.filled_amount().to_le_bytes();
+    let receipt_signer_seeds: &[&[u8]] = &[
+        b"receipt",
         accounts.escrow.key().as_ref(),
-        &[vault_bump],
+        accounts.taker.key().as_ref(),
+        &created_at,
+        &filled,
+        &[bump],
     ];
     
     invoke_signed(
-        &transfer_a_ix,
+        &create_receipt_ix,
         &[
-            accounts.vault,
-            accounts.taker_ata_a,
-            accounts.escrow,
+            accounts.taker,
+            receipt,
+            system_program,
         ],
-        &[vault_signer_seeds],
+        &[receipt_signer_seeds],
     )?;
     
-    // close the vault account
-    let close_vault_ix = spl_token::close_account(
-        &TOKEN_PROGRAM_ID,
-        &[
-            spl_token::CloseAccountParams {
-                account: accounts.vault.key(),
-                destination: accounts.taker.key(),
-                authority: accounts.escrow.key(),
-            },
-        ],
-    )?;
+    TakeReceipt::init(
+        receipt,
+        TakeReceipt {
+            discriminator: TakeReceipt::DISCRIMINATOR,
+            escrow: *accounts.escrow.key(),
+            maker: escrow.maker,
+            taker: *accounts.taker.key(),
+            mint_a: escrow.mint_a,
+            mint_b: escrow.mint_b,
+            amount: escrow.amount,
+            receive_amount,
+            timestamp: current_unix_timestamp()?,
+            bump,
+        },
+    )
+}
+
+// the associated token program's CreateIdempotent for a Maker ATA B the
+// maker closed, the taker paying its rent. The instruction's metas and the
+// account list passed to invoke are both built from these fields
+pub struct CreateReceiveAta<'a> {
+    pub funder: &'a AccountInfo,
+    pub account: &'a AccountInfo,
+    pub wallet: &'a AccountInfo,
+    pub mint: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+}
+
+impl<'a> CreateReceiveAta<'a> {
+    // the ATA program's CreateIdempotent discriminator
+    pub const DATA: [u8; 1] = [1];
     
-    invoke_signed(
-        &close_vault_ix,
-        &[
-            accounts.vault,
-            accounts.taker,
-            accounts.escrow,
-        ],
-        &[vault_signer_seeds],
-    )?;
+    // the creation a take asks for by passing the associated token program,
+    // None while Maker ATA B still exists. The ATA program derives the
+    // address from the maker and mint B, which validate_take checks against
+    // the escrow, so only the maker's own mint B ATA can be created
+    pub fn for_take(accounts: &TakeAccounts<'a>) -> Result<Option<Self>, ProgramError> {
+        let (Some(associated_token_program), Some(system_program)) =
+            (accounts.associated_token_program, accounts.system_program)
+        else {
+            return Ok(None);
+        };
+        if accounts.maker_ata_b.data_len() != 0 {
+            return Ok(None);
+        }
+        verify_associated_token_program(associated_token_program.key())?;
+        Ok(Some(Self {
+            funder: accounts.taker,
+            account: accounts.maker_ata_b,
+            wallet: accounts.maker,
+            mint: accounts.mint_b,
+            system_program,
+            token_program: accounts.token_program,
+        }))
+    }
     
-    // close the escrow account and return lamports to Taker
-    let escrow_lamports = accounts.escrow.lamports();
-    *accounts.escrow.try_borrow_mut_lamports()? = 0;
-    *accounts.taker.try_borrow_mut_lamports()? += escrow_lamports;
+    // the accounts handed to invoke, in the order the instruction lists them
+    pub fn accounts(&self) -> [&'a AccountInfo; 6] {
+        [
+            self.funder,
+            self.account,
+            self.wallet,
+            self.mint,
+            self.system_program,
+            self.token_program,
+        ]
+    }
     
-    // clear the escrow data
-    let mut escrow_data = accounts.escrow.try_borrow_mut_data()?;
-    escrow_data.fill(0);
+    // the funder pays the rent of the new account
+    pub fn metas(&self) -> [AccountMeta<'a>; 6] {
+        [
+            AccountMeta::writable_signer(self.funder.key()),
+            AccountMeta::writable(self.account.key()),
+            AccountMeta::readonly(self.wallet.key()),
+            AccountMeta::readonly(self.mint.key()),
+            AccountMeta::readonly(self.system_program.key()),
+            AccountMeta::readonly(self.token_program.key()),
+        ]
+    }
     
-    msg!("Escrow completed successfully");
+    pub fn invoke(&self) -> ProgramResult {
+        let metas = self.metas();
+        let ix = Instruction {
+            program_id: &ASSOCIATED_TOKEN_PROGRAM_ID,
+            data: &Self::DATA,
+            accounts: &metas,
+        };
+        invoke(&ix, &self.accounts())
+    }
+}
+
+// create Maker ATA B again when the taker asked for it, then check the new
+// account like any other receive account. Runs once the escrow is known to be
+// live and unpaused and its PDAs check out, so a take that fails those
+// creates nothing
+fn recreate_maker_receive_ata(accounts: &TakeAccounts, escrow: &Escrow) -> ProgramResult {
+    let Some(create_ata) = CreateReceiveAta::for_take(accounts)? else {
+        return Ok(());
+    };
+    create_ata.invoke()?;
+    msg!("Created maker receive account");
+    check_receive_account(accounts.maker_ata_b, accounts.token_program.key(), &escrow.mint_b, &escrow.maker)
+}
+
+fn vault_signer_seeds<'a>(accounts: &'a TakeAccounts, bump: &'a [u8; 1]) -> [&'a [u8]; 3] {
+    [
+        b"vault",
+        accounts.escrow.key().as_ref(),
+        bump,
+    ]
+}
+
+// the receive account must still be a mint B token account of the maker,
+// otherwise the token B transfer would fail inside the CPI with no useful error.
+// A receive vault owns itself, make only accepts one at the maker's PDA
+pub fn check_receive_account(
+    account: &AccountInfo,
+    token_program: &Pubkey,
+    mint_b: &Pubkey,
+    maker: &Pubkey,
+) -> ProgramResult {
+    if account.data_len() == 0 || !account.is_owned_by(token_program) {
+        msg!("maker receive account is closed");
+        return Err(EscrowError::InvalidState.into());
+    }
+    let data = account.try_borrow_data()?;
+    if check_token_account_data(&data, mint_b, maker).is_err()
+        && check_token_account_data(&data, mint_b, account.key()).is_err()
+    {
+        msg!("maker receive account is not a mint B account of the maker");
+        return Err(EscrowError::InvalidState.into());
+    }
     Ok(())
-} 
\ No newline at end of file
+}
+
+// reject the take if the maker now asks for more token B than `max_in`
+pub fn check_slippage(escrow: &Escrow, max_in: u64) -> ProgramResult {
+    if escrow.counter_amount(escrow.amount)? > max_in {
+        return Err(EscrowError::SlippageExceeded.into());
+    }
+    Ok(())
+}
+
+// reject the take up front when the taker's token B balance is known to be short
+pub fn check_taker_balance(balance: Option<u64>, required: u64) -> ProgramResult {
+    match balance {
+        Some(balance) if balance < required => Err(EscrowError::InsufficientTakerBalance.into()),
+        _ => Ok(()),
+    }
+}
+
+// check that all token accounts are owned by the given token program
+pub fn verify_token_program_owner(
+    token_program: &Pubkey,
+    token_accounts: &[&AccountInfo],
+) -> ProgramResult {
+    if token_accounts
+        .iter()
+        .any(|account| !account.is_owned_by(token_program))
+    {
+        return Err(EscrowError::InvalidTokenProgram.into());
+    }
+    Ok(())
+}
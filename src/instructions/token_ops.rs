@@ -0,0 +1,284 @@
+// token program CPIs the handlers share. Each op names its accounts once, the
+// instruction and the account list passed to invoke are both built from
+// them, so the two can't drift apart
+use crate::{
+    programs::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID},
+    sysvar::current_epoch,
+    token::{check_mint_data, current_transfer_fee, mint::MINT_DECIMALS_OFFSET, mint_transfer_fee},
+};
+use pinocchio::{
+    account_info::AccountInfo,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+    spl_token,
+};
+
+// most accounts a single op hands to invoke
+pub const MAX_OP_ACCOUNTS: usize = 4;
+
+// the accounts of one op in instruction order, kept inline so no CPI
+// allocates
+#[derive(Clone, Copy)]
+pub struct OpAccounts<'a> {
+    accounts: [&'a AccountInfo; MAX_OP_ACCOUNTS],
+    len: usize,
+}
+
+impl<'a> OpAccounts<'a> {
+    fn new(list: &[&'a AccountInfo]) -> Self {
+        // the unused slots repeat the first account and are never read
+        let mut accounts = [list[0]; MAX_OP_ACCOUNTS];
+        accounts[..list.len()].copy_from_slice(list);
+        Self { accounts, len: list.len() }
+    }
+
+    pub fn as_slice(&self) -> &[&'a AccountInfo] {
+        &self.accounts[..self.len]
+    }
+}
+
+// one token program instruction and the accounts it touches
+#[derive(Clone, Copy)]
+pub enum TokenOp<'a> {
+    // legacy Transfer of `amount` signed by `authority`
+    Transfer {
+        from: &'a AccountInfo,
+        to: &'a AccountInfo,
+        authority: &'a AccountInfo,
+        amount: u64,
+    },
+    // Token-2022 TransferChecked, or TransferCheckedWithFee when the mint
+    // withholds `fee` from the transfer
+    TransferChecked {
+        from: &'a AccountInfo,
+        mint: &'a AccountInfo,
+        to: &'a AccountInfo,
+        authority: &'a AccountInfo,
+        amount: u64,
+        decimals: u8,
+        fee: Option<u64>,
+    },
+    // CloseAccount, the rent goes to `destination`
+    Close {
+        account: &'a AccountInfo,
+        destination: &'a AccountInfo,
+        authority: &'a AccountInfo,
+    },
+    // InitializeAccount for `mint`, owned by `owner`
+    InitializeAccount {
+        account: &'a AccountInfo,
+        mint: &'a AccountInfo,
+        owner: &'a Pubkey,
+    },
+    // HarvestWithheldTokensToMint from a single account
+    HarvestWithheld {
+        mint: &'a AccountInfo,
+        account: &'a AccountInfo,
+    },
+}
+
+impl<'a> TokenOp<'a> {
+    // the accounts handed to invoke, in the order the instruction lists them
+    pub fn accounts(&self) -> OpAccounts<'a> {
+        match *self {
+            TokenOp::Transfer { from, to, authority, .. } => OpAccounts::new(&[from, to, authority]),
+            TokenOp::TransferChecked { from, mint, to, authority, .. } => OpAccounts::new(&[from, mint, to, authority]),
+            TokenOp::Close { account, destination, authority } => OpAccounts::new(&[account, destination, authority]),
+            TokenOp::InitializeAccount { account, mint, .. } => OpAccounts::new(&[account, mint]),
+            TokenOp::HarvestWithheld { mint, account } => OpAccounts::new(&[mint, account]),
+        }
+    }
+
+    // build the instruction for `token_program` and invoke it, signed with
+    // `signer_seeds` when the authority is a PDA of this program
+    pub fn invoke(&self, token_program: &AccountInfo, signer_seeds: Option<&[&[u8]]>) -> ProgramResult {
+        let ix = match *self {
+            TokenOp::Transfer { from, to, authority, amount } => spl_token::transfer(
+                token_program.key(),
+                &[
+                    spl_token::TransferParams {
+                        from: from.key(),
+                        to: to.key(),
+                        authority: authority.key(),
+                        amount,
+                    },
+                ],
+            )?,
+            TokenOp::TransferChecked { from, mint, to, authority, amount, decimals, fee: Some(fee) } => spl_token::transfer_checked_with_fee(
+                token_program.key(),
+                &[
+                    spl_token::TransferCheckedWithFeeParams {
+                        from: from.key(),
+                        mint: mint.key(),
+                        to: to.key(),
+                        authority: authority.key(),
+                        amount,
+                        decimals,
+                        fee,
+                    },
+                ],
+            )?,
+            TokenOp::TransferChecked { from, mint, to, authority, amount, decimals, fee: None } => spl_token::transfer_checked(
+                token_program.key(),
+                &[
+                    spl_token::TransferCheckedParams {
+                        from: from.key(),
+                        mint: mint.key(),
+                        to: to.key(),
+                        authority: authority.key(),
+                        amount,
+                        decimals,
+                    },
+                ],
+            )?,
+            TokenOp::Close { account, destination, authority } => spl_token::close_account(
+                token_program.key(),
+                &[
+                    spl_token::CloseAccountParams {
+                        account: account.key(),
+                        destination: destination.key(),
+                        authority: authority.key(),
+                    },
+                ],
+            )?,
+            TokenOp::InitializeAccount { account, mint, owner } => spl_token::initialize_account(
+                token_program.key(),
+                &[
+                    spl_token::InitializeAccountParams {
+                        account: account.key(),
+                        mint: mint.key(),
+                        owner,
+                    },
+                ],
+            )?,
+            TokenOp::HarvestWithheld { mint, account } => spl_token::harvest_withheld_tokens_to_mint(
+                token_program.key(),
+                &[
+                    spl_token::HarvestWithheldTokensToMintParams {
+                        mint: mint.key(),
+                        sources: &[account.key()],
+                    },
+                ],
+            )?,
+        };
+        invoke_with_seeds(&ix, self.accounts().as_slice(), signer_seeds)
+    }
+}
+
+// invoke when nothing needs signing, invoke_signed with a PDA's seeds otherwise
+pub fn invoke_with_seeds(
+    ix: &pinocchio::instruction::Instruction,
+    accounts: &[&AccountInfo],
+    signer_seeds: Option<&[&[u8]]>,
+) -> ProgramResult {
+    match signer_seeds {
+        Some(seeds) => invoke_signed(ix, accounts, &[seeds]),
+        None => invoke(ix, accounts),
+    }
+}
+
+// move `amount` with a legacy Transfer. Token-2022 aware callers go through
+// transfer_tokens, which lands here for the legacy program
+pub fn transfer(
+    token_program: &AccountInfo,
+    from: &AccountInfo,
+    to: &AccountInfo,
+    authority: &AccountInfo,
+    amount: u64,
+    signer_seeds: Option<&[&[u8]]>,
+) -> ProgramResult {
+    TokenOp::Transfer { from, to, authority, amount }.invoke(token_program, signer_seeds)
+}
+
+// close an (emptied) token account and send its rent to `destination`
+pub fn close(
+    token_program: &AccountInfo,
+    account: &AccountInfo,
+    destination: &AccountInfo,
+    authority: &AccountInfo,
+    signer_seeds: Option<&[&[u8]]>,
+) -> ProgramResult {
+    TokenOp::Close { account, destination, authority }.invoke(token_program, signer_seeds)
+}
+
+// initialize a freshly created token account for `mint`, owned by `owner`
+pub fn initialize_account(
+    token_program: &AccountInfo,
+    account: &AccountInfo,
+    mint: &AccountInfo,
+    owner: &Pubkey,
+    signer_seeds: Option<&[&[u8]]>,
+) -> ProgramResult {
+    TokenOp::InitializeAccount { account, mint, owner }.invoke(token_program, signer_seeds)
+}
+
+// move `amount` between token accounts and return what arrives. The legacy
+// program uses Transfer. Token-2022 needs the mint for TransferChecked, and
+// a mint with a transfer fee gets TransferCheckedWithFee carrying exactly
+// the fee the token program will withhold
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_tokens(
+    token_program: &AccountInfo,
+    from: &AccountInfo,
+    to: &AccountInfo,
+    authority: &AccountInfo,
+    mint: Option<&AccountInfo>,
+    amount: u64,
+    signer_seeds: Option<&[&[u8]]>,
+) -> Result<u64, ProgramError> {
+    if token_program.key() != &TOKEN_2022_PROGRAM_ID {
+        transfer(token_program, from, to, authority, amount, signer_seeds)?;
+        return Ok(amount);
+    }
+    
+    let mint = mint.ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let (decimals, fee) = {
+        let mint_data = mint.try_borrow_data()?;
+        check_mint_data(&mint_data)?;
+        let fee = mint_transfer_fee(token_program.key(), &mint_data, current_epoch()?)?;
+        (mint_data[MINT_DECIMALS_OFFSET], fee)
+    };
+    
+    let withheld = fee.map(|fee| fee.fee(amount)).transpose()?;
+    TokenOp::TransferChecked { from, mint, to, authority, amount, decimals, fee: withheld }
+        .invoke(token_program, signer_seeds)?;
+    
+    match fee {
+        Some(fee) => fee.net_amount(amount),
+        None => Ok(amount),
+    }
+}
+
+// a Token-2022 account keeps the fees withheld from transfers into it and
+// can't be closed until they are harvested to the mint. A no-op for mints
+// without a transfer fee
+pub fn harvest_withheld_fees(
+    token_program: &AccountInfo,
+    mint: Option<&AccountInfo>,
+    account: &AccountInfo,
+) -> ProgramResult {
+    let Some(mint) = mint else {
+        return Ok(());
+    };
+    if current_transfer_fee(token_program.key(), mint)?.is_none() {
+        return Ok(());
+    }
+    
+    TokenOp::HarvestWithheld { mint, account }.invoke(token_program, None)
+}
+
+// run SyncNative on a wSOL token account so its amount matches its lamports
+pub fn sync_native(account: &AccountInfo) -> ProgramResult {
+    let sync_ix = spl_token::sync_native(
+        &TOKEN_PROGRAM_ID,
+        &[
+            spl_token::SyncNativeParams {
+                account: account.key(),
+            },
+        ],
+    )?;
+    
+    invoke(&sync_ix, &[account])
+}
@@ -0,0 +1,73 @@
+use crate::{error::EscrowError, state::{Escrow, EscrowStatus}, token::verify_token_account};
+use super::{AccountRole, require_signer};
+use pinocchio::{
+    account_info::AccountInfo,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+
+// Accounts for the TransferAuthority instruction
+pub struct TransferAuthorityAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub escrow: &'a AccountInfo,
+    pub new_maker: &'a AccountInfo,
+    // mint B account of the new maker, replaces the stored receive account
+    pub new_receive_account: &'a AccountInfo,
+}
+
+impl<'a> TransferAuthorityAccounts<'a> {
+    pub const ROLES: [AccountRole; 4] = [
+        AccountRole::new("maker", true, false),
+        AccountRole::new("escrow", false, true),
+        AccountRole::new("new_maker", false, false),
+        AccountRole::new("new_receive_account", false, false),
+    ];
+    
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, escrow, new_maker, new_receive_account, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self {
+            maker,
+            escrow,
+            new_maker,
+            new_receive_account,
+        })
+    }
+}
+
+// hand an active escrow over to a new maker, no tokens move
+pub fn transfer_authority(
+    program_id: &Pubkey,
+    accounts: TransferAuthorityAccounts,
+    seed: u64,
+) -> ProgramResult {
+    verbose_msg!("TransferAuthority instruction: seed={}", seed);
+    
+    // verify the current maker is a signer
+    require_signer(accounts.maker, "maker")?;
+    
+    // verify the escrow account (and load it)
+    let escrow = Escrow::from_account(accounts.escrow, program_id)?;
+    
+    // only the current maker can hand the escrow over
+    escrow.verify_maker(accounts.maker.key())?;
+    
+    // settled or in-flight escrows can't change hands
+    if escrow.status()? != EscrowStatus::Active {
+        return Err(EscrowError::InvalidState.into());
+    }
+    
+    // take checks the receive account is owned by the current maker, so the
+    // old maker's account would fail every later take
+    verify_token_account(accounts.new_receive_account, &escrow.mint_b, accounts.new_maker.key())?;
+    
+    escrow.maker = *accounts.new_maker.key();
+    escrow.receive_account = *accounts.new_receive_account.key();
+    
+    msg!("Escrow authority transferred successfully");
+    Ok(())
+}
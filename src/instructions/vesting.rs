@@ -0,0 +1,338 @@
+use crate::{
+    error::EscrowError,
+    pda::{verify_vault_pda, verify_vesting_pda},
+    programs::{verify_system_program, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID},
+    state::{close_program_account, EscrowStatus, VestingEscrow},
+    sysvar::{current_rent, current_unix_timestamp},
+    token::{
+        check_accounts_not_frozen, sweep_amount, token_account_amount, vault_account_size, vault_rent_exempt_lamports,
+        verify_mint, verify_token_account,
+    },
+};
+use super::{
+    AccountRole,
+    check_distinct_accounts,
+    require_signer,
+    make::{MAX_ESCROW_AMOUNT, check_amount_cap},
+    config::check_not_paused,
+    token_ops,
+};
+use pinocchio::{
+    account_info::AccountInfo,
+    program::invoke_signed,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+    system_program,
+};
+
+// find the vesting account PDA
+pub fn find_vesting_address(
+    maker: &Pubkey,
+    seed: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"vesting",
+            maker.as_ref(),
+            &seed.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+// Accounts for the MakeVesting instruction
+pub struct MakeVestingAccounts<'a> {
+    pub maker: &'a AccountInfo,
+    pub recipient: &'a AccountInfo,
+    pub mint: &'a AccountInfo,
+    pub maker_ata_a: &'a AccountInfo,
+    pub vesting: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+    // the program's config PDA, checked for a pause
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> MakeVestingAccounts<'a> {
+    pub const ROLES: [AccountRole; 9] = [
+        AccountRole::new("maker", true, true),
+        AccountRole::new("recipient", false, false),
+        AccountRole::new("mint", false, false),
+        AccountRole::new("maker_ata_a", false, true),
+        AccountRole::new("vesting", false, true),
+        AccountRole::new("vault", false, true),
+        AccountRole::new("token_program", false, false),
+        AccountRole::new("system_program", false, false),
+        AccountRole::new("config", false, false),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [maker, recipient, mint, maker_ata_a, vesting, vault, token_program, system_program, config, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { maker, recipient, mint, maker_ata_a, vesting, vault, token_program, system_program, config })
+    }
+}
+
+// Accounts for the Claim instruction
+pub struct ClaimAccounts<'a> {
+    pub recipient: &'a AccountInfo,
+    // gets the rent of the vesting account and its vault after the last claim
+    pub maker: &'a AccountInfo,
+    pub vesting: &'a AccountInfo,
+    pub vault: &'a AccountInfo,
+    pub recipient_ata: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+}
+
+impl<'a> ClaimAccounts<'a> {
+    pub const ROLES: [AccountRole; 6] = [
+        AccountRole::new("recipient", true, true),
+        AccountRole::new("maker", false, true),
+        AccountRole::new("vesting", false, true),
+        AccountRole::new("vault", false, true),
+        AccountRole::new("recipient_ata", false, true),
+        AccountRole::new("token_program", false, false),
+    ];
+
+    // build from the instruction's account list in the documented order
+    pub fn from_slice(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let [recipient, maker, vesting, vault, recipient_ata, token_program, ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Ok(Self { recipient, maker, vesting, vault, recipient_ata, token_program })
+    }
+}
+
+// lock `amount` of token A for `recipient`, released from `start` to
+// `start + duration` with nothing claimable before `cliff`
+#[allow(clippy::too_many_arguments)]
+pub fn make_vesting(
+    program_id: &Pubkey,
+    accounts: MakeVestingAccounts,
+    seed: u64,
+    amount: u64,
+    start: i64,
+    cliff: i64,
+    duration: i64,
+) -> ProgramResult {
+    verbose_msg!(
+        "MakeVesting instruction: seed={}, amount={}, start={}, cliff={}, duration={}",
+        seed,
+        amount,
+        start,
+        cliff,
+        duration
+    );
+
+    // Verify the maker is a signer
+    require_signer(accounts.maker, "maker")?;
+
+    // verify programs
+    verify_system_program(accounts.system_program.key())?;
+
+    if accounts.token_program.key() != &TOKEN_PROGRAM_ID {
+        return Err(EscrowError::InvalidTokenProgram.into());
+    }
+
+    // an empty schedule could never complete
+    if amount == 0 {
+        return Err(EscrowError::ExpectedAmountMismatch.into());
+    }
+    VestingEscrow::check_schedule(start, cliff, duration)?;
+    check_amount_cap(amount, MAX_ESCROW_AMOUNT)?;
+
+    verify_mint(accounts.mint, accounts.token_program.key())?;
+    verify_token_account(accounts.maker_ata_a, accounts.mint.key(), accounts.maker.key())?;
+    check_distinct_accounts(&[accounts.maker, accounts.maker_ata_a, accounts.vesting, accounts.vault])?;
+
+    // a paused program creates nothing
+    check_not_paused(accounts.config, program_id)?;
+
+    // derive and verify the vesting and vault addresses
+    let vesting_bump = verify_vesting_pda(accounts.vesting, accounts.maker.key(), seed, program_id)?;
+    let vault_bump = verify_vault_pda(accounts.vault, accounts.vesting.key(), program_id)?;
+
+    let rent = current_rent()?;
+    let create_account_ix = system_program::create_account(
+        &SYSTEM_PROGRAM_ID,
+        &[
+            system_program::CreateAccountParams {
+                from: accounts.maker.key(),
+                new_account: accounts.vesting.key(),
+                lamports: rent.minimum_balance(VestingEscrow::LEN),
+                space: VestingEscrow::LEN,
+                owner: program_id,
+            },
+        ],
+    )?;
+
+    let seed_bytes = seed.to_le_bytes();
+    let vesting_signer_seeds: &[&[u8]] = &[
+        b"vesting",
+        accounts.maker.key().as_ref(),
+        &seed_bytes,
+        &[vesting_bump],
+    ];
+
+    invoke_signed(
+        &create_account_ix,
+        &[
+            accounts.maker,
+            accounts.vesting,
+            accounts.system_program,
+        ],
+        &[vesting_signer_seeds],
+    )?;
+
+    VestingEscrow::init(
+        accounts.vesting,
+        *accounts.maker.key(),
+        *accounts.recipient.key(),
+        *accounts.mint.key(),
+        amount,
+        start,
+        cliff,
+        duration,
+        vesting_bump,
+        vault_bump,
+    )?;
+
+    // the vault is a token account owned by the vesting PDA
+    let vault_size = vault_account_size(accounts.token_program.key(), &accounts.mint.try_borrow_data()?)?;
+    let create_vault_ix = system_program::create_account(
+        &SYSTEM_PROGRAM_ID,
+        &[
+            system_program::CreateAccountParams {
+                from: accounts.maker.key(),
+                new_account: accounts.vault.key(),
+                lamports: vault_rent_exempt_lamports(accounts.token_program.key(), accounts.mint, &rent)?,
+                space: vault_size,
+                owner: accounts.token_program.key(),
+            },
+        ],
+    )?;
+
+    let vault_signer_seeds: &[&[u8]] = &[
+        b"vault",
+        accounts.vesting.key().as_ref(),
+        &[vault_bump],
+    ];
+
+    invoke_signed(
+        &create_vault_ix,
+        &[
+            accounts.maker,
+            accounts.vault,
+            accounts.system_program,
+        ],
+        &[vault_signer_seeds],
+    )?;
+
+    token_ops::initialize_account(accounts.token_program, accounts.vault, accounts.mint, accounts.vesting.key(), None)?;
+
+    check_accounts_not_frozen(&[accounts.maker_ata_a])?;
+    token_ops::transfer(accounts.token_program, accounts.maker_ata_a, accounts.vault, accounts.maker, amount, None)?;
+
+    msg!("Vesting schedule created successfully");
+    Ok(())
+}
+
+// release everything vested so far to the recipient
+pub fn claim(
+    program_id: &Pubkey,
+    accounts: ClaimAccounts,
+    seed: u64,
+) -> ProgramResult {
+    claim_at(program_id, accounts, seed, current_unix_timestamp()?)
+}
+
+// claim as of `now`, the Clock read is kept in `claim` so the schedule can be
+// exercised at any time
+pub fn claim_at(
+    program_id: &Pubkey,
+    accounts: ClaimAccounts,
+    seed: u64,
+    now: i64,
+) -> ProgramResult {
+    verbose_msg!("Claim instruction: seed={}, now={}", seed, now);
+
+    // verify the recipient is a signer
+    require_signer(accounts.recipient, "recipient")?;
+
+    if accounts.token_program.key() != &TOKEN_PROGRAM_ID {
+        return Err(EscrowError::InvalidTokenProgram.into());
+    }
+
+    let vesting = VestingEscrow::from_account(accounts.vesting, program_id)?;
+    if vesting.status()? != EscrowStatus::Active {
+        return Err(EscrowError::InvalidState.into());
+    }
+    vesting.verify_recipient(accounts.recipient.key())?;
+
+    // the rent goes back to whoever funded the schedule
+    if vesting.maker != *accounts.maker.key() {
+        return Err(EscrowError::InvalidAuthority.into());
+    }
+
+    let vesting_bump = verify_vesting_pda(accounts.vesting, &vesting.maker, seed, program_id)?;
+    verify_vault_pda(accounts.vault, accounts.vesting.key(), program_id)?;
+    check_distinct_accounts(&[accounts.vesting, accounts.vault, accounts.recipient_ata])?;
+    verify_token_account(accounts.recipient_ata, &vesting.mint, &vesting.recipient)?;
+
+    let amount = vesting.claimable(now)?;
+    if amount == 0 {
+        msg!("nothing vested since the last claim");
+        return Err(EscrowError::NothingToClaim.into());
+    }
+    check_accounts_not_frozen(&[accounts.vault, accounts.recipient_ata])?;
+
+    // book the claim before any CPI
+    vesting.record_claim(amount)?;
+    let completed = vesting.status()? == EscrowStatus::Completed;
+
+    let seed_bytes = seed.to_le_bytes();
+    let vesting_signer_seeds: &[&[u8]] = &[
+        b"vesting",
+        accounts.maker.key().as_ref(),
+        &seed_bytes,
+        &[vesting_bump],
+    ];
+
+    // the last claim takes whatever the vault holds, so dust sent to it
+    // can't keep the vault from closing
+    let to_send = if completed {
+        sweep_amount(token_account_amount(accounts.vault)?, amount)?
+    } else {
+        amount
+    };
+    token_ops::transfer(
+        accounts.token_program,
+        accounts.vault,
+        accounts.recipient_ata,
+        accounts.vesting,
+        to_send,
+        Some(vesting_signer_seeds),
+    )?;
+
+    // the last claim empties the vault, hand both rents back to the maker
+    if completed {
+        token_ops::close(
+            accounts.token_program,
+            accounts.vault,
+            accounts.maker,
+            accounts.vesting,
+            Some(vesting_signer_seeds),
+        )?;
+
+        close_program_account(accounts.vesting, accounts.maker)?;
+    }
+
+    msg!("Vested tokens claimed successfully");
+    Ok(())
+}
@@ -13,23 +13,76 @@ use pinocchio::{
     ProgramResult,
 };
 
+// detailed logs go through `format!`, which allocates and burns compute units,
+// so they are only emitted with the `verbose-logs` feature. The default build
+// still type-checks the arguments but never formats them.
+#[cfg(feature = "verbose-logs")]
+macro_rules! verbose_msg {
+    ($($arg:tt)*) => {
+        pinocchio::msg!(&format!($($arg)*))
+    };
+}
+
+#[cfg(not(feature = "verbose-logs"))]
+macro_rules! verbose_msg {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
 pub mod error;
 pub mod instructions;
+pub mod math;
+pub mod pda;
+pub mod programs;
 pub mod state;
+pub mod sysvar;
+pub mod token;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(test)]
+mod mock;
 
 pub use error::EscrowError;
 pub use instructions::{
-    make::{make, MakeAccounts},
+    make::{
+        make, make_deposit, make_init, top_up, MakeAccounts, MakeDepositAccounts, MakeReturnData,
+        TopUpAccounts, DEFAULT_ESCROW_NAMESPACE, ESCROW_NAMESPACE_LEN,
+    },
     refund::{refund, RefundAccounts},
-    take::{take, TakeAccounts},
+    take::{take, take_available, TakeAccounts},
+    transfer_authority::{transfer_authority, TransferAuthorityAccounts},
+    get_escrow::{get_escrow, EscrowSnapshot, GetEscrowAccounts},
+    force_close::{force_close, ForceCloseAccounts},
+    sweep_excess::{sweep_excess, SweepExcessAccounts},
+    basket::{
+        make_basket, refund_basket, take_basket, MakeBasketAccounts, RefundBasketAccounts,
+        TakeBasketAccounts,
+    },
+    migrate_escrow::{migrate_escrow, MigrateEscrowAccounts},
+    vesting::{claim, make_vesting, ClaimAccounts, MakeVestingAccounts},
+    extend_expiry::{extend_expiry, ExtendExpiryAccounts},
+    claim_receive::{claim_receive, ClaimReceiveAccounts},
+    arbiter_resolve::{arbiter_resolve, ArbiterResolveAccounts},
+    config::{init_config, set_paused, InitConfigAccounts, SetPausedAccounts},
+};
+pub use state::{
+    BasketEscrow, Config, Escrow, EscrowKind, EscrowStatus, EscrowTuple, PriceMode, TakeReceipt, VestingEscrow,
+    ACCOUNT_DISCRIMINATORS, ESCROW_VERSION, MAX_BASKET_LEGS, MAX_MEMO_LEN,
+};
+#[cfg(feature = "client")]
+pub use client::{
+    decode_escrow, derive_escrow, derive_vault, deserialize_offer_book, estimated_compute_units,
+    filter_active, filter_by_pair, filter_older_than, from_hex, is_seed_available, make_instruction, make_priced_instruction,
+    serialize_offer_book, swap_accounts, take_instruction, to_hex, ChunkedMake, EscrowKeys, EscrowRef, OfferIndex,
+    SwapAccounts,
 };
-pub use state::Escrow;
 
 // declare program ID
 declare_id!("DVVd1pDf9TaTyhep1iYh7S111Hir4SQeqhhAG65m2CFB");
 
 // instruction enum for the escrow program
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EscrowInstruction {
     // Make instruction accounts:
     // 0. `[signer]` Maker
@@ -38,9 +91,40 @@ pub enum EscrowInstruction {
     // 3. `[writable]` Maker ATA A
     // 4. `[writable]` escrow account (PDA)
     // 5. `[writable]` vault account (PDA)
-    // 6. `[]` token program
+    // 6. `[]` token program (legacy or Token-2022)
     // 7. `[]` system program
-    Make { amount: u64, seed: u64 },
+    // 8. `[writable]` Maker ATA B (receives token B, a mint B account of the
+    //    Maker, or with `treasury` the Maker's receive vault)
+    // 9. `[]` config account (PDA, rejects makes while paused)
+    // 10. `[signer, writable]` rent payer (optional, defaults to the Maker)
+    // 11. `[]` arbiter (optional)
+    // native escrows pass the system program as Mint A and ignore Maker ATA A
+    // a Token-2022 transfer fee on Mint A is withheld from the deposit, the
+    // escrow records the amount that reached the vault
+    // `memo` is an opaque reference (invoice id, trade id) of up to
+    // MAX_MEMO_LEN bytes, stored on the escrow
+    // `namespace` isolates a frontend's seeds, all zeros for the default derivation
+    // a non-zero `price_den` prices token B per unit of token A (see PriceMode),
+    // `price_den == 0` keeps the fixed `receive_amount`
+    // `min_fill` is the smallest TakeAvailable fill of token A, except for the
+    // fill that takes the rest of the offer. 0 allows any fill
+    // a `persistent` escrow isn't closed when its last token A is taken
+    // `treasury` takes Maker ATA B as the maker's receive vault (see
+    // ClaimReceive), otherwise it has to be a mint B account of the Maker
+    Make {
+        amount: u64,
+        seed: u64,
+        expiry: i64,
+        kind: EscrowKind,
+        receive_amount: u64,
+        memo: Vec<u8>,
+        namespace: [u8; 8],
+        price_num: u64,
+        price_den: u64,
+        min_fill: u64,
+        persistent: bool,
+        treasury: bool,
+    },
     
     // Take an escrow offer 
     // 0. `[signer]` Taker
@@ -53,7 +137,14 @@ pub enum EscrowInstruction {
     // 7. `[writable]` Taker ATA B
     // 8. `[writable]` Maker ATA B
     // 9. `[]` token program
-    Take { amount: u64 },
+    // 10. `[]` config account (PDA, rejects takes while paused)
+    // 11. `[writable]` token A destination (optional, defaults to Taker ATA A)
+    // 12. `[]` system program (optional, required with a receipt)
+    // 13. `[]` associated token program (optional, recreates a closed Maker ATA B)
+    // 14. `[writable]` receipt account (optional PDA, Take only)
+    // `max_in` is the most token B the Taker is willing to pay,
+    // `amount` and `receive_amount` are the terms the Taker acknowledges
+    Take { amount: u64, seed: u64, max_in: u64, receive_amount: u64 },
 
     // refund an escrow
     // accounts:
@@ -62,41 +153,600 @@ pub enum EscrowInstruction {
     // 2. `[writable]` Vault account
     // 3. `[writable]` Maker's ATA A
     // 4. `[]` token program
-    Refund { amount: u64 },
+    // 5. `[]` system program (optional)
+    // 6. `[]` Mint A (optional, required for Token-2022 escrows)
+    Refund { amount: u64, seed: u64 },
+
+    // hand an escrow over to a new maker
+    // accounts:
+    // 0. `[signer]` current Maker
+    // 1. `[writable]` Escrow account
+    // 2. `[]` new Maker
+    // 3. `[]` new Maker ATA B (optional, replaces the receive account)
+    TransferAuthority { seed: u64 },
+
+    // read an escrow, the snapshot is written to return data (see EscrowSnapshot)
+    // accounts:
+    // 0. `[]` Escrow account
+    GetEscrow { seed: u64 },
+
+    // close an escrow left half-initialized by a failed make, whatever its
+    // vault holds. Only Active escrows, any token A goes back to the maker
+    // accounts:
+    // 0. `[signer]` Maker
+    // 1. `[writable]` Escrow account
+    // 2. `[writable]` Vault account
+    // 3. `[writable]` Maker's ATA A
+    // 4. `[]` token program
+    // 5. `[]` instructions sysvar (optional, rejects calls through a CPI)
+    ForceClose { seed: u64 },
+
+    // move lamports above the escrow's rent-exempt minimum to the maker,
+    // the escrow stays open
+    // accounts:
+    // 0. `[signer, writable]` Maker
+    // 1. `[writable]` Escrow account
+    SweepExcess { seed: u64 },
+
+    // offer a basket of up to MAX_BASKET_LEGS token A mints for one token B
+    // payment, `amounts[i]` of leg i's mint
+    // accounts:
+    // 0. `[signer, writable]` Maker
+    // 1. `[writable]` basket account (PDA)
+    // 2. `[]` Mint B
+    // 3. `[]` Maker ATA B (receives token B)
+    // 4. `[]` token program
+    // 5. `[]` system program
+    // then per leg: `[]` Mint A, `[writable]` Maker ATA A, `[writable]` leg vault (PDA)
+    MakeBasket { seed: u64, receive_amount: u64, expiry: i64, amounts: Vec<u64> },
+
+    // take a whole basket, every leg is released in the same instruction
+    // accounts:
+    // 0. `[signer]` Taker
+    // 1. `[]` Maker
+    // 2. `[writable]` basket account
+    // 3. `[writable]` Taker ATA B
+    // 4. `[writable]` Maker ATA B
+    // 5. `[]` token program
+    // then per leg: `[writable]` leg vault, `[writable]` Taker ATA A of the leg's mint
+    TakeBasket { seed: u64 },
+
+    // refund a whole basket to the maker
+    // accounts:
+    // 0. `[signer]` Maker
+    // 1. `[writable]` basket account
+    // 2. `[]` token program
+    // then per leg: `[writable]` leg vault, `[writable]` Maker ATA A of the leg's mint
+    RefundBasket { seed: u64 },
+
+    // rewrite an escrow made by an older version in the current layout,
+    // growing the account to Escrow::LEN and bumping its version
+    // accounts:
+    // 0. `[signer, writable]` Maker (pays the extra rent)
+    // 1. `[writable]` Escrow account
+    // 2. `[]` system program
+    MigrateEscrow { seed: u64 },
+
+    // fill up to `desired` of the offer, as much as the vault still holds,
+    // paying token B in proportion. The escrow stays open while token A is
+    // left, the filled amount is returned as return data (u64 LE)
+    // accounts: same as Take
+    TakeAvailable { desired: u64, seed: u64, max_in: u64 },
+
+    // first phase of a two-phase make: create the escrow and an empty vault,
+    // no tokens move. Same data and accounts as Make
+    MakeInit {
+        amount: u64,
+        seed: u64,
+        expiry: i64,
+        kind: EscrowKind,
+        receive_amount: u64,
+        memo: Vec<u8>,
+        namespace: [u8; 8],
+        price_num: u64,
+        price_den: u64,
+        min_fill: u64,
+        persistent: bool,
+        treasury: bool,
+    },
+
+    // second phase: deposit whatever the vault is still short of the escrowed
+    // amount, safe to retry
+    // accounts:
+    // 0. `[signer, writable]` Maker
+    // 1. `[]` Escrow account
+    // 2. `[writable]` Vault account
+    // 3. `[writable]` Maker's ATA A
+    // 4. `[]` token program
+    // 5. `[]` system program
+    // 6. `[]` Mint A (optional, required for Token-2022 escrows)
+    MakeDeposit { seed: u64 },
+
+    // lock `amount` of token A for a recipient, released linearly from
+    // `start` over `duration` seconds with nothing claimable before `cliff`
+    // accounts:
+    // 0. `[signer, writable]` Maker
+    // 1. `[]` Recipient
+    // 2. `[]` Mint
+    // 3. `[writable]` Maker's ATA
+    // 4. `[writable]` vesting account (PDA)
+    // 5. `[writable]` vault account (PDA)
+    // 6. `[]` token program
+    // 7. `[]` system program
+    MakeVesting { seed: u64, amount: u64, start: i64, cliff: i64, duration: i64 },
+
+    // release everything vested so far to the recipient, the last claim
+    // closes the schedule and returns its rent to the maker
+    // accounts:
+    // 0. `[signer, writable]` Recipient
+    // 1. `[writable]` Maker
+    // 2. `[writable]` vesting account
+    // 3. `[writable]` vault account
+    // 4. `[writable]` Recipient's ATA
+    // 5. `[]` token program
+    Claim { seed: u64 },
+
+    // push back the expiry of an active, unexpired offer. Expired offers are
+    // not revived, they can only be refunded
+    // accounts:
+    // 0. `[signer]` Maker
+    // 1. `[writable]` Escrow account
+    ExtendExpiry { new_expiry: i64, seed: u64 },
+
+    // withdraw what takers paid into the maker's receive vault (an escrow
+    // made with the receive vault PDA as Maker ATA B) and close the vault
+    // accounts:
+    // 0. `[signer, writable]` Maker the receive vault was derived for
+    // 1. `[writable]` Receive vault
+    // 2. `[writable]` Maker's mint B account
+    // 3. `[]` token program
+    // 4. `[]` mint B (optional, needed for Token-2022 mints)
+    ClaimReceive { seed: u64 },
+    
+    // settle an escrow made with an arbiter before it expires: the whole
+    // vault goes to the taker's account (`to_taker`) or back to the maker,
+    // and the escrow and vault rent goes to the maker either way
+    // accounts:
+    // 0. `[signer]` Arbiter named at make
+    // 1. `[writable]` Maker (receives rent)
+    // 2. `[writable]` Escrow account
+    // 3. `[writable]` Vault
+    // 4. `[writable]` Destination: a mint A account (the maker's on a refund),
+    //    or the receiving wallet for native escrows
+    // 5. `[]` token program
+    // 6. `[]` mint A (optional, needed for Token-2022 mints)
+    // 7. `[]` instructions sysvar (optional, rejects calls through a CPI)
+    ArbiterResolve { seed: u64, to_taker: bool },
+
+    // create the program's config PDA with the signer as admin, unpaused
+    // accounts:
+    // 0. `[signer, writable]` Admin, the program's upgrade authority (pays the rent)
+    // 1. `[writable]` config account (PDA)
+    // 2. `[]` system program
+    // 3. `[]` the program's ProgramData account
+    InitConfig,
+
+    // pause or resume new makes and takes, refunds are never paused
+    // accounts:
+    // 0. `[signer]` Admin named in the config
+    // 1. `[writable]` config account
+    SetPaused { paused: bool },
+
+    // refill a persistent escrow: send `amount` more token A (or lamports)
+    // into the vault, offered for `receive_amount` more token B. A priced
+    // offer ignores `receive_amount` and asks what the new total costs
+    // accounts:
+    // 0. `[signer, writable]` Maker
+    // 1. `[writable]` Escrow account
+    // 2. `[writable]` Vault account
+    // 3. `[writable]` Maker's ATA A
+    // 4. `[]` token program
+    // 5. `[]` system program
+    // 6. `[]` Mint A (optional, required for Token-2022 escrows)
+    TopUp { amount: u64, seed: u64, receive_amount: u64 },
+}
+
+// instruction wire format: a one byte discriminator followed by fixed-size
+// little-endian fields, shared by pack_instruction_data and unpack
+pub const MAKE_DISCRIMINATOR: u8 = 0;
+pub const TAKE_DISCRIMINATOR: u8 = 1;
+pub const REFUND_DISCRIMINATOR: u8 = 2;
+pub const TRANSFER_AUTHORITY_DISCRIMINATOR: u8 = 3;
+pub const GET_ESCROW_DISCRIMINATOR: u8 = 4;
+pub const FORCE_CLOSE_DISCRIMINATOR: u8 = 5;
+pub const SWEEP_EXCESS_DISCRIMINATOR: u8 = 6;
+pub const MAKE_BASKET_DISCRIMINATOR: u8 = 7;
+pub const TAKE_BASKET_DISCRIMINATOR: u8 = 8;
+pub const REFUND_BASKET_DISCRIMINATOR: u8 = 9;
+pub const MIGRATE_ESCROW_DISCRIMINATOR: u8 = 10;
+pub const TAKE_AVAILABLE_DISCRIMINATOR: u8 = 11;
+pub const MAKE_INIT_DISCRIMINATOR: u8 = 12;
+pub const MAKE_DEPOSIT_DISCRIMINATOR: u8 = 13;
+pub const MAKE_VESTING_DISCRIMINATOR: u8 = 14;
+pub const CLAIM_DISCRIMINATOR: u8 = 15;
+pub const EXTEND_EXPIRY_DISCRIMINATOR: u8 = 16;
+pub const CLAIM_RECEIVE_DISCRIMINATOR: u8 = 17;
+pub const ARBITER_RESOLVE_DISCRIMINATOR: u8 = 18;
+pub const INIT_CONFIG_DISCRIMINATOR: u8 = 19;
+pub const SET_PAUSED_DISCRIMINATOR: u8 = 20;
+pub const TOP_UP_DISCRIMINATOR: u8 = 21;
+
+// amount and seed sit at the same offsets in Make, Take, Refund, TopUp and
+// TakeAvailable (where the amount is the desired fill)
+pub const AMOUNT_OFFSET: usize = 1;
+pub const SEED_OFFSET: usize = 9;
+
+// Make / MakeInit: amount, seed, expiry, kind, receive_amount, namespace, price_num,
+// price_den, min_fill, persistent (0 or 1), treasury (0 or 1), then a one byte
+// memo length and the memo itself
+pub const MAKE_EXPIRY_OFFSET: usize = 17;
+pub const MAKE_KIND_OFFSET: usize = 25;
+pub const MAKE_RECEIVE_AMOUNT_OFFSET: usize = 26;
+pub const MAKE_NAMESPACE_OFFSET: usize = 34;
+pub const MAKE_PRICE_NUM_OFFSET: usize = 42;
+pub const MAKE_PRICE_DEN_OFFSET: usize = 50;
+pub const MAKE_MIN_FILL_OFFSET: usize = 58;
+pub const MAKE_PERSISTENT_OFFSET: usize = 66;
+pub const MAKE_TREASURY_OFFSET: usize = 67;
+pub const MAKE_MEMO_LEN_OFFSET: usize = 68;
+pub const MAKE_MEMO_OFFSET: usize = 69;
+// an empty memo / the longest memo
+pub const MAKE_IX_MIN_LEN: usize = MAKE_MEMO_OFFSET;
+pub const MAKE_IX_MAX_LEN: usize = MAKE_MEMO_OFFSET + MAX_MEMO_LEN;
+
+// Take / TakeAvailable: amount, seed, max_in
+pub const TAKE_MAX_IN_OFFSET: usize = 17;
+pub const TAKE_AVAILABLE_IX_LEN: usize = 25;
+// Take only: acknowledged receive_amount
+pub const TAKE_RECEIVE_AMOUNT_OFFSET: usize = 25;
+pub const TAKE_IX_LEN: usize = 33;
+
+// Refund: amount, seed
+pub const REFUND_IX_LEN: usize = 17;
+
+// TransferAuthority: seed
+pub const TRANSFER_AUTHORITY_SEED_OFFSET: usize = 1;
+pub const TRANSFER_AUTHORITY_IX_LEN: usize = 9;
+
+// GetEscrow: seed
+pub const GET_ESCROW_SEED_OFFSET: usize = 1;
+pub const GET_ESCROW_IX_LEN: usize = 9;
+
+// ForceClose: seed
+pub const FORCE_CLOSE_SEED_OFFSET: usize = 1;
+pub const FORCE_CLOSE_IX_LEN: usize = 9;
+
+// SweepExcess: seed
+pub const SWEEP_EXCESS_SEED_OFFSET: usize = 1;
+pub const SWEEP_EXCESS_IX_LEN: usize = 9;
+
+// MakeBasket: seed, receive_amount, expiry, a one byte leg count, then one
+// u64 amount per leg
+pub const MAKE_BASKET_SEED_OFFSET: usize = 1;
+pub const MAKE_BASKET_RECEIVE_AMOUNT_OFFSET: usize = 9;
+pub const MAKE_BASKET_EXPIRY_OFFSET: usize = 17;
+pub const MAKE_BASKET_LEG_COUNT_OFFSET: usize = 25;
+pub const MAKE_BASKET_AMOUNTS_OFFSET: usize = 26;
+// one leg / MAX_BASKET_LEGS legs
+pub const MAKE_BASKET_IX_MIN_LEN: usize = MAKE_BASKET_AMOUNTS_OFFSET + 8;
+pub const MAKE_BASKET_IX_MAX_LEN: usize = MAKE_BASKET_AMOUNTS_OFFSET + 8 * MAX_BASKET_LEGS;
+
+// TakeBasket / RefundBasket: seed
+pub const BASKET_SEED_OFFSET: usize = 1;
+pub const TAKE_BASKET_IX_LEN: usize = 9;
+pub const REFUND_BASKET_IX_LEN: usize = 9;
+
+// MigrateEscrow: seed
+pub const MIGRATE_ESCROW_SEED_OFFSET: usize = 1;
+pub const MIGRATE_ESCROW_IX_LEN: usize = 9;
+
+// MakeDeposit: seed
+pub const MAKE_DEPOSIT_SEED_OFFSET: usize = 1;
+pub const MAKE_DEPOSIT_IX_LEN: usize = 9;
+
+// MakeVesting: seed, amount, start, cliff, duration
+pub const MAKE_VESTING_SEED_OFFSET: usize = 1;
+pub const MAKE_VESTING_AMOUNT_OFFSET: usize = 9;
+pub const MAKE_VESTING_START_OFFSET: usize = 17;
+pub const MAKE_VESTING_CLIFF_OFFSET: usize = 25;
+pub const MAKE_VESTING_DURATION_OFFSET: usize = 33;
+pub const MAKE_VESTING_IX_LEN: usize = 41;
+
+// Claim: seed
+pub const CLAIM_SEED_OFFSET: usize = 1;
+pub const CLAIM_IX_LEN: usize = 9;
+
+// ExtendExpiry: new_expiry, seed
+pub const EXTEND_EXPIRY_NEW_EXPIRY_OFFSET: usize = 1;
+pub const EXTEND_EXPIRY_SEED_OFFSET: usize = 9;
+pub const EXTEND_EXPIRY_IX_LEN: usize = 17;
+
+// ClaimReceive: seed
+pub const CLAIM_RECEIVE_SEED_OFFSET: usize = 1;
+pub const CLAIM_RECEIVE_IX_LEN: usize = 9;
+
+// ArbiterResolve: seed, to_taker (0 or 1)
+pub const ARBITER_RESOLVE_SEED_OFFSET: usize = 1;
+pub const ARBITER_RESOLVE_TO_TAKER_OFFSET: usize = 9;
+pub const ARBITER_RESOLVE_IX_LEN: usize = 10;
+
+// InitConfig: no data
+pub const INIT_CONFIG_IX_LEN: usize = 1;
+
+// SetPaused: paused (0 or 1)
+pub const SET_PAUSED_PAUSED_OFFSET: usize = 1;
+pub const SET_PAUSED_IX_LEN: usize = 2;
+
+// TopUp: amount, seed, receive_amount
+pub const TOP_UP_RECEIVE_AMOUNT_OFFSET: usize = 17;
+pub const TOP_UP_IX_LEN: usize = 25;
+
+// fixed-size field readers, a short or out of range slice is an
+// InvalidInstruction rather than a panic even if a length check is missed
+fn read_array<const N: usize>(input: &[u8], offset: usize) -> Result<[u8; N], ProgramError> {
+    offset
+        .checked_add(N)
+        .and_then(|end| input.get(offset..end))
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| EscrowError::InvalidInstruction.into())
+}
+
+fn read_u64(input: &[u8], offset: usize) -> Result<u64, ProgramError> {
+    read_array(input, offset).map(u64::from_le_bytes)
+}
+
+fn read_i64(input: &[u8], offset: usize) -> Result<i64, ProgramError> {
+    read_array(input, offset).map(i64::from_le_bytes)
 }
 
 impl EscrowInstruction {
-    //unpack instruction data
+    // structured counter line emitted per processed instruction with the
+    // `metrics` feature, operators grep validator logs for the METRIC prefix
+    pub fn metric_log(&self) -> &'static str {
+        match self {
+            EscrowInstruction::Make { .. } => "METRIC make_count",
+            EscrowInstruction::Take { .. } => "METRIC take_count",
+            EscrowInstruction::Refund { .. } => "METRIC refund_count",
+            EscrowInstruction::TransferAuthority { .. } => "METRIC transfer_authority_count",
+            EscrowInstruction::GetEscrow { .. } => "METRIC get_escrow_count",
+            EscrowInstruction::ForceClose { .. } => "METRIC force_close_count",
+            EscrowInstruction::SweepExcess { .. } => "METRIC sweep_excess_count",
+            EscrowInstruction::MakeBasket { .. } => "METRIC make_basket_count",
+            EscrowInstruction::TakeBasket { .. } => "METRIC take_basket_count",
+            EscrowInstruction::RefundBasket { .. } => "METRIC refund_basket_count",
+            EscrowInstruction::MigrateEscrow { .. } => "METRIC migrate_escrow_count",
+            EscrowInstruction::TakeAvailable { .. } => "METRIC take_available_count",
+            EscrowInstruction::MakeInit { .. } => "METRIC make_init_count",
+            EscrowInstruction::MakeDeposit { .. } => "METRIC make_deposit_count",
+            EscrowInstruction::MakeVesting { .. } => "METRIC make_vesting_count",
+            EscrowInstruction::Claim { .. } => "METRIC claim_count",
+            EscrowInstruction::ExtendExpiry { .. } => "METRIC extend_expiry_count",
+            EscrowInstruction::ClaimReceive { .. } => "METRIC claim_receive_count",
+            EscrowInstruction::ArbiterResolve { .. } => "METRIC arbiter_resolve_count",
+            EscrowInstruction::InitConfig => "METRIC init_config_count",
+            EscrowInstruction::SetPaused { .. } => "METRIC set_paused_count",
+            EscrowInstruction::TopUp { .. } => "METRIC top_up_count",
+        }
+    }
+    
+    //unpack instruction data, each variant must be exactly its defined length
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         if input.is_empty() {
             return Err(EscrowError::InvalidInstruction.into());
         }
         
         match input[0] {
-            0 => {
-                if input.len() < 17 {
+            MAKE_DISCRIMINATOR | MAKE_INIT_DISCRIMINATOR => {
+                // the length prefix has to fit the cap and match the bytes
+                // actually left, a longer prefix would read past the end
+                let memo_len = *input
+                    .get(MAKE_MEMO_LEN_OFFSET)
+                    .ok_or(EscrowError::InvalidInstruction)? as usize;
+                if memo_len > MAX_MEMO_LEN || input.len() != MAKE_MEMO_OFFSET + memo_len {
                     return Err(EscrowError::InvalidInstruction.into());
                 }
-                let amount = u64::from_le_bytes(input[1..9].try_into().unwrap());
-                let seed = u64::from_le_bytes(input[9..17].try_into().unwrap());
-                Ok(EscrowInstruction::Make { amount, seed })
+                let amount = read_u64(input, AMOUNT_OFFSET)?;
+                let seed = read_u64(input, SEED_OFFSET)?;
+                let expiry = read_i64(input, MAKE_EXPIRY_OFFSET)?;
+                let kind = EscrowKind::try_from(input[MAKE_KIND_OFFSET])
+                    .map_err(|_| EscrowError::InvalidInstruction)?;
+                let receive_amount = read_u64(input, MAKE_RECEIVE_AMOUNT_OFFSET)?;
+                let memo = input
+                    .get(MAKE_MEMO_OFFSET..)
+                    .ok_or(EscrowError::InvalidInstruction)?
+                    .to_vec();
+                let namespace = read_array::<ESCROW_NAMESPACE_LEN>(input, MAKE_NAMESPACE_OFFSET)?;
+                let price_num = read_u64(input, MAKE_PRICE_NUM_OFFSET)?;
+                let price_den = read_u64(input, MAKE_PRICE_DEN_OFFSET)?;
+                let min_fill = read_u64(input, MAKE_MIN_FILL_OFFSET)?;
+                let persistent = match input[MAKE_PERSISTENT_OFFSET] {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(EscrowError::InvalidInstruction.into()),
+                };
+                let treasury = match input[MAKE_TREASURY_OFFSET] {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(EscrowError::InvalidInstruction.into()),
+                };
+                if input[0] == MAKE_INIT_DISCRIMINATOR {
+                    return Ok(EscrowInstruction::MakeInit { amount, seed, expiry, kind, receive_amount, memo, namespace, price_num, price_den, min_fill, persistent, treasury });
+                }
+                Ok(EscrowInstruction::Make { amount, seed, expiry, kind, receive_amount, memo, namespace, price_num, price_den, min_fill, persistent, treasury })
             }
-            1 => {
-                if input.len() < 17 {
+            TAKE_DISCRIMINATOR => {
+                if input.len() != TAKE_IX_LEN {
                     return Err(EscrowError::InvalidInstruction.into());
                 }
-                let amount = u64::from_le_bytes(input[1..9].try_into().unwrap());
-                let seed = u64::from_le_bytes(input[9..17].try_into().unwrap());
-                Ok(EscrowInstruction::Take { amount, seed })
+                let amount = read_u64(input, AMOUNT_OFFSET)?;
+                let seed = read_u64(input, SEED_OFFSET)?;
+                let max_in = read_u64(input, TAKE_MAX_IN_OFFSET)?;
+                let receive_amount = read_u64(input, TAKE_RECEIVE_AMOUNT_OFFSET)?;
+                Ok(EscrowInstruction::Take { amount, seed, max_in, receive_amount })
             }
-            2 => {
-                if input.len() < 17 {
+            REFUND_DISCRIMINATOR => {
+                if input.len() != REFUND_IX_LEN {
                     return Err(EscrowError::InvalidInstruction.into());
                 }
-                let amount = u64::from_le_bytes(input[1..9].try_into().unwrap());
-                let seed = u64::from_le_bytes(input[9..17].try_into().unwrap());
+                let amount = read_u64(input, AMOUNT_OFFSET)?;
+                let seed = read_u64(input, SEED_OFFSET)?;
                 Ok(EscrowInstruction::Refund { amount, seed })
             }
+            TRANSFER_AUTHORITY_DISCRIMINATOR => {
+                if input.len() != TRANSFER_AUTHORITY_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, TRANSFER_AUTHORITY_SEED_OFFSET)?;
+                Ok(EscrowInstruction::TransferAuthority { seed })
+            }
+            GET_ESCROW_DISCRIMINATOR => {
+                if input.len() != GET_ESCROW_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, GET_ESCROW_SEED_OFFSET)?;
+                Ok(EscrowInstruction::GetEscrow { seed })
+            }
+            FORCE_CLOSE_DISCRIMINATOR => {
+                if input.len() != FORCE_CLOSE_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, FORCE_CLOSE_SEED_OFFSET)?;
+                Ok(EscrowInstruction::ForceClose { seed })
+            }
+            SWEEP_EXCESS_DISCRIMINATOR => {
+                if input.len() != SWEEP_EXCESS_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, SWEEP_EXCESS_SEED_OFFSET)?;
+                Ok(EscrowInstruction::SweepExcess { seed })
+            }
+            MAKE_BASKET_DISCRIMINATOR => {
+                // the leg count has to be in range and match the amounts actually sent
+                let leg_count = *input
+                    .get(MAKE_BASKET_LEG_COUNT_OFFSET)
+                    .ok_or(EscrowError::InvalidInstruction)? as usize;
+                if leg_count == 0
+                    || leg_count > MAX_BASKET_LEGS
+                    || input.len() != MAKE_BASKET_AMOUNTS_OFFSET + 8 * leg_count
+                {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, MAKE_BASKET_SEED_OFFSET)?;
+                let receive_amount = read_u64(input, MAKE_BASKET_RECEIVE_AMOUNT_OFFSET)?;
+                let expiry = read_i64(input, MAKE_BASKET_EXPIRY_OFFSET)?;
+                let amounts = (0..leg_count)
+                    .map(|i| read_u64(input, MAKE_BASKET_AMOUNTS_OFFSET + 8 * i))
+                    .collect::<Result<Vec<u64>, ProgramError>>()?;
+                Ok(EscrowInstruction::MakeBasket { seed, receive_amount, expiry, amounts })
+            }
+            TAKE_BASKET_DISCRIMINATOR => {
+                if input.len() != TAKE_BASKET_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, BASKET_SEED_OFFSET)?;
+                Ok(EscrowInstruction::TakeBasket { seed })
+            }
+            REFUND_BASKET_DISCRIMINATOR => {
+                if input.len() != REFUND_BASKET_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, BASKET_SEED_OFFSET)?;
+                Ok(EscrowInstruction::RefundBasket { seed })
+            }
+            MIGRATE_ESCROW_DISCRIMINATOR => {
+                if input.len() != MIGRATE_ESCROW_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, MIGRATE_ESCROW_SEED_OFFSET)?;
+                Ok(EscrowInstruction::MigrateEscrow { seed })
+            }
+            TAKE_AVAILABLE_DISCRIMINATOR => {
+                if input.len() != TAKE_AVAILABLE_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let desired = read_u64(input, AMOUNT_OFFSET)?;
+                let seed = read_u64(input, SEED_OFFSET)?;
+                let max_in = read_u64(input, TAKE_MAX_IN_OFFSET)?;
+                Ok(EscrowInstruction::TakeAvailable { desired, seed, max_in })
+            }
+            MAKE_DEPOSIT_DISCRIMINATOR => {
+                if input.len() != MAKE_DEPOSIT_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, MAKE_DEPOSIT_SEED_OFFSET)?;
+                Ok(EscrowInstruction::MakeDeposit { seed })
+            }
+            MAKE_VESTING_DISCRIMINATOR => {
+                if input.len() != MAKE_VESTING_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, MAKE_VESTING_SEED_OFFSET)?;
+                let amount = read_u64(input, MAKE_VESTING_AMOUNT_OFFSET)?;
+                let start = read_i64(input, MAKE_VESTING_START_OFFSET)?;
+                let cliff = read_i64(input, MAKE_VESTING_CLIFF_OFFSET)?;
+                let duration = read_i64(input, MAKE_VESTING_DURATION_OFFSET)?;
+                Ok(EscrowInstruction::MakeVesting { seed, amount, start, cliff, duration })
+            }
+            CLAIM_DISCRIMINATOR => {
+                if input.len() != CLAIM_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, CLAIM_SEED_OFFSET)?;
+                Ok(EscrowInstruction::Claim { seed })
+            }
+            EXTEND_EXPIRY_DISCRIMINATOR => {
+                if input.len() != EXTEND_EXPIRY_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let new_expiry = read_i64(input, EXTEND_EXPIRY_NEW_EXPIRY_OFFSET)?;
+                let seed = read_u64(input, EXTEND_EXPIRY_SEED_OFFSET)?;
+                Ok(EscrowInstruction::ExtendExpiry { new_expiry, seed })
+            }
+            CLAIM_RECEIVE_DISCRIMINATOR => {
+                if input.len() != CLAIM_RECEIVE_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, CLAIM_RECEIVE_SEED_OFFSET)?;
+                Ok(EscrowInstruction::ClaimReceive { seed })
+            }
+            ARBITER_RESOLVE_DISCRIMINATOR => {
+                if input.len() != ARBITER_RESOLVE_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let seed = read_u64(input, ARBITER_RESOLVE_SEED_OFFSET)?;
+                let to_taker = match input[ARBITER_RESOLVE_TO_TAKER_OFFSET] {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(EscrowError::InvalidInstruction.into()),
+                };
+                Ok(EscrowInstruction::ArbiterResolve { seed, to_taker })
+            }
+            INIT_CONFIG_DISCRIMINATOR => {
+                if input.len() != INIT_CONFIG_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                Ok(EscrowInstruction::InitConfig)
+            }
+            SET_PAUSED_DISCRIMINATOR => {
+                if input.len() != SET_PAUSED_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let paused = match input[SET_PAUSED_PAUSED_OFFSET] {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(EscrowError::InvalidInstruction.into()),
+                };
+                Ok(EscrowInstruction::SetPaused { paused })
+            }
+            TOP_UP_DISCRIMINATOR => {
+                if input.len() != TOP_UP_IX_LEN {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let amount = read_u64(input, AMOUNT_OFFSET)?;
+                let seed = read_u64(input, SEED_OFFSET)?;
+                let receive_amount = read_u64(input, TOP_UP_RECEIVE_AMOUNT_OFFSET)?;
+                Ok(EscrowInstruction::TopUp { amount, seed, receive_amount })
+            }
             _ => Err(EscrowError::InvalidInstruction.into()),
         }
     }
@@ -108,50 +758,140 @@ pub fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
+    // a library deployed under another address would derive every PDA for
+    // the wrong program, embedders redeploying on purpose turn this off with
+    // the `skip-program-id-check` feature
+    #[cfg(not(feature = "skip-program-id-check"))]
+    if program_id != &crate::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
     let instruction = EscrowInstruction::unpack(instruction_data)?;
+    process(program_id, accounts, instruction)
+}
+
+// dispatch an already parsed instruction, for programs embedding the escrow
+// and for tests that build an EscrowInstruction directly instead of bytes
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: EscrowInstruction,
+) -> ProgramResult {
+    // no-op in the default build, counting costs a log per instruction
+    #[cfg(feature = "metrics")]
+    msg!(instruction.metric_log());
     
     match instruction {
-        EscrowInstruction::Make { amount, seed } => {
-            msg!(&format!("Processing Make instruction"));
-            let accounts = MakeAccounts {
-                maker: &accounts[0],
-                mint_a: &accounts[1],
-                mint_b: &accounts[2],
-                maker_ata_a: &accounts[3],
-                escrow: &accounts[4],
-                vault: &accounts[5],
-                token_program: &accounts[6],
-                system_program: &accounts[7],
-            };
-            make(program_id, accounts, amount, seed)
-        }
-        EscrowInstruction::Take { amount, seed } => {
-            msg!(&format!("Processing Take instruction"));
-            let accounts = TakeAccounts {
-                taker: &accounts[0],
-                maker: &accounts[1],
-                escrow: &accounts[2],
-                vault: &accounts[3],
-                mint_a: &accounts[4],
-                mint_b: &accounts[5],
-                taker_ata_a: &accounts[6],
-                taker_ata_b: &accounts[7],
-                maker_ata_b: &accounts[8],
-                token_program: &accounts[9],
-            };
-            take(program_id, accounts, amount, seed)
+        EscrowInstruction::Make { amount, seed, expiry, kind, receive_amount, memo, namespace, price_num, price_den, min_fill, persistent, treasury } => {
+            msg!("Processing Make instruction");
+            let accounts = MakeAccounts::from_slice(accounts)?;
+            make(program_id, accounts, amount, seed, expiry, kind, receive_amount, &memo, namespace, price_num, price_den, min_fill, persistent, treasury)
+        }
+        EscrowInstruction::Take { amount, seed, max_in, receive_amount } => {
+            msg!("Processing Take instruction");
+            let accounts = TakeAccounts::from_slice(accounts)?;
+            take(program_id, accounts, amount, seed, max_in, receive_amount)
         }
         EscrowInstruction::Refund { amount, seed } => {
-            msg!(&format!("Processing Refund instruction"));
-            let accounts = RefundAccounts {
-                maker: &accounts[0],
-                escrow: &accounts[1],
-                vault: &accounts[2],
-                maker_ata_a: &accounts[3],
-                token_program: &accounts[4],
-            };
+            msg!("Processing Refund instruction");
+            let accounts = RefundAccounts::from_slice(accounts)?;
             refund(program_id, accounts, amount, seed)
         }
+        EscrowInstruction::TransferAuthority { seed } => {
+            msg!("Processing TransferAuthority instruction");
+            let accounts = TransferAuthorityAccounts::from_slice(accounts)?;
+            transfer_authority(program_id, accounts, seed)
+        }
+        EscrowInstruction::GetEscrow { seed } => {
+            msg!("Processing GetEscrow instruction");
+            let accounts = GetEscrowAccounts::from_slice(accounts)?;
+            get_escrow(program_id, accounts, seed)
+        }
+        EscrowInstruction::ForceClose { seed } => {
+            msg!("Processing ForceClose instruction");
+            let accounts = ForceCloseAccounts::from_slice(accounts)?;
+            force_close(program_id, accounts, seed)
+        }
+        EscrowInstruction::SweepExcess { seed } => {
+            msg!("Processing SweepExcess instruction");
+            let accounts = SweepExcessAccounts::from_slice(accounts)?;
+            sweep_excess(program_id, accounts, seed)
+        }
+        EscrowInstruction::MakeBasket { seed, receive_amount, expiry, amounts } => {
+            msg!("Processing MakeBasket instruction");
+            let accounts = MakeBasketAccounts::from_slice(accounts)?;
+            make_basket(program_id, accounts, seed, receive_amount, expiry, &amounts)
+        }
+        EscrowInstruction::TakeBasket { seed } => {
+            msg!("Processing TakeBasket instruction");
+            let accounts = TakeBasketAccounts::from_slice(accounts)?;
+            take_basket(program_id, accounts, seed)
+        }
+        EscrowInstruction::RefundBasket { seed } => {
+            msg!("Processing RefundBasket instruction");
+            let accounts = RefundBasketAccounts::from_slice(accounts)?;
+            refund_basket(program_id, accounts, seed)
+        }
+        EscrowInstruction::MigrateEscrow { seed } => {
+            msg!("Processing MigrateEscrow instruction");
+            let accounts = MigrateEscrowAccounts::from_slice(accounts)?;
+            migrate_escrow(program_id, accounts, seed)
+        }
+        EscrowInstruction::TakeAvailable { desired, seed, max_in } => {
+            msg!("Processing TakeAvailable instruction");
+            let accounts = TakeAccounts::from_slice(accounts)?;
+            take_available(program_id, accounts, desired, seed, max_in)
+        }
+        EscrowInstruction::MakeInit { amount, seed, expiry, kind, receive_amount, memo, namespace, price_num, price_den, min_fill, persistent, treasury } => {
+            msg!("Processing MakeInit instruction");
+            let accounts = MakeAccounts::from_slice(accounts)?;
+            make_init(program_id, accounts, amount, seed, expiry, kind, receive_amount, &memo, namespace, price_num, price_den, min_fill, persistent, treasury)
+        }
+        EscrowInstruction::MakeDeposit { seed } => {
+            msg!("Processing MakeDeposit instruction");
+            let accounts = MakeDepositAccounts::from_slice(accounts)?;
+            make_deposit(program_id, accounts, seed)
+        }
+        EscrowInstruction::MakeVesting { seed, amount, start, cliff, duration } => {
+            msg!("Processing MakeVesting instruction");
+            let accounts = MakeVestingAccounts::from_slice(accounts)?;
+            make_vesting(program_id, accounts, seed, amount, start, cliff, duration)
+        }
+        EscrowInstruction::Claim { seed } => {
+            msg!("Processing Claim instruction");
+            let accounts = ClaimAccounts::from_slice(accounts)?;
+            claim(program_id, accounts, seed)
+        }
+        EscrowInstruction::ExtendExpiry { new_expiry, seed } => {
+            msg!("Processing ExtendExpiry instruction");
+            let accounts = ExtendExpiryAccounts::from_slice(accounts)?;
+            extend_expiry(program_id, accounts, new_expiry, seed)
+        }
+        EscrowInstruction::ClaimReceive { seed } => {
+            msg!("Processing ClaimReceive instruction");
+            let accounts = ClaimReceiveAccounts::from_slice(accounts)?;
+            claim_receive(program_id, accounts, seed)
+        }
+        EscrowInstruction::ArbiterResolve { seed, to_taker } => {
+            msg!("Processing ArbiterResolve instruction");
+            let accounts = ArbiterResolveAccounts::from_slice(accounts)?;
+            arbiter_resolve(program_id, accounts, seed, to_taker)
+        }
+        EscrowInstruction::InitConfig => {
+            msg!("Processing InitConfig instruction");
+            let accounts = InitConfigAccounts::from_slice(accounts)?;
+            init_config(program_id, accounts)
+        }
+        EscrowInstruction::SetPaused { paused } => {
+            msg!("Processing SetPaused instruction");
+            let accounts = SetPausedAccounts::from_slice(accounts)?;
+            set_paused(program_id, accounts, paused)
+        }
+        EscrowInstruction::TopUp { amount, seed, receive_amount } => {
+            msg!("Processing TopUp instruction");
+            let accounts = TopUpAccounts::from_slice(accounts)?;
+            top_up(program_id, accounts, amount, receive_amount, seed)
+        }
     }
 }
 
@@ -163,22 +903,170 @@ entrypoint!(process_instruction);
 // helper function for creating instruction data
 pub fn pack_instruction_data(instruction: &EscrowInstruction) -> Vec<u8> {
     match instruction {
-        EscrowInstruction::Make { amount, seed } => {
-            let mut data = vec![0u8]; // Make discriminator
-            data.extend_from_slice(&amount.to_le_bytes());
-            data.extend_from_slice(&seed.to_le_bytes());
+        EscrowInstruction::Make { amount, seed, expiry, kind, receive_amount, memo, namespace, price_num, price_den, min_fill, persistent, treasury }
+        | EscrowInstruction::MakeInit { amount, seed, expiry, kind, receive_amount, memo, namespace, price_num, price_den, min_fill, persistent, treasury } => {
+            let mut data = vec![0u8; MAKE_MEMO_OFFSET];
+            data[0] = if matches!(instruction, EscrowInstruction::MakeInit { .. }) {
+                MAKE_INIT_DISCRIMINATOR
+            } else {
+                MAKE_DISCRIMINATOR
+            };
+            data[AMOUNT_OFFSET..AMOUNT_OFFSET + 8].copy_from_slice(&amount.to_le_bytes());
+            data[SEED_OFFSET..SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data[MAKE_EXPIRY_OFFSET..MAKE_EXPIRY_OFFSET + 8].copy_from_slice(&expiry.to_le_bytes());
+            data[MAKE_KIND_OFFSET] = *kind as u8;
+            data[MAKE_RECEIVE_AMOUNT_OFFSET..MAKE_RECEIVE_AMOUNT_OFFSET + 8]
+                .copy_from_slice(&receive_amount.to_le_bytes());
+            data[MAKE_NAMESPACE_OFFSET..MAKE_NAMESPACE_OFFSET + ESCROW_NAMESPACE_LEN].copy_from_slice(namespace);
+            data[MAKE_PRICE_NUM_OFFSET..MAKE_PRICE_NUM_OFFSET + 8].copy_from_slice(&price_num.to_le_bytes());
+            data[MAKE_PRICE_DEN_OFFSET..MAKE_PRICE_DEN_OFFSET + 8].copy_from_slice(&price_den.to_le_bytes());
+            data[MAKE_MIN_FILL_OFFSET..MAKE_MIN_FILL_OFFSET + 8].copy_from_slice(&min_fill.to_le_bytes());
+            data[MAKE_PERSISTENT_OFFSET] = *persistent as u8;
+            data[MAKE_TREASURY_OFFSET] = *treasury as u8;
+            // a memo over MAX_MEMO_LEN packs a length unpack rejects
+            data[MAKE_MEMO_LEN_OFFSET] = u8::try_from(memo.len()).unwrap_or(u8::MAX);
+            data.extend_from_slice(memo);
             data
         }
-        EscrowInstruction::Take { amount, seed } => {
-            let mut data = vec![1u8]; // Take discriminator
-            data.extend_from_slice(&amount.to_le_bytes());
-            data.extend_from_slice(&seed.to_le_bytes());
+        EscrowInstruction::Take { amount, seed, max_in, receive_amount } => {
+            let mut data = vec![0u8; TAKE_IX_LEN];
+            data[0] = TAKE_DISCRIMINATOR;
+            data[AMOUNT_OFFSET..AMOUNT_OFFSET + 8].copy_from_slice(&amount.to_le_bytes());
+            data[SEED_OFFSET..SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data[TAKE_MAX_IN_OFFSET..TAKE_MAX_IN_OFFSET + 8].copy_from_slice(&max_in.to_le_bytes());
+            data[TAKE_RECEIVE_AMOUNT_OFFSET..TAKE_RECEIVE_AMOUNT_OFFSET + 8].copy_from_slice(&receive_amount.to_le_bytes());
             data
         }
         EscrowInstruction::Refund { amount, seed } => {
-            let mut data = vec![2u8]; // Refund discriminator
-            data.extend_from_slice(&amount.to_le_bytes());
-            data.extend_from_slice(&seed.to_le_bytes());
+            let mut data = vec![0u8; REFUND_IX_LEN];
+            data[0] = REFUND_DISCRIMINATOR;
+            data[AMOUNT_OFFSET..AMOUNT_OFFSET + 8].copy_from_slice(&amount.to_le_bytes());
+            data[SEED_OFFSET..SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::TransferAuthority { seed } => {
+            let mut data = vec![0u8; TRANSFER_AUTHORITY_IX_LEN];
+            data[0] = TRANSFER_AUTHORITY_DISCRIMINATOR;
+            data[TRANSFER_AUTHORITY_SEED_OFFSET..TRANSFER_AUTHORITY_SEED_OFFSET + 8]
+                .copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::GetEscrow { seed } => {
+            let mut data = vec![0u8; GET_ESCROW_IX_LEN];
+            data[0] = GET_ESCROW_DISCRIMINATOR;
+            data[GET_ESCROW_SEED_OFFSET..GET_ESCROW_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::ForceClose { seed } => {
+            let mut data = vec![0u8; FORCE_CLOSE_IX_LEN];
+            data[0] = FORCE_CLOSE_DISCRIMINATOR;
+            data[FORCE_CLOSE_SEED_OFFSET..FORCE_CLOSE_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::SweepExcess { seed } => {
+            let mut data = vec![0u8; SWEEP_EXCESS_IX_LEN];
+            data[0] = SWEEP_EXCESS_DISCRIMINATOR;
+            data[SWEEP_EXCESS_SEED_OFFSET..SWEEP_EXCESS_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::MakeBasket { seed, receive_amount, expiry, amounts } => {
+            let mut data = vec![0u8; MAKE_BASKET_AMOUNTS_OFFSET];
+            data[0] = MAKE_BASKET_DISCRIMINATOR;
+            data[MAKE_BASKET_SEED_OFFSET..MAKE_BASKET_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data[MAKE_BASKET_RECEIVE_AMOUNT_OFFSET..MAKE_BASKET_RECEIVE_AMOUNT_OFFSET + 8]
+                .copy_from_slice(&receive_amount.to_le_bytes());
+            data[MAKE_BASKET_EXPIRY_OFFSET..MAKE_BASKET_EXPIRY_OFFSET + 8].copy_from_slice(&expiry.to_le_bytes());
+            // too many legs packs a count unpack rejects
+            data[MAKE_BASKET_LEG_COUNT_OFFSET] = u8::try_from(amounts.len()).unwrap_or(u8::MAX);
+            for amount in amounts {
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            data
+        }
+        EscrowInstruction::TakeBasket { seed } => {
+            let mut data = vec![0u8; TAKE_BASKET_IX_LEN];
+            data[0] = TAKE_BASKET_DISCRIMINATOR;
+            data[BASKET_SEED_OFFSET..BASKET_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::RefundBasket { seed } => {
+            let mut data = vec![0u8; REFUND_BASKET_IX_LEN];
+            data[0] = REFUND_BASKET_DISCRIMINATOR;
+            data[BASKET_SEED_OFFSET..BASKET_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::MigrateEscrow { seed } => {
+            let mut data = vec![0u8; MIGRATE_ESCROW_IX_LEN];
+            data[0] = MIGRATE_ESCROW_DISCRIMINATOR;
+            data[MIGRATE_ESCROW_SEED_OFFSET..MIGRATE_ESCROW_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::TakeAvailable { desired, seed, max_in } => {
+            let mut data = vec![0u8; TAKE_AVAILABLE_IX_LEN];
+            data[0] = TAKE_AVAILABLE_DISCRIMINATOR;
+            data[AMOUNT_OFFSET..AMOUNT_OFFSET + 8].copy_from_slice(&desired.to_le_bytes());
+            data[SEED_OFFSET..SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data[TAKE_MAX_IN_OFFSET..TAKE_MAX_IN_OFFSET + 8].copy_from_slice(&max_in.to_le_bytes());
+            data
+        }
+        EscrowInstruction::MakeDeposit { seed } => {
+            let mut data = vec![0u8; MAKE_DEPOSIT_IX_LEN];
+            data[0] = MAKE_DEPOSIT_DISCRIMINATOR;
+            data[MAKE_DEPOSIT_SEED_OFFSET..MAKE_DEPOSIT_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::MakeVesting { seed, amount, start, cliff, duration } => {
+            let mut data = vec![0u8; MAKE_VESTING_IX_LEN];
+            data[0] = MAKE_VESTING_DISCRIMINATOR;
+            data[MAKE_VESTING_SEED_OFFSET..MAKE_VESTING_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data[MAKE_VESTING_AMOUNT_OFFSET..MAKE_VESTING_AMOUNT_OFFSET + 8].copy_from_slice(&amount.to_le_bytes());
+            data[MAKE_VESTING_START_OFFSET..MAKE_VESTING_START_OFFSET + 8].copy_from_slice(&start.to_le_bytes());
+            data[MAKE_VESTING_CLIFF_OFFSET..MAKE_VESTING_CLIFF_OFFSET + 8].copy_from_slice(&cliff.to_le_bytes());
+            data[MAKE_VESTING_DURATION_OFFSET..MAKE_VESTING_DURATION_OFFSET + 8]
+                .copy_from_slice(&duration.to_le_bytes());
+            data
+        }
+        EscrowInstruction::Claim { seed } => {
+            let mut data = vec![0u8; CLAIM_IX_LEN];
+            data[0] = CLAIM_DISCRIMINATOR;
+            data[CLAIM_SEED_OFFSET..CLAIM_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::ExtendExpiry { new_expiry, seed } => {
+            let mut data = vec![0u8; EXTEND_EXPIRY_IX_LEN];
+            data[0] = EXTEND_EXPIRY_DISCRIMINATOR;
+            data[EXTEND_EXPIRY_NEW_EXPIRY_OFFSET..EXTEND_EXPIRY_NEW_EXPIRY_OFFSET + 8]
+                .copy_from_slice(&new_expiry.to_le_bytes());
+            data[EXTEND_EXPIRY_SEED_OFFSET..EXTEND_EXPIRY_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::ClaimReceive { seed } => {
+            let mut data = vec![0u8; CLAIM_RECEIVE_IX_LEN];
+            data[0] = CLAIM_RECEIVE_DISCRIMINATOR;
+            data[CLAIM_RECEIVE_SEED_OFFSET..CLAIM_RECEIVE_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data
+        }
+        EscrowInstruction::ArbiterResolve { seed, to_taker } => {
+            let mut data = vec![0u8; ARBITER_RESOLVE_IX_LEN];
+            data[0] = ARBITER_RESOLVE_DISCRIMINATOR;
+            data[ARBITER_RESOLVE_SEED_OFFSET..ARBITER_RESOLVE_SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data[ARBITER_RESOLVE_TO_TAKER_OFFSET] = to_taker as u8;
+            data
+        }
+        EscrowInstruction::InitConfig => vec![INIT_CONFIG_DISCRIMINATOR],
+        EscrowInstruction::SetPaused { paused } => {
+            let mut data = vec![0u8; SET_PAUSED_IX_LEN];
+            data[0] = SET_PAUSED_DISCRIMINATOR;
+            data[SET_PAUSED_PAUSED_OFFSET] = *paused as u8;
+            data
+        }
+        EscrowInstruction::TopUp { amount, seed, receive_amount } => {
+            let mut data = vec![0u8; TOP_UP_IX_LEN];
+            data[0] = TOP_UP_DISCRIMINATOR;
+            data[AMOUNT_OFFSET..AMOUNT_OFFSET + 8].copy_from_slice(&amount.to_le_bytes());
+            data[SEED_OFFSET..SEED_OFFSET + 8].copy_from_slice(&seed.to_le_bytes());
+            data[TOP_UP_RECEIVE_AMOUNT_OFFSET..TOP_UP_RECEIVE_AMOUNT_OFFSET + 8]
+                .copy_from_slice(&receive_amount.to_le_bytes());
             data
         }
     }
@@ -187,29 +1075,53 @@ pub fn pack_instruction_data(instruction: &EscrowInstruction) -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::{account_infos, MockAccount};
+    use crate::instructions::{
+        make::{validate_expiry, MAX_EXPIRY_HORIZON},
+        refund::refund_amount,
+        take::{check_slippage, check_taker_balance},
+    };
+    use crate::programs::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+    use crate::token::{
+        check_mint_data, check_token_account_data, check_token_account_mint, mint_has_freeze_authority,
+        sweep_amount, vault_account_size, vault_rent_exempt_lamports, MINT_LEN, TOKEN_ACCOUNT_LEN,
+    };
 
     #[test]
     fn test_instruction_packing() {
         // test Make instruction
-        let make_instruction = EscrowInstruction::Make { amount: 1000, seed: 12345 };
+        let make_instruction = EscrowInstruction::Make { amount: 1000, seed: 12345, expiry: 1_700_000_000, kind: EscrowKind::Token, receive_amount: 4000, memo: vec![7u8; 32], namespace: [0u8; 8], price_num: 0, price_den: 0, min_fill: 0, persistent: false, treasury: false };
         let packed = pack_instruction_data(&make_instruction);
         
         let expected = {
             let mut data = vec![0u8]; // discriminator
             data.extend_from_slice(&1000u64.to_le_bytes());
             data.extend_from_slice(&12345u64.to_le_bytes());
+            data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+            data.push(0); // kind
+            data.extend_from_slice(&4000u64.to_le_bytes());
+            data.extend_from_slice(&[0u8; 8]); // namespace
+            data.extend_from_slice(&0u64.to_le_bytes()); // price_num
+            data.extend_from_slice(&0u64.to_le_bytes()); // price_den
+            data.extend_from_slice(&0u64.to_le_bytes()); // min_fill
+            data.push(0); // persistent
+            data.push(0); // treasury
+            data.push(32); // memo length
+            data.extend_from_slice(&[7u8; 32]); // memo
             data
         };
         
         assert_eq!(packed, expected);
         
         // test Take instruction
-        let take_instruction = EscrowInstruction::Take { amount: 2000, seed: 67890 };
+        let take_instruction = EscrowInstruction::Take { amount: 2000, seed: 67890, max_in: 5000, receive_amount: 4000 };
         let packed = pack_instruction_data(&take_instruction);
         let expected = {
             let mut data = vec![1u8]; // discriminator
             data.extend_from_slice(&2000u64.to_le_bytes());
             data.extend_from_slice(&67890u64.to_le_bytes());
+            data.extend_from_slice(&5000u64.to_le_bytes());
+            data.extend_from_slice(&4000u64.to_le_bytes());
             data
         };
         assert_eq!(packed, expected);
@@ -233,14 +1145,34 @@ mod tests {
             let mut data = vec![0u8]; // discriminator
             data.extend_from_slice(&1000u64.to_le_bytes());
             data.extend_from_slice(&12345u64.to_le_bytes());
+            data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+            data.push(0); // kind
+            data.extend_from_slice(&4000u64.to_le_bytes());
+            data.extend_from_slice(b"tenant-1"); // namespace
+            data.extend_from_slice(&3u64.to_le_bytes()); // price_num
+            data.extend_from_slice(&2u64.to_le_bytes()); // price_den
+            data.extend_from_slice(&250u64.to_le_bytes()); // min_fill
+            data.push(1); // persistent
+            data.push(1); // treasury
+            data.push(11); // memo length
+            data.extend_from_slice(b"invoice-042"); // memo
             data
         };
         
         let instruction = EscrowInstruction::unpack(&data).unwrap();
         match instruction {
-            EscrowInstruction::Make { amount, seed } => {
+            EscrowInstruction::Make { amount, seed, expiry, kind, receive_amount, memo, namespace, price_num, price_den, min_fill, persistent, treasury } => {
                 assert_eq!(amount, 1000);
                 assert_eq!(seed, 12345);
+                assert_eq!(expiry, 1_700_000_000);
+                assert_eq!(kind, EscrowKind::Token);
+                assert_eq!(receive_amount, 4000);
+                assert_eq!(memo, b"invoice-042");
+                assert_eq!(&namespace, b"tenant-1");
+                assert_eq!((price_num, price_den), (3, 2));
+                assert_eq!(min_fill, 250);
+                assert!(persistent);
+                assert!(treasury);
             }
             _ => panic!("Wrong instruction type"),
         }
@@ -250,13 +1182,17 @@ mod tests {
             let mut data = vec![1u8]; // discriminator
             data.extend_from_slice(&2000u64.to_le_bytes());
             data.extend_from_slice(&67890u64.to_le_bytes());
+            data.extend_from_slice(&5000u64.to_le_bytes());
+            data.extend_from_slice(&4000u64.to_le_bytes());
             data
         };
         let instruction = EscrowInstruction::unpack(&take_data).unwrap();
         match instruction {
-            EscrowInstruction::Take { amount, seed } => {
+            EscrowInstruction::Take { amount, seed, max_in, receive_amount } => {
                 assert_eq!(amount, 2000);
                 assert_eq!(seed, 67890);
+                assert_eq!(max_in, 5000);
+                assert_eq!(receive_amount, 4000);
             }
             _ => panic!("Wrong instruction type"),
         }
@@ -277,8 +1213,22 @@ mod tests {
             _ => panic!("Wrong instruction type"),
         }
         
+        // test TransferAuthority instruction unpacking
+        let transfer_data = {
+            let mut data = vec![3u8]; // discriminator
+            data.extend_from_slice(&445566u64.to_le_bytes());
+            data
+        };
+        let instruction = EscrowInstruction::unpack(&transfer_data).unwrap();
+        match instruction {
+            EscrowInstruction::TransferAuthority { seed } => {
+                assert_eq!(seed, 445566);
+            }
+            _ => panic!("Wrong instruction type"),
+        }
+        
         // test invalid instruction
-        let invalid_data = vec![3u8];
+        let invalid_data = vec![255u8];
         assert!(EscrowInstruction::unpack(&invalid_data).is_err());
         
         // test empty data
@@ -291,47 +1241,806 @@ mod tests {
     }
 
     #[test]
-    fn test_escrow_constants() {
-        // test that our constants are properly defined
-        assert_eq!(ID.len(), 32);
-        assert_eq!(TOKEN_PROGRAM_ID.len(), 32);
-        
-        // test escrow size is reasonable
-        assert!(Escrow::LEN > 0);
-        assert!(Escrow::LEN < 1000); // Reasonable size limit
+    fn test_instruction_length_constants() {
+        let cases = [
+            (EscrowInstruction::Make { amount: 1, seed: 2, expiry: 3, kind: EscrowKind::Token, receive_amount: 4, memo: vec![5u8; 32], namespace: [6u8; 8], price_num: 0, price_den: 0, min_fill: 0, persistent: false, treasury: false }, MAKE_IX_MIN_LEN + 32),
+            (EscrowInstruction::Take { amount: 1, seed: 2, max_in: 3, receive_amount: 4 }, TAKE_IX_LEN),
+            (EscrowInstruction::Refund { amount: 1, seed: 2 }, REFUND_IX_LEN),
+            (EscrowInstruction::TransferAuthority { seed: 2 }, TRANSFER_AUTHORITY_IX_LEN),
+            (EscrowInstruction::GetEscrow { seed: 2 }, GET_ESCROW_IX_LEN),
+            (EscrowInstruction::ForceClose { seed: 2 }, FORCE_CLOSE_IX_LEN),
+            (EscrowInstruction::SweepExcess { seed: 2 }, SWEEP_EXCESS_IX_LEN),
+            (EscrowInstruction::MakeBasket { seed: 2, receive_amount: 3, expiry: 4, amounts: vec![5] }, MAKE_BASKET_IX_MIN_LEN),
+            (EscrowInstruction::MakeBasket { seed: 2, receive_amount: 3, expiry: 4, amounts: vec![5; MAX_BASKET_LEGS] }, MAKE_BASKET_IX_MAX_LEN),
+            (EscrowInstruction::TakeBasket { seed: 2 }, TAKE_BASKET_IX_LEN),
+            (EscrowInstruction::RefundBasket { seed: 2 }, REFUND_BASKET_IX_LEN),
+            (EscrowInstruction::MigrateEscrow { seed: 2 }, MIGRATE_ESCROW_IX_LEN),
+            (EscrowInstruction::TakeAvailable { desired: 1, seed: 2, max_in: 3 }, TAKE_AVAILABLE_IX_LEN),
+            (EscrowInstruction::MakeInit { amount: 1, seed: 2, expiry: 3, kind: EscrowKind::Token, receive_amount: 4, memo: vec![5u8; 32], namespace: [6u8; 8], price_num: 0, price_den: 0, min_fill: 0, persistent: false, treasury: false }, MAKE_IX_MIN_LEN + 32),
+            (EscrowInstruction::MakeDeposit { seed: 2 }, MAKE_DEPOSIT_IX_LEN),
+            (EscrowInstruction::MakeVesting { seed: 2, amount: 3, start: 4, cliff: 5, duration: 6 }, MAKE_VESTING_IX_LEN),
+            (EscrowInstruction::Claim { seed: 2 }, CLAIM_IX_LEN),
+            (EscrowInstruction::ExtendExpiry { new_expiry: 1, seed: 2 }, EXTEND_EXPIRY_IX_LEN),
+            (EscrowInstruction::ClaimReceive { seed: 2 }, CLAIM_RECEIVE_IX_LEN),
+            (EscrowInstruction::ArbiterResolve { seed: 2, to_taker: true }, ARBITER_RESOLVE_IX_LEN),
+            (EscrowInstruction::InitConfig, INIT_CONFIG_IX_LEN),
+            (EscrowInstruction::SetPaused { paused: true }, SET_PAUSED_IX_LEN),
+            (EscrowInstruction::TopUp { amount: 1, seed: 2, receive_amount: 3 }, TOP_UP_IX_LEN),
+        ];
+
+        for (instruction, len) in cases.iter() {
+            assert_eq!(pack_instruction_data(instruction).len(), *len);
+        }
+
+        // the last field of each layout ends exactly at the declared length
+        assert_eq!(MAKE_RECEIVE_AMOUNT_OFFSET + 8, MAKE_NAMESPACE_OFFSET);
+        assert_eq!(MAKE_NAMESPACE_OFFSET + ESCROW_NAMESPACE_LEN, MAKE_PRICE_NUM_OFFSET);
+        assert_eq!(MAKE_PRICE_NUM_OFFSET + 8, MAKE_PRICE_DEN_OFFSET);
+        assert_eq!(MAKE_PRICE_DEN_OFFSET + 8, MAKE_MIN_FILL_OFFSET);
+        assert_eq!(MAKE_MIN_FILL_OFFSET + 8, MAKE_PERSISTENT_OFFSET);
+        assert_eq!(MAKE_PERSISTENT_OFFSET + 1, MAKE_TREASURY_OFFSET);
+        assert_eq!(MAKE_TREASURY_OFFSET + 1, MAKE_MEMO_LEN_OFFSET);
+        assert_eq!(MAKE_MEMO_LEN_OFFSET + 1, MAKE_MEMO_OFFSET);
+        assert_eq!(MAKE_IX_MAX_LEN - MAKE_IX_MIN_LEN, MAX_MEMO_LEN);
+        assert_eq!(TAKE_MAX_IN_OFFSET + 8, TAKE_IX_LEN);
+        assert_eq!(TAKE_MAX_IN_OFFSET + 8, TAKE_AVAILABLE_IX_LEN);
+        assert_eq!(SEED_OFFSET + 8, REFUND_IX_LEN);
+        assert_eq!(TRANSFER_AUTHORITY_SEED_OFFSET + 8, TRANSFER_AUTHORITY_IX_LEN);
+        assert_eq!(GET_ESCROW_SEED_OFFSET + 8, GET_ESCROW_IX_LEN);
+        assert_eq!(FORCE_CLOSE_SEED_OFFSET + 8, FORCE_CLOSE_IX_LEN);
+        assert_eq!(SWEEP_EXCESS_SEED_OFFSET + 8, SWEEP_EXCESS_IX_LEN);
+        assert_eq!(MAKE_BASKET_SEED_OFFSET + 8, MAKE_BASKET_RECEIVE_AMOUNT_OFFSET);
+        assert_eq!(MAKE_BASKET_RECEIVE_AMOUNT_OFFSET + 8, MAKE_BASKET_EXPIRY_OFFSET);
+        assert_eq!(MAKE_BASKET_EXPIRY_OFFSET + 8, MAKE_BASKET_LEG_COUNT_OFFSET);
+        assert_eq!(MAKE_BASKET_LEG_COUNT_OFFSET + 1, MAKE_BASKET_AMOUNTS_OFFSET);
+        assert_eq!(BASKET_SEED_OFFSET + 8, TAKE_BASKET_IX_LEN);
+        assert_eq!(BASKET_SEED_OFFSET + 8, REFUND_BASKET_IX_LEN);
+        assert_eq!(MIGRATE_ESCROW_SEED_OFFSET + 8, MIGRATE_ESCROW_IX_LEN);
+        assert_eq!(MAKE_DEPOSIT_SEED_OFFSET + 8, MAKE_DEPOSIT_IX_LEN);
+        assert_eq!(MAKE_VESTING_SEED_OFFSET + 8, MAKE_VESTING_AMOUNT_OFFSET);
+        assert_eq!(MAKE_VESTING_AMOUNT_OFFSET + 8, MAKE_VESTING_START_OFFSET);
+        assert_eq!(MAKE_VESTING_START_OFFSET + 8, MAKE_VESTING_CLIFF_OFFSET);
+        assert_eq!(MAKE_VESTING_CLIFF_OFFSET + 8, MAKE_VESTING_DURATION_OFFSET);
+        assert_eq!(MAKE_VESTING_DURATION_OFFSET + 8, MAKE_VESTING_IX_LEN);
+        assert_eq!(CLAIM_SEED_OFFSET + 8, CLAIM_IX_LEN);
+        assert_eq!(EXTEND_EXPIRY_NEW_EXPIRY_OFFSET + 8, EXTEND_EXPIRY_SEED_OFFSET);
+        assert_eq!(EXTEND_EXPIRY_SEED_OFFSET + 8, EXTEND_EXPIRY_IX_LEN);
+        assert_eq!(CLAIM_RECEIVE_SEED_OFFSET + 8, CLAIM_RECEIVE_IX_LEN);
+        assert_eq!(ARBITER_RESOLVE_SEED_OFFSET + 8, ARBITER_RESOLVE_TO_TAKER_OFFSET);
+        assert_eq!(ARBITER_RESOLVE_TO_TAKER_OFFSET + 1, ARBITER_RESOLVE_IX_LEN);
+        assert_eq!(SET_PAUSED_PAUSED_OFFSET + 1, SET_PAUSED_IX_LEN);
+        assert_eq!(SEED_OFFSET + 8, TOP_UP_RECEIVE_AMOUNT_OFFSET);
+        assert_eq!(TOP_UP_RECEIVE_AMOUNT_OFFSET + 8, TOP_UP_IX_LEN);
     }
 
     #[test]
-    fn test_escrow_discriminator() {
-        // test that discriminator is properly set
-        assert_eq!(Escrow::DISCRIMINATOR.len(), 8);
-        
-        // test discriminator is not all zeros (which would be invalid)
-        assert_ne!(Escrow::DISCRIMINATOR, [0u8; 8]);
+    fn test_unpack_rejects_trailing_bytes() {
+        let instructions = [
+            EscrowInstruction::Make { amount: 1, seed: 2, expiry: 3, kind: EscrowKind::Native, receive_amount: 4, memo: vec![5u8; 32], namespace: [6u8; 8], price_num: 0, price_den: 0, min_fill: 0, persistent: false, treasury: false },
+            EscrowInstruction::Take { amount: 1, seed: 2, max_in: 3, receive_amount: 4 },
+            EscrowInstruction::Refund { amount: 1, seed: 2 },
+            EscrowInstruction::TransferAuthority { seed: 2 },
+            EscrowInstruction::GetEscrow { seed: 2 },
+            EscrowInstruction::ForceClose { seed: 2 },
+            EscrowInstruction::SweepExcess { seed: 2 },
+            EscrowInstruction::MakeBasket { seed: 2, receive_amount: 3, expiry: 4, amounts: vec![5, 6] },
+            EscrowInstruction::TakeBasket { seed: 2 },
+            EscrowInstruction::RefundBasket { seed: 2 },
+            EscrowInstruction::MigrateEscrow { seed: 2 },
+            EscrowInstruction::TakeAvailable { desired: 1, seed: 2, max_in: 3 },
+            EscrowInstruction::MakeInit { amount: 1, seed: 2, expiry: 3, kind: EscrowKind::Token, receive_amount: 4, memo: vec![5u8; 32], namespace: [6u8; 8], price_num: 0, price_den: 0, min_fill: 0, persistent: false, treasury: false },
+            EscrowInstruction::MakeDeposit { seed: 2 },
+            EscrowInstruction::MakeVesting { seed: 2, amount: 3, start: 4, cliff: 5, duration: 6 },
+            EscrowInstruction::Claim { seed: 2 },
+            EscrowInstruction::ExtendExpiry { new_expiry: 1, seed: 2 },
+            EscrowInstruction::ClaimReceive { seed: 2 },
+            EscrowInstruction::ArbiterResolve { seed: 2, to_taker: false },
+            EscrowInstruction::InitConfig,
+            EscrowInstruction::SetPaused { paused: false },
+            EscrowInstruction::TopUp { amount: 1, seed: 2, receive_amount: 3 },
+        ];
+
+        for instruction in instructions.iter() {
+            let mut data = pack_instruction_data(instruction);
+            assert!(EscrowInstruction::unpack(&data).is_ok());
+
+            // one extra byte of garbage
+            data.push(0);
+            assert!(EscrowInstruction::unpack(&data).is_err());
+        }
     }
 
     #[test]
-    fn test_error_conversion() {
-        // test that custom errors properly convert to ProgramError
-        let escrow_error = EscrowError::InvalidInstruction;
-        let program_error: ProgramError = escrow_error.into();
+    fn test_field_readers_never_panic() {
+        let invalid: ProgramError = EscrowError::InvalidInstruction.into();
+        let data = [1u8; 12];
         
-        // should be a custom error
+        // in range
+        assert_eq!(read_u64(&data, 4).unwrap(), u64::from_le_bytes([1u8; 8]));
+        assert_eq!(read_i64(&data, 0).unwrap(), i64::from_le_bytes([1u8; 8]));
+        
+        // crafted short slice: the field would run past the end
+        assert_eq!(read_u64(&data, 5).unwrap_err(), invalid);
+        assert_eq!(read_i64(&data[..7], 0).unwrap_err(), invalid);
+        assert_eq!(read_array::<32>(&data, 0).unwrap_err(), invalid);
+        
+        // offset past the end, or one that overflows when the width is added
+        assert_eq!(read_u64(&data, 100).unwrap_err(), invalid);
+        assert!(read_array::<8>(&data, usize::MAX - 4).is_err());
+    }
+
+    // xorshift64*, deterministic so a failing case always reproduces
+    struct TestRng(u64);
+
+    impl TestRng {
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn next_bytes<const N: usize>(&mut self) -> [u8; N] {
+            let mut out = [0u8; N];
+            out.iter_mut().for_each(|byte| *byte = self.next_u64() as u8);
+            out
+        }
+
+        // mostly random values, with the edges of the range mixed in
+        fn next_edge_u64(&mut self) -> u64 {
+            match self.next_u64() % 8 {
+                0 => 0,
+                1 => u64::MAX,
+                2 => 1,
+                _ => self.next_u64(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_pack_unpack_property() {
+        let mut rng = TestRng(0x5eed_0001);
+
+        for _ in 0..2_000 {
+            let instructions = [
+                EscrowInstruction::Make {
+                    amount: rng.next_edge_u64(),
+                    seed: rng.next_edge_u64(),
+                    expiry: rng.next_edge_u64() as i64,
+                    kind: if rng.next_u64() % 2 == 0 { EscrowKind::Token } else { EscrowKind::Native },
+                    receive_amount: rng.next_edge_u64(),
+                    memo: {
+                        let len = (rng.next_u64() % (MAX_MEMO_LEN as u64 + 1)) as usize;
+                        rng.next_bytes::<MAX_MEMO_LEN>()[..len].to_vec()
+                    },
+                    namespace: rng.next_bytes(),
+                    price_num: rng.next_edge_u64(),
+                    price_den: rng.next_edge_u64(),
+                    min_fill: rng.next_edge_u64(),
+                    persistent: rng.next_u64() % 2 == 0,
+                    treasury: rng.next_u64() % 2 == 0,
+                },
+                EscrowInstruction::Take {
+                    amount: rng.next_edge_u64(),
+                    seed: rng.next_edge_u64(),
+                    max_in: rng.next_edge_u64(),
+                    receive_amount: rng.next_edge_u64(),
+                },
+                EscrowInstruction::Refund { amount: rng.next_edge_u64(), seed: rng.next_edge_u64() },
+                EscrowInstruction::TransferAuthority { seed: rng.next_edge_u64() },
+                EscrowInstruction::GetEscrow { seed: rng.next_edge_u64() },
+                EscrowInstruction::ForceClose { seed: rng.next_edge_u64() },
+                EscrowInstruction::SweepExcess { seed: rng.next_edge_u64() },
+                EscrowInstruction::MakeBasket {
+                    seed: rng.next_edge_u64(),
+                    receive_amount: rng.next_edge_u64(),
+                    expiry: rng.next_edge_u64() as i64,
+                    amounts: {
+                        let legs = 1 + (rng.next_u64() % MAX_BASKET_LEGS as u64) as usize;
+                        (0..legs).map(|_| rng.next_edge_u64()).collect()
+                    },
+                },
+                EscrowInstruction::TakeBasket { seed: rng.next_edge_u64() },
+                EscrowInstruction::RefundBasket { seed: rng.next_edge_u64() },
+                EscrowInstruction::MigrateEscrow { seed: rng.next_edge_u64() },
+                EscrowInstruction::TakeAvailable {
+                    desired: rng.next_edge_u64(),
+                    seed: rng.next_edge_u64(),
+                    max_in: rng.next_edge_u64(),
+                },
+                EscrowInstruction::MakeInit {
+                    amount: rng.next_edge_u64(),
+                    seed: rng.next_edge_u64(),
+                    expiry: rng.next_edge_u64() as i64,
+                    kind: if rng.next_u64() % 2 == 0 { EscrowKind::Token } else { EscrowKind::Native },
+                    receive_amount: rng.next_edge_u64(),
+                    memo: {
+                        let len = (rng.next_u64() % (MAX_MEMO_LEN as u64 + 1)) as usize;
+                        rng.next_bytes::<MAX_MEMO_LEN>()[..len].to_vec()
+                    },
+                    namespace: rng.next_bytes(),
+                    price_num: rng.next_edge_u64(),
+                    price_den: rng.next_edge_u64(),
+                    min_fill: rng.next_edge_u64(),
+                    persistent: rng.next_u64() % 2 == 0,
+                    treasury: rng.next_u64() % 2 == 0,
+                },
+                EscrowInstruction::MakeDeposit { seed: rng.next_edge_u64() },
+                EscrowInstruction::MakeVesting {
+                    seed: rng.next_edge_u64(),
+                    amount: rng.next_edge_u64(),
+                    start: rng.next_edge_u64() as i64,
+                    cliff: rng.next_edge_u64() as i64,
+                    duration: rng.next_edge_u64() as i64,
+                },
+                EscrowInstruction::Claim { seed: rng.next_edge_u64() },
+                EscrowInstruction::ExtendExpiry {
+                    new_expiry: rng.next_edge_u64() as i64,
+                    seed: rng.next_edge_u64(),
+                },
+                EscrowInstruction::ClaimReceive { seed: rng.next_edge_u64() },
+                EscrowInstruction::ArbiterResolve {
+                    seed: rng.next_edge_u64(),
+                    to_taker: rng.next_u64() % 2 == 1,
+                },
+                EscrowInstruction::InitConfig,
+                EscrowInstruction::SetPaused { paused: rng.next_u64() % 2 == 1 },
+                EscrowInstruction::TopUp {
+                    amount: rng.next_edge_u64(),
+                    seed: rng.next_edge_u64(),
+                    receive_amount: rng.next_edge_u64(),
+                },
+            ];
+
+            for instruction in instructions {
+                let packed = pack_instruction_data(&instruction);
+                assert_eq!(EscrowInstruction::unpack(&packed).unwrap(), instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unpack_never_panics() {
+        let mut rng = TestRng(0x5eed_0002);
+
+        for _ in 0..5_000 {
+            let len = (rng.next_u64() % 100) as usize;
+            let mut data: Vec<u8> = (0..len).map(|_| rng.next_u64() as u8).collect();
+            // bias towards real discriminators so the field readers get exercised
+            if let Some(first) = data.first_mut() {
+                *first %= 22;
+            }
+
+            // any result is fine, reaching the next line means it didn't panic
+            if let Ok(instruction) = EscrowInstruction::unpack(&data) {
+                assert_eq!(pack_instruction_data(&instruction), data);
+            }
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metric_log_per_instruction() {
+        let cases = [
+            (EscrowInstruction::Make {
+                amount: 1,
+                seed: 2,
+                expiry: 0,
+                kind: EscrowKind::Token,
+                receive_amount: 3,
+                memo: vec![0u8; 32],
+                namespace: [0u8; 8],
+                price_num: 0,
+                price_den: 0,
+                min_fill: 0,
+                persistent: false,
+                treasury: false,
+            }, "METRIC make_count"),
+            (EscrowInstruction::Take { amount: 1, seed: 2, max_in: 3, receive_amount: 4 }, "METRIC take_count"),
+            (EscrowInstruction::Refund { amount: 1, seed: 2 }, "METRIC refund_count"),
+            (EscrowInstruction::TransferAuthority { seed: 2 }, "METRIC transfer_authority_count"),
+            (EscrowInstruction::GetEscrow { seed: 2 }, "METRIC get_escrow_count"),
+            (EscrowInstruction::ForceClose { seed: 2 }, "METRIC force_close_count"),
+            (EscrowInstruction::SweepExcess { seed: 2 }, "METRIC sweep_excess_count"),
+            (EscrowInstruction::MakeBasket { seed: 2, receive_amount: 3, expiry: 0, amounts: vec![4] }, "METRIC make_basket_count"),
+            (EscrowInstruction::TakeBasket { seed: 2 }, "METRIC take_basket_count"),
+            (EscrowInstruction::RefundBasket { seed: 2 }, "METRIC refund_basket_count"),
+            (EscrowInstruction::MigrateEscrow { seed: 2 }, "METRIC migrate_escrow_count"),
+            (EscrowInstruction::TakeAvailable { desired: 1, seed: 2, max_in: 3 }, "METRIC take_available_count"),
+            (EscrowInstruction::MakeInit { amount: 1, seed: 2, expiry: 3, kind: EscrowKind::Token, receive_amount: 4, memo: Vec::new(), namespace: [0u8; 8], price_num: 0, price_den: 0, min_fill: 0, persistent: false, treasury: false }, "METRIC make_init_count"),
+            (EscrowInstruction::MakeDeposit { seed: 2 }, "METRIC make_deposit_count"),
+            (EscrowInstruction::MakeVesting { seed: 2, amount: 3, start: 4, cliff: 5, duration: 6 }, "METRIC make_vesting_count"),
+            (EscrowInstruction::Claim { seed: 2 }, "METRIC claim_count"),
+            (EscrowInstruction::ExtendExpiry { new_expiry: 1, seed: 2 }, "METRIC extend_expiry_count"),
+            (EscrowInstruction::ClaimReceive { seed: 2 }, "METRIC claim_receive_count"),
+            (EscrowInstruction::ArbiterResolve { seed: 2, to_taker: true }, "METRIC arbiter_resolve_count"),
+            (EscrowInstruction::InitConfig, "METRIC init_config_count"),
+            (EscrowInstruction::SetPaused { paused: true }, "METRIC set_paused_count"),
+            (EscrowInstruction::TopUp { amount: 1, seed: 2, receive_amount: 3 }, "METRIC top_up_count"),
+        ];
+
+        for (instruction, expected) in cases.iter() {
+            // process_instruction logs exactly this line for the unpacked instruction
+            let unpacked = EscrowInstruction::unpack(&pack_instruction_data(instruction)).unwrap();
+            assert_eq!(unpacked.metric_log(), *expected);
+        }
+    }
+
+    #[test]
+    fn test_escrow_kind() {
+        assert_eq!(EscrowKind::try_from(0).unwrap(), EscrowKind::Token);
+        assert_eq!(EscrowKind::try_from(1).unwrap(), EscrowKind::Native);
+        assert!(EscrowKind::try_from(2).is_err());
+
+        let mut escrow = test_escrow(1000, 0);
+        assert_eq!(escrow.kind().unwrap(), EscrowKind::Token);
+        escrow.kind = EscrowKind::Native as u8;
+        assert_eq!(escrow.kind().unwrap(), EscrowKind::Native);
+
+        // a native make is a SOL-for-token offer
+        let packed = pack_instruction_data(&EscrowInstruction::Make {
+            amount: 5_000_000_000,
+            seed: 1,
+            expiry: 0,
+            kind: EscrowKind::Native,
+            receive_amount: 100,
+            memo: vec![0u8; 32],
+            namespace: [0u8; 8],
+            price_num: 0,
+            price_den: 0,
+            min_fill: 0,
+            persistent: false,
+            treasury: false,
+        });
+        assert_eq!(packed[25], 1);
+
+        // unknown kinds are rejected
+        let mut bad = packed.clone();
+        bad[25] = 7;
+        assert!(EscrowInstruction::unpack(&bad).is_err());
+    }
+
+    #[test]
+    fn test_reentrancy_guard() {
+        let mut escrow = test_escrow(1000, 0);
+        assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+
+        // first entry locks the escrow
+        escrow.begin_processing().unwrap();
+        assert_eq!(escrow.status().unwrap(), EscrowStatus::Processing);
+
+        // a re-entrant take/refund (e.g. from a malicious token program CPI) is rejected
+        let err = escrow.begin_processing().unwrap_err();
+        assert_eq!(err, ProgramError::Custom(EscrowError::InvalidState as u32));
+
+        // terminal states stay locked
+        escrow.finish(EscrowStatus::Completed);
+        assert!(escrow.begin_processing().is_err());
+        escrow.finish(EscrowStatus::Cancelled);
+        assert!(escrow.begin_processing().is_err());
+
+        // unknown status bytes don't load
+        escrow.status = 9;
+        assert!(escrow.status().is_err());
+    }
+
+    #[test]
+    fn test_accounts_from_short_slice() {
+        let accounts: [AccountInfo; 0] = [];
+        assert_eq!(
+            MakeAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            TakeAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            RefundAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            TransferAuthorityAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            GetEscrowAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            ForceCloseAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            SweepExcessAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            MakeBasketAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            TakeBasketAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            RefundBasketAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            MigrateEscrowAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            MakeDepositAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            MakeVestingAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            ClaimAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            ExtendExpiryAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            ClaimReceiveAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            ArbiterResolveAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            InitConfigAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            SetPausedAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            TopUpAccounts::from_slice(&accounts).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[test]
+    fn test_account_roles_match_structs() {
+        use crate::instructions::AccountRole;
+        use core::mem::size_of;
+        
+        // every *Accounts field is one account reference (Option<&T> has the same size)
+        let field_count = |struct_size: usize| struct_size / size_of::<&AccountInfo>();
+        // the basket structs end in a slice of per-leg accounts, two words wide
+        let basket_field_count = |struct_size: usize| field_count(struct_size) - 2;
+        let cases: [(&[AccountRole], usize); 20] = [
+            (&MakeAccounts::ROLES, field_count(size_of::<MakeAccounts>())),
+            (&TakeAccounts::ROLES, field_count(size_of::<TakeAccounts>())),
+            (&RefundAccounts::ROLES, field_count(size_of::<RefundAccounts>())),
+            (&TransferAuthorityAccounts::ROLES, field_count(size_of::<TransferAuthorityAccounts>())),
+            (&GetEscrowAccounts::ROLES, field_count(size_of::<GetEscrowAccounts>())),
+            (&ForceCloseAccounts::ROLES, field_count(size_of::<ForceCloseAccounts>())),
+            (&SweepExcessAccounts::ROLES, field_count(size_of::<SweepExcessAccounts>())),
+            (&MakeBasketAccounts::ROLES, basket_field_count(size_of::<MakeBasketAccounts>())),
+            (&TakeBasketAccounts::ROLES, basket_field_count(size_of::<TakeBasketAccounts>())),
+            (&RefundBasketAccounts::ROLES, basket_field_count(size_of::<RefundBasketAccounts>())),
+            (&MigrateEscrowAccounts::ROLES, field_count(size_of::<MigrateEscrowAccounts>())),
+            (&MakeDepositAccounts::ROLES, field_count(size_of::<MakeDepositAccounts>())),
+            (&MakeVestingAccounts::ROLES, field_count(size_of::<MakeVestingAccounts>())),
+            (&ClaimAccounts::ROLES, field_count(size_of::<ClaimAccounts>())),
+            (&ExtendExpiryAccounts::ROLES, field_count(size_of::<ExtendExpiryAccounts>())),
+            (&ClaimReceiveAccounts::ROLES, field_count(size_of::<ClaimReceiveAccounts>())),
+            (&ArbiterResolveAccounts::ROLES, field_count(size_of::<ArbiterResolveAccounts>())),
+            (&InitConfigAccounts::ROLES, field_count(size_of::<InitConfigAccounts>())),
+            (&SetPausedAccounts::ROLES, field_count(size_of::<SetPausedAccounts>())),
+            (&TopUpAccounts::ROLES, field_count(size_of::<TopUpAccounts>())),
+        ];
+        
+        for (roles, fields) in cases.iter() {
+            assert_eq!(roles.len(), *fields);
+            
+            // at most one required authority, always first (read-only GetEscrow
+            // has none), and optional accounts (such as Make's payer) only at the end
+            let signers = roles.iter().filter(|role| role.is_signer && !role.is_optional).count();
+            assert!(signers <= 1);
+            assert_eq!(signers == 1, roles[0].is_signer);
+            let first_optional = roles.iter().position(|role| role.is_optional).unwrap_or(roles.len());
+            assert!(roles[first_optional..].iter().all(|role| role.is_optional));
+        }
+        
+        // the documented indices process_instruction relies on
+        assert_eq!(MakeAccounts::ROLES[4].name, "escrow");
+        assert_eq!(TakeAccounts::ROLES[2].name, "escrow");
+        assert_eq!(RefundAccounts::ROLES[1].name, "escrow");
+        assert_eq!(TransferAuthorityAccounts::ROLES[1].name, "escrow");
+        assert_eq!(GetEscrowAccounts::ROLES[0].name, "escrow");
+        assert_eq!(ForceCloseAccounts::ROLES[1].name, "escrow");
+        assert_eq!(SweepExcessAccounts::ROLES[1].name, "escrow");
+        assert_eq!(MigrateEscrowAccounts::ROLES[1].name, "escrow");
+        assert_eq!(MakeDepositAccounts::ROLES[1].name, "escrow");
+        assert_eq!(MakeVestingAccounts::ROLES[4].name, "vesting");
+        assert_eq!(ClaimAccounts::ROLES[2].name, "vesting");
+        assert_eq!(ExtendExpiryAccounts::ROLES[1].name, "escrow");
+        assert_eq!(ClaimReceiveAccounts::ROLES[1].name, "receive_vault");
+        assert_eq!(ArbiterResolveAccounts::ROLES[2].name, "escrow");
+        
+        // per-leg accounts never sign, the one authority is in the fixed prefix
+        let leg_roles: [&[AccountRole]; 3] = [
+            &MakeBasketAccounts::LEG_ROLES,
+            &TakeBasketAccounts::LEG_ROLES,
+            &RefundBasketAccounts::LEG_ROLES,
+        ];
+        assert!(leg_roles.iter().all(|roles| roles.iter().all(|role| !role.is_signer && !role.is_optional)));
+        
+        // Token-2022 escrows pass mint A after the optional system program
+        assert_eq!(RefundAccounts::ROLES[6].name, "mint_a");
+        assert_eq!(MakeDepositAccounts::ROLES[6].name, "mint_a");
+        
+        // Make's rent payer is an optional trailing signer
+        assert_eq!(MakeAccounts::ROLES[8].name, "maker_ata_b");
+        assert_eq!(MakeAccounts::ROLES[10].name, "payer");
+        assert!(MakeAccounts::ROLES[10].is_signer && MakeAccounts::ROLES[10].is_optional);
+        assert_eq!(MakeAccounts::ROLES[11].name, "arbiter");
+        assert!(MakeAccounts::ROLES[11].is_optional);
+        
+        // the config account has a fixed, required place in Make and Take
+        assert_eq!(MakeAccounts::ROLES[9].name, "config");
+        assert!(!MakeAccounts::ROLES[9].is_optional);
+        assert_eq!(TakeAccounts::ROLES[10].name, "config");
+        assert!(!TakeAccounts::ROLES[10].is_optional);
+        
+        // a taker recreating Maker ATA B passes the ATA program at its own place
+        assert_eq!(TakeAccounts::ROLES[13].name, "associated_token_program");
+        assert_eq!(TakeAccounts::ROLES[14].name, "receipt");
+    }
+
+    #[test]
+    fn test_as_account_slice_follows_roles() {
+        // one mock per documented account, keyed by its position
+        let mocks: Vec<MockAccount> = (0..15u8).map(|i| MockAccount::new([i; 32], [0u8; 32], 1, &[])).collect();
+        let infos = account_infos(&mocks.iter().collect::<Vec<_>>());
+        let keys = |slice: &[&AccountInfo]| slice.iter().map(|info| *info.key()).collect::<Vec<Pubkey>>();
+        let expected = |len: usize| (0..len as u8).map(|i| [i; 32]).collect::<Vec<Pubkey>>();
+        
+        // every account that was passed comes back at its ROLES index
+        let make = MakeAccounts::from_slice(&infos[..MakeAccounts::ROLES.len()]).unwrap();
+        assert_eq!(keys(&make.as_account_slice()), expected(MakeAccounts::ROLES.len()));
+        let take = TakeAccounts::from_slice(&infos[..TakeAccounts::ROLES.len()]).unwrap();
+        assert_eq!(keys(&take.as_account_slice()), expected(12));
+        assert_eq!(TakeAccounts::ROLES[11].name, "destination_a");
+        let refund = RefundAccounts::from_slice(&infos[..RefundAccounts::ROLES.len()]).unwrap();
+        assert_eq!(keys(&refund.as_account_slice()), expected(5));
+        assert!(RefundAccounts::ROLES[5..].iter().all(|role| role.is_optional));
+        
+        // optional accounts left out fall back to their defaults
+        let make = MakeAccounts::from_slice(&infos[..10]).unwrap();
+        assert_eq!(make.as_account_slice()[10].key(), &[0u8; 32]);
+        assert_eq!(make.as_account_slice()[11].key(), &[0u8; 32]);
+        let take = TakeAccounts::from_slice(&infos[..11]).unwrap();
+        assert_eq!(take.as_account_slice()[11].key(), &[6u8; 32]);
+        
+        // the config account is required
+        assert_eq!(MakeAccounts::from_slice(&infos[..9]).err(), Some(ProgramError::NotEnoughAccountKeys));
+        assert_eq!(TakeAccounts::from_slice(&infos[..10]).err(), Some(ProgramError::NotEnoughAccountKeys));
+    }
+
+    #[test]
+    fn test_escrow_constants() {
+        // test that our constants are properly defined
+        assert_eq!(ID.len(), 32);
+        assert_eq!(TOKEN_PROGRAM_ID.len(), 32);
+        
+        // test escrow size is reasonable
+        assert!(Escrow::LEN > 0);
+        assert!(Escrow::LEN < 1000); // Reasonable size limit
+    }
+
+    #[test]
+    fn test_escrow_data_len_check() {
+        // a too-small account is rejected before any cast
+        assert_eq!(Escrow::check_data_len(0).unwrap_err(), ProgramError::AccountDataTooSmall);
+        assert_eq!(
+            Escrow::check_data_len(Escrow::LEN - 1).unwrap_err(),
+            ProgramError::AccountDataTooSmall
+        );
+        
+        assert!(Escrow::check_data_len(Escrow::LEN).is_ok());
+        assert!(Escrow::check_data_len(Escrow::LEN + 64).is_ok());
+    }
+
+    #[test]
+    fn test_associated_token_program_check() {
+        use crate::programs::{verify_associated_token_program, ASSOCIATED_TOKEN_PROGRAM_ID};
+        
+        assert!(verify_associated_token_program(&ASSOCIATED_TOKEN_PROGRAM_ID).is_ok());
+        
+        // a spoofed program, or another well-known one in its place
+        assert_eq!(
+            verify_associated_token_program(&[7u8; 32]).unwrap_err(),
+            ProgramError::IncorrectProgramId
+        );
+        assert_eq!(
+            verify_associated_token_program(&TOKEN_PROGRAM_ID).unwrap_err(),
+            ProgramError::IncorrectProgramId
+        );
+    }
+
+    #[test]
+    fn test_escrow_discriminator() {
+        // test that discriminator is properly set
+        assert_eq!(Escrow::DISCRIMINATOR.len(), 8);
+        
+        // test discriminator is not all zeros (which would be invalid)
+        assert_ne!(Escrow::DISCRIMINATOR, [0u8; 8]);
+    }
+
+    #[test]
+    fn test_amount_cap() {
+        use crate::instructions::make::{check_amount_cap, parse_amount_cap, MAX_ESCROW_AMOUNT};
+        
+        // the default build is uncapped
+        #[cfg(not(feature = "amount-cap"))]
+        assert_eq!(MAX_ESCROW_AMOUNT, u64::MAX);
+        assert!(check_amount_cap(u64::MAX, u64::MAX).is_ok());
+        
+        // a deployment capped at 1_000_000
+        let cap = parse_amount_cap("1000000");
+        assert_eq!(cap, 1_000_000);
+        assert!(check_amount_cap(1_000_000, cap).is_ok());
+        assert_eq!(
+            check_amount_cap(1_000_001, cap).unwrap_err(),
+            ProgramError::Custom(EscrowError::AmountExceedsCap as u32)
+        );
+        assert_eq!(parse_amount_cap("18446744073709551615"), u64::MAX);
+        
+        // make checks the cap before any account is touched
+        #[cfg(not(feature = "amount-cap"))]
+        {
+            let mocks = MakeMocks::new(7);
+            assert!(mocks.run(7, None).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_mint_b_allowlist() {
+        use crate::instructions::make::{check_mint_b_allowed, ALLOWED_MINT_B};
+        
+        // the default build accepts any mint B
+        assert!(ALLOWED_MINT_B.is_empty());
+        assert!(check_mint_b_allowed(&[3u8; 32], ALLOWED_MINT_B).is_ok());
+        
+        // a deployment that only takes one stablecoin
+        let stablecoin = [7u8; 32];
+        assert!(check_mint_b_allowed(&stablecoin, &[stablecoin]).is_ok());
+        assert_eq!(
+            check_mint_b_allowed(&[3u8; 32], &[stablecoin]).unwrap_err(),
+            ProgramError::Custom(EscrowError::MintNotAllowed as u32)
+        );
+    }
+
+    #[test]
+    fn test_check_discriminator() {
+        use crate::state::discriminator_matches;
+        
+        // raw account data only has to start with the discriminator
+        let mut data = Escrow::DISCRIMINATOR.to_vec();
+        assert!(Escrow::check_discriminator(&data));
+        data.extend_from_slice(&[0xAB; 32]);
+        assert!(Escrow::check_discriminator(&data));
+        
+        // a difference in any single byte is a mismatch
+        for i in 0..8 {
+            let mut wrong = Escrow::DISCRIMINATOR;
+            wrong[i] ^= 0x80;
+            assert!(!Escrow::check_discriminator(&wrong));
+        }
+        assert!(!Escrow::check_discriminator(&[0u8; 8]));
+        assert!(!Escrow::check_discriminator(&BasketEscrow::DISCRIMINATOR));
+        
+        // too short to hold one
+        assert!(!Escrow::check_discriminator(&Escrow::DISCRIMINATOR[..7]));
+        assert!(!Escrow::check_discriminator(&[]));
+        
+        assert!(discriminator_matches(&BasketEscrow::DISCRIMINATOR, &BasketEscrow::DISCRIMINATOR));
+        assert!(!discriminator_matches(&Escrow::DISCRIMINATOR, &BasketEscrow::DISCRIMINATOR));
+    }
+
+    #[test]
+    fn test_error_conversion() {
+        // test that custom errors properly convert to ProgramError
+        let escrow_error = EscrowError::InvalidInstruction;
+        let program_error: ProgramError = escrow_error.into();
+        
+        // should be a custom error
         matches!(program_error, ProgramError::Custom(_));
     }
 
+    #[test]
+    fn test_verbose_msg_has_no_side_effects() {
+        // logging must not consume or alter values in either feature state
+        let amount = 1000u64;
+        let seed = 42u64;
+        verbose_msg!("Make instruction: amount={}, seed={}", amount, seed);
+        assert_eq!(amount, 1000);
+        assert_eq!(seed, 42);
+
+        // error codes are the same whether or not the error is logged
+        let program_error: ProgramError = EscrowError::EscrowExpired.into();
+        assert_eq!(program_error, ProgramError::Custom(EscrowError::EscrowExpired as u32));
+
+        // instruction parsing is unaffected by the log level
+        let packed = pack_instruction_data(&EscrowInstruction::Refund { amount, seed });
+        match EscrowInstruction::unpack(&packed).unwrap() {
+            EscrowInstruction::Refund { amount: a, seed: s } => {
+                assert_eq!(a, amount);
+                assert_eq!(s, seed);
+            }
+            _ => panic!("Wrong instruction type"),
+        }
+    }
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        let codes = [
+            (EscrowError::InvalidInstruction, 6000),
+            (EscrowError::NotRentExempt, 6001),
+            (EscrowError::ExpectedAmountMismatch, 6002),
+            (EscrowError::AmountOverflow, 6003),
+            (EscrowError::InvalidState, 6004),
+            (EscrowError::InvalidAuthority, 6005),
+            (EscrowError::InvalidTokenProgram, 6006),
+            (EscrowError::InvalidTokenMint, 6007),
+            (EscrowError::InvalidEscrowAccount, 6008),
+            (EscrowError::EscrowExpired, 6009),
+            (EscrowError::MintHasFreezeAuthority, 6010),
+            (EscrowError::InvalidExpiry, 6011),
+            (EscrowError::SlippageExceeded, 6012),
+            (EscrowError::InsufficientVaultBalance, 6013),
+            (EscrowError::InsufficientTakerBalance, 6014),
+            (EscrowError::AmountExceedsCap, 6015),
+            (EscrowError::MintNotAllowed, 6016),
+            (EscrowError::AccountFrozen, 6017),
+            (EscrowError::NothingToClaim, 6018),
+            (EscrowError::InsufficientMakerBalance, 6019),
+            (EscrowError::AmountBelowPrecision, 6020),
+            (EscrowError::CpiNotAllowed, 6021),
+            (EscrowError::FillBelowMinimum, 6022),
+            (EscrowError::ProgramPaused, 6023),
+        ];
+
+        for (error, code) in codes {
+            let program_error: ProgramError = error.into();
+            assert_eq!(program_error, ProgramError::Custom(code));
+        }
+    }
+
     #[test]
     fn test_instruction_round_trip() {
         // test that pack/unpack is symmetric
-        let original = EscrowInstruction::Make { amount: 999, seed: 777 };
+        let original = EscrowInstruction::Make { amount: 999, seed: 777, expiry: 555, kind: EscrowKind::Native, receive_amount: 333, memo: vec![9u8; 32], namespace: [3u8; 8], price_num: 7, price_den: 5, min_fill: 11, persistent: true, treasury: false };
         let packed = pack_instruction_data(&original);
         let unpacked = EscrowInstruction::unpack(&packed).unwrap();
         
         match (original, unpacked) {
-            (EscrowInstruction::Make { amount: a1, seed: s1 }, 
-             EscrowInstruction::Make { amount: a2, seed: s2 }) => {
+            (EscrowInstruction::Make { amount: a1, seed: s1, expiry: e1, kind: k1, receive_amount: r1, memo: m1, namespace: n1, price_num: pn1, price_den: pd1, min_fill: mf1, persistent: p1, treasury: t1 }, 
+             EscrowInstruction::Make { amount: a2, seed: s2, expiry: e2, kind: k2, receive_amount: r2, memo: m2, namespace: n2, price_num: pn2, price_den: pd2, min_fill: mf2, persistent: p2, treasury: t2 }) => {
                 assert_eq!(a1, a2);
                 assert_eq!(s1, s2);
+                assert_eq!(e1, e2);
+                assert_eq!(k1, k2);
+                assert_eq!(r1, r2);
+                assert_eq!(m1, m2);
+                assert_eq!(n1, n2);
+                assert_eq!(pn1, pn2);
+                assert_eq!(pd1, pd2);
+                assert_eq!(mf1, mf2);
+                assert_eq!(p1, p2);
+                assert_eq!(t1, t2);
             }
             _ => panic!("Round trip failed"),
         }
@@ -342,30 +2051,4298 @@ mod tests {
         // test with maximum values
         let max_instruction = EscrowInstruction::Make { 
             amount: u64::MAX, 
-            seed: u64::MAX 
+            seed: u64::MAX,
+            expiry: i64::MAX,
+            kind: EscrowKind::Token,
+            receive_amount: u64::MAX,
+            memo: vec![u8::MAX; MAX_MEMO_LEN],
+            namespace: [u8::MAX; 8],
+            price_num: u64::MAX,
+            price_den: u64::MAX,
+            min_fill: u64::MAX,
+            persistent: true,
+            treasury: true,
         };
         let packed = pack_instruction_data(&max_instruction);
         let unpacked = EscrowInstruction::unpack(&packed).unwrap();
         
         match unpacked {
-            EscrowInstruction::Make { amount, seed } => {
+            EscrowInstruction::Make { amount, seed, expiry, receive_amount, memo, .. } => {
                 assert_eq!(amount, u64::MAX);
+                assert_eq!(memo, [u8::MAX; MAX_MEMO_LEN]);
                 assert_eq!(seed, u64::MAX);
+                assert_eq!(expiry, i64::MAX);
+                assert_eq!(receive_amount, u64::MAX);
             }
             _ => panic!("Failed to handle max values"),
         }
         
         // test with zero values
-        let zero_instruction = EscrowInstruction::Make { amount: 0, seed: 0 };
+        let zero_instruction = EscrowInstruction::Make { amount: 0, seed: 0, expiry: 0, kind: EscrowKind::Token, receive_amount: 0, memo: vec![0u8; 32], namespace: [0u8; 8], price_num: 0, price_den: 0, min_fill: 0, persistent: false, treasury: false };
         let packed = pack_instruction_data(&zero_instruction);
         let unpacked = EscrowInstruction::unpack(&packed).unwrap();
         
         match unpacked {
-            EscrowInstruction::Make { amount, seed } => {
+            EscrowInstruction::Make { amount, seed, expiry, .. } => {
                 assert_eq!(amount, 0);
                 assert_eq!(seed, 0);
+                assert_eq!(expiry, 0);
             }
             _ => panic!("Failed to handle zero values"),
         }
     }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_escrow_borsh_matches_raw_layout() {
+        let escrow = Escrow {
+            discriminator: Escrow::DISCRIMINATOR,
+            maker: [1u8; 32],
+            mint_a: [2u8; 32],
+            mint_b: [3u8; 32],
+            receive_account: [4u8; 32],
+            amount: 1_000_000,
+            original_amount: 1_000_000,
+            receive_amount: 2_000_000,
+            expiry: 1_700_000_000,
+            price_num: 3,
+            price_den: 2,
+            bump: 254,
+            vault_bump: 253,
+            kind: EscrowKind::Native as u8,
+            status: EscrowStatus::Active as u8,
+            price_mode: PriceMode::Ratio as u8,
+            memo_len: MAX_MEMO_LEN as u8,
+            memo: [0xab; MAX_MEMO_LEN],
+            version: ESCROW_VERSION,
+            arbiter: [5u8; 32],
+            created_at: 1_699_000_000i64.to_le_bytes(),
+            min_fill: 500u64.to_le_bytes(),
+            persistent: 1,
+        };
+
+        let borsh_bytes = escrow.to_borsh().unwrap();
+        let raw_bytes = unsafe {
+            core::slice::from_raw_parts(&escrow as *const Escrow as *const u8, Escrow::LEN)
+        };
+        assert_eq!(borsh_bytes.len(), Escrow::LEN);
+        assert_eq!(borsh_bytes.as_slice(), raw_bytes);
+
+        // raw account bytes decode through the borsh path too
+        let decoded = Escrow::from_borsh(raw_bytes).unwrap();
+        assert_eq!(decoded.maker, escrow.maker);
+        assert_eq!(decoded.amount, escrow.amount);
+        assert_eq!(decoded.bump, escrow.bump);
+        assert_eq!(decoded.vault_bump, escrow.vault_bump);
+        assert_eq!(decoded.memo, escrow.memo);
+        assert_eq!(decoded.arbiter, escrow.arbiter);
+        assert_eq!(decoded.created_at(), 1_699_000_000);
+        assert_eq!(decoded.min_fill(), 500);
+        assert!(decoded.is_persistent());
+
+        // too short
+        assert!(Escrow::from_borsh(&raw_bytes[..Escrow::LEN - 1]).is_err());
+    }
+
+    fn test_escrow(amount: u64, expiry: i64) -> Escrow {
+        Escrow {
+            discriminator: Escrow::DISCRIMINATOR,
+            maker: [1u8; 32],
+            mint_a: [2u8; 32],
+            mint_b: [3u8; 32],
+            receive_account: [4u8; 32],
+            amount,
+            original_amount: amount,
+            receive_amount: amount * 2,
+            expiry,
+            price_num: 0,
+            price_den: 0,
+            bump: 255,
+            vault_bump: 254,
+            kind: EscrowKind::Token as u8,
+            status: EscrowStatus::Active as u8,
+            price_mode: PriceMode::Fixed as u8,
+            memo_len: 0,
+            memo: [0u8; MAX_MEMO_LEN],
+            version: ESCROW_VERSION,
+            arbiter: [0u8; 32],
+            created_at: [0u8; 8],
+            min_fill: [0u8; 8],
+            persistent: 0,
+        }
+    }
+
+    #[test]
+    fn test_account_discriminators() {
+        // live accounts carry these bytes, they must never change
+        assert_eq!(Escrow::DISCRIMINATOR, [139, 11, 230, 78, 92, 65, 103, 116]);
+        assert_eq!(ACCOUNT_DISCRIMINATORS[0], ("Escrow", Escrow::DISCRIMINATOR));
+        
+        // sha256("account:<Name>")[..8], what Anchor stores for an account of that name
+        let anchor_style: [(&str, [u8; 8]); 5] = [
+            ("Escrow", [31, 213, 123, 187, 186, 22, 218, 155]),
+            ("BasketEscrow", [38, 107, 205, 164, 236, 55, 123, 127]),
+            ("VestingEscrow", [244, 119, 183, 4, 73, 116, 135, 195]),
+            ("TakeReceipt", [182, 85, 187, 43, 195, 212, 24, 222]),
+            ("Config", [155, 12, 170, 224, 30, 250, 204, 130]),
+        ];
+        
+        for (i, (name, discriminator)) in ACCOUNT_DISCRIMINATORS.iter().enumerate() {
+            assert_eq!(anchor_style[i].0, *name);
+            // no two account types share a discriminator, and none can be
+            // confused with an Anchor account named like any of them
+            assert!(ACCOUNT_DISCRIMINATORS[i + 1..].iter().all(|(_, other)| other != discriminator));
+            assert!(anchor_style.iter().all(|(_, other)| other != discriminator));
+        }
+        
+        // an account of one type never validates as another
+        let mut basket_data = [0u8; 8];
+        basket_data.copy_from_slice(&BasketEscrow::DISCRIMINATOR);
+        assert!(!Escrow::check_discriminator(&basket_data));
+    }
+
+    #[test]
+    fn test_escrow_as_tuple() {
+        let mut escrow = test_escrow(1000, 2_000);
+        escrow.price_num = 3;
+        escrow.price_den = 2;
+        escrow.memo_len = 4;
+        escrow.memo[..4].copy_from_slice(b"memo");
+        
+        // more than 12 elements, so no tuple PartialEq: compare field by field
+        let (
+            discriminator,
+            maker,
+            mint_a,
+            mint_b,
+            receive_account,
+            amount,
+            original_amount,
+            receive_amount,
+            expiry,
+            price_num,
+            price_den,
+            bump,
+            vault_bump,
+            kind,
+            status,
+            price_mode,
+            memo_len,
+            memo,
+            version,
+            arbiter,
+            created_at,
+            min_fill,
+            persistent,
+        ) = escrow.as_tuple();
+        assert_eq!(discriminator, escrow.discriminator);
+        assert_eq!(maker, escrow.maker);
+        assert_eq!(mint_a, escrow.mint_a);
+        assert_eq!(mint_b, escrow.mint_b);
+        assert_eq!(receive_account, escrow.receive_account);
+        assert_eq!(amount, escrow.amount);
+        assert_eq!(original_amount, escrow.original_amount);
+        assert_eq!(receive_amount, escrow.receive_amount);
+        assert_eq!(expiry, escrow.expiry);
+        assert_eq!(price_num, escrow.price_num);
+        assert_eq!(price_den, escrow.price_den);
+        assert_eq!(bump, escrow.bump);
+        assert_eq!(vault_bump, escrow.vault_bump);
+        assert_eq!(kind, escrow.kind);
+        assert_eq!(status, escrow.status);
+        assert_eq!(price_mode, escrow.price_mode);
+        assert_eq!(memo_len, escrow.memo_len);
+        assert_eq!(memo, escrow.memo);
+        assert_eq!(version, escrow.version);
+        assert_eq!(arbiter, escrow.arbiter);
+        assert_eq!(created_at, escrow.created_at);
+        assert_eq!(min_fill, escrow.min_fill);
+        assert_eq!(persistent, escrow.persistent);
+    }
+
+    #[test]
+    fn test_refund_amount_active_is_strict() {
+        // not expired yet, amount has to match exactly
+        let escrow = test_escrow(1000, 2_000);
+        assert_eq!(refund_amount(&escrow, 1000, 1000, 1_000).unwrap(), 1000);
+        assert!(refund_amount(&escrow, 999, 1000, 1_000).is_err());
+
+        // no expiry set, always strict
+        let escrow = test_escrow(1000, 0);
+        assert!(refund_amount(&escrow, 1, 1000, i64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_refund_amount_expired_is_lenient() {
+        // past expiry, any amount refunds the whole vault balance
+        let escrow = test_escrow(1000, 2_000);
+        assert_eq!(refund_amount(&escrow, 0, 1000, 2_001).unwrap(), 1000);
+        assert_eq!(refund_amount(&escrow, 42, 750, 3_000).unwrap(), 750);
+    }
+
+    #[test]
+    fn test_is_expired() {
+        // zero expiry never expires
+        let escrow = test_escrow(1000, 0);
+        assert!(!escrow.is_expired(0));
+        assert!(!escrow.is_expired(i64::MAX));
+        
+        // not yet expired, the deadline itself still counts as open
+        let escrow = test_escrow(1000, 2_000);
+        assert!(!escrow.is_expired(1_999));
+        assert!(!escrow.is_expired(2_000));
+        
+        // expired
+        assert!(escrow.is_expired(2_001));
+    }
+
+    #[test]
+    fn test_fill_ratio() {
+        let mut escrow = test_escrow(1000, 0);
+        assert_eq!(escrow.fill_ratio(), 0);
+
+        escrow.amount = 500;
+        assert_eq!(escrow.fill_ratio(), 5_000);
+
+        escrow.amount = 0;
+        assert_eq!(escrow.fill_ratio(), 10_000);
+
+        // nothing was ever deposited
+        escrow.original_amount = 0;
+        assert_eq!(escrow.fill_ratio(), 0);
+    }
+
+    fn test_mint_data(freeze_authority: Option<Pubkey>) -> Vec<u8> {
+        let mut data = vec![0u8; MINT_LEN];
+        data[44] = 6; // decimals
+        data[45] = 1; // is_initialized
+        if let Some(authority) = freeze_authority {
+            data[46..50].copy_from_slice(&1u32.to_le_bytes());
+            data[50..82].copy_from_slice(&authority);
+        }
+        data
+    }
+
+    // a Token-2022 mint carrying a TransferFeeConfig with the given older and
+    // newer fees, each (epoch, maximum fee, basis points)
+    fn test_fee_mint_data(older: (u64, u64, u16), newer: (u64, u64, u16)) -> Vec<u8> {
+        let mut data = test_mint_data(None);
+        data.resize(TOKEN_ACCOUNT_LEN, 0);
+        data.push(1); // account type: mint
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&108u16.to_le_bytes());
+        // authorities and the withheld amount
+        data.extend_from_slice(&[0u8; 72]);
+        for (epoch, maximum_fee, basis_points) in [older, newer] {
+            data.extend_from_slice(&epoch.to_le_bytes());
+            data.extend_from_slice(&maximum_fee.to_le_bytes());
+            data.extend_from_slice(&basis_points.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_mint_freeze_authority_check() {
+        let freezable = test_mint_data(Some([7u8; 32]));
+        assert!(mint_has_freeze_authority(&freezable).unwrap());
+
+        let not_freezable = test_mint_data(None);
+        assert!(!mint_has_freeze_authority(&not_freezable).unwrap());
+
+        // not a mint
+        assert!(mint_has_freeze_authority(&[0u8; 10]).is_err());
+    }
+
+    #[test]
+    fn test_make_rejects_non_mint_accounts() {
+        let invalid_mint: ProgramError = EscrowError::InvalidTokenMint.into();
+        
+        // an initialized mint is accepted
+        assert!(check_mint_data(&test_mint_data(None)).is_ok());
+        
+        // a token account passed as mint_a
+        let token_account = test_token_account_data(&[2u8; 32], &[1u8; 32], 10);
+        assert_eq!(check_mint_data(&token_account).unwrap_err(), invalid_mint);
+        
+        // Token-2022 mints with extensions carry the mint account type
+        let mut extended_mint = test_mint_data(None);
+        extended_mint.resize(TOKEN_ACCOUNT_LEN, 0);
+        extended_mint.push(1);
+        assert!(check_mint_data(&extended_mint).is_ok());
+        let mut extended_account = token_account.clone();
+        extended_account.push(2);
+        assert_eq!(check_mint_data(&extended_account).unwrap_err(), invalid_mint);
+        
+        // a wallet or data-less account
+        assert_eq!(check_mint_data(&[]).unwrap_err(), invalid_mint);
+        assert_eq!(check_mint_data(&[1u8; MINT_LEN - 1]).unwrap_err(), invalid_mint);
+        
+        // mint-sized but never initialized
+        assert_eq!(check_mint_data(&[0u8; MINT_LEN]).unwrap_err(), invalid_mint);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_client_derivation_matches_program() {
+        use crate::instructions::make::{find_escrow_address, find_vault_address};
+
+        let maker = [5u8; 32];
+        let (escrow, escrow_bump) = derive_escrow(&maker, 42, &ID);
+        assert_eq!((escrow, escrow_bump), find_escrow_address(&maker, 42, &ID));
+
+        let (vault, vault_bump) = derive_vault(&escrow, &ID);
+        assert_eq!((vault, vault_bump), find_vault_address(&escrow, &ID));
+
+        // different seeds give different escrows
+        assert_ne!(derive_escrow(&maker, 43, &ID).0, escrow);
+    }
+
+    #[test]
+    fn test_validate_expiry() {
+        let now = 1_700_000_000;
+
+        // zero means no expiry
+        assert!(validate_expiry(0, now).is_ok());
+
+        // in the past or right now
+        assert!(validate_expiry(now - 1, now).is_err());
+        assert!(validate_expiry(now, now).is_err());
+
+        // too far in the future
+        assert!(validate_expiry(now + MAX_EXPIRY_HORIZON + 1, now).is_err());
+
+        // valid
+        assert!(validate_expiry(now + 1, now).is_ok());
+        assert!(validate_expiry(now + 7 * 24 * 60 * 60, now).is_ok());
+        assert!(validate_expiry(now + MAX_EXPIRY_HORIZON, now).is_ok());
+    }
+
+    fn test_token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+        data[0..32].copy_from_slice(mint);
+        data[32..64].copy_from_slice(owner);
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[108] = 1; // initialized
+        data
+    }
+
+    #[test]
+    fn test_token_account_parser() {
+        use crate::token::{TokenAccount, TokenAccountState};
+        
+        // known legacy token account blob
+        let mut data = test_token_account_data(&[2u8; 32], &[1u8; 32], 4_200);
+        let parsed = TokenAccount::from_account_data(&data).unwrap();
+        assert_eq!(parsed.mint(), &[2u8; 32]);
+        assert_eq!(parsed.owner(), &[1u8; 32]);
+        assert_eq!(parsed.amount(), 4_200);
+        assert_eq!(parsed.state(), TokenAccountState::Initialized);
+        
+        // frozen accounts still parse, callers decide what to do with them
+        data[108] = 2;
+        assert_eq!(TokenAccount::from_account_data(&data).unwrap().state(), TokenAccountState::Frozen);
+        
+        // Token-2022 account with an extension (ImmutableOwner, no value)
+        let mut extended = test_token_account_data(&[2u8; 32], &[1u8; 32], 9);
+        extended.push(2); // account type: account
+        extended.extend_from_slice(&7u16.to_le_bytes());
+        extended.extend_from_slice(&0u16.to_le_bytes());
+        assert_eq!(TokenAccount::from_account_data(&extended).unwrap().amount(), 9);
+        
+        // an extended mint is not a token account
+        let last = extended.len() - 5;
+        extended[last] = 1;
+        assert!(TokenAccount::from_account_data(&extended).is_err());
+        
+        // too short, or never initialized
+        assert!(TokenAccount::from_account_data(&data[..TOKEN_ACCOUNT_LEN - 1]).is_err());
+        data[108] = 0;
+        assert_eq!(
+            TokenAccount::from_account_data(&data).unwrap_err(),
+            ProgramError::UninitializedAccount
+        );
+    }
+
+    #[test]
+    fn test_refund_destination_check() {
+        let escrow = test_escrow(1000, 0);
+
+        // a freshly created mint A account of the maker, not the deposit ATA
+        let new_ata = test_token_account_data(&escrow.mint_a, &escrow.maker, 0);
+        assert!(check_token_account_data(&new_ata, &escrow.mint_a, &escrow.maker).is_ok());
+
+        // wrong mint
+        let wrong_mint = test_token_account_data(&escrow.mint_b, &escrow.maker, 0);
+        assert_eq!(
+            check_token_account_data(&wrong_mint, &escrow.mint_a, &escrow.maker).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidTokenMint as u32)
+        );
+
+        // someone else's account
+        let not_maker = test_token_account_data(&escrow.mint_a, &[8u8; 32], 0);
+        assert_eq!(
+            check_token_account_data(&not_maker, &escrow.mint_a, &escrow.maker).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+
+        // not a token account
+        assert!(check_token_account_data(&[0u8; 64], &escrow.mint_a, &escrow.maker).is_err());
+    }
+
+    #[test]
+    fn test_take_to_distinct_recipient() {
+        let escrow = test_escrow(1000, 0);
+        let settlement = [8u8; 32];
+        
+        // a settlement account for mint A that the taker doesn't own
+        let destination = test_token_account_data(&escrow.mint_a, &settlement, 0);
+        assert!(check_token_account_mint(&destination, &escrow.mint_a).is_ok());
+        
+        // a recipient holding the wrong token is rejected
+        let wrong_mint = test_token_account_data(&escrow.mint_b, &settlement, 0);
+        assert_eq!(
+            check_token_account_mint(&wrong_mint, &escrow.mint_a).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidTokenMint as u32)
+        );
+        assert!(check_token_account_mint(&[0u8; 64], &escrow.mint_a).is_err());
+    }
+
+    #[test]
+    fn test_vault_balance_check() {
+        use crate::sysvar::current_rent;
+        
+        let escrow = test_escrow(1000, 0);
+
+        // funded, or over-funded
+        assert!(escrow.check_vault_balance(1000).is_ok());
+        assert!(escrow.check_vault_balance(1500).is_ok());
+
+        // under-funded
+        let err = escrow.check_vault_balance(999).unwrap_err();
+        assert_eq!(err, ProgramError::Custom(EscrowError::InsufficientVaultBalance as u32));
+        assert!(escrow.check_vault_balance(0).is_err());
+        
+        // a native vault's rent reserve doesn't count towards the offer
+        let mut native = escrow;
+        native.kind = EscrowKind::Native as u8;
+        let rent = current_rent().unwrap().minimum_balance(0);
+        let funded = MockAccount::new([5u8; 32], ID, rent + 1000, &[]);
+        assert!(native.verify_vault_funded(&funded.info()).is_ok());
+        let rent_only = MockAccount::new([5u8; 32], ID, 1000, &[]);
+        assert_eq!(
+            native.verify_vault_funded(&rent_only.info()).unwrap_err(),
+            ProgramError::Custom(EscrowError::InsufficientVaultBalance as u32)
+        );
+        let short = MockAccount::new([5u8; 32], ID, rent + 999, &[]);
+        assert!(native.verify_vault_funded(&short.info()).is_err());
+        
+        // take_available only fills from what's above the rent
+        assert_eq!(native.vault_balance(&short.info()).unwrap(), 999);
+        assert_eq!(native.vault_balance(&rent_only.info()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_vault_dust_is_swept() {
+        let escrow = test_escrow(1000, 0);
+        
+        // an exact vault empties out
+        assert_eq!(sweep_amount(1000, escrow.amount).unwrap(), 1000);
+        
+        // dust from an outside deposit goes along, leaving nothing behind for close
+        let vault_balance = 1000 + 7;
+        let to_send = sweep_amount(vault_balance, escrow.amount).unwrap();
+        assert_eq!(vault_balance - to_send, 0);
+        
+        // expired refunds owe whatever is there
+        let owed = refund_amount(&test_escrow(1000, 10), 0, 3, 11).unwrap();
+        assert_eq!(sweep_amount(3, owed).unwrap(), 3);
+        
+        // short vaults are still rejected
+        assert_eq!(
+            sweep_amount(999, escrow.amount).unwrap_err(),
+            ProgramError::Custom(EscrowError::InsufficientVaultBalance as u32)
+        );
+    }
+
+    #[test]
+    fn test_taker_balance_check() {
+        // enough token B
+        assert!(check_taker_balance(Some(2000), 2000).is_ok());
+        assert!(check_taker_balance(Some(5000), 2000).is_ok());
+
+        // short balance
+        assert_eq!(
+            check_taker_balance(Some(1999), 2000).unwrap_err(),
+            ProgramError::Custom(EscrowError::InsufficientTakerBalance as u32)
+        );
+
+        // unreadable balance skips the check
+        assert!(check_taker_balance(None, 2000).is_ok());
+    }
+
+    #[test]
+    fn test_take_slippage_guard() {
+        let mut escrow = test_escrow(1000, 0);
+        assert_eq!(escrow.receive_amount, 2000);
+
+        // taker simulated against the current terms
+        assert!(check_slippage(&escrow, 2000).is_ok());
+        assert!(check_slippage(&escrow, 2500).is_ok());
+
+        // the offer was updated to ask for more token B before the take landed
+        escrow.receive_amount = 2100;
+        let err = check_slippage(&escrow, 2000).unwrap_err();
+        assert_eq!(err, ProgramError::Custom(EscrowError::SlippageExceeded as u32));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_escrow_keys_derive() {
+        use crate::instructions::make::{find_escrow_address, find_vault_address};
+
+        let maker = [5u8; 32];
+        let taker = [6u8; 32];
+        let mint_a = [7u8; 32];
+        let mint_b = [8u8; 32];
+        let keys = EscrowKeys::derive(&maker, &taker, &mint_a, &mint_b, 42, &ID);
+
+        let (escrow, escrow_bump) = find_escrow_address(&maker, 42, &ID);
+        assert_eq!(keys.escrow, escrow);
+        assert_eq!(keys.escrow_bump, escrow_bump);
+
+        let (vault, vault_bump) = find_vault_address(&escrow, &ID);
+        assert_eq!(keys.vault, vault);
+        assert_eq!(keys.vault_bump, vault_bump);
+
+        // each party gets its own ATA per mint
+        assert_ne!(keys.maker_ata_a, keys.maker_ata_b);
+        assert_ne!(keys.maker_ata_a, keys.taker_ata_a);
+        assert_ne!(keys.taker_ata_a, keys.taker_ata_b);
+        assert_eq!(keys.seed, 42);
+        assert_eq!(keys.program_id, ID);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_swap_accounts_derive() {
+        use crate::client::find_associated_token_address;
+        use crate::instructions::config::find_config_address;
+        use crate::instructions::make::{find_escrow_address, find_vault_address};
+
+        let maker = [5u8; 32];
+        let taker = [6u8; 32];
+        let mint_a = [7u8; 32];
+        let mint_b = [8u8; 32];
+        let accounts = swap_accounts(&maker, &taker, &mint_a, &mint_b, 42, &ID);
+
+        let (escrow, _) = find_escrow_address(&maker, 42, &ID);
+        assert_eq!(accounts.escrow, escrow);
+        assert_eq!(accounts.vault, find_vault_address(&escrow, &ID).0);
+        assert_eq!(accounts.taker_ata_a, find_associated_token_address(&taker, &mint_a));
+        assert_eq!(accounts.taker_ata_b, find_associated_token_address(&taker, &mint_b));
+        assert_eq!(accounts.maker_ata_b, find_associated_token_address(&maker, &mint_b));
+        assert_eq!(accounts.token_program, TOKEN_PROGRAM_ID);
+
+        // the take builder lays them out in TakeAccounts order
+        let ix = take_instruction(&accounts, 1000, 2000, 2000);
+        assert_eq!(ix.program_id, ID);
+        let keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys, vec![
+            taker,
+            maker,
+            escrow,
+            accounts.vault,
+            mint_a,
+            mint_b,
+            accounts.taker_ata_a,
+            accounts.taker_ata_b,
+            accounts.maker_ata_b,
+            TOKEN_PROGRAM_ID,
+            find_config_address(&ID).0,
+        ]);
+        for (meta, role) in ix.accounts.iter().zip(TakeAccounts::ROLES.iter()) {
+            assert_eq!((meta.is_signer, meta.is_writable), (role.is_signer, role.is_writable));
+        }
+        assert_eq!(
+            EscrowInstruction::unpack(&ix.data).unwrap(),
+            EscrowInstruction::Take { amount: 1000, seed: 42, max_in: 2000, receive_amount: 2000 }
+        );
+    }
+
+    #[test]
+    fn test_refund_closed_escrow_is_reported() {
+        let closed_err: ProgramError = EscrowError::InvalidEscrowAccount.into();
+        
+        // a closed escrow is all zeros, a resized one has no data at all
+        assert_eq!(Escrow::check_not_closed(&[0u8; Escrow::LEN]).unwrap_err(), closed_err);
+        assert_eq!(Escrow::check_not_closed(&[]).unwrap_err(), closed_err);
+        
+        // a live escrow passes on to the discriminator check
+        let mut data = vec![0u8; Escrow::LEN];
+        data[..8].copy_from_slice(&Escrow::DISCRIMINATOR);
+        assert!(Escrow::check_not_closed(&data).is_ok());
+    }
+
+    #[test]
+    fn test_wrapped_sol_escrow_flow() {
+        use crate::token::NATIVE_MINT;
+        
+        let mut escrow = test_escrow(1000, 0);
+        assert!(!escrow.is_wrapped_sol());
+        
+        // a wSOL offer is a token escrow over the native mint
+        escrow.mint_a = NATIVE_MINT;
+        assert!(escrow.is_wrapped_sol());
+        
+        // the vault is an ordinary token account for the native mint, checked
+        // against the synced amount like any other vault
+        let (vault, _) = crate::instructions::make::find_vault_address(&[9u8; 32], &ID);
+        let vault_data = test_token_account_data(&NATIVE_MINT, &vault, 1000);
+        assert!(check_token_account_data(&vault_data, &NATIVE_MINT, &vault).is_ok());
+        assert!(escrow.check_vault_balance(1000).is_ok());
+        
+        // native escrows hold plain lamports and never sync
+        escrow.kind = EscrowKind::Native as u8;
+        assert!(!escrow.is_wrapped_sol());
+    }
+
+    #[test]
+    fn test_bump_accessors() {
+        let escrow = test_escrow(1000, 0);
+        assert_eq!(escrow.escrow_bump(), 255);
+        assert_eq!(escrow.vault_bump(), 254);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_decode_escrow_keeps_bumps() {
+        let escrow = test_escrow(1000, 0);
+        let raw = unsafe {
+            core::slice::from_raw_parts(&escrow as *const Escrow as *const u8, Escrow::LEN)
+        };
+        
+        // what a client reads back after make stored both bumps
+        let decoded = decode_escrow(raw).unwrap();
+        assert_eq!(decoded.escrow_bump(), escrow.bump);
+        assert_eq!(decoded.vault_bump(), escrow.vault_bump);
+        assert_eq!(decoded.maker, escrow.maker);
+        
+        assert!(decode_escrow(&raw[..Escrow::LEN - 1]).is_err());
+        assert!(decode_escrow(&[0u8; Escrow::LEN]).is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_hex_round_trip() {
+        let instructions = [
+            EscrowInstruction::Make { amount: 1, seed: 2, expiry: 3, kind: EscrowKind::Native, receive_amount: 4, memo: vec![5u8; 32], namespace: [6u8; 8], price_num: 7, price_den: 8, min_fill: 9, persistent: true, treasury: false },
+            EscrowInstruction::Take { amount: 1, seed: 2, max_in: 3, receive_amount: 4 },
+            EscrowInstruction::Refund { amount: 1, seed: 2 },
+            EscrowInstruction::TransferAuthority { seed: 2 },
+            EscrowInstruction::GetEscrow { seed: 2 },
+            EscrowInstruction::ForceClose { seed: 2 },
+            EscrowInstruction::SweepExcess { seed: 2 },
+            EscrowInstruction::MakeBasket { seed: 2, receive_amount: 3, expiry: 4, amounts: vec![5, 6] },
+            EscrowInstruction::TakeBasket { seed: 2 },
+            EscrowInstruction::RefundBasket { seed: 2 },
+            EscrowInstruction::MigrateEscrow { seed: 2 },
+            EscrowInstruction::TakeAvailable { desired: 1, seed: 2, max_in: 3 },
+            EscrowInstruction::MakeInit { amount: 1, seed: 2, expiry: 3, kind: EscrowKind::Token, receive_amount: 4, memo: Vec::new(), namespace: [0u8; 8], price_num: 0, price_den: 0, min_fill: 0, persistent: false, treasury: false },
+            EscrowInstruction::MakeDeposit { seed: 2 },
+            EscrowInstruction::MakeVesting { seed: 2, amount: 3, start: 4, cliff: 5, duration: 6 },
+            EscrowInstruction::Claim { seed: 2 },
+            EscrowInstruction::ExtendExpiry { new_expiry: 1, seed: 2 },
+            EscrowInstruction::ClaimReceive { seed: 2 },
+            EscrowInstruction::ArbiterResolve { seed: 2, to_taker: false },
+            EscrowInstruction::InitConfig,
+            EscrowInstruction::SetPaused { paused: true },
+            EscrowInstruction::TopUp { amount: 1, seed: 2, receive_amount: 3 },
+        ];
+        
+        for instruction in instructions.iter() {
+            let hex = to_hex(instruction);
+            assert_eq!(hex.len(), pack_instruction_data(instruction).len() * 2);
+            assert_eq!(&from_hex(&hex).unwrap(), instruction);
+            // upper case pastes work too
+            assert_eq!(&from_hex(&hex.to_uppercase()).unwrap(), instruction);
+        }
+        
+        // a Refund of 1 for seed 2
+        let refund = to_hex(&EscrowInstruction::Refund { amount: 1, seed: 2 });
+        assert_eq!(refund, "0201000000000000000200000000000000");
+        
+        // odd length, a non-hex digit, and hex that isn't an instruction
+        let invalid = ProgramError::Custom(EscrowError::InvalidInstruction as u32);
+        assert_eq!(from_hex(&refund[1..]).unwrap_err(), invalid);
+        assert_eq!(from_hex(&refund.replacen('0', "g", 1)).unwrap_err(), invalid);
+        assert_eq!(from_hex("").unwrap_err(), invalid);
+        assert_eq!(from_hex("ff").unwrap_err(), invalid);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_is_seed_available() {
+        // nothing at the escrow address yet
+        assert!(is_seed_available(None));
+        
+        // an initialized escrow holds the seed
+        let escrow = test_escrow(1000, 0);
+        let raw = unsafe {
+            core::slice::from_raw_parts(&escrow as *const Escrow as *const u8, Escrow::LEN)
+        };
+        assert!(!is_seed_available(Some(raw)));
+        
+        // zeroed data is no escrow
+        assert!(is_seed_available(Some(&[0u8; Escrow::LEN])));
+        assert!(is_seed_available(Some(&[])));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_estimated_compute_units() {
+        let make = EscrowInstruction::Make {
+            amount: 1000,
+            seed: 1,
+            expiry: 0,
+            kind: EscrowKind::Token,
+            receive_amount: 2000,
+            memo: Vec::new(),
+            namespace: DEFAULT_ESCROW_NAMESPACE,
+            price_num: 0,
+            price_den: 0,
+            min_fill: 0,
+            persistent: false,
+            treasury: false,
+        };
+        let take = EscrowInstruction::Take { amount: 1000, seed: 1, max_in: 2000, receive_amount: 2000 };
+        let refund = EscrowInstruction::Refund { amount: 1000, seed: 1 };
+        let basket = |legs: usize| EscrowInstruction::MakeBasket {
+            seed: 1,
+            receive_amount: 900,
+            expiry: 0,
+            amounts: vec![100; legs],
+        };
+        let all = [
+            make.clone(),
+            take.clone(),
+            refund.clone(),
+            EscrowInstruction::TransferAuthority { seed: 1 },
+            EscrowInstruction::GetEscrow { seed: 1 },
+            EscrowInstruction::ForceClose { seed: 1 },
+            EscrowInstruction::SweepExcess { seed: 1 },
+            basket(MAX_BASKET_LEGS),
+            EscrowInstruction::TakeBasket { seed: 1 },
+            EscrowInstruction::RefundBasket { seed: 1 },
+            EscrowInstruction::MigrateEscrow { seed: 1 },
+            EscrowInstruction::TakeAvailable { desired: 1000, seed: 1, max_in: 2000 },
+            EscrowInstruction::MakeInit {
+                amount: 1000,
+                seed: 1,
+                expiry: 0,
+                kind: EscrowKind::Token,
+                receive_amount: 2000,
+                memo: Vec::new(),
+                namespace: DEFAULT_ESCROW_NAMESPACE,
+                price_num: 0,
+                price_den: 0,
+                min_fill: 0,
+                persistent: false,
+                treasury: false,
+            },
+            EscrowInstruction::MakeDeposit { seed: 1 },
+            EscrowInstruction::MakeVesting { seed: 1, amount: 1000, start: 0, cliff: 0, duration: 100 },
+            EscrowInstruction::Claim { seed: 1 },
+            EscrowInstruction::ExtendExpiry { new_expiry: 1, seed: 1 },
+            EscrowInstruction::ClaimReceive { seed: 1 },
+            EscrowInstruction::ArbiterResolve { seed: 1, to_taker: true },
+            EscrowInstruction::InitConfig,
+            EscrowInstruction::SetPaused { paused: true },
+            EscrowInstruction::TopUp { amount: 500, seed: 1, receive_amount: 1000 },
+        ];
+        
+        // every estimate fits the per-instruction default of 200k
+        for ix in &all {
+            let units = estimated_compute_units(ix);
+            assert!((1_000..=200_000).contains(&units), "{:?}: {}", ix, units);
+        }
+        
+        // make creates two accounts, take moves tokens twice and closes the
+        // vault, refund only moves them once. Read-only calls are cheapest
+        let units = |ix: &EscrowInstruction| estimated_compute_units(ix);
+        assert!(units(&make) > units(&take));
+        assert!(units(&take) > units(&refund));
+        assert!(units(&refund) > units(&EscrowInstruction::SweepExcess { seed: 1 }));
+        assert!(units(&EscrowInstruction::GetEscrow { seed: 1 }) < units(&refund));
+        
+        // baskets grow with their legs, one leg costs about a single make
+        assert!(units(&basket(2)) > units(&basket(1)));
+        assert!(units(&basket(1)) < units(&make) * 2);
+        assert!(units(&EscrowInstruction::TakeBasket { seed: 1 }) > units(&take));
+        
+        // the two phases together cost at least a single-phase make
+        assert!(units(&all[12]) + units(&all[13]) >= units(&make));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_offer_book_round_trip() {
+        let mut native = test_escrow(5_000_000_000, 1_700_000_000);
+        native.kind = EscrowKind::Native as u8;
+        native.mint_a = [0u8; 32];
+        let mut priced = test_escrow(250, 0);
+        priced.price_num = 3;
+        priced.price_den = 2;
+        priced.price_mode = PriceMode::Ratio as u8;
+        priced.memo_len = 5;
+        priced.memo[..5].copy_from_slice(b"hello");
+        let mut cancelled = test_escrow(1, -1);
+        cancelled.status = EscrowStatus::Cancelled as u8;
+        cancelled.maker = [42u8; 32];
+        let book = [test_escrow(1000, 0), native, priced, cancelled];
+        
+        let bytes = serialize_offer_book(&book);
+        assert_eq!(bytes.len(), 4 + book.len() * (4 + Escrow::LEN));
+        
+        let decoded = deserialize_offer_book(&bytes).unwrap();
+        assert_eq!(decoded.len(), book.len());
+        for (got, want) in decoded.iter().zip(book.iter()) {
+            assert_eq!(got.maker, want.maker);
+            assert_eq!(got.mint_a, want.mint_a);
+            assert_eq!(got.amount, want.amount);
+            assert_eq!(got.receive_amount, want.receive_amount);
+            assert_eq!(got.expiry, want.expiry);
+            assert_eq!(got.kind, want.kind);
+            assert_eq!(got.status, want.status);
+            assert_eq!(got.price_num, want.price_num);
+            assert_eq!(got.price_den, want.price_den);
+            assert_eq!(got.memo(), want.memo());
+        }
+        assert_eq!(decoded[2].memo(), b"hello");
+        
+        // an empty book is just the count
+        assert_eq!(serialize_offer_book(&[]), vec![0, 0, 0, 0]);
+        assert!(deserialize_offer_book(&[0, 0, 0, 0]).unwrap().is_empty());
+        
+        // truncated or padded snapshots are rejected
+        assert!(deserialize_offer_book(&bytes[..bytes.len() - 1]).is_err());
+        assert!(deserialize_offer_book(&bytes[..2]).is_err());
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert!(deserialize_offer_book(&padded).is_err());
+    }
+
+    #[test]
+    fn test_vault_account_size() {
+        // legacy mints always get a plain 165 byte account
+        let legacy_mint = test_mint_data(None);
+        assert_eq!(vault_account_size(&TOKEN_PROGRAM_ID, &legacy_mint).unwrap(), TOKEN_ACCOUNT_LEN);
+        
+        // Token-2022 mint without extensions
+        assert_eq!(vault_account_size(&TOKEN_2022_PROGRAM_ID, &legacy_mint).unwrap(), TOKEN_ACCOUNT_LEN);
+        
+        // Token-2022 mint with a TransferFeeConfig (type 1, 108 bytes) and a
+        // MintCloseAuthority (type 3, 32 bytes) extension
+        let mut mint = legacy_mint.clone();
+        mint.resize(TOKEN_ACCOUNT_LEN, 0);
+        mint.push(1); // account type: mint
+        mint.extend_from_slice(&1u16.to_le_bytes());
+        mint.extend_from_slice(&108u16.to_le_bytes());
+        mint.extend_from_slice(&[0u8; 108]);
+        mint.extend_from_slice(&3u16.to_le_bytes());
+        mint.extend_from_slice(&32u16.to_le_bytes());
+        mint.extend_from_slice(&[0u8; 32]);
+        
+        // only the transfer fee needs space in the vault: TransferFeeAmount (8 bytes)
+        assert_eq!(
+            vault_account_size(&TOKEN_2022_PROGRAM_ID, &mint).unwrap(),
+            TOKEN_ACCOUNT_LEN + 1 + 4 + 8
+        );
+        
+        // the legacy program ignores trailing data
+        assert_eq!(vault_account_size(&TOKEN_PROGRAM_ID, &mint).unwrap(), TOKEN_ACCOUNT_LEN);
+        
+        // extension data cut off before the account type
+        assert!(vault_account_size(&TOKEN_2022_PROGRAM_ID, &mint[..100]).is_err());
+    }
+
+    #[test]
+    fn test_vault_rent_exempt_lamports() {
+        use pinocchio::sysvars::rent::Rent;
+        
+        let rent = Rent { lamports_per_byte_year: 3480, exemption_threshold: 2.0, burn_percent: 50 };
+        // (128 bytes of account overhead + data) * 3480 * 2
+        let reserve = |len: usize| (128 + len as u64) * 3480 * 2;
+        
+        // a legacy vault is 165 bytes
+        let legacy = MockAccount::new([2u8; 32], TOKEN_PROGRAM_ID, 1, &test_mint_data(None));
+        assert_eq!(
+            vault_rent_exempt_lamports(&TOKEN_PROGRAM_ID, &legacy.info(), &rent).unwrap(),
+            2_039_280
+        );
+        assert_eq!(reserve(TOKEN_ACCOUNT_LEN), 2_039_280);
+        
+        // a Token-2022 mint with a TransferFeeConfig grows the vault by the
+        // account type byte and a TransferFeeAmount extension
+        let mut mint_data = test_mint_data(None);
+        mint_data.resize(TOKEN_ACCOUNT_LEN, 0);
+        mint_data.push(1); // account type: mint
+        mint_data.extend_from_slice(&1u16.to_le_bytes());
+        mint_data.extend_from_slice(&108u16.to_le_bytes());
+        mint_data.extend_from_slice(&[0u8; 108]);
+        let extended = MockAccount::new([2u8; 32], TOKEN_2022_PROGRAM_ID, 1, &mint_data);
+        assert_eq!(
+            vault_rent_exempt_lamports(&TOKEN_2022_PROGRAM_ID, &extended.info(), &rent).unwrap(),
+            reserve(TOKEN_ACCOUNT_LEN + 1 + 4 + 8)
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_chunked_make() {
+        let chunked = ChunkedMake {
+            maker: [5u8; 32],
+            mint_a: [6u8; 32],
+            mint_b: [7u8; 32],
+            total_amount: 1000,
+            chunk_size: 300,
+            total_receive_amount: 2000,
+            first_seed: 10,
+            expiry: 0,
+            memo: b"otc-desk".to_vec(),
+        };
+        let chunks = chunked.instructions(&ID).unwrap();
+        assert_eq!(chunks.len(), 4);
+        
+        let mut amount_total = 0;
+        let mut receive_total = 0;
+        for (i, (ix, escrow, seed)) in chunks.iter().enumerate() {
+            // every chunk has its own seed and escrow
+            assert!(chunks[..i].iter().all(|(_, e, s)| s != seed && e != escrow));
+            assert_eq!(*escrow, derive_escrow(&chunked.maker, *seed, &ID).0);
+            assert_eq!(ix.accounts[4].pubkey, *escrow);
+            
+            match EscrowInstruction::unpack(&ix.data).unwrap() {
+                EscrowInstruction::Make { amount, seed: packed_seed, receive_amount, .. } => {
+                    assert_eq!(packed_seed, *seed);
+                    assert_eq!(amount, if i < 3 { 300 } else { 100 });
+                    amount_total += amount;
+                    receive_total += receive_amount;
+                }
+                _ => panic!("Wrong instruction type"),
+            }
+        }
+        assert_eq!(amount_total, 1000);
+        assert_eq!(receive_total, 2000);
+        
+        // nothing to split, or seeds that would wrap around
+        assert!(ChunkedMake { chunk_size: 0, ..chunked.clone() }.instructions(&ID).is_err());
+        assert!(ChunkedMake { first_seed: u64::MAX - 1, ..chunked }.instructions(&ID).is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_client_filters() {
+        let open = test_escrow(100, 0);
+        let expiring = test_escrow(200, 1_000);
+        let mut taken = test_escrow(300, 0);
+        taken.status = EscrowStatus::Completed as u8;
+        let mut in_flight = test_escrow(400, 0);
+        in_flight.status = EscrowStatus::Processing as u8;
+        let mut other_pair = test_escrow(500, 0);
+        other_pair.mint_b = [9u8; 32];
+        let escrows = [open, expiring, taken, in_flight, other_pair];
+        
+        // before the deadline the expiring offer is still listed
+        let amounts = |list: Vec<&Escrow>| list.iter().map(|e| e.amount).collect::<Vec<_>>();
+        assert_eq!(amounts(filter_active(&escrows, 1_000)), vec![100, 200, 500]);
+        // after it, only offers without expiry remain
+        assert_eq!(amounts(filter_active(&escrows, 1_001)), vec![100, 500]);
+        
+        // mint pair matching is directional
+        assert_eq!(amounts(filter_by_pair(&escrows, &[2u8; 32], &[3u8; 32])), vec![100, 200, 300, 400]);
+        assert_eq!(amounts(filter_by_pair(&escrows, &[2u8; 32], &[9u8; 32])), vec![500]);
+        assert!(filter_by_pair(&escrows, &[3u8; 32], &[2u8; 32]).is_empty());
+        
+        // the two compose
+        let active = filter_active(&escrows, 2_000);
+        let pair: Vec<&Escrow> = active
+            .into_iter()
+            .filter(|e| e.mint_a == [2u8; 32] && e.mint_b == [3u8; 32])
+            .collect();
+        assert_eq!(amounts(pair), vec![100]);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_offer_index() {
+        let other_maker = [8u8; 32];
+        let address = |n: u8| [100 + n; 32];
+        let mut escrows = Vec::new();
+        for n in 0..6u8 {
+            let mut escrow = test_escrow(100 * (n as u64 + 1), 0);
+            if n % 2 == 1 {
+                escrow.maker = other_maker;
+            }
+            if n >= 4 {
+                escrow.mint_b = [9u8; 32];
+            }
+            if n == 2 || n == 5 {
+                escrow.status = EscrowStatus::Completed as u8;
+            }
+            // ingest in reverse so the index can't rely on sorted input
+            escrows.insert(0, (address(n), escrow));
+        }
+        let index = OfferIndex::new(escrows);
+        assert_eq!(index.len(), 6);
+        assert!(!index.is_empty());
+        assert!(OfferIndex::new(Vec::new()).is_empty());
+        
+        // results come back in ingest order
+        let amounts = |refs: &[EscrowRef]| refs.iter().map(|entry| index.get(entry).amount).collect::<Vec<_>>();
+        let addresses = |refs: &[EscrowRef]| refs.iter().map(|entry| entry.address).collect::<Vec<_>>();
+        
+        assert_eq!(amounts(index.by_maker(&[1u8; 32])), vec![500, 300, 100]);
+        assert_eq!(amounts(index.by_maker(&other_maker)), vec![600, 400, 200]);
+        assert_eq!(addresses(index.by_maker(&other_maker)), vec![address(5), address(3), address(1)]);
+        assert!(index.by_maker(&[7u8; 32]).is_empty());
+        
+        // mint pairs are directional, like filter_by_pair
+        assert_eq!(amounts(index.by_pair(&[2u8; 32], &[3u8; 32])), vec![400, 300, 200, 100]);
+        assert_eq!(amounts(index.by_pair(&[2u8; 32], &[9u8; 32])), vec![600, 500]);
+        assert!(index.by_pair(&[3u8; 32], &[2u8; 32]).is_empty());
+        
+        assert_eq!(amounts(index.by_status(EscrowStatus::Active)), vec![500, 400, 200, 100]);
+        assert_eq!(amounts(index.by_status(EscrowStatus::Completed)), vec![600, 300]);
+        assert!(index.by_status(EscrowStatus::Cancelled).is_empty());
+    }
+
+    #[test]
+    fn test_transfer_authority_replaces_maker() {
+        let old_maker = [1u8; 32];
+        let new_maker = [9u8; 32];
+        let mut escrow = test_escrow(1000, 0);
+        assert!(escrow.verify_maker(&old_maker).is_ok());
+
+        // what TransferAuthority does once the current maker is verified
+        escrow.verify_maker(&old_maker).unwrap();
+        escrow.maker = new_maker;
+
+        // the old maker can no longer refund, the new one can
+        assert!(escrow.verify_maker(&old_maker).is_err());
+        assert!(escrow.verify_maker(&new_maker).is_ok());
+
+        let packed = pack_instruction_data(&EscrowInstruction::TransferAuthority { seed: 7 });
+        assert_eq!(packed.len(), 9);
+        assert_eq!(packed[0], 3);
+    }
+
+    #[test]
+    fn test_take_after_transfer_authority() {
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        const NEW_MAKER: Pubkey = [9u8; 32];
+        let mocks = MakeMocks::new(61);
+        mocks.run(61, None).unwrap();
+        let new_maker = MockAccount::new(NEW_MAKER, SYSTEM_PROGRAM_ID, 1_000_000, &[]).read_only();
+        let transfer_to = |receive: &MockAccount| {
+            let infos = account_infos(&[&mocks.maker, &mocks.escrow, &new_maker, receive]);
+            transfer_authority(&ID, TransferAuthorityAccounts::from_slice(&infos).unwrap(), 61)
+        };
+        
+        // the receive account has to move with the escrow
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &new_maker]);
+        assert_eq!(TransferAuthorityAccounts::from_slice(&infos).err(), Some(ProgramError::NotEnoughAccountKeys));
+        // the old maker's account, or one of the wrong mint, is refused
+        assert_eq!(
+            transfer_to(&mocks.maker_ata_b).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+        let wrong_mint = MockAccount::new([16u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &NEW_MAKER, 0));
+        assert_eq!(
+            transfer_to(&wrong_mint).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidTokenMint as u32)
+        );
+        
+        let new_receive = MockAccount::new([17u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &NEW_MAKER, 0));
+        transfer_to(&new_receive).unwrap();
+        {
+            let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+            assert_eq!((escrow.maker, escrow.receive_account), (NEW_MAKER, [17u8; 32]));
+        }
+        
+        // a take after the transfer pays token B to the new maker's account
+        let vault = MockAccount::new(*mocks.vault.info().key(), TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000));
+        let taker = MockAccount::new([12u8; 32], SYSTEM_PROGRAM_ID, 1_000_000_000, &[]).signer();
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &[12u8; 32], 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &[12u8; 32], 5000));
+        let infos = account_infos(&[
+            &taker,
+            &new_maker,
+            &mocks.escrow,
+            &vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &new_receive,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 61, 2000, 2000).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+    }
+
+    #[test]
+    fn test_memo_round_trip() {
+        let memo = b"invoice-042".to_vec();
+        
+        let packed = pack_instruction_data(&EscrowInstruction::Make {
+            amount: 1,
+            seed: 2,
+            expiry: 0,
+            kind: EscrowKind::Token,
+            receive_amount: 3,
+            memo: memo.clone(),
+            namespace: [0u8; 8],
+            price_num: 0,
+            price_den: 0,
+            min_fill: 0,
+            persistent: false,
+            treasury: false,
+        });
+        assert_eq!(packed[MAKE_MEMO_LEN_OFFSET], 11);
+        assert_eq!(&packed[MAKE_MEMO_OFFSET..], memo.as_slice());
+        
+        match EscrowInstruction::unpack(&packed).unwrap() {
+            EscrowInstruction::Make { memo: unpacked, .. } => assert_eq!(unpacked, memo),
+            _ => panic!("Wrong instruction type"),
+        }
+        
+        // what Escrow::init stores, read back from the raw account bytes
+        let mut escrow = test_escrow(1000, 0);
+        escrow.memo[..memo.len()].copy_from_slice(&memo);
+        escrow.memo_len = memo.len() as u8;
+        assert_eq!(escrow.memo(), memo.as_slice());
+        let raw = unsafe {
+            core::slice::from_raw_parts(&escrow as *const Escrow as *const u8, Escrow::LEN)
+        };
+        let memo_offset = core::mem::offset_of!(Escrow, memo);
+        assert_eq!(raw[memo_offset - 1], 11);
+        assert_eq!(&raw[memo_offset..][..11], memo.as_slice());
+        
+        // a corrupted length never reads past the stored bytes
+        escrow.memo_len = u8::MAX;
+        assert_eq!(escrow.memo().len(), MAX_MEMO_LEN);
+    }
+
+    #[test]
+    fn test_memo_length_prefix() {
+        let make_with_memo = |memo: Vec<u8>| pack_instruction_data(&EscrowInstruction::Make {
+            amount: 1,
+            seed: 2,
+            expiry: 0,
+            kind: EscrowKind::Token,
+            receive_amount: 3,
+            memo,
+            namespace: [0u8; 8],
+            price_num: 0,
+            price_den: 0,
+            min_fill: 0,
+            persistent: false,
+            treasury: false,
+        });
+        let invalid: ProgramError = EscrowError::InvalidInstruction.into();
+        
+        // valid: empty, short and the longest allowed memo
+        for len in [0, 1, 40, MAX_MEMO_LEN] {
+            let packed = make_with_memo(vec![0x42; len]);
+            assert_eq!(packed.len(), MAKE_IX_MIN_LEN + len);
+            match EscrowInstruction::unpack(&packed).unwrap() {
+                EscrowInstruction::Make { memo, .. } => assert_eq!(memo, vec![0x42; len]),
+                _ => panic!("Wrong instruction type"),
+            }
+        }
+        
+        // a length prefix claiming more bytes than are left
+        let mut overlong = make_with_memo(vec![0x42; 8]);
+        overlong[MAKE_MEMO_LEN_OFFSET] = 9;
+        assert_eq!(EscrowInstruction::unpack(&overlong).unwrap_err(), invalid);
+        
+        // over the cap, even when the bytes are all there
+        let too_long = make_with_memo(vec![0x42; MAX_MEMO_LEN + 1]);
+        assert_eq!(too_long.len(), MAKE_IX_MAX_LEN + 1);
+        assert_eq!(EscrowInstruction::unpack(&too_long).unwrap_err(), invalid);
+        
+        // truncated memo, and data that ends before the length prefix
+        let full = make_with_memo(vec![0x42; 16]);
+        assert_eq!(EscrowInstruction::unpack(&full[..full.len() - 1]).unwrap_err(), invalid);
+        assert_eq!(EscrowInstruction::unpack(&full[..MAKE_MEMO_LEN_OFFSET]).unwrap_err(), invalid);
+    }
+
+    #[test]
+    fn test_namespaced_escrow_address() {
+        use crate::instructions::make::{find_escrow_address, find_namespaced_escrow_address};
+        
+        let maker = [1u8; 32];
+        let a = find_namespaced_escrow_address(&maker, b"tenant-a", 0, &ID);
+        let b = find_namespaced_escrow_address(&maker, b"tenant-b", 0, &ID);
+        let default = find_escrow_address(&maker, 0, &ID);
+        
+        // same maker and seed, isolated per namespace
+        assert_ne!(a.0, b.0);
+        assert_ne!(a.0, default.0);
+        assert_ne!(b.0, default.0);
+        
+        // the zero namespace is the original derivation
+        assert_eq!(find_namespaced_escrow_address(&maker, &DEFAULT_ESCROW_NAMESPACE, 0, &ID), default);
+        
+        // deterministic
+        assert_eq!(find_namespaced_escrow_address(&maker, b"tenant-a", 0, &ID), a);
+    }
+
+    #[test]
+    fn test_get_escrow_snapshot() {
+        let escrow = test_escrow(1000, 0);
+        let snapshot = EscrowSnapshot::from_escrow(&escrow).unwrap();
+        let return_data = snapshot.to_bytes();
+        assert_eq!(return_data.len(), EscrowSnapshot::LEN);
+        
+        // what a CPI caller decodes from get_return_data
+        let decoded = EscrowSnapshot::from_bytes(&return_data).unwrap();
+        assert_eq!(decoded, snapshot);
+        assert_eq!(decoded.maker, escrow.maker);
+        assert_eq!(decoded.mint_a, escrow.mint_a);
+        assert_eq!(decoded.mint_b, escrow.mint_b);
+        assert_eq!(decoded.amount, 1000);
+        assert_eq!(decoded.receive_amount, 2000);
+        assert_eq!(decoded.status, EscrowStatus::Active);
+        
+        // truncated or corrupted return data
+        assert!(EscrowSnapshot::from_bytes(&return_data[..EscrowSnapshot::LEN - 1]).is_err());
+        let mut bad_status = return_data;
+        bad_status[112] = 9;
+        assert!(EscrowSnapshot::from_bytes(&bad_status).is_err());
+        
+        let packed = pack_instruction_data(&EscrowInstruction::GetEscrow { seed: 7 });
+        assert_eq!(packed[0], GET_ESCROW_DISCRIMINATOR);
+        assert_eq!(EscrowInstruction::unpack(&packed).unwrap(), EscrowInstruction::GetEscrow { seed: 7 });
+    }
+
+    #[test]
+    fn test_refund_after_authority_transfer() {
+        use crate::instructions::make::find_escrow_address;
+        
+        let seed_maker = [1u8; 32];
+        let new_maker = [9u8; 32];
+        let mut escrow = test_escrow(1000, 0);
+        let (escrow_key, _) = find_escrow_address(&seed_maker, 7, &ID);
+        
+        escrow.maker = new_maker;
+        
+        // the PDA still comes from the original maker's seeds...
+        assert_eq!(find_escrow_address(&seed_maker, 7, &ID).0, escrow_key);
+        assert_ne!(find_escrow_address(&new_maker, 7, &ID).0, escrow_key);
+        
+        // ...but only the new maker passes the refund authorization
+        assert_eq!(
+            escrow.verify_maker(&seed_maker).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+        assert!(escrow.verify_maker(&new_maker).is_ok());
+        
+        // and the refund has to land in a mint A account of the new maker
+        let new_maker_ata = test_token_account_data(&escrow.mint_a, &new_maker, 0);
+        assert!(check_token_account_data(&new_maker_ata, &escrow.mint_a, &escrow.maker).is_ok());
+        let old_maker_ata = test_token_account_data(&escrow.mint_a, &seed_maker, 0);
+        assert!(check_token_account_data(&old_maker_ata, &escrow.mint_a, &escrow.maker).is_err());
+    }
+
+    #[test]
+    fn test_mul_div_rounding() {
+        use crate::math::{mul_div_ceil, mul_div_floor};
+        
+        // exact quotients round the same both ways
+        assert_eq!(mul_div_floor(10, 6, 4).unwrap(), 15);
+        assert_eq!(mul_div_ceil(10, 6, 4).unwrap(), 15);
+        assert_eq!(mul_div_floor(0, u64::MAX, 1).unwrap(), 0);
+        assert_eq!(mul_div_ceil(0, u64::MAX, 1).unwrap(), 0);
+        
+        // a remainder splits them by exactly one
+        assert_eq!(mul_div_floor(10, 1, 3).unwrap(), 3);
+        assert_eq!(mul_div_ceil(10, 1, 3).unwrap(), 4);
+        assert_eq!(mul_div_floor(1, 1, u64::MAX).unwrap(), 0);
+        assert_eq!(mul_div_ceil(1, 1, u64::MAX).unwrap(), 1);
+        
+        // the u128 product can't overflow, only the final result can
+        assert_eq!(mul_div_floor(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert_eq!(mul_div_ceil(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert_eq!(mul_div_floor(u64::MAX, 3, 4).unwrap(), u64::MAX / 4 * 3 + 2);
+        assert!(matches!(mul_div_floor(u64::MAX, 2, 1), Err(EscrowError::AmountOverflow)));
+        // floor fits at the boundary where ceil tips over
+        assert_eq!(mul_div_floor(u64::MAX, 2, 2).unwrap(), u64::MAX);
+        // 31 * 1_190_112_520_884_487_201 = 2 * u64::MAX + 1
+        assert_eq!(mul_div_floor(31, 1_190_112_520_884_487_201, 2).unwrap(), u64::MAX);
+        assert!(matches!(mul_div_ceil(31, 1_190_112_520_884_487_201, 2), Err(EscrowError::AmountOverflow)));
+        
+        // a zero divisor is an error, never a panic
+        assert!(matches!(mul_div_floor(1, 1, 0), Err(EscrowError::AmountOverflow)));
+        assert!(matches!(mul_div_ceil(0, 0, 0), Err(EscrowError::AmountOverflow)));
+        
+        // small values against the naive formulas, both directions
+        for a in 0..=24u64 {
+            for b in 0..=24u64 {
+                for c in 1..=24u64 {
+                    let floor = mul_div_floor(a, b, c).unwrap();
+                    let ceil = mul_div_ceil(a, b, c).unwrap();
+                    assert_eq!(floor, a * b / c);
+                    assert_eq!(ceil, (a * b).div_ceil(c));
+                    assert!(floor * c <= a * b && a * b <= ceil * c);
+                    assert_eq!(ceil - floor, u64::from(a * b % c != 0));
+                }
+            }
+        }
+        
+        // usable in const contexts
+        const HALF: u64 = match mul_div_floor(1_000, 1, 2) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+        assert_eq!(HALF, 500);
+    }
+
+    #[test]
+    fn test_price_mode_fills() {
+        use crate::state::price_amount;
+        
+        assert_eq!(PriceMode::try_from(0).unwrap(), PriceMode::Fixed);
+        assert_eq!(PriceMode::try_from(1).unwrap(), PriceMode::Ratio);
+        assert!(PriceMode::try_from(2).is_err());
+        
+        // exact: 1000 A at 3/2 is 1500 B, nothing to round
+        assert_eq!(price_amount(1000, 3, 2).unwrap(), 1500);
+        
+        // fractional: 1001 * 3 / 2 = 1501.5, the maker gets 1502
+        assert_eq!(price_amount(1001, 3, 2).unwrap(), 1502);
+        // 1 A at 1/3 would floor to 0 B, a free fill
+        assert_eq!(price_amount(1, 1, 3).unwrap(), 1);
+        assert_eq!(price_amount(0, 1, 3).unwrap(), 0);
+        
+        // the product is taken in u128, only a result past u64 overflows
+        assert_eq!(price_amount(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert_eq!(
+            price_amount(u64::MAX, 2, 1).unwrap_err(),
+            ProgramError::Custom(EscrowError::AmountOverflow as u32)
+        );
+        assert!(price_amount(1, 1, 0).is_err());
+        
+        // the escrow prices fills by its mode
+        let mut escrow = test_escrow(1001, 0);
+        assert_eq!(escrow.counter_amount(1001).unwrap(), escrow.receive_amount);
+        
+        escrow.price_mode = PriceMode::Ratio as u8;
+        escrow.price_num = 3;
+        escrow.price_den = 2;
+        escrow.receive_amount = price_amount(1001, 3, 2).unwrap();
+        assert_eq!(escrow.counter_amount(1001).unwrap(), 1502);
+        assert!(check_slippage(&escrow, 1502).is_ok());
+        assert!(check_slippage(&escrow, 1501).is_err());
+    }
+
+    #[test]
+    fn test_force_close_half_initialized_escrow() {
+        use crate::instructions::force_close::{check_force_closable, AbandonedVault};
+        
+        // make created and initialized the escrow, but the deposit never
+        // reached the vault
+        let escrow = test_escrow(1000, 0);
+        
+        // refund can't recover it, it needs the full deposit in the vault...
+        assert_eq!(
+            escrow.check_vault_balance(0).unwrap_err(),
+            ProgramError::Custom(EscrowError::InsufficientVaultBalance as u32)
+        );
+        // ...force close can
+        assert!(check_force_closable(&escrow).is_ok());
+        
+        // vault never created
+        assert_eq!(AbandonedVault::classify(false, false, &[]).unwrap(), AbandonedVault::Missing);
+        // vault created but never initialized as a token account
+        assert_eq!(
+            AbandonedVault::classify(true, false, &[0u8; TOKEN_ACCOUNT_LEN]).unwrap(),
+            AbandonedVault::ProgramOwned
+        );
+        // vault initialized, empty or partly funded
+        let empty = test_token_account_data(&escrow.mint_a, &[5u8; 32], 0);
+        assert_eq!(AbandonedVault::classify(false, true, &empty).unwrap(), AbandonedVault::Token { amount: 0 });
+        let partial = test_token_account_data(&escrow.mint_a, &[5u8; 32], 400);
+        assert_eq!(AbandonedVault::classify(false, true, &partial).unwrap(), AbandonedVault::Token { amount: 400 });
+        // owned by the token program but uninitialized
+        assert_eq!(
+            AbandonedVault::classify(false, true, &[0u8; TOKEN_ACCOUNT_LEN]).unwrap(),
+            AbandonedVault::UninitializedToken
+        );
+        
+        // filled or in-flight escrows can't be force closed
+        for status in [EscrowStatus::Processing, EscrowStatus::Completed, EscrowStatus::Cancelled] {
+            let mut escrow = test_escrow(1000, 0);
+            escrow.status = status as u8;
+            assert_eq!(
+                check_force_closable(&escrow).unwrap_err(),
+                ProgramError::Custom(EscrowError::InvalidState as u32)
+            );
+        }
+        
+        let packed = pack_instruction_data(&EscrowInstruction::ForceClose { seed: 7 });
+        assert_eq!(packed[0], FORCE_CLOSE_DISCRIMINATOR);
+        assert_eq!(EscrowInstruction::unpack(&packed).unwrap(), EscrowInstruction::ForceClose { seed: 7 });
+    }
+
+    // the program's config PDA as InitConfig leaves it, unpaused with admin [40; 32]
+    fn test_config() -> MockAccount {
+        use crate::instructions::config::find_config_address;
+
+        let (key, bump) = find_config_address(&ID);
+        let config = MockAccount::new(key, ID, 1_000_000, &[0u8; Config::LEN]);
+        Config::init(&config.info(), [40u8; 32], bump).unwrap();
+        config
+    }
+
+    // the accounts of a make right after its create_account CPIs, which are
+    // no-ops off-chain: the escrow is already allocated and owned by the program,
+    // the vault already initialized as an empty mint A account of the escrow
+    struct MakeMocks {
+        maker: MockAccount,
+        mint_a: MockAccount,
+        mint_b: MockAccount,
+        maker_ata_a: MockAccount,
+        escrow: MockAccount,
+        vault: MockAccount,
+        token_program: MockAccount,
+        system_program: MockAccount,
+        maker_ata_b: MockAccount,
+        config: MockAccount,
+    }
+
+    impl MakeMocks {
+        const MAKER: Pubkey = [1u8; 32];
+        // a little over the rent-exempt minimum for Escrow::LEN
+        const ESCROW_LAMPORTS: u64 = 3_100_000;
+
+        fn new(seed: u64) -> Self {
+            use crate::instructions::make::{find_escrow_address, find_vault_address};
+            use crate::programs::SYSTEM_PROGRAM_ID;
+
+            let (escrow, _) = find_escrow_address(&Self::MAKER, seed, &ID);
+            let (vault, _) = find_vault_address(&escrow, &ID);
+            Self {
+                maker: MockAccount::new(Self::MAKER, SYSTEM_PROGRAM_ID, 10_000_000_000, &[]).signer(),
+                mint_a: MockAccount::new([2u8; 32], TOKEN_PROGRAM_ID, 1_000_000, &test_mint_data(None)).read_only(),
+                mint_b: MockAccount::new([3u8; 32], TOKEN_PROGRAM_ID, 1_000_000, &test_mint_data(None)).read_only(),
+                maker_ata_a: MockAccount::new(
+                    [4u8; 32],
+                    TOKEN_PROGRAM_ID,
+                    2_000_000,
+                    &test_token_account_data(&[2u8; 32], &Self::MAKER, 5_000),
+                ),
+                escrow: MockAccount::new(escrow, ID, Self::ESCROW_LAMPORTS, &[0u8; Escrow::LEN]),
+                vault: MockAccount::new(vault, TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &escrow, 0)),
+                token_program: MockAccount::new(TOKEN_PROGRAM_ID, [0u8; 32], 1, &[]).read_only(),
+                system_program: MockAccount::new(SYSTEM_PROGRAM_ID, [0u8; 32], 1, &[]).read_only(),
+                maker_ata_b: MockAccount::new(
+                    [15u8; 32],
+                    TOKEN_PROGRAM_ID,
+                    2_000_000,
+                    &test_token_account_data(&[3u8; 32], &Self::MAKER, 0),
+                ),
+                config: test_config(),
+            }
+        }
+
+        fn run(&self, seed: u64, payer: Option<&MockAccount>) -> ProgramResult {
+            self.run_with_treasury(seed, payer, false)
+        }
+
+        fn run_with_treasury(&self, seed: u64, payer: Option<&MockAccount>, treasury: bool) -> ProgramResult {
+            let mut mocks = vec![
+                &self.maker,
+                &self.mint_a,
+                &self.mint_b,
+                &self.maker_ata_a,
+                &self.escrow,
+                &self.vault,
+                &self.token_program,
+                &self.system_program,
+                &self.maker_ata_b,
+                &self.config,
+            ];
+            mocks.extend(payer);
+            let infos = account_infos(&mocks);
+            let accounts = MakeAccounts::from_slice(&infos)?;
+            make(&ID, accounts, 1000, seed, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, treasury)
+        }
+    }
+
+    #[test]
+    fn test_verify_pdas() {
+        use crate::instructions::make::{find_escrow_address, find_namespaced_escrow_address, find_vault_address};
+        use crate::pda::{verify_escrow_pda, verify_namespaced_escrow_pda, verify_vault_pda};
+        
+        let maker = [1u8; 32];
+        let mismatch = ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32);
+        let (escrow_key, escrow_bump) = find_escrow_address(&maker, 42, &ID);
+        let (vault_key, vault_bump) = find_vault_address(&escrow_key, &ID);
+        let escrow = MockAccount::new(escrow_key, ID, 1, &[]);
+        let vault = MockAccount::new(vault_key, ID, 1, &[]);
+        
+        // the right addresses give back their bumps
+        assert_eq!(verify_escrow_pda(&escrow.info(), &maker, 42, &ID).unwrap(), escrow_bump);
+        assert_eq!(verify_vault_pda(&vault.info(), &escrow_key, &ID).unwrap(), vault_bump);
+        
+        // another seed, maker or program derives another escrow
+        assert_eq!(verify_escrow_pda(&escrow.info(), &maker, 43, &ID).unwrap_err(), mismatch);
+        assert_eq!(verify_escrow_pda(&escrow.info(), &[2u8; 32], 42, &ID).unwrap_err(), mismatch);
+        assert_eq!(verify_escrow_pda(&escrow.info(), &maker, 42, &[7u8; 32]).unwrap_err(), mismatch);
+        
+        // a vault belongs to exactly one escrow, and is never the escrow itself
+        assert_eq!(verify_vault_pda(&vault.info(), &[9u8; 32], &ID).unwrap_err(), mismatch);
+        assert_eq!(verify_vault_pda(&escrow.info(), &escrow_key, &ID).unwrap_err(), mismatch);
+        
+        // namespaced escrows only match in their own namespace
+        let (tenant_key, tenant_bump) = find_namespaced_escrow_address(&maker, b"tenant-a", 42, &ID);
+        let tenant = MockAccount::new(tenant_key, ID, 1, &[]);
+        assert_eq!(verify_namespaced_escrow_pda(&tenant.info(), &maker, b"tenant-a", 42, &ID).unwrap(), tenant_bump);
+        assert_eq!(verify_namespaced_escrow_pda(&tenant.info(), &maker, b"tenant-b", 42, &ID).unwrap_err(), mismatch);
+        assert_eq!(verify_escrow_pda(&tenant.info(), &maker, 42, &ID).unwrap_err(), mismatch);
+    }
+
+    #[test]
+    fn test_two_phase_make() {
+        use crate::instructions::make::deposit_shortfall;
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        let mocks = MakeMocks::new(17);
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &mocks.config,
+        ]);
+        make_init(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 17, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false).unwrap();
+        
+        // the escrow is there, waiting for its deposit
+        let info = mocks.escrow.info();
+        let escrow = Escrow::from_account(&info, &ID).unwrap();
+        assert_eq!(escrow.amount, 1000);
+        assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+        
+        // the vault as the token program would show it, holding `balance`
+        let vault_key = *mocks.vault.info().key();
+        let vault_with = |balance: u64| {
+            MockAccount::new(vault_key, TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], info.key(), balance))
+        };
+        let deposit_with = |maker: &MockAccount, vault: &MockAccount| {
+            let infos = account_infos(&[maker, &mocks.escrow, vault, &mocks.maker_ata_a, &mocks.token_program, &mocks.system_program]);
+            make_deposit(&ID, MakeDepositAccounts::from_slice(&infos).unwrap(), 17)
+        };
+        
+        // first attempt, then a retry after only part of it landed, then a
+        // retry once the vault is already funded
+        assert!(deposit_with(&mocks.maker, &vault_with(0)).is_ok());
+        assert_eq!(deposit_shortfall(1000, 0), 1000);
+        assert!(deposit_with(&mocks.maker, &vault_with(400)).is_ok());
+        assert_eq!(deposit_shortfall(1000, 400), 600);
+        assert!(deposit_with(&mocks.maker, &vault_with(1000)).is_ok());
+        assert_eq!(deposit_shortfall(1000, 1000), 0);
+        assert_eq!(deposit_shortfall(1000, 1500), 0);
+        
+        // only the maker deposits, into this escrow's vault
+        let stranger = MockAccount::new([9u8; 32], SYSTEM_PROGRAM_ID, 1_000_000, &[]).signer();
+        assert_eq!(
+            deposit_with(&stranger, &vault_with(0)).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+        let other_vault = MockAccount::new([10u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &ID, 0));
+        assert_eq!(
+            deposit_with(&mocks.maker, &other_vault).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32)
+        );
+        
+        // a vault of another mint can't back the escrow
+        let wrong_mint = MockAccount::new(vault_key, TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &ID, 0));
+        assert_eq!(
+            deposit_with(&mocks.maker, &wrong_mint).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidTokenMint as u32)
+        );
+        
+        // none of the deposits touched the escrow
+        let escrow = Escrow::from_account(&info, &ID).unwrap();
+        assert_eq!(escrow.amount, 1000);
+        assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+    }
+
+    #[cfg(not(feature = "skip-program-id-check"))]
+    #[test]
+    fn test_process_instruction_checks_program_id() {
+        let data = pack_instruction_data(&EscrowInstruction::GetEscrow { seed: 1 });
+        
+        // rejected before the data or the accounts are looked at
+        assert_eq!(
+            process_instruction(&[7u8; 32], &[], &data).unwrap_err(),
+            ProgramError::IncorrectProgramId
+        );
+        assert_eq!(
+            process_instruction(&[7u8; 32], &[], &[]).unwrap_err(),
+            ProgramError::IncorrectProgramId
+        );
+        
+        // the declared id gets as far as the account checks
+        assert_eq!(
+            process_instruction(&ID, &[], &data).unwrap_err(),
+            ProgramError::NotEnoughAccountKeys
+        );
+    }
+
+    #[test]
+    fn test_vesting_schedule() {
+        let invalid_expiry = ProgramError::Custom(EscrowError::InvalidExpiry as u32);
+        let data = [0u8; VestingEscrow::LEN];
+        let account = MockAccount::new([30u8; 32], ID, 1, &data);
+        let info = account.info();
+        VestingEscrow::init(&info, [1u8; 32], [31u8; 32], [2u8; 32], 1000, 1000, 1250, 1000, 1, 2).unwrap();
+        let vesting = VestingEscrow::from_account(&info, &ID).unwrap();
+        
+        // pre-cliff: a quarter of the time is gone but nothing is claimable yet
+        assert_eq!(vesting.vested_amount(999).unwrap(), 0);
+        assert_eq!(vesting.claimable(1249).unwrap(), 0);
+        
+        // mid-vesting: the cliff releases everything accrued since the start
+        assert_eq!(vesting.claimable(1250).unwrap(), 250);
+        assert_eq!(vesting.claimable(1333).unwrap(), 333);
+        vesting.record_claim(333).unwrap();
+        assert_eq!(vesting.claimable(1333).unwrap(), 0);
+        assert_eq!(vesting.claimable(1500).unwrap(), 167);
+        assert_eq!(vesting.status().unwrap(), EscrowStatus::Active);
+        
+        // post-end: the rest, and claiming it completes the schedule
+        assert_eq!(vesting.claimable(2000).unwrap(), 667);
+        assert_eq!(vesting.claimable(i64::MAX).unwrap(), 667);
+        vesting.record_claim(667).unwrap();
+        assert_eq!(vesting.status().unwrap(), EscrowStatus::Completed);
+        assert_eq!(
+            vesting.record_claim(1).unwrap_err(),
+            ProgramError::Custom(EscrowError::AmountOverflow as u32)
+        );
+        
+        // odd totals round down until the end
+        let mut odd = *vesting;
+        odd.total_amount = 7;
+        odd.released = 0;
+        assert_eq!(odd.vested_amount(1500).unwrap(), 3);
+        assert_eq!(odd.vested_amount(2000).unwrap(), 7);
+        
+        // the cliff falls inside the schedule, which can't run backwards
+        assert!(VestingEscrow::check_schedule(0, 0, 0).is_ok());
+        assert!(VestingEscrow::check_schedule(0, 100, 100).is_ok());
+        assert_eq!(VestingEscrow::check_schedule(100, 99, 10).unwrap_err(), invalid_expiry);
+        assert_eq!(VestingEscrow::check_schedule(0, 101, 100).unwrap_err(), invalid_expiry);
+        assert_eq!(VestingEscrow::check_schedule(0, 0, -1).unwrap_err(), invalid_expiry);
+        assert_eq!(VestingEscrow::check_schedule(i64::MAX, i64::MAX, 1).unwrap_err(), invalid_expiry);
+    }
+
+    #[test]
+    fn test_extend_expiry() {
+        use crate::instructions::extend_expiry::extend_expiry_at;
+        
+        let escrow = test_escrow(1000, 2_000);
+        let raw = unsafe {
+            core::slice::from_raw_parts(&escrow as *const Escrow as *const u8, Escrow::LEN)
+        };
+        let escrow_account = MockAccount::new([40u8; 32], ID, 2_000_000, raw);
+        let maker = MockAccount::new([1u8; 32], [0u8; 32], 1_000_000, &[]).signer();
+        let extend_at = |maker: &MockAccount, new_expiry: i64, now: i64| {
+            let infos = account_infos(&[maker, &escrow_account]);
+            extend_expiry_at(&ID, ExtendExpiryAccounts::from_slice(&infos).unwrap(), new_expiry, 1, now)
+        };
+        let stored_expiry = || Escrow::from_account(&escrow_account.info(), &ID).unwrap().expiry;
+        let invalid_expiry = ProgramError::Custom(EscrowError::InvalidExpiry as u32);
+        
+        // an active offer is pushed back
+        extend_at(&maker, 5_000, 1_000).unwrap();
+        assert_eq!(stored_expiry(), 5_000);
+        
+        // in the past, earlier than the current expiry, past the horizon, or
+        // removed altogether
+        assert_eq!(extend_at(&maker, 500, 1_000).unwrap_err(), invalid_expiry);
+        assert_eq!(extend_at(&maker, 4_000, 1_000).unwrap_err(), invalid_expiry);
+        assert_eq!(extend_at(&maker, 1_000 + MAX_EXPIRY_HORIZON + 1, 1_000).unwrap_err(), invalid_expiry);
+        assert_eq!(extend_at(&maker, 0, 1_000).unwrap_err(), invalid_expiry);
+        
+        // only the maker's signature extends it
+        let unsigned = MockAccount::new([1u8; 32], [0u8; 32], 1_000_000, &[]);
+        assert_eq!(extend_at(&unsigned, 6_000, 1_000).unwrap_err(), ProgramError::MissingRequiredSignature);
+        let stranger = MockAccount::new([9u8; 32], [0u8; 32], 1_000_000, &[]).signer();
+        assert_eq!(
+            extend_at(&stranger, 6_000, 1_000).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+        
+        // once expired the offer stays expired
+        assert_eq!(
+            extend_at(&maker, 9_000, 5_001).unwrap_err(),
+            ProgramError::Custom(EscrowError::EscrowExpired as u32)
+        );
+        assert_eq!(stored_expiry(), 5_000);
+        
+        let packed = pack_instruction_data(&EscrowInstruction::ExtendExpiry { new_expiry: 5_000, seed: 1 });
+        assert_eq!(packed[0], EXTEND_EXPIRY_DISCRIMINATOR);
+    }
+
+    #[test]
+    fn test_vesting_claims() {
+        use crate::instructions::{
+            make::find_vault_address,
+            vesting::{claim_at, find_vesting_address},
+        };
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        const RECIPIENT: Pubkey = [30u8; 32];
+        let mocks = MakeMocks::new(20);
+        let (vesting_key, _) = find_vesting_address(&MakeMocks::MAKER, 20, &ID);
+        let (vault_key, _) = find_vault_address(&vesting_key, &ID);
+        let recipient = MockAccount::new(RECIPIENT, SYSTEM_PROGRAM_ID, 1_000_000, &[]).signer();
+        let vesting = MockAccount::new(vesting_key, ID, 3_000_000, &[0u8; VestingEscrow::LEN]);
+        let vault = MockAccount::new(vault_key, SYSTEM_PROGRAM_ID, 0, &[]);
+        let infos = account_infos(&[
+            &mocks.maker,
+            &recipient,
+            &mocks.mint_a,
+            &mocks.maker_ata_a,
+            &vesting,
+            &vault,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.config,
+        ]);
+        make_vesting(&ID, MakeVestingAccounts::from_slice(&infos).unwrap(), 20, 1000, 1000, 1250, 1000).unwrap();
+        
+        let recipient_ata = MockAccount::new([31u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &RECIPIENT, 0));
+        let claim_with = |signer: &MockAccount, now: i64| {
+            let infos = account_infos(&[signer, &mocks.maker, &vesting, &vault, &recipient_ata, &mocks.token_program]);
+            claim_at(&ID, ClaimAccounts::from_slice(&infos).unwrap(), 20, now)
+        };
+        let released = || VestingEscrow::from_account(&vesting.info(), &ID).unwrap().released;
+        
+        // pre-cliff
+        assert_eq!(
+            claim_with(&recipient, 1100).unwrap_err(),
+            ProgramError::Custom(EscrowError::NothingToClaim as u32)
+        );
+        assert_eq!(released(), 0);
+        
+        // only the recipient claims
+        assert_eq!(
+            claim_with(&mocks.maker, 1500).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+        
+        // mid-vesting, twice
+        claim_with(&recipient, 1500).unwrap();
+        assert_eq!(released(), 500);
+        claim_with(&recipient, 1600).unwrap();
+        assert_eq!(released(), 600);
+        assert_eq!(
+            claim_with(&recipient, 1600).unwrap_err(),
+            ProgramError::Custom(EscrowError::NothingToClaim as u32)
+        );
+        
+        // post-end: the remainder and any dust sent to the vault, then the
+        // schedule is closed to the maker. CPIs are no-ops off-chain, so stand
+        // in the vault as the earlier claims left it, plus 5 of dust
+        let funded_vault = MockAccount::new(vault_key, TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &vesting_key, 405));
+        let maker_lamports = mocks.maker.lamports();
+        let infos = account_infos(&[&recipient, &mocks.maker, &vesting, &funded_vault, &recipient_ata, &mocks.token_program]);
+        claim_at(&ID, ClaimAccounts::from_slice(&infos).unwrap(), 20, 2500).unwrap();
+        assert_eq!(vesting.lamports(), 0);
+        assert_eq!(mocks.maker.lamports(), maker_lamports + 3_000_000);
+        assert_eq!(
+            VestingEscrow::from_account(&vesting.info(), &ID).unwrap_err(),
+            ProgramError::IncorrectProgramId
+        );
+        
+        let packed = pack_instruction_data(&EscrowInstruction::Claim { seed: 20 });
+        assert_eq!(packed[0], CLAIM_DISCRIMINATOR);
+        assert_eq!(EscrowInstruction::unpack(&packed).unwrap(), EscrowInstruction::Claim { seed: 20 });
+    }
+
+    #[test]
+    fn test_make_with_mock_accounts() {
+        let mocks = MakeMocks::new(7);
+        mocks.run(7, None).unwrap();
+
+        // the escrow was initialized in place
+        let info = mocks.escrow.info();
+        let escrow = Escrow::from_account(&info, &ID).unwrap();
+        assert_eq!(escrow.maker, MakeMocks::MAKER);
+        assert_eq!(escrow.mint_a, [2u8; 32]);
+        assert_eq!(escrow.mint_b, [3u8; 32]);
+        assert_eq!(escrow.receive_account, [15u8; 32]);
+        assert_eq!(escrow.amount, 1000);
+        assert_eq!(escrow.receive_amount, 2000);
+        assert_eq!(escrow.memo(), b"mock");
+        assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+    }
+
+    #[test]
+    fn test_make_rejects_mismatched_receive_account() {
+        let invalid_mint = ProgramError::Custom(EscrowError::InvalidTokenMint as u32);
+        
+        // a token A account passed where token B is paid
+        let mocks = MakeMocks {
+            maker_ata_b: MockAccount::new(
+                [15u8; 32],
+                TOKEN_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0),
+            ),
+            ..MakeMocks::new(7)
+        };
+        assert_eq!(mocks.run(7, None).unwrap_err(), invalid_mint);
+        
+        // the right mint, but someone else's account
+        let mocks = MakeMocks {
+            maker_ata_b: MockAccount::new(
+                [15u8; 32],
+                TOKEN_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[3u8; 32], &[8u8; 32], 0),
+            ),
+            ..MakeMocks::new(7)
+        };
+        assert_eq!(mocks.run(7, None).unwrap_err(), invalid_mint);
+        
+        // not a token account at all
+        let mocks = MakeMocks {
+            maker_ata_b: MockAccount::new([15u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &[]),
+            ..MakeMocks::new(7)
+        };
+        assert_eq!(mocks.run(7, None).unwrap_err(), invalid_mint);
+        
+        // nothing was written to the escrow
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_make_return_data_round_trip() {
+        use crate::instructions::make::{find_escrow_address, find_vault_address};
+        
+        let (escrow, escrow_bump) = find_escrow_address(&MakeMocks::MAKER, 44, &ID);
+        let (vault, vault_bump) = find_vault_address(&escrow, &ID);
+        let returned = MakeReturnData { escrow, escrow_bump, vault, vault_bump };
+        
+        // escrow, its bump, vault, its bump
+        let bytes = returned.to_bytes();
+        assert_eq!(bytes.len(), MakeReturnData::LEN);
+        assert_eq!(&bytes[0..32], &escrow);
+        assert_eq!(bytes[32], escrow_bump);
+        assert_eq!(&bytes[33..65], &vault);
+        assert_eq!(bytes[65], vault_bump);
+        assert_eq!(MakeReturnData::from_bytes(&bytes).unwrap(), returned);
+        
+        // anything but exactly LEN bytes is refused
+        assert_eq!(MakeReturnData::from_bytes(&bytes[..MakeReturnData::LEN - 1]).unwrap_err(), ProgramError::InvalidAccountData);
+        assert_eq!(MakeReturnData::from_bytes(&[0u8; MakeReturnData::LEN + 1]).unwrap_err(), ProgramError::InvalidAccountData);
+        assert!(MakeReturnData::from_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn test_make_rejects_missing_signer() {
+        let mocks = MakeMocks {
+            maker: MockAccount::new(MakeMocks::MAKER, [0u8; 32], 10_000_000_000, &[]),
+            ..MakeMocks::new(7)
+        };
+        assert_eq!(mocks.run(7, None).unwrap_err(), ProgramError::MissingRequiredSignature);
+        // nothing was written to the escrow
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_require_signer() {
+        use crate::instructions::require_signer;
+        
+        let signed = MockAccount::new([1u8; 32], [0u8; 32], 1, &[]).signer();
+        assert!(require_signer(&signed.info(), "maker").is_ok());
+        let unsigned = MockAccount::new([1u8; 32], [0u8; 32], 1, &[]);
+        assert_eq!(require_signer(&unsigned.info(), "maker").unwrap_err(), ProgramError::MissingRequiredSignature);
+        
+        // the maker signed, the separate rent payer didn't
+        let mocks = MakeMocks::new(42);
+        let payer = MockAccount::new([16u8; 32], [0u8; 32], 10_000_000_000, &[]);
+        assert_eq!(mocks.run(42, Some(&payer)).unwrap_err(), ProgramError::MissingRequiredSignature);
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+        
+        let infos = account_infos(&[&payer, &mocks.escrow, &mocks.vault, &mocks.maker_ata_a, &mocks.token_program]);
+        assert_eq!(
+            refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 42).unwrap_err(),
+            ProgramError::MissingRequiredSignature
+        );
+    }
+
+    #[test]
+    fn test_amount_precision() {
+        use crate::instructions::make::{check_amount_precision, min_precise_amount, MIN_AMOUNT_PRECISION};
+        
+        // a thousandth of a token by default, one base unit for coarse mints
+        assert_eq!(min_precise_amount(6, MIN_AMOUNT_PRECISION), 1_000);
+        assert_eq!(min_precise_amount(9, MIN_AMOUNT_PRECISION), 1_000_000);
+        assert_eq!(min_precise_amount(2, MIN_AMOUNT_PRECISION), 1);
+        assert_eq!(min_precise_amount(255, 0), u64::MAX);
+        
+        // 1 instead of 1_000_000 for a 6-decimal mint only warns by default
+        assert!(check_amount_precision(1, 6, MIN_AMOUNT_PRECISION, false).is_ok());
+        assert!(check_amount_precision(1_000, 6, MIN_AMOUNT_PRECISION, true).is_ok());
+        assert_eq!(
+            check_amount_precision(999, 6, MIN_AMOUNT_PRECISION, true).unwrap_err(),
+            ProgramError::Custom(EscrowError::AmountBelowPrecision as u32)
+        );
+        assert!(check_amount_precision(1, 0, MIN_AMOUNT_PRECISION, true).is_ok());
+    }
+
+    #[test]
+    fn test_make_rejects_underfunded_maker() {
+        use crate::instructions::make::check_maker_balance;
+        
+        let short = ProgramError::Custom(EscrowError::InsufficientMakerBalance as u32);
+        assert!(check_maker_balance(Some(1000), 1000).is_ok());
+        assert_eq!(check_maker_balance(Some(999), 1000).unwrap_err(), short);
+        // an unreadable balance is left to the other checks
+        assert!(check_maker_balance(None, 1000).is_ok());
+        
+        // Maker ATA A holds one token less than the offer
+        let underfunded = || MockAccount::new([4u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 999));
+        let mocks = MakeMocks { maker_ata_a: underfunded(), ..MakeMocks::new(36) };
+        assert_eq!(mocks.run(36, None).unwrap_err(), short);
+        // nothing was created
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+        
+        // a two-phase make only deposits later, so it doesn't check yet
+        let mocks = MakeMocks { maker_ata_a: underfunded(), ..MakeMocks::new(37) };
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &mocks.config,
+        ]);
+        make_init(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 37, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false).unwrap();
+    }
+
+    #[test]
+    fn test_make_rejects_wrong_programs() {
+        // Token-2022 is accepted, any other token program is not
+        let mocks = MakeMocks {
+            token_program: MockAccount::new([30u8; 32], [0u8; 32], 1, &[]).read_only(),
+            ..MakeMocks::new(7)
+        };
+        assert_eq!(
+            mocks.run(7, None).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidTokenProgram as u32)
+        );
+
+        let mocks = MakeMocks {
+            system_program: MockAccount::new([9u8; 32], [0u8; 32], 1, &[]).read_only(),
+            ..MakeMocks::new(7)
+        };
+        assert_eq!(mocks.run(7, None).unwrap_err(), ProgramError::IncorrectProgramId);
+    }
+
+    #[test]
+    fn test_make_rejects_wrong_addresses() {
+        let invalid_escrow = ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32);
+
+        // accounts derived for seed 7, instruction for seed 8
+        let mocks = MakeMocks::new(7);
+        assert_eq!(mocks.run(8, None).unwrap_err(), invalid_escrow);
+
+        // right escrow, vault that isn't its PDA
+        let mocks = MakeMocks {
+            vault: MockAccount::new([8u8; 32], [0u8; 32], 0, &[]),
+            ..MakeMocks::new(7)
+        };
+        assert_eq!(mocks.run(7, None).unwrap_err(), invalid_escrow);
+    }
+
+    #[test]
+    fn test_make_with_distinct_payer() {
+        let mocks = MakeMocks::new(7);
+
+        // the payer funds the new accounts, so it must sign
+        let unsigned_payer = MockAccount::new([6u8; 32], [0u8; 32], 5_000_000_000, &[]);
+        assert_eq!(
+            mocks.run(7, Some(&unsigned_payer)).unwrap_err(),
+            ProgramError::MissingRequiredSignature
+        );
+
+        let payer = MockAccount::new([6u8; 32], [0u8; 32], 5_000_000_000, &[]).signer();
+        mocks.run(7, Some(&payer)).unwrap();
+
+        // the maker is still the escrow's authority, and make itself never
+        // debits the maker's lamports (rent only moves inside the CPIs)
+        let info = mocks.escrow.info();
+        assert_eq!(Escrow::from_account(&info, &ID).unwrap().maker, MakeMocks::MAKER);
+        assert_eq!(mocks.maker.lamports(), 10_000_000_000);
+    }
+
+    #[test]
+    fn test_validate_account_rejects_small_account() {
+        let short = MockAccount::new([5u8; 32], ID, 1, &Escrow::DISCRIMINATOR);
+        assert_eq!(
+            Escrow::from_account(&short.info(), &ID).err(),
+            Some(ProgramError::AccountDataTooSmall)
+        );
+
+        // right size but owned by someone else
+        let foreign = MockAccount::new([5u8; 32], TOKEN_PROGRAM_ID, 1, &[0u8; Escrow::LEN]);
+        assert_eq!(
+            Escrow::from_account(&foreign.info(), &ID).err(),
+            Some(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn test_from_account_data_lengths() {
+        // a real escrow, then the same bytes in accounts of other sizes
+        let mocks = MakeMocks::new(7);
+        mocks.run(7, None).unwrap();
+        let data = mocks.escrow.data().to_vec();
+        assert_eq!(data.len(), Escrow::LEN);
+        
+        let exact = MockAccount::new([5u8; 32], ID, 1, &data);
+        assert_eq!(Escrow::from_account(&exact.info(), &ID).unwrap().amount, 1000);
+        
+        // one byte short would cast past the end of the account
+        let short = MockAccount::new([5u8; 32], ID, 1, &data[..Escrow::LEN - 1]);
+        assert_eq!(
+            Escrow::from_account(&short.info(), &ID).err(),
+            Some(ProgramError::AccountDataTooSmall)
+        );
+        
+        // longer accounts load, trailing bytes are left for future fields
+        let mut long_data = data.clone();
+        long_data.extend_from_slice(&[0xAB; 32]);
+        let long = MockAccount::new([5u8; 32], ID, 1, &long_data);
+        let escrow = Escrow::from_account(&long.info(), &ID).unwrap();
+        assert_eq!(escrow.amount, 1000);
+        assert_eq!(escrow.memo(), b"mock");
+        
+        // the right length is not enough on its own, the discriminator still has to match
+        let mut wrong = data.clone();
+        wrong[0] ^= 1;
+        let wrong = MockAccount::new([5u8; 32], ID, 1, &wrong);
+        assert_eq!(
+            Escrow::from_account(&wrong.info(), &ID).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_force_close_recovers_half_initialized_escrow() {
+        use crate::programs::SYSTEM_PROGRAM_ID;
+
+        // make initialized the escrow, the vault was never created
+        let mocks = MakeMocks::new(7);
+        mocks.run(7, None).unwrap();
+        let vault = MockAccount::new(*mocks.vault.info().key(), SYSTEM_PROGRAM_ID, 0, &[]);
+
+        let sysvar = top_level_sysvar();
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program, &sysvar]);
+        force_close(&ID, ForceCloseAccounts::from_slice(&infos).unwrap(), 7).unwrap();
+
+        // the escrow is gone and its rent went back to the maker
+        assert_eq!(mocks.maker.lamports(), 10_000_000_000 + MakeMocks::ESCROW_LAMPORTS);
+        assert_eq!(mocks.escrow.lamports(), 0);
+        assert_eq!(mocks.escrow.owner(), SYSTEM_PROGRAM_ID);
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_force_close_funded_token_2022_vault() {
+        use crate::instructions::force_close::AbandonedVault;
+
+        // 1% on mint A, Token-2022 accounts throughout
+        let fee_mint = test_fee_mint_data((0, 1_000_000, 100), (0, 1_000_000, 100));
+        let mocks = MakeMocks {
+            mint_a: MockAccount::new([2u8; 32], TOKEN_2022_PROGRAM_ID, 1_000_000, &fee_mint).read_only(),
+            mint_b: MockAccount::new([3u8; 32], TOKEN_2022_PROGRAM_ID, 1_000_000, &test_mint_data(None)).read_only(),
+            maker_ata_a: MockAccount::new(
+                [4u8; 32],
+                TOKEN_2022_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 5_000),
+            ),
+            token_program: MockAccount::new(TOKEN_2022_PROGRAM_ID, [0u8; 32], 1, &[]).read_only(),
+            maker_ata_b: MockAccount::new(
+                [15u8; 32],
+                TOKEN_2022_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 0),
+            ),
+            ..MakeMocks::new(60)
+        };
+        mocks.run(60, None).unwrap();
+        
+        // part of the deposit reached the vault, owned by Token-2022
+        let vault_data = test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 400);
+        let vault = MockAccount::new(*mocks.vault.info().key(), TOKEN_2022_PROGRAM_ID, 2_000_000, &vault_data);
+        let vault_info = vault.info();
+        assert_eq!(
+            AbandonedVault::classify(false, vault_info.is_owned_by(mocks.token_program.info().key()), &vault_data).unwrap(),
+            AbandonedVault::Token { amount: 400 }
+        );
+        
+        // the mint passed for the transfer has to be the escrow's mint A
+        let top_level = top_level_sysvar();
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program, &top_level, &mocks.mint_b]);
+        assert_eq!(
+            force_close(&ID, ForceCloseAccounts::from_slice(&infos).unwrap(), 60).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidTokenMint as u32)
+        );
+        
+        // the tokens go back with a checked transfer and the escrow closes
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program, &top_level, &mocks.mint_a]);
+        force_close(&ID, ForceCloseAccounts::from_slice(&infos).unwrap(), 60).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_sweep_excess_overfunded_escrow() {
+        use crate::instructions::sweep_excess::excess_lamports;
+        
+        // deliberately overfunded: rent minimum plus a stray 0.5 SOL transfer
+        let minimum = 2_658_720;
+        assert_eq!(excess_lamports(minimum + 500_000_000, minimum), 500_000_000);
+        // exactly rent exempt, or somehow below it: nothing to sweep
+        assert_eq!(excess_lamports(minimum, minimum), 0);
+        assert_eq!(excess_lamports(minimum - 1, minimum), 0);
+        
+        // only the maker may sweep, checked before the rent sysvar is read
+        let mocks = MakeMocks::new(7);
+        mocks.run(7, None).unwrap();
+        let stranger = MockAccount::new([9u8; 32], [0u8; 32], 0, &[]).signer();
+        let infos = account_infos(&[&stranger, &mocks.escrow]);
+        assert_eq!(
+            sweep_excess(&ID, SweepExcessAccounts::from_slice(&infos).unwrap(), 7).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+        let unsigned_maker = MockAccount::new(MakeMocks::MAKER, [0u8; 32], 0, &[]);
+        let infos = account_infos(&[&unsigned_maker, &mocks.escrow]);
+        assert_eq!(
+            sweep_excess(&ID, SweepExcessAccounts::from_slice(&infos).unwrap(), 7).unwrap_err(),
+            ProgramError::MissingRequiredSignature
+        );
+        
+        let packed = pack_instruction_data(&EscrowInstruction::SweepExcess { seed: 7 });
+        assert_eq!(packed[0], SWEEP_EXCESS_DISCRIMINATOR);
+        assert_eq!(EscrowInstruction::unpack(&packed).unwrap(), EscrowInstruction::SweepExcess { seed: 7 });
+    }
+
+    #[test]
+    fn test_take_and_refund_reject_wrong_system_program() {
+        use crate::programs::{verify_system_program, SYSTEM_PROGRAM_ID};
+        
+        assert!(verify_system_program(&SYSTEM_PROGRAM_ID).is_ok());
+        assert_eq!(verify_system_program(&TOKEN_PROGRAM_ID).unwrap_err(), ProgramError::IncorrectProgramId);
+        
+        let mocks = MakeMocks::new(8);
+        mocks.run(8, None).unwrap();
+        let spoofed = MockAccount::new([9u8; 32], [0u8; 32], 1, &[]).read_only();
+        
+        // the system program stays optional for callers that don't pass it
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &mocks.vault, &mocks.maker_ata_a, &mocks.token_program]);
+        assert!(RefundAccounts::from_slice(&infos).unwrap().system_program.is_none());
+        
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.maker_ata_a,
+            &mocks.token_program,
+            &spoofed,
+        ]);
+        assert_eq!(
+            refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 8).unwrap_err(),
+            ProgramError::IncorrectProgramId
+        );
+        
+        // the maker stands in for the taker, the check runs before any escrow state is read
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.maker_ata_a,
+            &mocks.maker_ata_a,
+            &mocks.token_program,
+            &mocks.config,
+            &mocks.maker_ata_a,
+            &spoofed,
+        ]);
+        assert_eq!(
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 8, 2000, 2000).unwrap_err(),
+            ProgramError::IncorrectProgramId
+        );
+    }
+
+    #[test]
+    fn test_frozen_token_accounts_rejected() {
+        use crate::token::check_accounts_not_frozen;
+        
+        let frozen = ProgramError::Custom(EscrowError::AccountFrozen as u32);
+        let frozen_data = |mint: &Pubkey, owner: &Pubkey, amount: u64| {
+            let mut data = test_token_account_data(mint, owner, amount);
+            data[108] = 2; // frozen
+            data
+        };
+        
+        // only a frozen token account trips the check, anything unparsable is
+        // left to the other validations
+        let open = MockAccount::new([20u8; 32], TOKEN_PROGRAM_ID, 1, &test_token_account_data(&[2u8; 32], &[1u8; 32], 5));
+        let locked = MockAccount::new([21u8; 32], TOKEN_PROGRAM_ID, 1, &frozen_data(&[2u8; 32], &[1u8; 32], 5));
+        let empty = MockAccount::new([22u8; 32], [0u8; 32], 1, &[]);
+        assert!(check_accounts_not_frozen(&[&open.info(), &empty.info()]).is_ok());
+        assert_eq!(check_accounts_not_frozen(&[&open.info(), &locked.info()]).unwrap_err(), frozen);
+        
+        // make: the maker's token A account is frozen
+        let mut mocks = MakeMocks::new(18);
+        mocks.maker_ata_a = MockAccount::new([4u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &frozen_data(&[2u8; 32], &MakeMocks::MAKER, 5_000));
+        assert_eq!(mocks.run(18, None).unwrap_err(), frozen);
+        
+        // take: the taker's token B account is frozen, caught before any transfer
+        let mocks = MakeMocks::new(19);
+        mocks.run(19, None).unwrap();
+        let funded_vault = MockAccount::new(*mocks.vault.info().key(), TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000));
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &frozen_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &mocks.escrow,
+            &funded_vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &mocks.maker_ata_b,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        assert_eq!(
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 19, 2000, 2000).unwrap_err(),
+            frozen
+        );
+    }
+
+    #[test]
+    fn test_take_and_refund_check_vault_authority() {
+        let wrong_authority = ProgramError::Custom(EscrowError::InvalidAuthority as u32);
+        // a funded vault whose authority is `authority`
+        let vault_of = |mocks: &MakeMocks, authority: &Pubkey| {
+            MockAccount::new(
+                *mocks.vault.info().key(),
+                TOKEN_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[2u8; 32], authority, 1000),
+            )
+        };
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        
+        // take: a vault the escrow can't sign for
+        let mocks = MakeMocks::new(29);
+        mocks.run(29, None).unwrap();
+        let vault = vault_of(&mocks, &ID);
+        // the maker stands in for the taker
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &mocks.escrow,
+            &vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &mocks.maker_ata_b,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        assert_eq!(
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 29, 2000, 2000).unwrap_err(),
+            wrong_authority
+        );
+        
+        // refund: the same, with the maker as the vault's authority
+        let mocks = MakeMocks::new(30);
+        mocks.run(30, None).unwrap();
+        let vault = vault_of(&mocks, &MakeMocks::MAKER);
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program]);
+        assert_eq!(
+            refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 30).unwrap_err(),
+            wrong_authority
+        );
+        
+        // both were rejected before the escrow was touched
+        let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+        assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+    }
+
+    #[test]
+    fn test_refund_without_vault() {
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        use crate::instructions::refund::vault_is_missing;
+        
+        // make created the escrow, the vault was never created
+        let mocks = MakeMocks::new(31);
+        mocks.run(31, None).unwrap();
+        let vault = MockAccount::new(*mocks.vault.info().key(), SYSTEM_PROGRAM_ID, 0, &[]);
+        assert!(vault_is_missing(&vault.info()));
+        
+        // the escrow still wants its amount named
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program]);
+        assert_eq!(
+            refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 999, 31).unwrap_err(),
+            ProgramError::Custom(EscrowError::ExpectedAmountMismatch as u32)
+        );
+        
+        let mocks = MakeMocks::new(31);
+        mocks.run(31, None).unwrap();
+        let escrow_rent = mocks.escrow.lamports();
+        let maker_before = mocks.maker.lamports();
+        let vault = MockAccount::new(*mocks.vault.info().key(), SYSTEM_PROGRAM_ID, 0, &[]);
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program]);
+        refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 31).unwrap();
+        
+        // only the escrow is closed, its rent back with the maker
+        assert_eq!(mocks.escrow.lamports(), 0);
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+        assert_eq!(mocks.maker.lamports(), maker_before + escrow_rent);
+        
+        // an empty account that isn't the escrow's vault can't stand in for it
+        let mocks = MakeMocks::new(32);
+        mocks.run(32, None).unwrap();
+        let stand_in = MockAccount::new([41u8; 32], SYSTEM_PROGRAM_ID, 0, &[]);
+        assert!(vault_is_missing(&stand_in.info()));
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &stand_in, &mocks.maker_ata_a, &mocks.token_program]);
+        assert_eq!(
+            refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 32).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32)
+        );
+        
+        // a funded vault is never missing
+        let funded = MockAccount::new(
+            *mocks.vault.info().key(),
+            TOKEN_PROGRAM_ID,
+            2_000_000,
+            &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000),
+        );
+        assert!(!vault_is_missing(&funded.info()));
+    }
+
+    #[test]
+    fn test_take_and_refund_reject_drained_escrow() {
+        let closed = ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32);
+        
+        // an earlier close moved the lamports out but left the data behind
+        let mocks = MakeMocks::new(35);
+        mocks.run(35, None).unwrap();
+        let drained = MockAccount::new(*mocks.escrow.info().key(), ID, 0, mocks.escrow.data());
+        assert!(Escrow::check_live(&mocks.escrow.info()).is_ok());
+        assert_eq!(Escrow::check_live(&drained.info()).unwrap_err(), closed);
+        
+        let vault = MockAccount::new(*mocks.vault.info().key(), TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000));
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &drained,
+            &vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &mocks.maker_ata_b,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        assert_eq!(take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 35, 2000, 2000).unwrap_err(), closed);
+        
+        let infos = account_infos(&[&mocks.maker, &drained, &vault, &mocks.maker_ata_a, &mocks.token_program]);
+        assert_eq!(refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 35).unwrap_err(), closed);
+        
+        // the drained escrow was never moved into processing
+        let escrow = Escrow::from_account(&drained.info(), &ID).unwrap();
+        assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+    }
+
+    #[test]
+    fn test_take_then_claim_receive() {
+        use crate::instructions::make::find_receive_vault_address;
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        let mismatch = ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32);
+        let (receive_key, _) = find_receive_vault_address(&MakeMocks::MAKER, 33, &ID);
+        
+        // the flag is a strict bool on the wire
+        let mut packed = pack_instruction_data(&EscrowInstruction::Make {
+            amount: 1,
+            seed: 2,
+            expiry: 0,
+            kind: EscrowKind::Token,
+            receive_amount: 3,
+            memo: Vec::new(),
+            namespace: [0u8; 8],
+            price_num: 0,
+            price_den: 0,
+            min_fill: 0,
+            persistent: false,
+            treasury: true,
+        });
+        assert_eq!(packed[MAKE_TREASURY_OFFSET], 1);
+        packed[MAKE_TREASURY_OFFSET] = 2;
+        assert_eq!(
+            EscrowInstruction::unpack(&packed).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidInstruction as u32)
+        );
+        
+        // without the treasury flag Maker ATA B has to be the maker's own
+        // mint B account, the receive vault is refused
+        let wrong_mint = ProgramError::Custom(EscrowError::InvalidTokenMint as u32);
+        let mut mocks = MakeMocks::new(33);
+        mocks.maker_ata_b = MockAccount::new(receive_key, SYSTEM_PROGRAM_ID, 0, &[]);
+        assert_eq!(mocks.run(33, None).unwrap_err(), wrong_mint);
+        
+        // and with it, the maker's own account is no receive vault
+        let ata = MakeMocks::new(35);
+        assert_eq!(ata.run_with_treasury(35, None, true).unwrap_err(), wrong_mint);
+        
+        // make with the maker's (not yet created) receive vault as Maker ATA B
+        mocks.run_with_treasury(33, None, true).unwrap();
+        let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+        assert_eq!(escrow.receive_account, receive_key);
+        
+        // any other account is refused
+        let mut other = MakeMocks::new(34);
+        other.maker_ata_b = MockAccount::new([16u8; 32], SYSTEM_PROGRAM_ID, 0, &[]);
+        assert_eq!(other.run_with_treasury(34, None, true).unwrap_err(), mismatch);
+        
+        // take pays token B into the receive vault, which owns itself
+        let receive_vault = MockAccount::new(receive_key, TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &receive_key, 0));
+        let funded_vault = MockAccount::new(*mocks.vault.info().key(), TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000));
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &mocks.escrow,
+            &funded_vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &receive_vault,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 33, 2000, 2000).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+        
+        // then the maker claims it into any of their mint B accounts
+        let paid = MockAccount::new(receive_key, TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &receive_key, 2000));
+        let maker_ata_b = MockAccount::new([15u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 0));
+        let infos = account_infos(&[&mocks.maker, &paid, &maker_ata_b, &mocks.token_program]);
+        assert_eq!(claim_receive(&ID, ClaimReceiveAccounts::from_slice(&infos).unwrap(), 34).unwrap_err(), mismatch);
+        claim_receive(&ID, ClaimReceiveAccounts::from_slice(&infos).unwrap(), 33).unwrap();
+        
+        // nobody else can sign for the maker's receive vault
+        let stranger = MockAccount::new([9u8; 32], SYSTEM_PROGRAM_ID, 1_000_000, &[]).signer();
+        let stranger_ata_b = MockAccount::new([17u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &[9u8; 32], 0));
+        let infos = account_infos(&[&stranger, &paid, &stranger_ata_b, &mocks.token_program]);
+        assert_eq!(claim_receive(&ID, ClaimReceiveAccounts::from_slice(&infos).unwrap(), 33).unwrap_err(), mismatch);
+        
+        // and the claim goes to the maker's own account only
+        let infos = account_infos(&[&mocks.maker, &paid, &stranger_ata_b, &mocks.token_program]);
+        assert_eq!(
+            claim_receive(&ID, ClaimReceiveAccounts::from_slice(&infos).unwrap(), 33).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+    }
+
+    // a made escrow handed to arbiter [30; 32] until 5_000, with its funded vault
+    fn arbitrated_escrow(seed: u64) -> (MakeMocks, MockAccount) {
+        let mocks = MakeMocks::new(seed);
+        mocks.run(seed, None).unwrap();
+        {
+            let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+            escrow.arbiter = [30u8; 32];
+            escrow.expiry = 5_000;
+        }
+        let vault = MockAccount::new(*mocks.vault.info().key(), TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000));
+        (mocks, vault)
+    }
+
+    #[test]
+    fn test_arbiter_releases_to_taker() {
+        use crate::instructions::arbiter_resolve::arbiter_resolve_at;
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        let (mocks, vault) = arbitrated_escrow(38);
+        let arbiter = MockAccount::new([30u8; 32], SYSTEM_PROGRAM_ID, 1_000_000, &[]).signer();
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &[12u8; 32], 0));
+        let sysvar = top_level_sysvar();
+        let resolve = |signer: &MockAccount, now: i64| {
+            let infos = account_infos(&[signer, &mocks.maker, &mocks.escrow, &vault, &taker_ata_a, &mocks.token_program, &sysvar]);
+            arbiter_resolve_at(&ID, ArbiterResolveAccounts::from_slice(&infos).unwrap(), 38, true, now)
+        };
+        
+        // neither the maker nor anyone else can stand in for the arbiter
+        let maker = MockAccount::new(MakeMocks::MAKER, SYSTEM_PROGRAM_ID, 1_000_000, &[]).signer();
+        let stranger = MockAccount::new([9u8; 32], SYSTEM_PROGRAM_ID, 1_000_000, &[]).signer();
+        for signer in [&maker, &stranger] {
+            assert_eq!(resolve(signer, 1_000).unwrap_err(), ProgramError::Custom(EscrowError::InvalidAuthority as u32));
+        }
+        let unsigned = MockAccount::new([30u8; 32], SYSTEM_PROGRAM_ID, 1_000_000, &[]);
+        assert_eq!(resolve(&unsigned, 1_000).unwrap_err(), ProgramError::MissingRequiredSignature);
+        
+        // the dispute window closes at expiry
+        assert_eq!(resolve(&arbiter, 5_001).unwrap_err(), ProgramError::Custom(EscrowError::EscrowExpired as u32));
+        
+        // the arbiter can't release the vault to its own account
+        let arbiter_ata_a = MockAccount::new([16u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &[30u8; 32], 0));
+        let infos = account_infos(&[&arbiter, &mocks.maker, &mocks.escrow, &vault, &arbiter_ata_a, &mocks.token_program, &sysvar]);
+        assert_eq!(
+            arbiter_resolve_at(&ID, ArbiterResolveAccounts::from_slice(&infos).unwrap(), 38, true, 1_000).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+        
+        // the vault goes to the taker's account and the rent to the maker
+        let maker_lamports = mocks.maker.lamports();
+        resolve(&arbiter, 1_000).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+        assert_eq!(mocks.maker.lamports(), maker_lamports + MakeMocks::ESCROW_LAMPORTS);
+        
+        // an escrow made without an arbiter can't be resolved
+        let plain = MakeMocks::new(39);
+        plain.run(39, None).unwrap();
+        let plain_vault = MockAccount::new(*plain.vault.info().key(), TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], plain.escrow.info().key(), 1000));
+        let infos = account_infos(&[&arbiter, &plain.maker, &plain.escrow, &plain_vault, &taker_ata_a, &plain.token_program, &sysvar]);
+        assert_eq!(
+            arbiter_resolve_at(&ID, ArbiterResolveAccounts::from_slice(&infos).unwrap(), 39, true, 1_000).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+        
+        let packed = pack_instruction_data(&EscrowInstruction::ArbiterResolve { seed: 38, to_taker: true });
+        assert_eq!((packed[0], packed[ARBITER_RESOLVE_TO_TAKER_OFFSET]), (ARBITER_RESOLVE_DISCRIMINATOR, 1));
+        let mut bad_flag = packed.clone();
+        bad_flag[ARBITER_RESOLVE_TO_TAKER_OFFSET] = 2;
+        assert_eq!(EscrowInstruction::unpack(&bad_flag).unwrap_err(), ProgramError::Custom(EscrowError::InvalidInstruction as u32));
+    }
+
+    // instructions sysvar data for a transaction of account-less, data-less
+    // instructions to `programs`, executing the one at `current`
+    fn instructions_sysvar_data(programs: &[Pubkey], current: u16) -> Vec<u8> {
+        let mut data = (programs.len() as u16).to_le_bytes().to_vec();
+        let first = 2 + 2 * programs.len();
+        for i in 0..programs.len() {
+            data.extend_from_slice(&((first + i * 36) as u16).to_le_bytes());
+        }
+        for program in programs {
+            data.extend_from_slice(&0u16.to_le_bytes());
+            data.extend_from_slice(program);
+            data.extend_from_slice(&0u16.to_le_bytes());
+        }
+        data.extend_from_slice(&current.to_le_bytes());
+        data
+    }
+
+    // the instructions sysvar of a transaction that calls this program directly
+    fn top_level_sysvar() -> MockAccount {
+        use crate::sysvar::INSTRUCTIONS_SYSVAR_ID;
+
+        MockAccount::new(INSTRUCTIONS_SYSVAR_ID, [0u8; 32], 1, &instructions_sysvar_data(&[ID], 0)).read_only()
+    }
+
+    #[test]
+    fn test_cpi_guard() {
+        use crate::sysvar::{check_not_cpi, current_top_level_program, is_cpi, INSTRUCTIONS_SYSVAR_ID};
+        
+        let other_program = [77u8; 32];
+        let data = instructions_sysvar_data(&[other_program, ID], 0);
+        assert_eq!(current_top_level_program(&data).unwrap(), other_program);
+        assert_eq!(current_top_level_program(&instructions_sysvar_data(&[other_program, ID], 1)).unwrap(), ID);
+        assert!(current_top_level_program(&instructions_sysvar_data(&[ID], 1)).is_err());
+        assert!(current_top_level_program(&data[..5]).is_err());
+        
+        // called directly, or from inside another program's instruction
+        let top_level = MockAccount::new(INSTRUCTIONS_SYSVAR_ID, [0u8; 32], 1, &instructions_sysvar_data(&[other_program, ID], 1)).read_only();
+        let via_cpi = MockAccount::new(INSTRUCTIONS_SYSVAR_ID, [0u8; 32], 1, &data).read_only();
+        assert!(!is_cpi(&top_level.info(), &ID).unwrap());
+        assert!(is_cpi(&via_cpi.info(), &ID).unwrap());
+        assert!(check_not_cpi(&top_level.info(), &ID).is_ok());
+        // checked against the id the program runs under, not the declared one
+        assert!(!is_cpi(&via_cpi.info(), &other_program).unwrap());
+        
+        // any other account can't stand in for the sysvar
+        let spoofed = MockAccount::new([78u8; 32], [0u8; 32], 1, &instructions_sysvar_data(&[ID], 0)).read_only();
+        assert_eq!(is_cpi(&spoofed.info(), &ID).unwrap_err(), ProgramError::UnsupportedSysvar);
+        
+        // ForceClose needs the sysvar, and driven by another program is
+        // rejected before anything closes
+        let mocks = MakeMocks::new(43);
+        mocks.run(43, None).unwrap();
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &mocks.vault, &mocks.maker_ata_a, &mocks.token_program]);
+        assert_eq!(ForceCloseAccounts::from_slice(&infos).err(), Some(ProgramError::NotEnoughAccountKeys));
+        assert_eq!(ArbiterResolveAccounts::from_slice(&infos).err(), Some(ProgramError::NotEnoughAccountKeys));
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &mocks.vault, &mocks.maker_ata_a, &mocks.token_program, &via_cpi]);
+        assert_eq!(
+            force_close(&ID, ForceCloseAccounts::from_slice(&infos).unwrap(), 43).unwrap_err(),
+            ProgramError::Custom(EscrowError::CpiNotAllowed as u32)
+        );
+        assert_ne!(mocks.escrow.lamports(), 0);
+        
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &mocks.vault, &mocks.maker_ata_a, &mocks.token_program, &top_level]);
+        force_close(&ID, ForceCloseAccounts::from_slice(&infos).unwrap(), 43).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+    }
+
+    #[test]
+    fn test_arbiter_refunds_to_maker() {
+        use crate::instructions::arbiter_resolve::arbiter_resolve_at;
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        let held = ProgramError::Custom(EscrowError::InvalidAuthority as u32);
+        let (mocks, vault) = arbitrated_escrow(40);
+        let arbiter = MockAccount::new([30u8; 32], SYSTEM_PROGRAM_ID, 1_000_000, &[]).signer();
+        let sysvar = top_level_sysvar();
+        
+        // the maker can't refund or force close while the escrow is held (the
+        // host clock reads 0, before the expiry)
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program]);
+        assert_eq!(refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 40).unwrap_err(), held);
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program, &sysvar]);
+        assert_eq!(force_close(&ID, ForceCloseAccounts::from_slice(&infos).unwrap(), 40).unwrap_err(), held);
+        
+        // a refund goes to a mint A account of the maker only
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &[12u8; 32], 0));
+        let infos = account_infos(&[&arbiter, &mocks.maker, &mocks.escrow, &vault, &taker_ata_a, &mocks.token_program, &sysvar]);
+        assert_eq!(arbiter_resolve_at(&ID, ArbiterResolveAccounts::from_slice(&infos).unwrap(), 40, false, 1_000).unwrap_err(), held);
+        
+        let infos = account_infos(&[&arbiter, &mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program, &sysvar]);
+        arbiter_resolve_at(&ID, ArbiterResolveAccounts::from_slice(&infos).unwrap(), 40, false, 1_000).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+    }
+
+    #[test]
+    fn test_make_checks_arbiter() {
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        let run_with = |mocks: &MakeMocks, arbiter: &MockAccount, expiry: i64| {
+            let infos = account_infos(&[
+                &mocks.maker,
+                &mocks.mint_a,
+                &mocks.mint_b,
+                &mocks.maker_ata_a,
+                &mocks.escrow,
+                &mocks.vault,
+                &mocks.token_program,
+                &mocks.system_program,
+                &mocks.maker_ata_b,
+                &mocks.config,
+                &mocks.maker,
+                arbiter,
+            ]);
+            let accounts = MakeAccounts::from_slice(&infos)?;
+            make(&ID, accounts, 1000, 41, expiry, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false)
+        };
+        
+        // an arbiter without an expiry would hold the escrow forever
+        let mocks = MakeMocks::new(41);
+        let arbiter = MockAccount::new([30u8; 32], SYSTEM_PROGRAM_ID, 0, &[]).read_only();
+        assert_eq!(run_with(&mocks, &arbiter, 0).unwrap_err(), ProgramError::Custom(EscrowError::InvalidExpiry as u32));
+        
+        // and the maker can't arbitrate their own offer
+        let maker = MockAccount::new(MakeMocks::MAKER, SYSTEM_PROGRAM_ID, 0, &[]).read_only();
+        assert_eq!(run_with(&mocks, &maker, 5_000).unwrap_err(), ProgramError::Custom(EscrowError::InvalidAuthority as u32));
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_invalid_signer_checked_before_pdas() {
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        let unsigned = || MockAccount::new(MakeMocks::MAKER, SYSTEM_PROGRAM_ID, 10_000_000_000, &[]);
+        
+        // make under the wrong seed fails the escrow PDA check, and without
+        // the maker's signature it fails on the signer first
+        let mocks = MakeMocks::new(23);
+        assert_eq!(mocks.run(99, None).unwrap_err(), ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32));
+        let mut mocks = MakeMocks::new(24);
+        mocks.maker = unsigned();
+        assert_eq!(mocks.run(99, None).unwrap_err(), ProgramError::MissingRequiredSignature);
+        
+        // take and refund of a live escrow against a vault that isn't its PDA.
+        // A failed PDA check leaves the escrow mid-processing, so each signed
+        // attempt gets its own escrow
+        let live = |seed: u64| {
+            let mocks = MakeMocks::new(seed);
+            mocks.run(seed, None).unwrap();
+            let wrong_vault = MockAccount::new([16u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000));
+            (mocks, wrong_vault)
+        };
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let invalid_escrow = ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32);
+        
+        let (mocks, wrong_vault) = live(25);
+        let take_with = |taker: &MockAccount| {
+            let infos = account_infos(&[
+                taker,
+                &mocks.maker,
+                &mocks.escrow,
+                &wrong_vault,
+                &mocks.mint_a,
+                &mocks.mint_b,
+                &taker_ata_a,
+                &taker_ata_b,
+                &mocks.maker_ata_b,
+                &mocks.token_program,
+                &mocks.config,
+            ]);
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 25, 2000, 2000)
+        };
+        assert_eq!(take_with(&unsigned()).unwrap_err(), ProgramError::MissingRequiredSignature);
+        assert_eq!(take_with(&mocks.maker).unwrap_err(), invalid_escrow);
+        
+        let (mocks, wrong_vault) = live(26);
+        let refund_with = |maker: &MockAccount| {
+            let infos = account_infos(&[maker, &mocks.escrow, &wrong_vault, &mocks.maker_ata_a, &mocks.token_program]);
+            refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 26)
+        };
+        assert_eq!(refund_with(&unsigned()).unwrap_err(), ProgramError::MissingRequiredSignature);
+        assert_eq!(refund_with(&mocks.maker).unwrap_err(), invalid_escrow);
+    }
+
+    #[test]
+    fn test_take_writes_receipt() {
+        use crate::{instructions::take::find_receipt_address, programs::ASSOCIATED_TOKEN_PROGRAM_ID};
+        
+        let setup = |seed: u64| {
+            let mocks = MakeMocks::new(seed);
+            mocks.run(seed, None).unwrap();
+            let vault = MockAccount::new(
+                *mocks.vault.info().key(),
+                TOKEN_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000),
+            );
+            (mocks, vault)
+        };
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        // the receipt follows the associated token program, a no-op while Maker ATA B exists
+        let associated_token_program = MockAccount::new(ASSOCIATED_TOKEN_PROGRAM_ID, [0u8; 32], 1, &[]).read_only();
+        let take_infos = |mocks: &MakeMocks, vault: &MockAccount, receipt: &MockAccount| {
+            // the maker stands in for the taker
+            account_infos(&[
+                &mocks.maker,
+                &mocks.maker,
+                &mocks.escrow,
+                vault,
+                &mocks.mint_a,
+                &mocks.mint_b,
+                &taker_ata_a,
+                &taker_ata_b,
+                &mocks.maker_ata_b,
+                &mocks.token_program,
+                &mocks.config,
+                &taker_ata_a,
+                &mocks.system_program,
+                &associated_token_program,
+                receipt,
+            ])
+        };
+        
+        // the receipt has to be this take's PDA
+        let (mocks, vault) = setup(21);
+        let other = MockAccount::new([22u8; 32], ID, 2_000_000, &[0u8; TakeReceipt::LEN]);
+        let infos = take_infos(&mocks, &vault, &other);
+        assert_eq!(
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 21, 2000, 2000).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32)
+        );
+        
+        let (mocks, vault) = setup(22);
+        let escrow_key = *mocks.escrow.info().key();
+        // nothing filled yet, and host builds have no clock
+        let (receipt_key, receipt_bump) = find_receipt_address(&escrow_key, &MakeMocks::MAKER, 0, 0, &ID);
+        let receipt = MockAccount::new(receipt_key, ID, 2_000_000, &[0u8; TakeReceipt::LEN]);
+        let infos = take_infos(&mocks, &vault, &receipt);
+        take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 22, 2000, 2000).unwrap();
+        
+        // the escrow is gone, the receipt stays with the terms of the take
+        assert_eq!(mocks.escrow.lamports(), 0);
+        let info = receipt.info();
+        let written = TakeReceipt::from_account(&info, &ID).unwrap();
+        assert_eq!(written.escrow, escrow_key);
+        assert_eq!(written.maker, MakeMocks::MAKER);
+        assert_eq!(written.taker, MakeMocks::MAKER);
+        assert_eq!(written.mint_a, [2u8; 32]);
+        assert_eq!(written.mint_b, [3u8; 32]);
+        assert_eq!(written.amount, 1000);
+        assert_eq!(written.receive_amount, 2000);
+        // host builds have no clock
+        assert_eq!(written.timestamp, 0);
+        assert_eq!(written.bump, receipt_bump);
+        
+        // a later take of a refilled or re-made escrow gets its own receipt
+        assert_ne!(find_receipt_address(&escrow_key, &MakeMocks::MAKER, 0, 1000, &ID).0, receipt_key);
+        assert_ne!(find_receipt_address(&escrow_key, &MakeMocks::MAKER, 1, 0, &ID).0, receipt_key);
+    }
+
+    #[test]
+    fn test_transfer_fee() {
+        use crate::token::{mint_transfer_fee, TransferFee};
+        
+        // 1% capped at 50
+        let fee = TransferFee { epoch: 0, maximum_fee: 50, basis_points: 100 };
+        assert_eq!(fee.fee(1000).unwrap(), 10);
+        // rounded up, and capped
+        assert_eq!(fee.fee(1).unwrap(), 1);
+        assert_eq!(fee.fee(10_000).unwrap(), 50);
+        assert_eq!(fee.fee(0).unwrap(), 0);
+        assert_eq!(fee.net_amount(1000).unwrap(), 990);
+        
+        // the smallest gross amount that still delivers the net
+        assert_eq!(fee.gross_amount(990).unwrap(), 1000);
+        assert_eq!(fee.gross_amount(10_000).unwrap(), 10_050);
+        assert_eq!(fee.gross_amount(0).unwrap(), 0);
+        for net in 1..3000 {
+            let gross = fee.gross_amount(net).unwrap();
+            assert!(fee.net_amount(gross).unwrap() >= net);
+            assert!(fee.net_amount(gross - 1).unwrap() < net);
+        }
+        
+        // a zero rate never withholds anything
+        let free = TransferFee { epoch: 0, maximum_fee: 50, basis_points: 0 };
+        assert_eq!(free.fee(1000).unwrap(), 0);
+        assert_eq!(free.gross_amount(1000).unwrap(), 1000);
+        
+        // the newer fee takes over from its epoch on
+        let mint = test_fee_mint_data((0, 50, 100), (5, 80, 200));
+        let older = mint_transfer_fee(&TOKEN_2022_PROGRAM_ID, &mint, 4).unwrap().unwrap();
+        assert_eq!(older, TransferFee { epoch: 0, maximum_fee: 50, basis_points: 100 });
+        let newer = mint_transfer_fee(&TOKEN_2022_PROGRAM_ID, &mint, 5).unwrap().unwrap();
+        assert_eq!(newer, TransferFee { epoch: 5, maximum_fee: 80, basis_points: 200 });
+        
+        // legacy mints and Token-2022 mints without the extension charge nothing
+        assert_eq!(mint_transfer_fee(&TOKEN_PROGRAM_ID, &mint, 5).unwrap(), None);
+        assert_eq!(mint_transfer_fee(&TOKEN_2022_PROGRAM_ID, &test_mint_data(None), 5).unwrap(), None);
+        
+        // a config cut short is a malformed mint
+        assert_eq!(
+            mint_transfer_fee(&TOKEN_2022_PROGRAM_ID, &mint[..mint.len() - 1], 5).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidTokenMint as u32)
+        );
+    }
+
+    #[test]
+    fn test_take_with_transfer_fee_mint() {
+        // 1% on mint A, Token-2022 accounts throughout
+        let fee_mint = test_fee_mint_data((0, 1_000_000, 100), (0, 1_000_000, 100));
+        let setup = |seed: u64| MakeMocks {
+            mint_a: MockAccount::new([2u8; 32], TOKEN_2022_PROGRAM_ID, 1_000_000, &fee_mint).read_only(),
+            mint_b: MockAccount::new([3u8; 32], TOKEN_2022_PROGRAM_ID, 1_000_000, &test_mint_data(None)).read_only(),
+            maker_ata_a: MockAccount::new(
+                [4u8; 32],
+                TOKEN_2022_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 5_000),
+            ),
+            token_program: MockAccount::new(TOKEN_2022_PROGRAM_ID, [0u8; 32], 1, &[]).read_only(),
+            maker_ata_b: MockAccount::new(
+                [15u8; 32],
+                TOKEN_2022_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 0),
+            ),
+            ..MakeMocks::new(seed)
+        };
+        
+        // 1000 deposited, 10 withheld: the escrow offers what reached the vault
+        let mocks = setup(26);
+        mocks.run(26, None).unwrap();
+        let info = mocks.escrow.info();
+        let escrow = Escrow::from_account(&info, &ID).unwrap();
+        assert_eq!(escrow.amount, 990);
+        assert_eq!(escrow.receive_amount, 2000);
+        
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_2022_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_2022_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let take_with_vault = |seed: u64, vault_balance: u64| {
+            let mocks = setup(seed);
+            mocks.run(seed, None).unwrap();
+            let vault = MockAccount::new(
+                *mocks.vault.info().key(),
+                TOKEN_2022_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), vault_balance),
+            );
+            // the maker stands in for the taker
+            let infos = account_infos(&[
+                &mocks.maker,
+                &mocks.maker,
+                &mocks.escrow,
+                &vault,
+                &mocks.mint_a,
+                &mocks.mint_b,
+                &taker_ata_a,
+                &taker_ata_b,
+                &mocks.maker_ata_b,
+                &mocks.token_program,
+                &mocks.config,
+            ]);
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 990, seed, 2000, 2000)
+        };
+        
+        // a vault holding the requested 1000 minus the fee covers the offer
+        take_with_vault(27, 990).unwrap();
+        // one token short of the net deposit is not enough
+        assert_eq!(
+            take_with_vault(28, 989).unwrap_err(),
+            ProgramError::Custom(EscrowError::InsufficientVaultBalance as u32)
+        );
+    }
+
+    #[test]
+    fn test_take_rejects_unacknowledged_terms() {
+        let mocks = MakeMocks::new(16);
+        mocks.run(16, None).unwrap();
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let take_with = |amount: u64, receive_amount: u64| {
+            // the maker stands in for the taker
+            let infos = account_infos(&[
+                &mocks.maker,
+                &mocks.maker,
+                &mocks.escrow,
+                &mocks.vault,
+                &mocks.mint_a,
+                &mocks.mint_b,
+                &taker_ata_a,
+                &taker_ata_b,
+                &mocks.maker_ata_b,
+                &mocks.token_program,
+                &mocks.config,
+            ]);
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), amount, 16, 5000, receive_amount).unwrap_err()
+        };
+        let mismatch = ProgramError::Custom(EscrowError::ExpectedAmountMismatch as u32);
+        
+        // the escrow offers 1000 token A for 2000 token B
+        assert_eq!(take_with(999, 2000), mismatch);
+        assert_eq!(take_with(1000, 1999), mismatch);
+        // within max_in but not the price the taker acknowledged
+        assert_eq!(take_with(1000, 2001), mismatch);
+        
+        // the rejected takes left the escrow untouched
+        let info = mocks.escrow.info();
+        let escrow = Escrow::from_account(&info, &ID).unwrap();
+        assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+        assert_eq!(escrow.amount, 1000);
+        assert_eq!(escrow.receive_amount, 2000);
+    }
+
+    #[test]
+    fn test_take_rejects_closed_receive_account() {
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        let mocks = MakeMocks::new(9);
+        mocks.run(9, None).unwrap();
+        let receive_key = *mocks.maker_ata_b.info().key();
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let take_with = |receive: &MockAccount| {
+            // the maker stands in for the taker
+            let infos = account_infos(&[
+                &mocks.maker,
+                &mocks.maker,
+                &mocks.escrow,
+                &mocks.vault,
+                &mocks.mint_a,
+                &mocks.mint_b,
+                &taker_ata_a,
+                &taker_ata_b,
+                receive,
+                &mocks.token_program,
+                &mocks.config,
+            ]);
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 9, 2000, 2000).unwrap_err()
+        };
+        
+        // closed after the offer was made: zeroed and handed back to the system program
+        let closed = MockAccount::new(receive_key, SYSTEM_PROGRAM_ID, 0, &[]);
+        assert_eq!(take_with(&closed), ProgramError::Custom(EscrowError::InvalidState as u32));
+        
+        // closed and then recreated for another mint, or for someone else
+        let wrong_mint = MockAccount::new(
+            receive_key,
+            TOKEN_PROGRAM_ID,
+            2_000_000,
+            &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0),
+        );
+        assert_eq!(take_with(&wrong_mint), ProgramError::Custom(EscrowError::InvalidState as u32));
+        let wrong_owner = MockAccount::new(
+            receive_key,
+            TOKEN_PROGRAM_ID,
+            2_000_000,
+            &test_token_account_data(&[3u8; 32], &[8u8; 32], 0),
+        );
+        assert_eq!(take_with(&wrong_owner), ProgramError::Custom(EscrowError::InvalidState as u32));
+        
+        // a live mint B account of the maker gets past the check
+        let open = MockAccount::new(
+            receive_key,
+            TOKEN_PROGRAM_ID,
+            2_000_000,
+            &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 0),
+        );
+        assert_ne!(take_with(&open), ProgramError::Custom(EscrowError::InvalidState as u32));
+    }
+
+    #[test]
+    fn test_migrate_escrow_from_old_layouts() {
+        use crate::instructions::make::find_vault_address;
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        // the version byte has a fixed place in the layout
+        assert_eq!(core::mem::offset_of!(Escrow, version), Escrow::VERSION_OFFSET);
+        assert_eq!(Escrow::VERSION_OFFSET, Escrow::V1_LEN);
+        
+        // the original layout: discriminator, maker, mints, receive account, amount, bump
+        let mut v0 = Escrow::DISCRIMINATOR.to_vec();
+        for key in [MakeMocks::MAKER, [2u8; 32], [3u8; 32], [4u8; 32]] {
+            v0.extend_from_slice(&key);
+        }
+        v0.extend_from_slice(&1000u64.to_le_bytes());
+        v0.push(250);
+        assert_eq!(v0.len(), Escrow::V0_LEN);
+        assert_eq!(Escrow::layout_version(&v0).unwrap(), 0);
+        
+        let escrow_key = [20u8; 32];
+        let escrow_account = MockAccount::new(escrow_key, ID, MakeMocks::ESCROW_LAMPORTS, &v0);
+        let maker = MockAccount::new(MakeMocks::MAKER, SYSTEM_PROGRAM_ID, 10_000_000_000, &[]).signer();
+        let system_program = MockAccount::new(SYSTEM_PROGRAM_ID, [0u8; 32], 1, &[]).read_only();
+        
+        // too short for the current struct until migrated
+        assert_eq!(
+            Escrow::from_account(&escrow_account.info(), &ID).err(),
+            Some(ProgramError::AccountDataTooSmall)
+        );
+        
+        // only the maker can migrate
+        let stranger = MockAccount::new([9u8; 32], SYSTEM_PROGRAM_ID, 1_000_000, &[]).signer();
+        let infos = account_infos(&[&stranger, &escrow_account, &system_program]);
+        assert_eq!(
+            migrate_escrow(&ID, MigrateEscrowAccounts::from_slice(&infos).unwrap(), 3).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidAuthority as u32)
+        );
+        
+        // the top-up is a no-op on host, so an underfunded escrow stays
+        // short of rent exemption at the new size
+        let underfunded = MockAccount::new(escrow_key, ID, 1_000_000, &v0);
+        let infos = account_infos(&[&maker, &underfunded, &system_program]);
+        assert_eq!(
+            migrate_escrow(&ID, MigrateEscrowAccounts::from_slice(&infos).unwrap(), 3).unwrap_err(),
+            ProgramError::Custom(EscrowError::NotRentExempt as u32)
+        );
+        
+        let infos = account_infos(&[&maker, &escrow_account, &system_program]);
+        migrate_escrow(&ID, MigrateEscrowAccounts::from_slice(&infos).unwrap(), 3).unwrap();
+        assert_eq!(escrow_account.data().len(), Escrow::LEN);
+        
+        {
+            let escrow = Escrow::from_account(&escrow_account.info(), &ID).unwrap();
+            assert_eq!(escrow.version, ESCROW_VERSION);
+            assert_eq!(escrow.maker, MakeMocks::MAKER);
+            assert_eq!((escrow.mint_a, escrow.mint_b, escrow.receive_account), ([2u8; 32], [3u8; 32], [4u8; 32]));
+            assert_eq!((escrow.amount, escrow.original_amount), (1000, 1000));
+            // v0 takes paid token B 1:1 and never expired
+            assert_eq!(escrow.receive_amount, 1000);
+            assert_eq!(escrow.counter_amount(1000).unwrap(), 1000);
+            assert_eq!(escrow.expiry, 0);
+            assert_eq!(escrow.escrow_bump(), 250);
+            assert_eq!(escrow.vault_bump(), find_vault_address(&escrow_key, &ID).1);
+            assert_eq!(escrow.kind().unwrap(), EscrowKind::Token);
+            assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+            assert_eq!(escrow.price_mode().unwrap(), PriceMode::Fixed);
+            assert!(escrow.memo().is_empty());
+        }
+        
+        // a second migration changes nothing
+        let before = escrow_account.data().to_vec();
+        migrate_escrow(&ID, MigrateEscrowAccounts::from_slice(&infos).unwrap(), 3).unwrap();
+        assert_eq!(escrow_account.data(), &before[..]);
+        
+        // version 1 is the current layout without its last byte
+        let mocks = MakeMocks::new(11);
+        mocks.run(11, None).unwrap();
+        let v1 = &mocks.escrow.data()[..Escrow::V1_LEN];
+        assert_eq!(Escrow::layout_version(v1).unwrap(), 1);
+        let v1_account = MockAccount::new(*mocks.escrow.info().key(), ID, MakeMocks::ESCROW_LAMPORTS, v1);
+        let infos = account_infos(&[&maker, &v1_account, &system_program]);
+        migrate_escrow(&ID, MigrateEscrowAccounts::from_slice(&infos).unwrap(), 11).unwrap();
+        assert_eq!(v1_account.data(), mocks.escrow.data());
+        let escrow = Escrow::from_account(&v1_account.info(), &ID).unwrap();
+        assert_eq!((escrow.amount, escrow.receive_amount, escrow.version), (1000, 2000, ESCROW_VERSION));
+        assert_eq!(escrow.memo(), b"mock");
+        
+        // version 2 is the current layout without the arbiter
+        let mut v2 = mocks.escrow.data()[..Escrow::V2_LEN].to_vec();
+        v2[Escrow::VERSION_OFFSET] = 2;
+        assert_eq!(Escrow::layout_version(&v2).unwrap(), 2);
+        let v2_account = MockAccount::new(*mocks.escrow.info().key(), ID, MakeMocks::ESCROW_LAMPORTS, &v2);
+        let infos = account_infos(&[&maker, &v2_account, &system_program]);
+        migrate_escrow(&ID, MigrateEscrowAccounts::from_slice(&infos).unwrap(), 11).unwrap();
+        assert_eq!(v2_account.data(), mocks.escrow.data());
+        assert!(!Escrow::from_account(&v2_account.info(), &ID).unwrap().has_arbiter());
+        
+        // version 3 is the current layout without the creation time, which
+        // stays unknown
+        let mut v3 = mocks.escrow.data()[..Escrow::V3_LEN].to_vec();
+        v3[Escrow::VERSION_OFFSET] = 3;
+        assert_eq!(Escrow::layout_version(&v3).unwrap(), 3);
+        let v3_account = MockAccount::new(*mocks.escrow.info().key(), ID, MakeMocks::ESCROW_LAMPORTS, &v3);
+        let infos = account_infos(&[&maker, &v3_account, &system_program]);
+        migrate_escrow(&ID, MigrateEscrowAccounts::from_slice(&infos).unwrap(), 11).unwrap();
+        let migrated = Escrow::from_account(&v3_account.info(), &ID).unwrap();
+        assert_eq!((migrated.version, migrated.created_at()), (ESCROW_VERSION, 0));
+        assert_eq!(migrated.age(1_000), None);
+        
+        // version 4 is the current layout without the minimum fill, which
+        // stays zero (any fill)
+        let mut v4 = mocks.escrow.data()[..Escrow::V4_LEN].to_vec();
+        v4[Escrow::VERSION_OFFSET] = 4;
+        assert_eq!(Escrow::layout_version(&v4).unwrap(), 4);
+        let v4_account = MockAccount::new(*mocks.escrow.info().key(), ID, MakeMocks::ESCROW_LAMPORTS, &v4);
+        let infos = account_infos(&[&maker, &v4_account, &system_program]);
+        migrate_escrow(&ID, MigrateEscrowAccounts::from_slice(&infos).unwrap(), 11).unwrap();
+        assert_eq!(v4_account.data(), mocks.escrow.data());
+        assert_eq!(Escrow::from_account(&v4_account.info(), &ID).unwrap().min_fill(), 0);
+        
+        // version 5 is the current layout without the persistent flag
+        let mut v5 = mocks.escrow.data()[..Escrow::V5_LEN].to_vec();
+        v5[Escrow::VERSION_OFFSET] = 5;
+        assert_eq!(Escrow::layout_version(&v5).unwrap(), 5);
+        let v5_account = MockAccount::new(*mocks.escrow.info().key(), ID, MakeMocks::ESCROW_LAMPORTS, &v5);
+        let infos = account_infos(&[&maker, &v5_account, &system_program]);
+        migrate_escrow(&ID, MigrateEscrowAccounts::from_slice(&infos).unwrap(), 11).unwrap();
+        assert_eq!(v5_account.data(), mocks.escrow.data());
+        assert!(!Escrow::from_account(&v5_account.info(), &ID).unwrap().is_persistent());
+        
+        // anything else isn't an escrow layout
+        assert!(Escrow::layout_version(&v0[..Escrow::V0_LEN - 1]).is_err());
+        assert!(Escrow::layout_version(&[0u8; Escrow::V0_LEN]).is_err());
+    }
+
+    #[test]
+    fn test_take_available_fills_partially_drained_vault() {
+        use crate::instructions::take::available_fill;
+        
+        // the fill is capped by the request, the escrow and the vault
+        assert_eq!(available_fill(1000, 1000, 600), 600);
+        assert_eq!(available_fill(300, 1000, 600), 300);
+        assert_eq!(available_fill(1000, 400, 600), 400);
+        assert_eq!(available_fill(1000, 1000, 0), 0);
+        
+        // 1000 token A offered for 2000 token B, 400 of it taken by earlier
+        // partial fills and the other 600 left in the vault
+        let setup = |seed: u64| {
+            let mocks = MakeMocks::new(seed);
+            mocks.run(seed, None).unwrap();
+            Escrow::from_account(&mocks.escrow.info(), &ID).unwrap().record_fill(400, 800);
+            let vault = MockAccount::new(
+                *mocks.vault.info().key(),
+                TOKEN_PROGRAM_ID,
+                2_000_000,
+                &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 600),
+            );
+            (mocks, vault)
+        };
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let take_infos = |mocks: &MakeMocks, vault: &MockAccount| {
+            // the maker stands in for the taker
+            account_infos(&[
+                &mocks.maker,
+                &mocks.maker,
+                &mocks.escrow,
+                vault,
+                &mocks.mint_a,
+                &mocks.mint_b,
+                &taker_ata_a,
+                &taker_ata_b,
+                &mocks.maker_ata_b,
+                &mocks.token_program,
+                &mocks.config,
+            ])
+        };
+        
+        // an exact take of the original amount no longer matches the offer
+        let (mocks, vault) = setup(12);
+        let infos = take_infos(&mocks, &vault);
+        assert_eq!(
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 12, 2000, 2000).unwrap_err(),
+            ProgramError::Custom(EscrowError::ExpectedAmountMismatch as u32)
+        );
+        
+        // a vault drained behind the escrow's back can't be filled from
+        let mocks = MakeMocks::new(16);
+        mocks.run(16, None).unwrap();
+        let short = MockAccount::new(
+            *mocks.vault.info().key(),
+            TOKEN_PROGRAM_ID,
+            2_000_000,
+            &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 600),
+        );
+        let infos = take_infos(&mocks, &short);
+        assert_eq!(
+            take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 600, 16, 2000).unwrap_err(),
+            ProgramError::Custom(EscrowError::InsufficientVaultBalance as u32)
+        );
+        
+        // 600 token A cost 1200 token B, a lower cap is slippage
+        let (mocks, vault) = setup(13);
+        let infos = take_infos(&mocks, &vault);
+        assert_eq!(
+            take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 13, 1199).unwrap_err(),
+            ProgramError::Custom(EscrowError::SlippageExceeded as u32)
+        );
+        
+        // a smaller fill takes its share and leaves the rest of the offer open
+        let (mocks, vault) = setup(14);
+        let infos = take_infos(&mocks, &vault);
+        take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 300, 14, 2000).unwrap();
+        {
+            let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+            assert_eq!(escrow.amount, 300);
+            assert_eq!(escrow.receive_amount, 600);
+            assert_eq!(escrow.fill_ratio(), 7_000);
+            assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+        }
+        
+        // taking what is available fills the last 300, settles the escrow and
+        // closes it
+        take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 14, 600).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+        
+        // nothing to take from an empty request
+        let (mocks, vault) = setup(15);
+        let infos = take_infos(&mocks, &vault);
+        assert_eq!(
+            take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 0, 15, 2000).unwrap_err(),
+            ProgramError::Custom(EscrowError::ExpectedAmountMismatch as u32)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_accounts_rejected() {
+        use crate::instructions::check_distinct_accounts;
+        
+        let mocks = MakeMocks::new(10);
+        let (escrow, vault, maker_ata_a) = (mocks.escrow.info(), mocks.vault.info(), mocks.maker_ata_a.info());
+        assert!(check_distinct_accounts(&[&escrow, &vault, &maker_ata_a]).is_ok());
+        assert_eq!(
+            check_distinct_accounts(&[&escrow, &vault, &escrow]).unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+        
+        // make: the vault passed again as the escrow
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.escrow,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &mocks.config,
+        ]);
+        assert_eq!(
+            make(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 10, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false).unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+        
+        mocks.run(10, None).unwrap();
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        
+        // take: the taker pays token B out of the maker's own receive account
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &mocks.maker_ata_a,
+            &mocks.maker_ata_a,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        assert_eq!(
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 10, 2000, 2000).unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+        
+        // refund: the vault refunded into itself
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &mocks.vault, &mocks.vault, &mocks.token_program]);
+        assert_eq!(
+            refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 10).unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+        
+        // nothing was touched, the escrow is still live
+        let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+        assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+    }
+
+    #[test]
+    fn test_basket_two_mints_filled_in_one_take() {
+        use crate::instructions::basket::{find_basket_address, find_basket_vault_address};
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        const MAKER: Pubkey = [1u8; 32];
+        const TAKER: Pubkey = [11u8; 32];
+        let (mint_a1, mint_a2, mint_b) = ([2u8; 32], [12u8; 32], [3u8; 32]);
+        let (basket_key, _) = find_basket_address(&MAKER, 5, &ID);
+        let (vault1_key, _) = find_basket_vault_address(&basket_key, 0, &ID);
+        let (vault2_key, _) = find_basket_vault_address(&basket_key, 1, &ID);
+        let token_account = |key: Pubkey, mint: &Pubkey, owner: &Pubkey, amount: u64| {
+            MockAccount::new(key, TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(mint, owner, amount))
+        };
+        
+        let maker = MockAccount::new(MAKER, SYSTEM_PROGRAM_ID, 10_000_000_000, &[]).signer();
+        let basket = MockAccount::new(basket_key, ID, 3_000_000, &[0u8; BasketEscrow::LEN]);
+        let mint_b_account = MockAccount::new(mint_b, TOKEN_PROGRAM_ID, 1_000_000, &test_mint_data(None)).read_only();
+        let maker_ata_b = token_account([4u8; 32], &mint_b, &MAKER, 0);
+        let token_program = MockAccount::new(TOKEN_PROGRAM_ID, [0u8; 32], 1, &[]).read_only();
+        let system_program = MockAccount::new(SYSTEM_PROGRAM_ID, [0u8; 32], 1, &[]).read_only();
+        let mint_a1_account = MockAccount::new(mint_a1, TOKEN_PROGRAM_ID, 1_000_000, &test_mint_data(None)).read_only();
+        let mint_a2_account = MockAccount::new(mint_a2, TOKEN_PROGRAM_ID, 1_000_000, &test_mint_data(None)).read_only();
+        let maker_ata_a1 = token_account([5u8; 32], &mint_a1, &MAKER, 500);
+        let maker_ata_a2 = token_account([6u8; 32], &mint_a2, &MAKER, 500);
+        let vault1 = MockAccount::new(vault1_key, SYSTEM_PROGRAM_ID, 0, &[]);
+        let vault2 = MockAccount::new(vault2_key, SYSTEM_PROGRAM_ID, 0, &[]);
+        let config = test_config();
+        // the same config with the pause flag set
+        let mut paused_data = config.data().to_vec();
+        paused_data[40] = 1;
+        let paused_config = MockAccount::new(*config.info().key(), ID, 1_000_000, &paused_data);
+        let paused = ProgramError::Custom(EscrowError::ProgramPaused as u32);
+        let legs: [&MockAccount; 6] = [&mint_a1_account, &maker_ata_a1, &vault1, &mint_a2_account, &maker_ata_a2, &vault2];
+        
+        // a paused program makes no baskets
+        let infos = account_infos(&[&[&maker, &basket, &mint_b_account, &maker_ata_b, &token_program, &system_program, &paused_config][..], &legs[..]].concat());
+        assert_eq!(make_basket(&ID, MakeBasketAccounts::from_slice(&infos).unwrap(), 5, 900, 0, &[100, 200]).unwrap_err(), paused);
+        
+        let prefix = [&maker, &basket, &mint_b_account, &maker_ata_b, &token_program, &system_program, &config];
+        
+        // every leg needs its three accounts
+        let infos = account_infos(&[&prefix[..], &[&mint_a1_account, &maker_ata_a1, &vault1]].concat());
+        assert_eq!(
+            make_basket(&ID, MakeBasketAccounts::from_slice(&infos).unwrap(), 5, 900, 0, &[100, 200]).unwrap_err(),
+            ProgramError::NotEnoughAccountKeys
+        );
+        
+        let infos = account_infos(&[&prefix[..], &legs[..]].concat());
+        make_basket(&ID, MakeBasketAccounts::from_slice(&infos).unwrap(), 5, 900, 0, &[100, 200]).unwrap();
+        
+        {
+            let stored = BasketEscrow::from_account(&basket.info(), &ID).unwrap();
+            let legs: Vec<_> = stored.legs().collect();
+            assert_eq!(legs.len(), 2);
+            assert_eq!((legs[0].mint, legs[0].amount), (mint_a1, 100));
+            assert_eq!((legs[1].mint, legs[1].amount), (mint_a2, 200));
+            assert_eq!(stored.receive_amount, 900);
+            assert_eq!(stored.receive_account, [4u8; 32]);
+            assert_eq!(stored.status().unwrap(), EscrowStatus::Active);
+        }
+        
+        // CPIs are no-ops off-chain, so stand in the funded vaults make leaves behind
+        let vault1 = token_account(vault1_key, &mint_a1, &basket_key, 100);
+        let vault2 = token_account(vault2_key, &mint_a2, &basket_key, 200);
+        let taker = MockAccount::new(TAKER, SYSTEM_PROGRAM_ID, 1_000_000_000, &[]).signer();
+        let taker_ata_b = token_account([13u8; 32], &mint_b, &TAKER, 900);
+        let taker_ata_a1 = token_account([14u8; 32], &mint_a1, &TAKER, 0);
+        let taker_ata_a2 = token_account([15u8; 32], &mint_a2, &TAKER, 0);
+        
+        // nor takes them while paused
+        let infos = account_infos(&[&[&taker, &maker, &basket, &taker_ata_b, &maker_ata_b, &token_program, &paused_config][..], &[&vault1, &taker_ata_a1, &vault2, &taker_ata_a2]].concat());
+        assert_eq!(take_basket(&ID, TakeBasketAccounts::from_slice(&infos).unwrap(), 5).unwrap_err(), paused);
+        
+        let prefix = [&taker, &maker, &basket, &taker_ata_b, &maker_ata_b, &token_program, &config];
+        
+        // legs passed in the wrong order are caught before anything moves
+        let infos = account_infos(&[&prefix[..], &[&vault1, &taker_ata_a2, &vault2, &taker_ata_a1]].concat());
+        assert_eq!(
+            take_basket(&ID, TakeBasketAccounts::from_slice(&infos).unwrap(), 5).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidTokenMint as u32)
+        );
+        
+        // one take releases both legs and closes the basket
+        let infos = account_infos(&[&prefix[..], &[&vault1, &taker_ata_a1, &vault2, &taker_ata_a2]].concat());
+        take_basket(&ID, TakeBasketAccounts::from_slice(&infos).unwrap(), 5).unwrap();
+        assert_eq!(taker.lamports(), 1_000_000_000 + 3_000_000);
+        assert_eq!(basket.lamports(), 0);
+        assert_eq!(basket.owner(), SYSTEM_PROGRAM_ID);
+        assert!(basket.data().iter().all(|byte| *byte == 0));
+        
+        // baskets are capped for compute
+        assert!(BasketEscrow::check_leg_count(0).is_err());
+        assert!(BasketEscrow::check_leg_count(MAX_BASKET_LEGS + 1).is_err());
+        let too_many = pack_instruction_data(&EscrowInstruction::MakeBasket {
+            seed: 5,
+            receive_amount: 900,
+            expiry: 0,
+            amounts: vec![1; MAX_BASKET_LEGS + 1],
+        });
+        assert!(EscrowInstruction::unpack(&too_many).is_err());
+    }
+
+    #[test]
+    fn test_process_parsed_instruction() {
+        let mocks = MakeMocks::new(11);
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &mocks.config,
+        ]);
+        
+        // no instruction bytes involved
+        process(&ID, &infos, EscrowInstruction::Make {
+            amount: 1000,
+            seed: 11,
+            expiry: 0,
+            kind: EscrowKind::Token,
+            receive_amount: 2000,
+            memo: b"direct".to_vec(),
+            namespace: DEFAULT_ESCROW_NAMESPACE,
+            price_num: 0,
+            price_den: 0,
+            min_fill: 0,
+            persistent: false,
+            treasury: false,
+        })
+        .unwrap();
+        
+        let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+        assert_eq!(escrow.amount, 1000);
+        assert_eq!(escrow.receive_amount, 2000);
+        assert_eq!(escrow.memo(), b"direct");
+        
+        // the accounts are still checked the same way as through process_instruction
+        assert_eq!(
+            process(&ID, &infos[..1], EscrowInstruction::Refund { amount: 1000, seed: 11 }).unwrap_err(),
+            ProgramError::NotEnoughAccountKeys
+        );
+    }
+
+    #[test]
+    fn test_token_ops_build_expected_instructions() {
+        use crate::instructions::token_ops::{self, TokenOp};
+        
+        let token_program = MockAccount::new(TOKEN_PROGRAM_ID, [0u8; 32], 1, &[]);
+        let from = MockAccount::new([4u8; 32], TOKEN_PROGRAM_ID, 1, &[]);
+        let to = MockAccount::new([5u8; 32], TOKEN_PROGRAM_ID, 1, &[]);
+        let authority = MockAccount::new([6u8; 32], [0u8; 32], 1, &[]).signer();
+        let mint = MockAccount::new([2u8; 32], TOKEN_PROGRAM_ID, 1, &[]);
+        let (from, to, authority, mint) = (from.info(), to.info(), authority.info(), mint.info());
+        let keys = |op: TokenOp| op.accounts().as_slice().iter().map(|account| *account.key()).collect::<Vec<_>>();
+        
+        // the accounts passed to invoke follow the instruction's account order
+        assert_eq!(
+            keys(TokenOp::Transfer { from: &from, to: &to, authority: &authority, amount: 10 }),
+            vec![[4u8; 32], [5u8; 32], [6u8; 32]]
+        );
+        assert_eq!(
+            keys(TokenOp::Close { account: &from, destination: &to, authority: &authority }),
+            vec![[4u8; 32], [5u8; 32], [6u8; 32]]
+        );
+        assert_eq!(
+            keys(TokenOp::InitializeAccount { account: &from, mint: &mint, owner: &[6u8; 32] }),
+            vec![[4u8; 32], [2u8; 32]]
+        );
+        assert_eq!(
+            keys(TokenOp::TransferChecked { from: &from, mint: &mint, to: &to, authority: &authority, amount: 10, decimals: 6, fee: Some(1) }),
+            vec![[4u8; 32], [2u8; 32], [5u8; 32], [6u8; 32]]
+        );
+        assert_eq!(
+            keys(TokenOp::HarvestWithheld { mint: &mint, account: &to }),
+            vec![[2u8; 32], [5u8; 32]]
+        );
+        
+        // a wallet authority invokes, a PDA authority signs with its seeds
+        let seeds: &[&[u8]] = &[b"vault", &[7u8; 32], &[255]];
+        let token_program = token_program.info();
+        assert!(token_ops::transfer(&token_program, &from, &to, &authority, 10, None).is_ok());
+        assert!(token_ops::transfer(&token_program, &from, &to, &authority, 10, Some(seeds)).is_ok());
+        assert!(token_ops::close(&token_program, &from, &to, &authority, Some(seeds)).is_ok());
+        assert!(token_ops::initialize_account(&token_program, &from, &mint, &[6u8; 32], Some(seeds)).is_ok());
+        let checked = TokenOp::TransferChecked { from: &from, mint: &mint, to: &to, authority: &authority, amount: 10, decimals: 6, fee: None };
+        assert!(checked.invoke(&token_program, Some(seeds)).is_ok());
+    }
+
+    #[test]
+    fn test_make_records_creation_time() {
+        use crate::instructions::make::make_at;
+        
+        // make stores the clock's unix timestamp
+        let mocks = MakeMocks::new(47);
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &mocks.config,
+        ]);
+        let make_with_expiry = |expiry: i64| {
+            make_at(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 47, expiry, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false, 1_700_000_000)
+        };
+        
+        // the expiry is checked against the same time
+        assert_eq!(make_with_expiry(1_700_000_000).unwrap_err(), ProgramError::Custom(EscrowError::InvalidExpiry as u32));
+        make_with_expiry(0).unwrap();
+        let escrow = *Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+        assert_eq!(escrow.created_at(), 1_700_000_000);
+        assert_eq!(escrow.age(1_700_000_000 + 3_600), Some(3_600));
+        assert_eq!(escrow.as_tuple().20, 1_700_000_000i64.to_le_bytes());
+        
+        // the raw layout keeps the field right after the arbiter, unpadded
+        assert_eq!(core::mem::offset_of!(Escrow, created_at), Escrow::V3_LEN);
+        assert_eq!(Escrow::V4_LEN, Escrow::V3_LEN + 8);
+        
+        #[cfg(feature = "client")]
+        {
+            use crate::client::{decode_escrow, filter_older_than};
+            
+            let decoded = decode_escrow(mocks.escrow.data()).unwrap();
+            assert_eq!(decoded.created_at(), 1_700_000_000);
+            
+            // a week old offer, a fresh one and one with no known creation time
+            const WEEK: i64 = 7 * 24 * 3600;
+            let now = 1_700_000_000 + WEEK;
+            let mut fresh = decoded;
+            fresh.created_at = (now - 60).to_le_bytes();
+            let mut migrated = decoded;
+            migrated.created_at = [0u8; 8];
+            let escrows = [decoded, fresh, migrated];
+            let old = filter_older_than(&escrows, now, WEEK);
+            assert_eq!(old.len(), 1);
+            assert_eq!(old[0].created_at(), 1_700_000_000);
+        }
+    }
+
+    #[test]
+    fn test_take_available_min_fill() {
+        let mut escrow = test_escrow(1000, 0);
+        escrow.min_fill = 300u64.to_le_bytes();
+        let below: ProgramError = EscrowError::FillBelowMinimum.into();
+        assert!(escrow.check_fill(300).is_ok());
+        assert_eq!(escrow.check_fill(299).unwrap_err(), below);
+        // the rest of the offer can always be taken
+        escrow.amount = 100;
+        assert!(escrow.check_fill(100).is_ok());
+        assert_eq!(escrow.check_fill(99).unwrap_err(), below);
+        
+        // 1000 token A offered for 2000 token B, fills of at least 300
+        let mocks = MakeMocks::new(48);
+        mocks.run(48, None).unwrap();
+        Escrow::from_account(&mocks.escrow.info(), &ID).unwrap().min_fill = 300u64.to_le_bytes();
+        let vault = MockAccount::new(
+            *mocks.vault.info().key(),
+            TOKEN_PROGRAM_ID,
+            2_000_000,
+            &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000),
+        );
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        // the maker stands in for the taker
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &mocks.escrow,
+            &vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &mocks.maker_ata_b,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        
+        // a dust fill is rejected and leaves the escrow as it was
+        assert_eq!(
+            take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 200, 48, 2000).unwrap_err(),
+            below
+        );
+        assert_eq!(Escrow::from_account(&mocks.escrow.info(), &ID).unwrap().amount, 1000);
+        
+        // a fill at the minimum or above goes through
+        take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 800, 48, 2000).unwrap();
+        {
+            let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+            assert_eq!(escrow.amount, 200);
+            assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+        }
+        
+        // the last 200 are below the minimum but close the offer
+        take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 48, 2000).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+    }
+
+    #[test]
+    fn test_escrow_and_vault_must_differ() {
+        use crate::instructions::check_escrow_not_vault;
+        
+        let mocks = MakeMocks::new(49);
+        let (escrow, vault) = (mocks.escrow.info(), mocks.vault.info());
+        assert!(check_escrow_not_vault(&escrow, &vault).is_ok());
+        assert_eq!(check_escrow_not_vault(&escrow, &escrow).unwrap_err(), ProgramError::InvalidArgument);
+        
+        // make: the escrow passed again as the vault, under a seed whose
+        // escrow PDA check would fail later
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.escrow,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &mocks.config,
+        ]);
+        assert_eq!(
+            make(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 99, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false).unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+        
+        mocks.run(49, None).unwrap();
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        
+        // take: the escrow passed as its own vault
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &mocks.escrow,
+            &mocks.escrow,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &mocks.maker_ata_b,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        assert_eq!(
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 49, 2000, 2000).unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+        
+        // refund: the same
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &mocks.escrow, &mocks.maker_ata_a, &mocks.token_program]);
+        assert_eq!(
+            refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 49).unwrap_err(),
+            ProgramError::InvalidArgument
+        );
+        
+        // the escrow is untouched
+        let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+        assert_eq!((escrow.amount, escrow.status().unwrap()), (1000, EscrowStatus::Active));
+    }
+
+    #[test]
+    fn test_persistent_escrow_survives_take() {
+        // the flag is a strict bool on the wire
+        let mut packed = pack_instruction_data(&EscrowInstruction::Make {
+            amount: 1,
+            seed: 2,
+            expiry: 0,
+            kind: EscrowKind::Token,
+            receive_amount: 3,
+            memo: Vec::new(),
+            namespace: [0u8; 8],
+            price_num: 0,
+            price_den: 0,
+            min_fill: 0,
+            persistent: true,
+            treasury: false,
+        });
+        assert_eq!(packed[MAKE_PERSISTENT_OFFSET], 1);
+        packed[MAKE_PERSISTENT_OFFSET] = 2;
+        assert_eq!(
+            EscrowInstruction::unpack(&packed).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidInstruction as u32)
+        );
+        
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let funded_vault = |mocks: &MakeMocks| MockAccount::new(
+            *mocks.vault.info().key(),
+            TOKEN_PROGRAM_ID,
+            2_000_000,
+            &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000),
+        );
+        // the maker stands in for the taker
+        let take_infos = |mocks: &MakeMocks, vault: &MockAccount| account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &mocks.escrow,
+            vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &mocks.maker_ata_b,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        
+        // a persistent escrow is drained by the take but stays open
+        let mocks = MakeMocks::new(50);
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &mocks.config,
+        ]);
+        make(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 50, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, true, false).unwrap();
+        assert!(Escrow::from_account(&mocks.escrow.info(), &ID).unwrap().is_persistent());
+        let vault = funded_vault(&mocks);
+        let infos = take_infos(&mocks, &vault);
+        take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 50, 2000, 2000).unwrap();
+        assert_eq!(mocks.escrow.lamports(), MakeMocks::ESCROW_LAMPORTS);
+        assert_eq!(vault.lamports(), 2_000_000);
+        {
+            let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+            assert_eq!((escrow.amount, escrow.receive_amount), (0, 0));
+            assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+        }
+        
+        // nothing left to take until the maker refills it
+        assert_eq!(
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 0, 50, 0, 0).unwrap_err(),
+            ProgramError::Custom(EscrowError::ExpectedAmountMismatch as u32)
+        );
+        assert_eq!(
+            take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 50, 2000).unwrap_err(),
+            ProgramError::Custom(EscrowError::InsufficientVaultBalance as u32)
+        );
+        
+        // the maker closes it with a refund
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program]);
+        refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 0, 50).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+        
+        // without the flag the take closes the escrow
+        let mocks = MakeMocks::new(51);
+        mocks.run(51, None).unwrap();
+        assert!(!Escrow::from_account(&mocks.escrow.info(), &ID).unwrap().is_persistent());
+        let vault = funded_vault(&mocks);
+        let infos = take_infos(&mocks, &vault);
+        take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 51, 2000, 2000).unwrap();
+        assert_eq!(mocks.escrow.lamports(), 0);
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+    }
+    
+    #[test]
+    fn test_make_checks_created_vault_mint() {
+        // a vault that came out of initialization for another mint
+        let mut mocks = MakeMocks::new(52);
+        let vault = *mocks.vault.info().key();
+        let escrow = *mocks.escrow.info().key();
+        mocks.vault = MockAccount::new(vault, TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[9u8; 32], &escrow, 0));
+        assert_eq!(
+            mocks.run(52, None).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidTokenMint as u32)
+        );
+        
+        // one that was never initialized isn't waved through either
+        let mut mocks = MakeMocks::new(52);
+        let vault = *mocks.vault.info().key();
+        mocks.vault = MockAccount::new(vault, crate::programs::SYSTEM_PROGRAM_ID, 0, &[]);
+        assert!(mocks.run(52, None).is_err());
+        
+        // a vault for the escrow's mint_a passes
+        let mocks = MakeMocks::new(53);
+        mocks.run(53, None).unwrap();
+        assert_eq!(Escrow::from_account(&mocks.escrow.info(), &ID).unwrap().mint_a, [2u8; 32]);
+    }
+    
+    #[test]
+    fn test_make_checks_escrow_rent_exempt() {
+        use crate::sysvar::current_rent;
+
+        // a create funded one lamport short is caught before the escrow is written
+        let minimum = current_rent().unwrap().minimum_balance(Escrow::LEN);
+        assert!(minimum <= MakeMocks::ESCROW_LAMPORTS);
+        let mut mocks = MakeMocks::new(54);
+        let escrow = *mocks.escrow.info().key();
+        mocks.escrow = MockAccount::new(escrow, ID, minimum - 1, &[0u8; Escrow::LEN]);
+        assert_eq!(
+            mocks.run(54, None).unwrap_err(),
+            ProgramError::Custom(EscrowError::NotRentExempt as u32)
+        );
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+        
+        // exactly the minimum is enough
+        let mut mocks = MakeMocks::new(55);
+        let escrow = *mocks.escrow.info().key();
+        mocks.escrow = MockAccount::new(escrow, ID, minimum, &[0u8; Escrow::LEN]);
+        mocks.run(55, None).unwrap();
+    }
+    
+    #[test]
+    fn test_pause_blocks_make_and_take() {
+        use crate::instructions::config::{find_config_address, find_program_data_address, upgrade_authority};
+        use crate::programs::{BPF_LOADER_UPGRADEABLE_ID, SYSTEM_PROGRAM_ID};
+
+        let admin = MockAccount::new([40u8; 32], SYSTEM_PROGRAM_ID, 1_000_000_000, &[]).signer();
+        let config = MockAccount::new(find_config_address(&ID).0, ID, 1_000_000, &[0u8; Config::LEN]);
+        let system_program = MockAccount::new(SYSTEM_PROGRAM_ID, [0u8; 32], 1, &[]).read_only();
+        let set = |signer: &MockAccount, paused: bool| {
+            let infos = account_infos(&[signer, &config]);
+            set_paused(&ID, SetPausedAccounts::from_slice(&infos).unwrap(), paused)
+        };
+        
+        // ProgramData as the upgradeable loader writes it: state tag 3, the
+        // deploy slot, then the optional upgrade authority
+        let program_data_bytes = |authority: Option<Pubkey>| {
+            let mut data = vec![0u8; 45];
+            data[0..4].copy_from_slice(&3u32.to_le_bytes());
+            data[4..12].copy_from_slice(&77u64.to_le_bytes());
+            if let Some(authority) = authority {
+                data[12] = 1;
+                data[13..45].copy_from_slice(&authority);
+            }
+            data
+        };
+        let program_data_key = find_program_data_address(&ID).0;
+        let program_data = |key: Pubkey, authority: Option<Pubkey>| {
+            MockAccount::new(key, BPF_LOADER_UPGRADEABLE_ID, 1_000_000, &program_data_bytes(authority)).read_only()
+        };
+        assert_eq!(upgrade_authority(&program_data_bytes(Some([40u8; 32]))), Some([40u8; 32]));
+        assert_eq!(upgrade_authority(&program_data_bytes(None)), None);
+        assert_eq!(upgrade_authority(&program_data_bytes(Some([40u8; 32]))[..44]), None);
+        let init_with = |signer: &MockAccount, program_data: &MockAccount| {
+            let infos = account_infos(&[signer, &config, &system_program, program_data]);
+            init_config(&ID, InitConfigAccounts::from_slice(&infos).unwrap())
+        };
+        
+        // only the program's upgrade authority can create the config
+        let invalid_authority = ProgramError::Custom(EscrowError::InvalidAuthority as u32);
+        let stranger = MockAccount::new([41u8; 32], SYSTEM_PROGRAM_ID, 1_000_000, &[]).signer();
+        let upgradeable = program_data(program_data_key, Some([40u8; 32]));
+        assert_eq!(init_with(&stranger, &upgradeable).unwrap_err(), invalid_authority);
+        assert_eq!(init_with(&admin, &program_data(program_data_key, None)).unwrap_err(), invalid_authority);
+        // another program's ProgramData, or one not owned by the loader
+        assert_eq!(
+            init_with(&admin, &program_data([42u8; 32], Some([40u8; 32]))).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32)
+        );
+        let spoofed = MockAccount::new(program_data_key, ID, 1_000_000, &program_data_bytes(Some([40u8; 32]))).read_only();
+        assert_eq!(init_with(&admin, &spoofed).unwrap_err(), ProgramError::IncorrectProgramId);
+        assert!(config.data().iter().all(|byte| *byte == 0));
+        
+        // the first InitConfig makes its signer the admin, there is no second
+        let infos = account_infos(&[&admin, &config, &system_program, &upgradeable]);
+        init_config(&ID, InitConfigAccounts::from_slice(&infos).unwrap()).unwrap();
+        assert_eq!(Config::from_account(&config.info(), &ID).unwrap().admin, [40u8; 32]);
+        assert_eq!(
+            init_config(&ID, InitConfigAccounts::from_slice(&infos).unwrap()).unwrap_err(),
+            ProgramError::AccountAlreadyInitialized
+        );
+        
+        // only the admin can pause
+        assert_eq!(set(&stranger, true).unwrap_err(), invalid_authority);
+        
+        // an offer made before the pause
+        let made = MakeMocks::new(57);
+        let make_infos = |mocks: &MakeMocks| account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &config,
+        ]);
+        let infos = make_infos(&made);
+        let accounts = MakeAccounts::from_slice(&infos).unwrap();
+        assert_eq!(accounts.config.key(), config.info().key());
+        assert_eq!(accounts.payer.key(), &MakeMocks::MAKER);
+        make(&ID, accounts, 1000, 57, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false).unwrap();
+        
+        set(&admin, true).unwrap();
+        assert!(Config::from_account(&config.info(), &ID).unwrap().is_paused());
+        
+        // no new makes
+        let mocks = MakeMocks::new(56);
+        let paused = ProgramError::Custom(EscrowError::ProgramPaused as u32);
+        let infos = make_infos(&mocks);
+        assert_eq!(
+            make(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 56, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false).unwrap_err(),
+            paused
+        );
+        assert!(mocks.escrow.data().iter().all(|byte| *byte == 0));
+        
+        // no takes of open offers
+        let vault = MockAccount::new(*made.vault.info().key(), TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], made.escrow.info().key(), 1000));
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let infos = account_infos(&[
+            &made.maker,
+            &made.maker,
+            &made.escrow,
+            &vault,
+            &made.mint_a,
+            &made.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &made.maker_ata_b,
+            &made.token_program,
+            &config,
+        ]);
+        assert_eq!(take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 57, 2000, 2000).unwrap_err(), paused);
+        assert_eq!(take_available(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 57, 2000).unwrap_err(), paused);
+        assert_eq!(Escrow::from_account(&made.escrow.info(), &ID).unwrap().amount, 1000);
+        
+        // the config can't be left out, or swapped for an unpaused copy
+        // somewhere other than the config PDA
+        assert_eq!(TakeAccounts::from_slice(&infos[..10]).err(), Some(ProgramError::NotEnoughAccountKeys));
+        let forged = MockAccount::new([42u8; 32], ID, 1_000_000, made.config.data());
+        assert!(!Config::from_account(&forged.info(), &ID).unwrap().is_paused());
+        let infos = account_infos(&[
+            &made.maker,
+            &made.maker,
+            &made.escrow,
+            &vault,
+            &made.mint_a,
+            &made.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &made.maker_ata_b,
+            &made.token_program,
+            &forged,
+        ]);
+        assert_eq!(
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 57, 2000, 2000).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32)
+        );
+        assert_eq!(Escrow::from_account(&made.escrow.info(), &ID).unwrap().status().unwrap(), EscrowStatus::Active);
+        
+        // the maker can still get out
+        let infos = account_infos(&[&made.maker, &made.escrow, &vault, &made.maker_ata_a, &made.token_program]);
+        refund(&ID, RefundAccounts::from_slice(&infos).unwrap(), 1000, 57).unwrap();
+        assert_eq!(made.escrow.lamports(), 0);
+        
+        // resumed, makes go through again
+        set(&admin, false).unwrap();
+        let infos = make_infos(&mocks);
+        make(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 56, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false).unwrap();
+        
+        // the flag is a strict bool on the wire
+        let mut packed = pack_instruction_data(&EscrowInstruction::SetPaused { paused: true });
+        assert_eq!((packed[0], packed[SET_PAUSED_PAUSED_OFFSET]), (SET_PAUSED_DISCRIMINATOR, 1));
+        packed[SET_PAUSED_PAUSED_OFFSET] = 2;
+        assert_eq!(
+            EscrowInstruction::unpack(&packed).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidInstruction as u32)
+        );
+    }
+    
+    #[test]
+    fn test_missing_config_is_not_paused() {
+        use crate::instructions::config::{check_not_paused, find_config_address};
+        use crate::programs::SYSTEM_PROGRAM_ID;
+
+        // InitConfig never ran: the config PDA is an empty system account
+        let empty_config = MockAccount::new(find_config_address(&ID).0, SYSTEM_PROGRAM_ID, 0, &[]).read_only();
+        assert!(check_not_paused(&empty_config.info(), &ID).is_ok());
+        // an empty account elsewhere is still not the config
+        let elsewhere = MockAccount::new([42u8; 32], SYSTEM_PROGRAM_ID, 0, &[]).read_only();
+        assert_eq!(
+            check_not_paused(&elsewhere.info(), &ID).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32)
+        );
+        
+        // makes go through without a config
+        let mocks = MakeMocks::new(59);
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &empty_config,
+        ]);
+        make(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 59, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, false, false).unwrap();
+        assert_eq!(Escrow::from_account(&mocks.escrow.info(), &ID).unwrap().amount, 1000);
+        
+        // a created config is checked against its stored bump
+        let config = test_config();
+        assert!(check_not_paused(&config.info(), &ID).is_ok());
+        let mut data = config.data().to_vec();
+        data[Config::LEN - 1] = data[Config::LEN - 1].wrapping_sub(1);
+        let wrong_bump = MockAccount::new(*config.info().key(), ID, 1_000_000, &data);
+        assert_eq!(
+            check_not_paused(&wrong_bump.info(), &ID).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32)
+        );
+    }
+    
+    #[test]
+    fn test_take_recreates_closed_maker_ata() {
+        use crate::programs::{ASSOCIATED_TOKEN_PROGRAM_ID, SYSTEM_PROGRAM_ID};
+        use crate::instructions::take::CreateReceiveAta;
+
+        let mocks = MakeMocks::new(58);
+        mocks.run(58, None).unwrap();
+        
+        // the maker closed their ATA B after making the offer
+        let maker_ata_b_key = *mocks.maker_ata_b.info().key();
+        let maker_ata_b = MockAccount::new(maker_ata_b_key, SYSTEM_PROGRAM_ID, 0, &[]);
+        let vault = MockAccount::new(*mocks.vault.info().key(), TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000));
+        let taker = MockAccount::new([12u8; 32], SYSTEM_PROGRAM_ID, 1_000_000_000, &[]).signer();
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &[12u8; 32], 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &[12u8; 32], 5000));
+        let associated_token_program = MockAccount::new(ASSOCIATED_TOKEN_PROGRAM_ID, [0u8; 32], 1, &[]).read_only();
+        let system_program = MockAccount::new(SYSTEM_PROGRAM_ID, [0u8; 32], 1, &[]).read_only();
+        let required: [&MockAccount; 11] = [
+            &taker,
+            &mocks.maker,
+            &mocks.escrow,
+            &vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &maker_ata_b,
+            &mocks.token_program,
+            &mocks.config,
+        ];
+        let take_with = |extra: &[&MockAccount]| {
+            let infos = account_infos(&[&required[..], extra].concat());
+            take(&ID, TakeAccounts::from_slice(&infos).unwrap(), 1000, 58, 2000, 2000)
+        };
+        
+        // without the ATA program the take can't pay the maker
+        assert_eq!(take_with(&[]).unwrap_err(), ProgramError::Custom(EscrowError::InvalidState as u32));
+        // a spoofed program in the ATA program's place never gets the CPI
+        let spoofed = MockAccount::new([7u8; 32], [0u8; 32], 1, &[]).read_only();
+        assert_eq!(
+            take_with(&[&taker_ata_a, &system_program, &spoofed]).unwrap_err(),
+            ProgramError::IncorrectProgramId
+        );
+        
+        // the ATA program has its place after the system program
+        let infos = account_infos(&[&required[..], &[&taker_ata_a, &system_program, &associated_token_program]].concat());
+        let accounts = TakeAccounts::from_slice(&infos).unwrap();
+        assert!(accounts.associated_token_program.is_some() && accounts.receipt.is_none());
+        
+        // the taker funds CreateIdempotent for the maker's mint B ATA, the
+        // metas and the invoked accounts list the same keys
+        let create_ata = CreateReceiveAta::for_take(&accounts).unwrap().unwrap();
+        let keys = create_ata.accounts().iter().map(|account| *account.key()).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![[12u8; 32], maker_ata_b_key, MakeMocks::MAKER, [3u8; 32], SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID]
+        );
+        let metas = create_ata.metas().iter().map(|meta| (*meta.pubkey, meta.is_writable, meta.is_signer)).collect::<Vec<_>>();
+        assert_eq!(
+            metas,
+            vec![
+                ([12u8; 32], true, true),
+                (maker_ata_b_key, true, false),
+                (MakeMocks::MAKER, false, false),
+                ([3u8; 32], false, false),
+                (SYSTEM_PROGRAM_ID, false, false),
+                (TOKEN_PROGRAM_ID, false, false),
+            ]
+        );
+        assert_eq!(CreateReceiveAta::DATA, [1]);
+        
+        // host builds don't run the ATA program, so take reads the account
+        // back still closed after the CPI and pays nothing
+        assert_eq!(
+            take(&ID, accounts, 1000, 58, 2000, 2000).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidState as u32)
+        );
+        
+        // nothing to create while Maker ATA B exists
+        let infos = account_infos(&[
+            &taker,
+            &mocks.maker,
+            &mocks.escrow,
+            &vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &mocks.maker_ata_b,
+            &mocks.token_program,
+            &mocks.config,
+            &taker_ata_a,
+            &system_program,
+            &associated_token_program,
+        ]);
+        assert!(CreateReceiveAta::for_take(&TakeAccounts::from_slice(&infos).unwrap()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_top_up_refills_persistent_escrow() {
+        use crate::programs::SYSTEM_PROGRAM_ID;
+        
+        // a fixed offer adds the token B asked for, a priced one reprices the total
+        let mut escrow = test_escrow(0, 0);
+        escrow.original_amount = 1000;
+        escrow.top_up(600, 1500).unwrap();
+        assert_eq!((escrow.amount, escrow.receive_amount, escrow.original_amount), (600, 1500, 1600));
+        let mut priced = test_escrow(200, 0);
+        priced.price_mode = PriceMode::Ratio as u8;
+        (priced.price_num, priced.price_den) = (3, 2);
+        priced.top_up(400, 1).unwrap();
+        assert_eq!((priced.amount, priced.receive_amount, priced.original_amount), (600, 900, 600));
+        let mut full = test_escrow(1, 0);
+        full.amount = u64::MAX;
+        assert_eq!(full.top_up(1, 0).unwrap_err(), ProgramError::Custom(EscrowError::AmountOverflow as u32));
+        
+        let taker_ata_a = MockAccount::new([14u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], &MakeMocks::MAKER, 0));
+        let taker_ata_b = MockAccount::new([13u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[3u8; 32], &MakeMocks::MAKER, 5000));
+        let funded_vault = |mocks: &MakeMocks| MockAccount::new(
+            *mocks.vault.info().key(),
+            TOKEN_PROGRAM_ID,
+            2_000_000,
+            &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 1000),
+        );
+        
+        // a persistent escrow, drained by a take (the maker stands in for the taker)
+        let mocks = MakeMocks::new(59);
+        let infos = account_infos(&[
+            &mocks.maker,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &mocks.maker_ata_a,
+            &mocks.escrow,
+            &mocks.vault,
+            &mocks.token_program,
+            &mocks.system_program,
+            &mocks.maker_ata_b,
+            &mocks.config,
+        ]);
+        make(&ID, MakeAccounts::from_slice(&infos).unwrap(), 1000, 59, 0, EscrowKind::Token, 2000, b"mock", DEFAULT_ESCROW_NAMESPACE, 0, 0, 0, true, false).unwrap();
+        let vault = funded_vault(&mocks);
+        let take_infos = account_infos(&[
+            &mocks.maker,
+            &mocks.maker,
+            &mocks.escrow,
+            &vault,
+            &mocks.mint_a,
+            &mocks.mint_b,
+            &taker_ata_a,
+            &taker_ata_b,
+            &mocks.maker_ata_b,
+            &mocks.token_program,
+            &mocks.config,
+        ]);
+        take(&ID, TakeAccounts::from_slice(&take_infos).unwrap(), 1000, 59, 2000, 2000).unwrap();
+        
+        let top_up_with = |maker: &MockAccount, vault: &MockAccount, amount: u64| {
+            let infos = account_infos(&[maker, &mocks.escrow, vault, &mocks.maker_ata_a, &mocks.token_program, &mocks.system_program]);
+            top_up(&ID, TopUpAccounts::from_slice(&infos).unwrap(), amount, 1500, 59)
+        };
+        
+        // only the maker refills, with something, into the escrow's own vault
+        let unsigned = MockAccount::new(MakeMocks::MAKER, SYSTEM_PROGRAM_ID, 10_000_000_000, &[]);
+        assert_eq!(top_up_with(&unsigned, &vault, 600).unwrap_err(), ProgramError::MissingRequiredSignature);
+        let stranger = MockAccount::new([9u8; 32], SYSTEM_PROGRAM_ID, 10_000_000_000, &[]).signer();
+        assert_eq!(top_up_with(&stranger, &vault, 600).unwrap_err(), ProgramError::Custom(EscrowError::InvalidAuthority as u32));
+        assert_eq!(top_up_with(&mocks.maker, &vault, 0).unwrap_err(), ProgramError::Custom(EscrowError::ExpectedAmountMismatch as u32));
+        let wrong_vault = MockAccount::new([16u8; 32], TOKEN_PROGRAM_ID, 2_000_000, &test_token_account_data(&[2u8; 32], mocks.escrow.info().key(), 0));
+        assert_eq!(top_up_with(&mocks.maker, &wrong_vault, 600).unwrap_err(), ProgramError::Custom(EscrowError::InvalidEscrowAccount as u32));
+        
+        // the refill raises the offer, and the escrow can be taken again
+        top_up_with(&mocks.maker, &vault, 600).unwrap();
+        {
+            let escrow = Escrow::from_account(&mocks.escrow.info(), &ID).unwrap();
+            assert_eq!((escrow.amount, escrow.receive_amount, escrow.original_amount), (600, 1500, 1600));
+            assert_eq!(escrow.status().unwrap(), EscrowStatus::Active);
+        }
+        take(&ID, TakeAccounts::from_slice(&take_infos).unwrap(), 600, 59, 1500, 1500).unwrap();
+        assert_eq!(Escrow::from_account(&mocks.escrow.info(), &ID).unwrap().amount, 0);
+        
+        // an escrow closed by its last take has nothing to refill
+        let mocks = MakeMocks::new(60);
+        mocks.run(60, None).unwrap();
+        let vault = funded_vault(&mocks);
+        let infos = account_infos(&[&mocks.maker, &mocks.escrow, &vault, &mocks.maker_ata_a, &mocks.token_program, &mocks.system_program]);
+        assert_eq!(
+            top_up(&ID, TopUpAccounts::from_slice(&infos).unwrap(), 600, 1500, 60).unwrap_err(),
+            ProgramError::Custom(EscrowError::InvalidState as u32)
+        );
+    }
 }
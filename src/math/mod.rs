@@ -0,0 +1,27 @@
+// proportional amount math shared by partial fills, price modes and fees.
+// `a * b / c` runs in u128 so the product can't overflow, only a zero divisor
+// or a result past u64::MAX fails
+use crate::error::EscrowError;
+
+// a * b / c rounded down
+pub const fn mul_div_floor(a: u64, b: u64, c: u64) -> Result<u64, EscrowError> {
+    if c == 0 {
+        return Err(EscrowError::AmountOverflow);
+    }
+    narrow(a as u128 * b as u128 / c as u128)
+}
+
+// a * b / c rounded up
+pub const fn mul_div_ceil(a: u64, b: u64, c: u64) -> Result<u64, EscrowError> {
+    if c == 0 {
+        return Err(EscrowError::AmountOverflow);
+    }
+    narrow((a as u128 * b as u128).div_ceil(c as u128))
+}
+
+const fn narrow(value: u128) -> Result<u64, EscrowError> {
+    if value > u64::MAX as u128 {
+        return Err(EscrowError::AmountOverflow);
+    }
+    Ok(value as u64)
+}
@@ -0,0 +1,97 @@
+// mock accounts for unit testing the handlers without a validator.
+// Each MockAccount owns a buffer laid out the way the runtime serializes an
+// account for pinocchio (header, then data), and hands out an AccountInfo
+// pointing into it. CPIs are no-ops off-chain, so a handler runs all of its
+// checks and state writes but moves no tokens or lamports through a CPI
+use pinocchio::{account_info::AccountInfo, pubkey::Pubkey};
+
+// runtime account header:
+// borrow_state (0), is_signer (1), is_writable (2), executable (3),
+// resize_delta (4..8), key (8..40), owner (40..72), lamports (72..80),
+// data_len (80..88), data (88..)
+const BORROW_STATE_OFFSET: usize = 0;
+const IS_SIGNER_OFFSET: usize = 1;
+const IS_WRITABLE_OFFSET: usize = 2;
+const KEY_OFFSET: usize = 8;
+const OWNER_OFFSET: usize = 40;
+const LAMPORTS_OFFSET: usize = 72;
+const DATA_LEN_OFFSET: usize = 80;
+const DATA_OFFSET: usize = 88;
+
+// every borrow flag clear
+const NOT_BORROWED: u8 = u8::MAX;
+
+// room the runtime leaves after each account's data for realloc
+const MAX_PERMITTED_DATA_INCREASE: usize = 10 * 1024;
+
+pub struct MockAccount {
+    // u64 words keep the header and data 8-byte aligned, as on-chain
+    buf: *mut [u64],
+}
+
+impl MockAccount {
+    pub fn new(key: Pubkey, owner: Pubkey, lamports: u64, data: &[u8]) -> Self {
+        let words = (DATA_OFFSET + data.len() + MAX_PERMITTED_DATA_INCREASE).div_ceil(8);
+        let buf = Box::into_raw(vec![0u64; words].into_boxed_slice());
+        let account = Self { buf };
+
+        let bytes = account.bytes_mut();
+        bytes[BORROW_STATE_OFFSET] = NOT_BORROWED;
+        bytes[IS_WRITABLE_OFFSET] = 1;
+        bytes[KEY_OFFSET..KEY_OFFSET + 32].copy_from_slice(&key);
+        bytes[OWNER_OFFSET..OWNER_OFFSET + 32].copy_from_slice(&owner);
+        bytes[LAMPORTS_OFFSET..LAMPORTS_OFFSET + 8].copy_from_slice(&lamports.to_le_bytes());
+        bytes[DATA_LEN_OFFSET..DATA_LEN_OFFSET + 8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        bytes[DATA_OFFSET..DATA_OFFSET + data.len()].copy_from_slice(data);
+        account
+    }
+
+    pub fn signer(self) -> Self {
+        self.bytes_mut()[IS_SIGNER_OFFSET] = 1;
+        self
+    }
+
+    pub fn read_only(self) -> Self {
+        self.bytes_mut()[IS_WRITABLE_OFFSET] = 0;
+        self
+    }
+
+    // an AccountInfo over this buffer, valid for as long as the mock lives
+    pub fn info(&self) -> AccountInfo {
+        // AccountInfo is a single pointer to the serialized account
+        unsafe { core::mem::transmute::<*mut u8, AccountInfo>(self.buf as *mut u8) }
+    }
+
+    pub fn owner(&self) -> Pubkey {
+        self.bytes()[OWNER_OFFSET..OWNER_OFFSET + 32].try_into().unwrap()
+    }
+
+    pub fn lamports(&self) -> u64 {
+        u64::from_le_bytes(self.bytes()[LAMPORTS_OFFSET..LAMPORTS_OFFSET + 8].try_into().unwrap())
+    }
+
+    pub fn data(&self) -> &[u8] {
+        let len = u64::from_le_bytes(self.bytes()[DATA_LEN_OFFSET..DATA_LEN_OFFSET + 8].try_into().unwrap());
+        &self.bytes()[DATA_OFFSET..DATA_OFFSET + len as usize]
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.buf as *const u8, (*self.buf).len() * 8) }
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    fn bytes_mut(&self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.buf as *mut u8, (*self.buf).len() * 8) }
+    }
+}
+
+impl Drop for MockAccount {
+    fn drop(&mut self) {
+        unsafe { drop(Box::from_raw(self.buf)) };
+    }
+}
+
+// AccountInfos for a list of mocks, in order, ready for *Accounts::from_slice
+pub fn account_infos(accounts: &[&MockAccount]) -> Vec<AccountInfo> {
+    accounts.iter().map(|account| account.info()).collect()
+}
@@ -0,0 +1,120 @@
+// derive-and-compare checks for the escrow and vault PDAs, shared by the
+// handlers so every mismatch is the same InvalidEscrowAccount. Each
+// derivation is a find_program_address, the most expensive check a handler
+// runs, so handlers call these last
+use crate::{
+    error::EscrowError,
+    instructions::{
+        config::find_config_address,
+        make::{find_namespaced_escrow_address, find_receive_vault_address, find_vault_address, DEFAULT_ESCROW_NAMESPACE, ESCROW_NAMESPACE_LEN},
+        take::find_receipt_address,
+        vesting::find_vesting_address,
+    },
+};
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+// check `escrow_account` is the maker's escrow PDA for `seed` in the default
+// namespace, returning its bump
+pub fn verify_escrow_pda(
+    escrow_account: &AccountInfo,
+    maker: &Pubkey,
+    seed: u64,
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    verify_namespaced_escrow_pda(escrow_account, maker, &DEFAULT_ESCROW_NAMESPACE, seed, program_id)
+}
+
+// check `escrow_account` is the maker's escrow PDA for `seed` in `namespace`,
+// returning its bump
+pub fn verify_namespaced_escrow_pda(
+    escrow_account: &AccountInfo,
+    maker: &Pubkey,
+    namespace: &[u8; ESCROW_NAMESPACE_LEN],
+    seed: u64,
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (escrow_key, bump) = find_namespaced_escrow_address(maker, namespace, seed, program_id);
+    if escrow_key != *escrow_account.key() {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    Ok(bump)
+}
+
+// check `vault_account` is the vault PDA of `escrow`, returning its bump
+pub fn verify_vault_pda(
+    vault_account: &AccountInfo,
+    escrow: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (vault_key, bump) = find_vault_address(escrow, program_id);
+    if vault_key != *vault_account.key() {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    Ok(bump)
+}
+
+// check `vesting_account` is the maker's vesting PDA for `seed`, returning its bump
+pub fn verify_vesting_pda(
+    vesting_account: &AccountInfo,
+    maker: &Pubkey,
+    seed: u64,
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (vesting_key, bump) = find_vesting_address(maker, seed, program_id);
+    if vesting_key != *vesting_account.key() {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    Ok(bump)
+}
+
+// check `receipt_account` is the receipt PDA of `taker`'s take of `escrow`
+// at `created_at` and `filled`, returning its bump
+pub fn verify_receipt_pda(
+    receipt_account: &AccountInfo,
+    escrow: &Pubkey,
+    taker: &Pubkey,
+    created_at: i64,
+    filled: u64,
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (receipt_key, bump) = find_receipt_address(escrow, taker, created_at, filled, program_id);
+    if receipt_key != *receipt_account.key() {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    Ok(bump)
+}
+
+// check `receive_account` is the maker's receive vault PDA for `seed`,
+// returning its bump
+pub fn verify_receive_vault_pda(
+    receive_account: &AccountInfo,
+    maker: &Pubkey,
+    seed: u64,
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (receive_key, bump) = find_receive_vault_address(maker, seed, program_id);
+    if receive_key != *receive_account.key() {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    Ok(bump)
+}
+
+// check `config_account` is the program's config PDA, returning its bump
+pub fn verify_config_pda(config_account: &AccountInfo, program_id: &Pubkey) -> Result<u8, ProgramError> {
+    let (config_key, bump) = find_config_address(program_id);
+    if config_key != *config_account.key() {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    Ok(bump)
+}
+
+// check `config_account` is the config PDA for the bump the config stored,
+// a single derivation instead of a bump search
+pub fn verify_config_bump(config_account: &AccountInfo, bump: u8, program_id: &Pubkey) -> Result<(), ProgramError> {
+    let config_key = Pubkey::create_program_address(&[b"config", &[bump]], program_id)
+        .map_err(|_| ProgramError::from(EscrowError::InvalidEscrowAccount))?;
+    if config_key != *config_account.key() {
+        return Err(EscrowError::InvalidEscrowAccount.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+// the programs the handlers invoke or check accounts against, and the
+// checks that an account passed as one of them is the real one
+use crate::error::EscrowError;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, ProgramResult, spl_token, system_program};
+
+// Pinocchio constants
+pub use spl_token::ID as TOKEN_PROGRAM_ID;
+pub use system_program::ID as SYSTEM_PROGRAM_ID;
+
+// Token-2022 program (TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb)
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = [
+    6, 221, 246, 225, 238, 117, 143, 222, 24, 66, 93, 188, 228, 108, 205, 218,
+    182, 26, 252, 77, 131, 185, 13, 39, 254, 189, 249, 40, 216, 161, 139, 252,
+];
+
+// associated token account program (ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL)
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = [
+    140, 151, 37, 143, 78, 36, 137, 241, 187, 61, 16, 41, 20, 142, 13, 131,
+    11, 90, 19, 153, 218, 255, 16, 132, 4, 142, 123, 216, 219, 233, 248, 89,
+];
+
+// upgradeable BPF loader (BPFLoaderUpgradeab1e11111111111111111111111)
+pub const BPF_LOADER_UPGRADEABLE_ID: Pubkey = [
+    2, 168, 246, 145, 78, 136, 161, 176, 226, 16, 21, 62, 247, 99, 174, 43,
+    0, 194, 185, 61, 22, 193, 36, 210, 192, 83, 122, 16, 4, 128, 0, 0,
+];
+
+// any account passed as the associated token program must be the real one
+// before ATA creation is invoked, a spoofed program could take over the CPI
+pub fn verify_associated_token_program(program: &Pubkey) -> ProgramResult {
+    if program != &ASSOCIATED_TOKEN_PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+// any account passed as the system program must be the real one before a
+// system CPI (account creation, lamport transfers) is invoked through it
+pub fn verify_system_program(program: &Pubkey) -> ProgramResult {
+    if program != &SYSTEM_PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+// the legacy token program, or Token-2022 whose transfers go through
+// TransferChecked so mints with a transfer fee work
+pub fn verify_token_program(program: &Pubkey) -> ProgramResult {
+    if program != &TOKEN_PROGRAM_ID && program != &TOKEN_2022_PROGRAM_ID {
+        return Err(EscrowError::InvalidTokenProgram.into());
+    }
+    Ok(())
+}
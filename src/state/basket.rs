@@ -0,0 +1,199 @@
+use crate::error::EscrowError;
+use super::{discriminator_matches, EscrowStatus};
+use pinocchio::{
+    account_info::AccountInfo,
+    pubkey::Pubkey,
+    program_error::ProgramError,
+    account_validation::{AccountValidation, ValidateAccount},
+};
+
+// most token A mints one basket can offer. Every leg adds a vault and two
+// token CPIs to take and refund, so this keeps them inside the compute budget
+pub const MAX_BASKET_LEGS: usize = 4;
+
+// one token A entry of a basket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BasketLeg {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub vault_bump: u8,
+}
+
+// an offer of several token A mints for a single token B payment, taken or
+// refunded as a whole. Leg i keeps its tokens in the vault PDA
+// ["vault", basket, i]
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct BasketEscrow {
+    // discriminator to identify account type
+    pub discriminator: [u8; 8],
+
+    // the maker who created the basket
+    pub maker: Pubkey,
+
+    // the mint of token B (Taker's token)
+    pub mint_b: Pubkey,
+
+    // the maker's token account for receiving token B
+    pub receive_account: Pubkey,
+
+    // token A mint of each leg, zeroed past leg_count
+    pub mints: [Pubkey; MAX_BASKET_LEGS],
+
+    // token A amount of each leg, zeroed past leg_count
+    pub amounts: [u64; MAX_BASKET_LEGS],
+
+    // the amount of token B the maker asks for the whole basket
+    pub receive_amount: u64,
+
+    // unix timestamp after which the basket can no longer be taken (0 = never expires)
+    pub expiry: i64,
+
+    // bump seed for the basket PDA
+    pub bump: u8,
+
+    // bump seed of each leg's vault PDA
+    pub vault_bumps: [u8; MAX_BASKET_LEGS],
+
+    // number of legs in use
+    pub leg_count: u8,
+
+    // EscrowStatus, stored as its u8 value
+    pub status: u8,
+}
+
+impl AccountValidation for BasketEscrow {
+    fn validate_account<'a>(account: &'a AccountInfo) -> Result<&'a mut Self, ProgramError> {
+        // never cast less than a whole BasketEscrow
+        if account.data_len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        let basket = unsafe {
+            let mut data = account.try_borrow_mut_data()?;
+            let basket = &mut *(data.as_mut_ptr() as *mut BasketEscrow);
+
+            // Verify discriminator
+            if !discriminator_matches(&basket.discriminator, &Self::DISCRIMINATOR) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            basket
+        };
+
+        Ok(basket)
+    }
+}
+
+impl BasketEscrow {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 * MAX_BASKET_LEGS + 8 * MAX_BASKET_LEGS + 8 + 8 + 1 + MAX_BASKET_LEGS + 1 + 1;
+    pub const DISCRIMINATOR: [u8; 8] = [52, 198, 17, 240, 83, 9, 161, 74];
+
+    // a basket holds between one and MAX_BASKET_LEGS legs
+    pub fn check_leg_count(count: usize) -> Result<(), ProgramError> {
+        if count == 0 || count > MAX_BASKET_LEGS {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        Ok(())
+    }
+
+    // initialize a new BasketEscrow account
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(
+        account: &AccountInfo,
+        maker: Pubkey,
+        mint_b: Pubkey,
+        receive_account: Pubkey,
+        legs: &[BasketLeg],
+        receive_amount: u64,
+        expiry: i64,
+        bump: u8,
+    ) -> Result<(), ProgramError> {
+        Self::check_leg_count(legs.len())?;
+
+        let mut mints = [[0u8; 32]; MAX_BASKET_LEGS];
+        let mut amounts = [0u64; MAX_BASKET_LEGS];
+        let mut vault_bumps = [0u8; MAX_BASKET_LEGS];
+        for (i, leg) in legs.iter().enumerate() {
+            mints[i] = leg.mint;
+            amounts[i] = leg.amount;
+            vault_bumps[i] = leg.vault_bump;
+        }
+
+        let basket = BasketEscrow {
+            discriminator: Self::DISCRIMINATOR,
+            maker,
+            mint_b,
+            receive_account,
+            mints,
+            amounts,
+            receive_amount,
+            expiry,
+            bump,
+            vault_bumps,
+            leg_count: legs.len() as u8,
+            status: EscrowStatus::Active as u8,
+        };
+
+        unsafe {
+            let mut data = account.try_borrow_mut_data()?;
+            let dst = data.as_mut_ptr() as *mut BasketEscrow;
+            *dst = basket;
+        }
+
+        Ok(())
+    }
+
+    // load a BasketEscrow account owned by `program_id`
+    pub fn from_account<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<&'a mut Self, ProgramError> {
+        if !account.is_owned_by(program_id) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::validate_account(account)
+    }
+
+    // the legs in use, in vault index order
+    pub fn legs(&self) -> impl Iterator<Item = BasketLeg> + '_ {
+        let count = (self.leg_count as usize).min(MAX_BASKET_LEGS);
+        (0..count).map(|i| BasketLeg {
+            mint: self.mints[i],
+            amount: self.amounts[i],
+            vault_bump: self.vault_bumps[i],
+        })
+    }
+
+    // past its deadline: can no longer be taken, only refunded
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expiry != 0 && now > self.expiry
+    }
+
+    // where the basket is in its lifecycle
+    pub fn status(&self) -> Result<EscrowStatus, ProgramError> {
+        EscrowStatus::try_from(self.status)
+    }
+
+    // same reentrancy guard as Escrow::begin_processing
+    pub fn begin_processing(&mut self) -> Result<(), ProgramError> {
+        if self.status()? != EscrowStatus::Active {
+            return Err(EscrowError::InvalidState.into());
+        }
+        self.status = EscrowStatus::Processing as u8;
+        Ok(())
+    }
+
+    // leave the Processing state once all CPIs are done
+    pub fn finish(&mut self, status: EscrowStatus) {
+        self.status = status as u8;
+    }
+
+    // check that the given key is the maker
+    pub fn verify_maker(&self, maker: &Pubkey) -> Result<(), ProgramError> {
+        if self.maker != *maker {
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+        Ok(())
+    }
+}
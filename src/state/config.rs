@@ -0,0 +1,93 @@
+use super::discriminator_matches;
+use pinocchio::{
+    account_info::AccountInfo,
+    pubkey::Pubkey,
+    program_error::ProgramError,
+    account_validation::{AccountValidation, ValidateAccount},
+};
+
+// program-wide settings in the config PDA ["config"]. There is one per
+// deployment, created by InitConfig, whose signer becomes the admin
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Config {
+    // discriminator to identify account type
+    pub discriminator: [u8; 8],
+
+    // the only key that can change the config
+    pub admin: Pubkey,
+
+    // 1 while the admin has paused new makes and takes, refunds stay open
+    pub paused: u8,
+
+    // bump seed for the config PDA
+    pub bump: u8,
+}
+
+impl AccountValidation for Config {
+    fn validate_account<'a>(account: &'a AccountInfo) -> Result<&'a mut Self, ProgramError> {
+        // never cast less than a whole Config
+        if account.data_len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        let config = unsafe {
+            let mut data = account.try_borrow_mut_data()?;
+            let config = &mut *(data.as_mut_ptr() as *mut Config);
+
+            // Verify discriminator
+            if !discriminator_matches(&config.discriminator, &Self::DISCRIMINATOR) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            config
+        };
+
+        Ok(config)
+    }
+}
+
+impl Config {
+    pub const LEN: usize = 8 + 32 + 1 + 1;
+    pub const DISCRIMINATOR: [u8; 8] = [38, 217, 150, 64, 11, 203, 92, 170];
+
+    // whether raw account data starts with the Config discriminator
+    pub fn check_discriminator(data: &[u8]) -> bool {
+        discriminator_matches(data, &Self::DISCRIMINATOR)
+    }
+
+    // write a config into a freshly created account, unpaused
+    pub fn init(account: &AccountInfo, admin: Pubkey, bump: u8) -> Result<(), ProgramError> {
+        if account.data_len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        unsafe {
+            let mut data = account.try_borrow_mut_data()?;
+            let dst = data.as_mut_ptr() as *mut Config;
+            *dst = Config {
+                discriminator: Self::DISCRIMINATOR,
+                admin,
+                paused: 0,
+                bump,
+            };
+        }
+
+        Ok(())
+    }
+
+    // load a Config account owned by `program_id`
+    pub fn from_account<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<&'a mut Self, ProgramError> {
+        if !account.is_owned_by(program_id) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::validate_account(account)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused != 0
+    }
+}
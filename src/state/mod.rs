@@ -1,12 +1,183 @@
+use crate::{
+    error::EscrowError,
+    math::{mul_div_ceil, mul_div_floor},
+    programs::SYSTEM_PROGRAM_ID,
+    sysvar::current_rent,
+    token::{token_account_amount, NATIVE_MINT},
+};
 use pinocchio::{
     account_info::AccountInfo,
+    msg,
     pubkey::Pubkey,
     program_error::ProgramError,
     account_validation::{AccountValidation, ValidateAccount},
 };
 
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub mod basket;
+pub use basket::{BasketEscrow, BasketLeg, MAX_BASKET_LEGS};
+pub mod vesting;
+pub use vesting::VestingEscrow;
+pub mod receipt;
+pub use receipt::TakeReceipt;
+pub mod config;
+pub use config::Config;
+
+// what the escrow vault holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EscrowKind {
+    // the vault is an SPL token account for mint_a
+    Token = 0,
+    // the vault is a program-owned account holding lamports
+    Native = 1,
+}
+
+impl TryFrom<u8> for EscrowKind {
+    type Error = ProgramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(EscrowKind::Token),
+            1 => Ok(EscrowKind::Native),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+// lifecycle of an escrow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EscrowStatus {
+    // open for take/refund
+    Active = 0,
+    // a mutating handler is running, any re-entry is rejected
+    Processing = 1,
+    // taken
+    Completed = 2,
+    // refunded
+    Cancelled = 3,
+}
+
+impl TryFrom<u8> for EscrowStatus {
+    type Error = ProgramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(EscrowStatus::Active),
+            1 => Ok(EscrowStatus::Processing),
+            2 => Ok(EscrowStatus::Completed),
+            3 => Ok(EscrowStatus::Cancelled),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+// how the token B owed for a fill is priced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PriceMode {
+    // the whole offer costs `receive_amount`
+    Fixed = 0,
+    // token B = amount * price_num / price_den, rounded up in the maker's favour
+    Ratio = 1,
+}
+
+impl TryFrom<u8> for PriceMode {
+    type Error = ProgramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PriceMode::Fixed),
+            1 => Ok(PriceMode::Ratio),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+// token B owed for `amount` of token A at price_num/price_den, rounded up so
+// the maker is never paid less than the quoted price
+pub fn price_amount(amount: u64, price_num: u64, price_den: u64) -> Result<u64, ProgramError> {
+    mul_div_ceil(amount, price_num, price_den).map_err(Into::into)
+}
+
+// whether `data` starts with `expected`. Every byte is compared no matter
+// where the first difference is, so the time taken doesn't depend on how
+// much of the discriminator matches
+pub fn discriminator_matches(data: &[u8], expected: &[u8; 8]) -> bool {
+    let Some(actual) = data.get(..8) else {
+        return false;
+    };
+    let diff = actual
+        .iter()
+        .zip(expected)
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b));
+    diff == 0
+}
+
+// the discriminator of every account type this program owns, the documented
+// source of those magic bytes. They were fixed when each type was added and
+// are not a hash of the type name, so changing them would orphan live
+// accounts. They also differ from the Anchor-style sha256("account:<Name>")
+// prefix, so an Anchor account named like one of ours is never mistaken for
+// it. Programs composing with this one can check their own discriminators
+// against this table
+pub const ACCOUNT_DISCRIMINATORS: [(&str, [u8; 8]); 5] = [
+    ("Escrow", Escrow::DISCRIMINATOR),
+    ("BasketEscrow", BasketEscrow::DISCRIMINATOR),
+    ("VestingEscrow", VestingEscrow::DISCRIMINATOR),
+    ("TakeReceipt", TakeReceipt::DISCRIMINATOR),
+    ("Config", Config::DISCRIMINATOR),
+];
+
+// longest memo a maker can attach to an escrow
+pub const MAX_MEMO_LEN: usize = 64;
+
+// layout version written by make and MigrateEscrow
+// 0: the original layout, up to `amount` and the escrow bump (145 bytes)
+// 1: the version 2 fields without the version byte (254 bytes)
+// 2: up to the version byte, without the arbiter (255 bytes)
+// 3: up to the arbiter, without the creation time (287 bytes)
+// 4: up to the creation time, without the minimum fill (295 bytes)
+// 5: up to the minimum fill, without the persistent flag (303 bytes)
+// 6: the current layout
+pub const ESCROW_VERSION: u8 = 6;
+
+// every Escrow field in declaration order, see Escrow::as_tuple
+pub type EscrowTuple = (
+    [u8; 8],
+    Pubkey,
+    Pubkey,
+    Pubkey,
+    Pubkey,
+    u64,
+    u64,
+    u64,
+    i64,
+    u64,
+    u64,
+    u8,
+    u8,
+    u8,
+    u8,
+    u8,
+    u8,
+    [u8; MAX_MEMO_LEN],
+    u8,
+    Pubkey,
+    [u8; 8],
+    [u8; 8],
+    u8,
+);
+
 // Escrow account structure
+// with the `borsh` feature the derived encoding writes the fields in declaration
+// order without padding, which is the same as the first `Escrow::LEN` bytes
+// of the on-chain account
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 #[repr(C)]
 pub struct Escrow {
     // discriminator to identify account type
@@ -27,18 +198,75 @@ pub struct Escrow {
     // the amount of token A the maker deposits
     pub amount: u64,
     
+    // the amount of token A deposited in make, never decremented
+    pub original_amount: u64,
+    
+    // the amount of token B the maker asks for
+    pub receive_amount: u64,
+    
+    // unix timestamp after which the offer can no longer be taken (0 = never expires)
+    pub expiry: i64,
+    
+    // token B per token A as a fraction, only used in PriceMode::Ratio
+    pub price_num: u64,
+    pub price_den: u64,
+    
     // bump seed for the escrow PDA
     pub bump: u8,
+    
+    // bump seed for the vault PDA
+    pub vault_bump: u8,
+    
+    // EscrowKind of the vault, stored as its u8 value
+    pub kind: u8,
+    
+    // EscrowStatus, stored as its u8 value
+    pub status: u8,
+    
+    // PriceMode, stored as its u8 value
+    pub price_mode: u8,
+    
+    // number of bytes of `memo` in use
+    pub memo_len: u8,
+    
+    // opaque reference set by the maker for off-chain reconciliation,
+    // zero padded past memo_len
+    pub memo: [u8; MAX_MEMO_LEN],
+    
+    // layout version, always at VERSION_OFFSET. Fields added later go after it
+    pub version: u8,
+    
+    // may settle the escrow to either side until it expires, all zeros when
+    // the escrow has no arbiter (added in version 3)
+    pub arbiter: Pubkey,
+    
+    // unix timestamp of the make that created the escrow, little endian so
+    // the field doesn't pad the layout, see created_at(). Zero for escrows
+    // migrated from before version 4
+    pub created_at: [u8; 8],
+    
+    // smallest partial fill of token A, except for the one that takes the
+    // rest of the offer. Little endian like created_at, see min_fill(). Zero
+    // (any fill) for escrows migrated from before version 5
+    pub min_fill: [u8; 8],
+    
+    // 1 when the escrow stays open after its last token A is taken, for the
+    // maker to refill. 0 (closed by the last take) for escrows migrated from
+    // before version 6
+    pub persistent: u8,
 }
 
 impl AccountValidation for Escrow {
     fn validate_account<'a>(account: &'a AccountInfo) -> Result<&'a mut Self, ProgramError> {
+        // never cast less than a whole Escrow, a short account would be read out of bounds
+        Self::check_data_len(account.data_len())?;
+        
         let escrow = unsafe {
             let mut data = account.try_borrow_mut_data()?;
             let escrow = &mut *(data.as_mut_ptr() as *mut Escrow);
             
             // Verify discriminator
-            if escrow.discriminator != Self::DISCRIMINATOR {
+            if !Self::check_discriminator(&escrow.discriminator) {
                 return Err(ProgramError::InvalidAccountData);
             }
             
@@ -50,10 +278,28 @@ impl AccountValidation for Escrow {
 }
 
 impl Escrow {
-    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 8 + 1;
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 1 + MAX_MEMO_LEN + 1 + 32 + 8 + 8 + 1;
+    // see ACCOUNT_DISCRIMINATORS
     pub const DISCRIMINATOR: [u8; 8] = [139, 11, 230, 78, 92, 65, 103, 116];
+    pub const VERSION_OFFSET: usize = 254;
+    
+    // sizes of the layouts before the version byte, see ESCROW_VERSION
+    pub const V0_LEN: usize = 145;
+    pub const V1_LEN: usize = 254;
+    // and of the layouts with one, before the arbiter and the creation time
+    pub const V2_LEN: usize = 255;
+    pub const V3_LEN: usize = 287;
+    pub const V4_LEN: usize = 295;
+    pub const V5_LEN: usize = 303;
+    
+    // the maker sits right after the discriminator in every layout
+    pub const MAKER_OFFSET: usize = 8;
     
-    // initialize a new Escrow account
+    // initialize a new Escrow account, a zero `price_den` means PriceMode::Fixed
+    // and an all-zero `arbiter` means no arbiter. `created_at` is the unix
+    // timestamp of the make, a zero `min_fill` allows fills of any size and a
+    // `persistent` escrow outlives its last take
+    #[allow(clippy::too_many_arguments)]
     pub fn init(
         account: &AccountInfo,
         maker: Pubkey,
@@ -61,8 +307,26 @@ impl Escrow {
         mint_b: Pubkey,
         receive_account: Pubkey,
         amount: u64,
+        receive_amount: u64,
+        expiry: i64,
         bump: u8,
+        vault_bump: u8,
+        kind: EscrowKind,
+        memo: &[u8],
+        price_num: u64,
+        price_den: u64,
+        arbiter: Pubkey,
+        created_at: i64,
+        min_fill: u64,
+        persistent: bool,
     ) -> Result<(), ProgramError> {
+        let price_mode = if price_den == 0 { PriceMode::Fixed } else { PriceMode::Ratio };
+        
+        if memo.len() > MAX_MEMO_LEN {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        let mut memo_bytes = [0u8; MAX_MEMO_LEN];
+        memo_bytes[..memo.len()].copy_from_slice(memo);
         let escrow = Escrow {
             discriminator: Self::DISCRIMINATOR,
             maker,
@@ -70,25 +334,430 @@ impl Escrow {
             mint_b,
             receive_account,
             amount,
+            original_amount: amount,
+            receive_amount,
+            expiry,
+            price_num,
+            price_den,
             bump,
+            vault_bump,
+            kind: kind as u8,
+            status: EscrowStatus::Active as u8,
+            price_mode: price_mode as u8,
+            memo_len: memo.len() as u8,
+            memo: memo_bytes,
+            version: ESCROW_VERSION,
+            arbiter,
+            created_at: created_at.to_le_bytes(),
+            min_fill: min_fill.to_le_bytes(),
+            persistent: persistent as u8,
         };
         
+        escrow.store(account)
+    }
+    
+    // write the whole struct over the start of the account's data
+    pub fn store(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        Self::check_data_len(account.data_len())?;
         unsafe {
             let mut data = account.try_borrow_mut_data()?;
             let dst = data.as_mut_ptr() as *mut Escrow;
-            *dst = escrow;
+            *dst = *self;
         }
-        
         Ok(())
     }
     
+    // raw account data starts with the Escrow discriminator, in constant time
+    pub fn check_discriminator(data: &[u8]) -> bool {
+        discriminator_matches(data, &Self::DISCRIMINATOR)
+    }
+    
+    // layout version of raw escrow account data. Layouts before the version
+    // byte are told apart by their length
+    pub fn layout_version(data: &[u8]) -> Result<u8, ProgramError> {
+        if data.len() < Self::V0_LEN || !Self::check_discriminator(data) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        match data.len() {
+            Self::V0_LEN => Ok(0),
+            Self::V1_LEN => Ok(1),
+            len if len >= Self::V2_LEN => Ok(data[Self::VERSION_OFFSET]),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+    
+    // maker of raw escrow account data in any layout
+    pub fn maker_of(data: &[u8]) -> Result<Pubkey, ProgramError> {
+        data.get(Self::MAKER_OFFSET..Self::MAKER_OFFSET + 32)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+    
+    // a version 0 escrow in the current layout. Fields v0 didn't have keep
+    // v0 behaviour: token B is paid 1:1, no expiry, no memo, unknown
+    // creation time
+    pub fn from_v0(data: &[u8], vault_bump: u8) -> Result<Self, ProgramError> {
+        if Self::layout_version(data)? != 0 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let pubkey = |offset: usize| -> Pubkey { data[offset..offset + 32].try_into().unwrap() };
+        let amount = u64::from_le_bytes(data[136..144].try_into().unwrap());
+        Ok(Escrow {
+            discriminator: Self::DISCRIMINATOR,
+            maker: pubkey(8),
+            mint_a: pubkey(40),
+            mint_b: pubkey(72),
+            receive_account: pubkey(104),
+            amount,
+            original_amount: amount,
+            receive_amount: amount,
+            expiry: 0,
+            price_num: 0,
+            price_den: 0,
+            bump: data[144],
+            vault_bump,
+            kind: EscrowKind::Token as u8,
+            status: EscrowStatus::Active as u8,
+            price_mode: PriceMode::Fixed as u8,
+            memo_len: 0,
+            memo: [0u8; MAX_MEMO_LEN],
+            version: ESCROW_VERSION,
+            arbiter: [0u8; 32],
+            created_at: [0u8; 8],
+            min_fill: [0u8; 8],
+            persistent: 0,
+        })
+    }
+    
     // load an Escrow account from the AccountInfo
-    pub fn from_account(account: &AccountInfo) -> Result<&mut Self, ProgramError> {
+    // the owner is checked here rather than in validate_account because the
+    // library can be deployed under any program id, so it has to be passed in
+    pub fn from_account<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<&'a mut Self, ProgramError> {
+        if !account.is_owned_by(program_id) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
         Self::validate_account(account)
     }
     
+    // an escrow account holds at least a whole Escrow. Longer accounts are
+    // accepted so fields appended by a later version don't lock out this one.
+    // Older, shorter layouts have to go through MigrateEscrow first
+    pub fn check_data_len(len: usize) -> Result<(), ProgramError> {
+        if len < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        Ok(())
+    }
+    
+    // closed escrows are zeroed (and possibly resized to nothing), so a second
+    // refund/take of the same escrow lands here instead of failing on the
+    // discriminator with a generic InvalidAccountData
+    pub fn check_not_closed(data: &[u8]) -> Result<(), ProgramError> {
+        if data.iter().all(|byte| *byte == 0) {
+            msg!("escrow already closed or not initialized");
+            return Err(EscrowError::InvalidEscrowAccount.into());
+        }
+        Ok(())
+    }
+    
+    // an escrow drained of its lamports by an earlier close is gone even if
+    // its data hasn't been wiped yet, so take and refund stop here rather
+    // than working on an account the runtime is about to garbage collect
+    pub fn check_live(account: &AccountInfo) -> Result<(), ProgramError> {
+        if account.lamports() == 0 {
+            msg!("escrow has no lamports left, already closed");
+            return Err(EscrowError::InvalidEscrowAccount.into());
+        }
+        Self::check_not_closed(&account.try_borrow_data()?)
+    }
+    
     //check if the account has been initialized
     pub fn is_initialized(&self) -> bool {
-        self.discriminator == Self::DISCRIMINATOR
+        Self::check_discriminator(&self.discriminator)
+    }
+    
+    // canonical bump of the escrow PDA, stored in make
+    pub fn escrow_bump(&self) -> u8 {
+        self.bump
+    }
+    
+    // canonical bump of the vault PDA, stored in make
+    pub fn vault_bump(&self) -> u8 {
+        self.vault_bump
+    }
+    
+    // past its deadline: can no longer be taken, only refunded
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expiry != 0 && now > self.expiry
+    }
+    
+    // whether the vault holds SPL tokens or lamports
+    pub fn kind(&self) -> Result<EscrowKind, ProgramError> {
+        EscrowKind::try_from(self.kind)
+    }
+    
+    // all fields in declaration order, for indexers and tests. The pattern
+    // has no `..`, so a new field doesn't compile until it is added here
+    pub fn as_tuple(&self) -> EscrowTuple {
+        let Self {
+            discriminator,
+            maker,
+            mint_a,
+            mint_b,
+            receive_account,
+            amount,
+            original_amount,
+            receive_amount,
+            expiry,
+            price_num,
+            price_den,
+            bump,
+            vault_bump,
+            kind,
+            status,
+            price_mode,
+            memo_len,
+            memo,
+            version,
+            arbiter,
+            created_at,
+            min_fill,
+            persistent,
+        } = *self;
+        (
+            discriminator,
+            maker,
+            mint_a,
+            mint_b,
+            receive_account,
+            amount,
+            original_amount,
+            receive_amount,
+            expiry,
+            price_num,
+            price_den,
+            bump,
+            vault_bump,
+            kind,
+            status,
+            price_mode,
+            memo_len,
+            memo,
+            version,
+            arbiter,
+            created_at,
+            min_fill,
+            persistent,
+        )
+    }
+    
+    // unix timestamp of the make, 0 when unknown (migrated escrows)
+    pub fn created_at(&self) -> i64 {
+        i64::from_le_bytes(self.created_at)
+    }
+    
+    // seconds since the make as of `now`, None when the creation time is
+    // unknown
+    pub fn age(&self, now: i64) -> Option<i64> {
+        match self.created_at() {
+            0 => None,
+            created_at => Some(now.saturating_sub(created_at)),
+        }
+    }
+    
+    // smallest partial fill of token A, 0 when any fill is allowed
+    pub fn min_fill(&self) -> u64 {
+        u64::from_le_bytes(self.min_fill)
     }
-} 
\ No newline at end of file
+    
+    // a partial fill can't be smaller than min_fill, so makers aren't paid
+    // in dust. The fill that takes the rest of the offer is always allowed,
+    // however small the rest has become
+    pub fn check_fill(&self, filled: u64) -> Result<(), ProgramError> {
+        if filled < self.min_fill() && filled != self.amount {
+            msg!("fill is below the escrow's minimum");
+            return Err(EscrowError::FillBelowMinimum.into());
+        }
+        Ok(())
+    }
+    
+    // whether the escrow stays open, drained, after its last token A is taken
+    pub fn is_persistent(&self) -> bool {
+        self.persistent != 0
+    }
+    
+    // whether an arbiter may settle this escrow
+    pub fn has_arbiter(&self) -> bool {
+        self.arbiter != [0u8; 32]
+    }
+    
+    // an escrow is held by its arbiter until it expires: the maker can't
+    // refund or force close it out from under a dispute in the meantime
+    pub fn check_not_held(&self, now: i64) -> Result<(), ProgramError> {
+        if self.has_arbiter() && !self.is_expired(now) {
+            msg!("escrow is held by its arbiter until it expires");
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+        Ok(())
+    }
+    
+    // the maker's memo without its padding
+    pub fn memo(&self) -> &[u8] {
+        &self.memo[..(self.memo_len as usize).min(MAX_MEMO_LEN)]
+    }
+    
+    // fixed total or per-unit price
+    pub fn price_mode(&self) -> Result<PriceMode, ProgramError> {
+        PriceMode::try_from(self.price_mode)
+    }
+    
+    // token B the taker pays for `amount` of token A. A whole fill of a
+    // fixed-price escrow costs `receive_amount`, a partial one its share of
+    // it, rounded up in the maker's favour
+    pub fn counter_amount(&self, amount: u64) -> Result<u64, ProgramError> {
+        match self.price_mode()? {
+            PriceMode::Fixed if amount >= self.amount => Ok(self.receive_amount),
+            PriceMode::Fixed => mul_div_ceil(self.receive_amount, amount, self.amount).map_err(Into::into),
+            PriceMode::Ratio => price_amount(amount, self.price_num, self.price_den),
+        }
+    }
+    
+    // book a partial fill: `filled` token A left the vault for `paid` token B
+    pub fn record_fill(&mut self, filled: u64, paid: u64) {
+        self.amount = self.amount.saturating_sub(filled);
+        self.receive_amount = self.receive_amount.saturating_sub(paid);
+    }
+    
+    // book a refill: `added` token A reached the vault, offered for
+    // `receive_amount` more token B. A priced offer asks whatever the new
+    // total costs at its price instead, as in make
+    pub fn top_up(&mut self, added: u64, receive_amount: u64) -> Result<(), ProgramError> {
+        let amount = self.amount.checked_add(added).ok_or(EscrowError::AmountOverflow)?;
+        let original_amount = self.original_amount.checked_add(added).ok_or(EscrowError::AmountOverflow)?;
+        let receive_amount = match self.price_mode()? {
+            PriceMode::Fixed => self
+                .receive_amount
+                .checked_add(receive_amount)
+                .ok_or(EscrowError::AmountOverflow)?,
+            PriceMode::Ratio => price_amount(amount, self.price_num, self.price_den)?,
+        };
+        self.amount = amount;
+        self.original_amount = original_amount;
+        self.receive_amount = receive_amount;
+        Ok(())
+    }
+    
+    // a token escrow of wrapped SOL, whose vault needs SyncNative before its
+    // amount can be trusted
+    pub fn is_wrapped_sol(&self) -> bool {
+        self.kind == EscrowKind::Token as u8 && self.mint_a == NATIVE_MINT
+    }
+    
+    // where the escrow is in its lifecycle
+    pub fn status(&self) -> Result<EscrowStatus, ProgramError> {
+        EscrowStatus::try_from(self.status)
+    }
+    
+    // reentrancy guard: the escrow must be Active on entry and stays
+    // Processing until the handler sets a terminal state with finish
+    pub fn begin_processing(&mut self) -> Result<(), ProgramError> {
+        if self.status()? != EscrowStatus::Active {
+            return Err(EscrowError::InvalidState.into());
+        }
+        self.status = EscrowStatus::Processing as u8;
+        Ok(())
+    }
+    
+    // leave the Processing state once all CPIs are done
+    pub fn finish(&mut self, status: EscrowStatus) {
+        self.status = status as u8;
+    }
+    
+    // token A taken from the escrow so far. Refills raise the original
+    // amount and the amount alike, so every fill still moves it forward
+    pub fn filled_amount(&self) -> u64 {
+        self.original_amount.saturating_sub(self.amount)
+    }
+    
+    // how much of the original deposit has been filled, in basis points
+    pub fn fill_ratio(&self) -> u16 {
+        if self.original_amount == 0 {
+            return 0;
+        }
+        let filled = self.filled_amount();
+        // filled never exceeds the original amount, so this is at most 10_000
+        mul_div_floor(filled, 10_000, self.original_amount).unwrap_or(0) as u16
+    }
+    
+    // token A the vault holds for this escrow. A native vault's rent-exempt
+    // minimum isn't escrowed, only the lamports above it count, as in
+    // make_deposit, so paying out the balance never leaves the vault below it
+    pub fn vault_balance(&self, vault: &AccountInfo) -> Result<u64, ProgramError> {
+        match self.kind()? {
+            EscrowKind::Token => token_account_amount(vault),
+            EscrowKind::Native => Ok(vault
+                .lamports()
+                .saturating_sub(current_rent()?.minimum_balance(vault.data_len()))),
+        }
+    }
+    
+    // check that the vault still holds at least `amount` of token A
+    pub fn verify_vault_funded(&self, vault: &AccountInfo) -> Result<(), ProgramError> {
+        self.check_vault_balance(self.vault_balance(vault)?)
+    }
+    
+    // compare a vault balance against the escrowed amount
+    pub fn check_vault_balance(&self, balance: u64) -> Result<(), ProgramError> {
+        if balance < self.amount {
+            return Err(EscrowError::InsufficientVaultBalance.into());
+        }
+        Ok(())
+    }
+    
+    // check that the given key is the current maker (it changes on TransferAuthority)
+    pub fn verify_maker(&self, maker: &Pubkey) -> Result<(), ProgramError> {
+        if self.maker != *maker {
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+        Ok(())
+    }
+
+    // encode the escrow with Borsh, byte-for-byte equal to the raw account layout
+    #[cfg(feature = "borsh")]
+    pub fn to_borsh(&self) -> Result<Vec<u8>, ProgramError> {
+        borsh::to_vec(self).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    // decode an escrow from Borsh bytes (or raw account data)
+    #[cfg(feature = "borsh")]
+    pub fn from_borsh(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        Self::try_from_slice(&data[..Self::LEN]).map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
+
+// close an account owned by this program: zero its data, move all lamports to
+// the destination and hand it back to the system program, so a re-funded
+// address can't be loaded as one of our accounts again
+pub fn close_program_account(
+    account: &AccountInfo,
+    destination: &AccountInfo,
+) -> Result<(), ProgramError> {
+    account.try_borrow_mut_data()?.fill(0);
+    
+    let lamports = account.lamports();
+    *account.try_borrow_mut_lamports()? = 0;
+    *destination.try_borrow_mut_lamports()? += lamports;
+    
+    // data is zeroed and the account is drained, so ownership can change
+    unsafe {
+        account.assign(&SYSTEM_PROGRAM_ID);
+    }
+    
+    Ok(())
+}
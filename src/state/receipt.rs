@@ -0,0 +1,93 @@
+use super::discriminator_matches;
+use pinocchio::{
+    account_info::AccountInfo,
+    pubkey::Pubkey,
+    program_error::ProgramError,
+    account_validation::{AccountValidation, ValidateAccount},
+};
+
+// a record that a take settled, written when the taker passes the receipt
+// PDA ["receipt", escrow, taker, created_at, filled], see
+// find_receipt_address. Unlike the escrow it is never closed, so
+// indexers and disputes can still point at it after the escrow is gone
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TakeReceipt {
+    // discriminator to identify account type
+    pub discriminator: [u8; 8],
+
+    // the escrow that was taken, closed by the take
+    pub escrow: Pubkey,
+
+    // the two sides of the trade
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+
+    // the traded mints
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+
+    // token A the taker got and token B the maker got
+    pub amount: u64,
+    pub receive_amount: u64,
+
+    // unix timestamp of the take
+    pub timestamp: i64,
+
+    // bump seed for the receipt PDA
+    pub bump: u8,
+}
+
+impl AccountValidation for TakeReceipt {
+    fn validate_account<'a>(account: &'a AccountInfo) -> Result<&'a mut Self, ProgramError> {
+        // never cast less than a whole TakeReceipt
+        if account.data_len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        let receipt = unsafe {
+            let mut data = account.try_borrow_mut_data()?;
+            let receipt = &mut *(data.as_mut_ptr() as *mut TakeReceipt);
+
+            // Verify discriminator
+            if !discriminator_matches(&receipt.discriminator, &Self::DISCRIMINATOR) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            receipt
+        };
+
+        Ok(receipt)
+    }
+}
+
+impl TakeReceipt {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 1;
+    pub const DISCRIMINATOR: [u8; 8] = [114, 51, 9, 240, 77, 186, 3, 145];
+
+    // write a receipt into a freshly created account
+    pub fn init(account: &AccountInfo, receipt: TakeReceipt) -> Result<(), ProgramError> {
+        if account.data_len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        unsafe {
+            let mut data = account.try_borrow_mut_data()?;
+            let dst = data.as_mut_ptr() as *mut TakeReceipt;
+            *dst = TakeReceipt { discriminator: Self::DISCRIMINATOR, ..receipt };
+        }
+
+        Ok(())
+    }
+
+    // load a TakeReceipt account owned by `program_id`
+    pub fn from_account<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<&'a mut Self, ProgramError> {
+        if !account.is_owned_by(program_id) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::validate_account(account)
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{error::EscrowError, math::mul_div_floor};
+use super::{discriminator_matches, EscrowStatus};
+use pinocchio::{
+    account_info::AccountInfo,
+    pubkey::Pubkey,
+    program_error::ProgramError,
+    account_validation::{AccountValidation, ValidateAccount},
+};
+
+// token A released to a recipient over time instead of all at once. Nothing
+// is claimable before `cliff`, then the vested share grows linearly from
+// `start` until `start + duration`, when the whole amount is vested. The
+// tokens sit in the vault PDA ["vault", vesting], owned by the vesting PDA
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct VestingEscrow {
+    // discriminator to identify account type
+    pub discriminator: [u8; 8],
+
+    // the maker who funded the schedule, gets the rent back once it is done
+    pub maker: Pubkey,
+
+    // who may claim the vested tokens
+    pub recipient: Pubkey,
+
+    // the mint of the vested token
+    pub mint: Pubkey,
+
+    // everything the schedule releases
+    pub total_amount: u64,
+
+    // already claimed, never more than total_amount
+    pub released: u64,
+
+    // unix timestamps: vesting starts at `start`, nothing is claimable before `cliff`
+    pub start: i64,
+    pub cliff: i64,
+
+    // seconds from `start` until everything is vested
+    pub duration: i64,
+
+    // bump seed for the vesting PDA
+    pub bump: u8,
+
+    // bump seed for the vault PDA
+    pub vault_bump: u8,
+
+    // EscrowStatus, stored as its u8 value. Active until the last claim
+    pub status: u8,
+}
+
+impl AccountValidation for VestingEscrow {
+    fn validate_account<'a>(account: &'a AccountInfo) -> Result<&'a mut Self, ProgramError> {
+        // never cast less than a whole VestingEscrow
+        if account.data_len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        let vesting = unsafe {
+            let mut data = account.try_borrow_mut_data()?;
+            let vesting = &mut *(data.as_mut_ptr() as *mut VestingEscrow);
+
+            // Verify discriminator
+            if !discriminator_matches(&vesting.discriminator, &Self::DISCRIMINATOR) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            vesting
+        };
+
+        Ok(vesting)
+    }
+}
+
+impl VestingEscrow {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 1;
+    pub const DISCRIMINATOR: [u8; 8] = [201, 44, 118, 7, 163, 90, 35, 212];
+
+    // the cliff has to fall inside the schedule and the schedule can't run
+    // backwards. A zero duration vests everything at the cliff, which must
+    // then be the start
+    pub fn check_schedule(start: i64, cliff: i64, duration: i64) -> Result<(), ProgramError> {
+        let end = start.checked_add(duration).ok_or(EscrowError::InvalidExpiry)?;
+        if duration < 0 || cliff < start || cliff > end {
+            return Err(EscrowError::InvalidExpiry.into());
+        }
+        Ok(())
+    }
+
+    // initialize a new VestingEscrow account
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(
+        account: &AccountInfo,
+        maker: Pubkey,
+        recipient: Pubkey,
+        mint: Pubkey,
+        total_amount: u64,
+        start: i64,
+        cliff: i64,
+        duration: i64,
+        bump: u8,
+        vault_bump: u8,
+    ) -> Result<(), ProgramError> {
+        Self::check_schedule(start, cliff, duration)?;
+
+        let vesting = VestingEscrow {
+            discriminator: Self::DISCRIMINATOR,
+            maker,
+            recipient,
+            mint,
+            total_amount,
+            released: 0,
+            start,
+            cliff,
+            duration,
+            bump,
+            vault_bump,
+            status: EscrowStatus::Active as u8,
+        };
+
+        unsafe {
+            let mut data = account.try_borrow_mut_data()?;
+            let dst = data.as_mut_ptr() as *mut VestingEscrow;
+            *dst = vesting;
+        }
+
+        Ok(())
+    }
+
+    // load a VestingEscrow account owned by `program_id`
+    pub fn from_account<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<&'a mut Self, ProgramError> {
+        if !account.is_owned_by(program_id) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::validate_account(account)
+    }
+
+    // everything vested as of `now`, claimed or not, rounded down so the
+    // recipient never gets ahead of the schedule
+    pub fn vested_amount(&self, now: i64) -> Result<u64, ProgramError> {
+        if now < self.cliff {
+            return Ok(0);
+        }
+        let elapsed = now.saturating_sub(self.start);
+        if elapsed >= self.duration {
+            return Ok(self.total_amount);
+        }
+        // start <= cliff <= now, so elapsed is positive here
+        mul_div_floor(self.total_amount, elapsed as u64, self.duration as u64).map_err(Into::into)
+    }
+
+    // vested but not yet claimed as of `now`
+    pub fn claimable(&self, now: i64) -> Result<u64, ProgramError> {
+        Ok(self.vested_amount(now)?.saturating_sub(self.released))
+    }
+
+    // book a claim of `amount`, the schedule completes with the last token
+    pub fn record_claim(&mut self, amount: u64) -> Result<(), ProgramError> {
+        self.released = self
+            .released
+            .checked_add(amount)
+            .filter(|released| *released <= self.total_amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        if self.released == self.total_amount {
+            self.status = EscrowStatus::Completed as u8;
+        }
+        Ok(())
+    }
+
+    // where the schedule is in its lifecycle
+    pub fn status(&self) -> Result<EscrowStatus, ProgramError> {
+        EscrowStatus::try_from(self.status)
+    }
+
+    // check that the given key is the recipient
+    pub fn verify_recipient(&self, recipient: &Pubkey) -> Result<(), ProgramError> {
+        if self.recipient != *recipient {
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+// sysvar reads: the rent and clock every handler goes through, and the
+// instructions sysvar, to tell a top-level instruction from a CPI
+use crate::error::EscrowError;
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult, sysvars::rent::Rent};
+// only the on-chain builds read sysvars, host builds use the stand-ins below
+#[cfg(target_os = "solana")]
+use pinocchio::sysvars::{clock::Clock, Sysvar};
+
+// the Rent sysvar. Host builds have no sysvars, so they use the default
+// cluster rent to keep make runnable in tests
+#[cfg(target_os = "solana")]
+pub fn current_rent() -> Result<Rent, ProgramError> {
+    Rent::get()
+}
+#[cfg(not(target_os = "solana"))]
+pub fn current_rent() -> Result<Rent, ProgramError> {
+    Ok(Rent {
+        lamports_per_byte_year: 3480,
+        exemption_threshold: 2.0,
+        burn_percent: 50,
+    })
+}
+
+// the Clock sysvar's unix timestamp. Host builds have no clock and read 0,
+// tests pass their own time to the *_at handlers
+#[cfg(target_os = "solana")]
+pub fn current_unix_timestamp() -> Result<i64, ProgramError> {
+    Ok(Clock::get()?.unix_timestamp)
+}
+#[cfg(not(target_os = "solana"))]
+pub fn current_unix_timestamp() -> Result<i64, ProgramError> {
+    Ok(0)
+}
+
+// the current epoch, which picks a mint's transfer fee. Host builds have no
+// clock and use epoch 0
+#[cfg(target_os = "solana")]
+pub fn current_epoch() -> Result<u64, ProgramError> {
+    Ok(Clock::get()?.epoch)
+}
+#[cfg(not(target_os = "solana"))]
+pub fn current_epoch() -> Result<u64, ProgramError> {
+    Ok(0)
+}
+
+// instructions sysvar (Sysvar1nstructions1111111111111111111111111)
+pub const INSTRUCTIONS_SYSVAR_ID: Pubkey = [
+    6, 167, 213, 23, 24, 123, 209, 102, 53, 218, 212, 4, 85, 253, 194, 192,
+    193, 36, 198, 143, 33, 86, 117, 165, 219, 186, 203, 95, 8, 0, 0, 0,
+];
+
+// instructions sysvar layout: instruction count (u16), one u16 offset per
+// instruction, the instructions, and the index of the executing top-level
+// instruction (u16) in the last two bytes. Each instruction is an account
+// count (u16), one flags byte and key per account, the program id, then the
+// data length (u16) and data
+const ACCOUNT_META_LEN: usize = 1 + 32;
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ProgramError> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+// program id of the top-level instruction being executed, read from raw
+// instructions sysvar data
+pub fn current_top_level_program(data: &[u8]) -> Result<Pubkey, ProgramError> {
+    let count = read_u16(data, 0)? as usize;
+    let current = read_u16(data, data.len().checked_sub(2).ok_or(ProgramError::InvalidAccountData)?)? as usize;
+    if current >= count {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let start = read_u16(data, 2 + current * 2)? as usize;
+    let accounts = read_u16(data, start)? as usize;
+    let program_id = start + 2 + accounts * ACCOUNT_META_LEN;
+    data.get(program_id..program_id + 32)
+        .and_then(|key| key.try_into().ok())
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+// whether `program_id` runs as a CPI: the top-level instruction being
+// executed belongs to another program. Handlers pass the id they were
+// invoked with, so a deployment under another address checks against itself
+pub fn is_cpi(instructions_sysvar: &AccountInfo, program_id: &Pubkey) -> Result<bool, ProgramError> {
+    if instructions_sysvar.key() != &INSTRUCTIONS_SYSVAR_ID {
+        return Err(ProgramError::UnsupportedSysvar);
+    }
+    let top_level = current_top_level_program(&instructions_sysvar.try_borrow_data()?)?;
+    Ok(top_level != *program_id)
+}
+
+// for handlers another program shouldn't drive on a signer's behalf: reject
+// the call when it arrives through a CPI
+pub fn check_not_cpi(instructions_sysvar: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+    if is_cpi(instructions_sysvar, program_id)? {
+        return Err(EscrowError::CpiNotAllowed.into());
+    }
+    Ok(())
+}
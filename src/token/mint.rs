@@ -0,0 +1,230 @@
+// SPL mint data: the base layout, Token-2022 extensions, and what they mean
+// for the vaults the handlers create and the transfers they make
+use crate::{
+    error::EscrowError,
+    math::mul_div_ceil,
+    programs::TOKEN_2022_PROGRAM_ID,
+    sysvar::current_epoch,
+};
+use super::TOKEN_ACCOUNT_LEN;
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, sysvars::rent::Rent};
+
+// SPL mint layout: mint_authority COption (0..36), supply (36..44),
+// decimals (44), is_initialized (45), freeze_authority COption (46..82)
+pub const MINT_LEN: usize = 82;
+const MINT_FREEZE_AUTHORITY_OFFSET: usize = 46;
+const MINT_IS_INITIALIZED_OFFSET: usize = 45;
+
+// check raw account data looks like an initialized mint: exactly MINT_LEN
+// bytes, or a Token-2022 mint with extensions tagged with the mint account type
+pub fn check_mint_data(mint_data: &[u8]) -> Result<(), ProgramError> {
+    let is_mint_layout = match mint_data.len() {
+        MINT_LEN => true,
+        len if len > TOKEN_ACCOUNT_LEN => mint_data[TOKEN_ACCOUNT_LEN] == ACCOUNT_TYPE_MINT,
+        _ => false,
+    };
+    if !is_mint_layout || mint_data[MINT_IS_INITIALIZED_OFFSET] == 0 {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    Ok(())
+}
+
+// check an account is a mint of `token_program`
+pub fn verify_mint(account: &AccountInfo, token_program: &Pubkey) -> Result<(), ProgramError> {
+    if !account.is_owned_by(token_program) {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    check_mint_data(&account.try_borrow_data()?)
+}
+
+// check whether a mint account has a freeze authority set
+pub fn mint_has_freeze_authority(mint_data: &[u8]) -> Result<bool, ProgramError> {
+    if mint_data.len() < MINT_LEN {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    let tag = mint_data[MINT_FREEZE_AUTHORITY_OFFSET..MINT_FREEZE_AUTHORITY_OFFSET + 4]
+        .try_into()
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok(u32::from_le_bytes(tag) != 0)
+}
+
+// Token-2022 extension layout: the base state padded to 165 bytes, an account
+// type byte, then TLV entries of type (u16) + length (u16) + value
+const EXTENSION_ACCOUNT_TYPE_OFFSET: usize = TOKEN_ACCOUNT_LEN;
+const EXTENSION_TLV_OFFSET: usize = EXTENSION_ACCOUNT_TYPE_OFFSET + 1;
+const EXTENSION_TLV_HEADER_LEN: usize = 4;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+
+// account extension (and its length) a token account must carry for a given
+// mint extension: TransferFeeConfig, NonTransferable, TransferHook
+fn required_account_extension_len(mint_extension: u16) -> Option<usize> {
+    match mint_extension {
+        1 => Some(8),
+        9 => Some(0),
+        14 => Some(1),
+        _ => None,
+    }
+}
+
+// size of a vault token account for `mint` under `token_program`
+// legacy accounts are always 165 bytes, Token-2022 accounts grow by the
+// account extensions the mint's extensions require
+pub fn vault_account_size(token_program: &Pubkey, mint_data: &[u8]) -> Result<usize, ProgramError> {
+    if token_program != &TOKEN_2022_PROGRAM_ID || mint_data.len() <= MINT_LEN {
+        return Ok(TOKEN_ACCOUNT_LEN);
+    }
+    if mint_data.len() < EXTENSION_TLV_OFFSET {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    
+    let mut extensions_len = 0;
+    let mut offset = EXTENSION_TLV_OFFSET;
+    while offset + EXTENSION_TLV_HEADER_LEN <= mint_data.len() {
+        let extension = u16::from_le_bytes([mint_data[offset], mint_data[offset + 1]]);
+        let len = u16::from_le_bytes([mint_data[offset + 2], mint_data[offset + 3]]) as usize;
+        // uninitialized space ends the TLV list
+        if extension == 0 {
+            break;
+        }
+        if let Some(account_len) = required_account_extension_len(extension) {
+            extensions_len += EXTENSION_TLV_HEADER_LEN + account_len;
+        }
+        offset += EXTENSION_TLV_HEADER_LEN + len;
+    }
+    
+    if extensions_len == 0 {
+        return Ok(TOKEN_ACCOUNT_LEN);
+    }
+    Ok(EXTENSION_TLV_OFFSET + extensions_len)
+}
+
+// Token-2022 TransferFeeConfig mint extension: two authorities (0..64), the
+// withheld amount (64..72), then the older and the newer fee, each an epoch
+// (u64), a maximum fee (u64) and a rate in basis points (u16)
+const EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+const TRANSFER_FEE_CONFIG_LEN: usize = 108;
+const OLDER_TRANSFER_FEE_OFFSET: usize = 72;
+const NEWER_TRANSFER_FEE_OFFSET: usize = 90;
+const TRANSFER_FEE_LEN: usize = 18;
+const MAX_FEE_BASIS_POINTS: u64 = 10_000;
+pub const MINT_DECIMALS_OFFSET: usize = 44;
+
+// the value of one extension of a Token-2022 mint, None for a mint without it
+fn find_mint_extension(mint_data: &[u8], extension: u16) -> Result<Option<&[u8]>, ProgramError> {
+    if mint_data.len() <= EXTENSION_TLV_OFFSET || mint_data[EXTENSION_ACCOUNT_TYPE_OFFSET] != ACCOUNT_TYPE_MINT {
+        return Ok(None);
+    }
+    let mut offset = EXTENSION_TLV_OFFSET;
+    while offset + EXTENSION_TLV_HEADER_LEN <= mint_data.len() {
+        let found = u16::from_le_bytes([mint_data[offset], mint_data[offset + 1]]);
+        let len = u16::from_le_bytes([mint_data[offset + 2], mint_data[offset + 3]]) as usize;
+        // uninitialized space ends the TLV list
+        if found == 0 {
+            return Ok(None);
+        }
+        let value = offset + EXTENSION_TLV_HEADER_LEN;
+        if found == extension {
+            // a length running past the account is a malformed mint
+            return mint_data
+                .get(value..value + len)
+                .map(Some)
+                .ok_or_else(|| EscrowError::InvalidTokenMint.into());
+        }
+        offset = value + len;
+    }
+    Ok(None)
+}
+
+// one of the two fees of a TransferFeeConfig, in force from `epoch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferFee {
+    pub epoch: u64,
+    pub maximum_fee: u64,
+    pub basis_points: u16,
+}
+
+impl TransferFee {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, ProgramError> {
+        let invalid = |_| ProgramError::from(EscrowError::InvalidTokenMint);
+        Ok(Self {
+            epoch: u64::from_le_bytes(bytes[0..8].try_into().map_err(invalid)?),
+            maximum_fee: u64::from_le_bytes(bytes[8..16].try_into().map_err(invalid)?),
+            basis_points: u16::from_le_bytes(bytes[16..18].try_into().map_err(invalid)?),
+        })
+    }
+
+    // what the token program withholds from a transfer of `amount`, rounded
+    // up and capped at the maximum fee like Token-2022 does
+    pub fn fee(&self, amount: u64) -> Result<u64, ProgramError> {
+        if self.basis_points == 0 || amount == 0 {
+            return Ok(0);
+        }
+        let basis_points = (self.basis_points as u64).min(MAX_FEE_BASIS_POINTS);
+        Ok(mul_div_ceil(amount, basis_points, MAX_FEE_BASIS_POINTS)?.min(self.maximum_fee))
+    }
+
+    // what arrives of a transfer of `amount`
+    pub fn net_amount(&self, amount: u64) -> Result<u64, ProgramError> {
+        // the fee never exceeds the amount, the rate is at most 100%
+        Ok(amount - self.fee(amount)?)
+    }
+
+    // the smallest transfer that delivers at least `net`
+    pub fn gross_amount(&self, net: u64) -> Result<u64, ProgramError> {
+        if self.fee(net)? == 0 {
+            return Ok(net);
+        }
+        // paying the whole maximum fee on top always delivers `net`
+        let capped = net.checked_add(self.maximum_fee).ok_or(EscrowError::AmountOverflow)?;
+        let basis_points = self.basis_points as u64;
+        if basis_points >= MAX_FEE_BASIS_POINTS {
+            return Ok(capped);
+        }
+        // below the cap the fee is a rate, invert it and step past rounding
+        let mut gross = mul_div_ceil(net, MAX_FEE_BASIS_POINTS, MAX_FEE_BASIS_POINTS - basis_points)?;
+        while gross < capped && self.net_amount(gross)? < net {
+            gross += 1;
+        }
+        Ok(gross.min(capped))
+    }
+}
+
+// the transfer fee a mint charges in `epoch`, None for legacy mints and
+// Token-2022 mints without a TransferFeeConfig
+pub fn mint_transfer_fee(
+    token_program: &Pubkey,
+    mint_data: &[u8],
+    epoch: u64,
+) -> Result<Option<TransferFee>, ProgramError> {
+    if token_program != &TOKEN_2022_PROGRAM_ID {
+        return Ok(None);
+    }
+    let Some(config) = find_mint_extension(mint_data, EXTENSION_TRANSFER_FEE_CONFIG)? else {
+        return Ok(None);
+    };
+    if config.len() < TRANSFER_FEE_CONFIG_LEN {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    let older = TransferFee::from_bytes(&config[OLDER_TRANSFER_FEE_OFFSET..OLDER_TRANSFER_FEE_OFFSET + TRANSFER_FEE_LEN])?;
+    let newer = TransferFee::from_bytes(&config[NEWER_TRANSFER_FEE_OFFSET..NEWER_TRANSFER_FEE_OFFSET + TRANSFER_FEE_LEN])?;
+    Ok(Some(if epoch >= newer.epoch { newer } else { older }))
+}
+
+// the fee `mint` currently charges, None when it charges none
+pub fn current_transfer_fee(
+    token_program: &Pubkey,
+    mint: &AccountInfo,
+) -> Result<Option<TransferFee>, ProgramError> {
+    mint_transfer_fee(token_program, &mint.try_borrow_data()?, current_epoch()?)
+}
+
+// lamports a vault for `mint_account` needs to be rent exempt, from its real
+// size rather than the legacy 165 bytes
+pub fn vault_rent_exempt_lamports(
+    token_program: &Pubkey,
+    mint_account: &AccountInfo,
+    rent: &Rent,
+) -> Result<u64, ProgramError> {
+    let vault_size = vault_account_size(token_program, &mint_account.try_borrow_data()?)?;
+    Ok(rent.minimum_balance(vault_size))
+}
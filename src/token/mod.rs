@@ -0,0 +1,194 @@
+// typed views over SPL token account data, and the checks the handlers run
+// on token accounts
+use crate::{
+    error::EscrowError,
+    programs::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID},
+};
+use pinocchio::{account_info::AccountInfo, msg, program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+
+pub mod mint;
+pub use mint::{
+    check_mint_data, current_transfer_fee, mint_has_freeze_authority, mint_transfer_fee, vault_account_size,
+    vault_rent_exempt_lamports, verify_mint, TransferFee, MINT_LEN,
+};
+
+// wrapped SOL mint (So11111111111111111111111111111111111111112)
+pub const NATIVE_MINT: Pubkey = [
+    6, 155, 136, 87, 254, 171, 129, 132, 251, 104, 127, 99, 70, 24, 192, 53,
+    218, 196, 57, 220, 26, 235, 59, 85, 152, 160, 240, 0, 0, 0, 0, 1,
+];
+
+// SPL token account layout: mint (0..32), owner (32..64), amount (64..72),
+// delegate COption (72..108), state (108), ... padded to 165 bytes.
+// Token-2022 uses the same base layout, accounts with extensions append an
+// account type byte at 165 followed by the extension TLVs
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+const MINT_OFFSET: usize = 0;
+const OWNER_OFFSET: usize = 32;
+const AMOUNT_OFFSET: usize = 64;
+const STATE_OFFSET: usize = 108;
+const ACCOUNT_TYPE_OFFSET: usize = TOKEN_ACCOUNT_LEN;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+// token account state byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TokenAccountState {
+    Uninitialized = 0,
+    Initialized = 1,
+    Frozen = 2,
+}
+
+impl TryFrom<u8> for TokenAccountState {
+    type Error = ProgramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(TokenAccountState::Uninitialized),
+            1 => Ok(TokenAccountState::Initialized),
+            2 => Ok(TokenAccountState::Frozen),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+// the fields of a token account the escrow handlers validate against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAccount {
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    state: TokenAccountState,
+}
+
+impl TokenAccount {
+    // parse a legacy or Token-2022 token account, rejecting anything that
+    // isn't an initialized (or frozen) token account
+    pub fn from_account_data(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < TOKEN_ACCOUNT_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // extended Token-2022 data must be tagged as an account, not a mint
+        if data.len() > TOKEN_ACCOUNT_LEN && data[ACCOUNT_TYPE_OFFSET] != ACCOUNT_TYPE_ACCOUNT {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let state = TokenAccountState::try_from(data[STATE_OFFSET])?;
+        if state == TokenAccountState::Uninitialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let pubkey_at = |offset: usize| -> Result<Pubkey, ProgramError> {
+            data[offset..offset + 32]
+                .try_into()
+                .map_err(|_| ProgramError::InvalidAccountData)
+        };
+        let amount = data[AMOUNT_OFFSET..AMOUNT_OFFSET + 8]
+            .try_into()
+            .map(u64::from_le_bytes)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        Ok(Self {
+            mint: pubkey_at(MINT_OFFSET)?,
+            owner: pubkey_at(OWNER_OFFSET)?,
+            amount,
+            state,
+        })
+    }
+
+    pub fn mint(&self) -> &Pubkey {
+        &self.mint
+    }
+
+    pub fn owner(&self) -> &Pubkey {
+        &self.owner
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    pub fn state(&self) -> TokenAccountState {
+        self.state
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.state == TokenAccountState::Frozen
+    }
+}
+
+// check raw token account data is for `mint`, whoever owns it
+pub fn check_token_account_mint(data: &[u8], mint: &Pubkey) -> Result<TokenAccount, ProgramError> {
+    let token_account = TokenAccount::from_account_data(data)?;
+    if token_account.mint() != mint {
+        return Err(EscrowError::InvalidTokenMint.into());
+    }
+    Ok(token_account)
+}
+
+// check raw token account data is for `mint` and owned by `owner`
+pub fn check_token_account_data(
+    data: &[u8],
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Result<TokenAccount, ProgramError> {
+    let token_account = check_token_account_mint(data, mint)?;
+    if token_account.owner() != owner {
+        return Err(EscrowError::InvalidAuthority.into());
+    }
+    Ok(token_account)
+}
+
+// check an account is a token account of either token program for `mint` owned by `owner`
+pub fn verify_token_account(
+    account: &AccountInfo,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Result<(), ProgramError> {
+    if !account.is_owned_by(&TOKEN_PROGRAM_ID) && !account.is_owned_by(&TOKEN_2022_PROGRAM_ID) {
+        return Err(EscrowError::InvalidTokenProgram.into());
+    }
+    check_token_account_data(&account.try_borrow_data()?, mint, owner).map(|_| ())
+}
+
+// check a token vault's authority is its escrow, the only key take and refund
+// can sign the vault's transfers with
+pub fn verify_vault_authority(vault: &AccountInfo, escrow: &Pubkey) -> ProgramResult {
+    let vault = TokenAccount::from_account_data(&vault.try_borrow_data()?)?;
+    if vault.owner() != escrow {
+        return Err(EscrowError::InvalidAuthority.into());
+    }
+    Ok(())
+}
+
+// read the token balance of an SPL token account
+pub fn token_account_amount(account: &AccountInfo) -> Result<u64, ProgramError> {
+    TokenAccount::from_account_data(&account.try_borrow_data()?).map(|token_account| token_account.amount())
+}
+
+// reject the instruction before any transfer CPI when one of the token
+// accounts it moves tokens through was frozen by the mint's freeze authority.
+// The token program would fail the transfer anyway, but without saying why.
+// Accounts that don't parse are left to the other checks
+pub fn check_accounts_not_frozen(accounts: &[&AccountInfo]) -> ProgramResult {
+    for account in accounts {
+        let frozen = TokenAccount::from_account_data(&account.try_borrow_data()?)
+            .map(|token_account| token_account.is_frozen())
+            .unwrap_or(false);
+        if frozen {
+            msg!("token account is frozen by its mint's freeze authority");
+            return Err(EscrowError::AccountFrozen.into());
+        }
+    }
+    Ok(())
+}
+
+// how much to move out of a vault that owes `owed` before closing it
+// everything goes, so dust from rounding or outside deposits can't leave a
+// non-zero balance behind and make close_account fail
+pub fn sweep_amount(vault_balance: u64, owed: u64) -> Result<u64, ProgramError> {
+    if vault_balance < owed {
+        return Err(EscrowError::InsufficientVaultBalance.into());
+    }
+    Ok(vault_balance)
+}